@@ -0,0 +1,2 @@
+from .mft import *  # noqa: F401,F403
+from .mft import __doc__  # noqa: F401
@@ -0,0 +1,5 @@
+import sys
+
+from mft import main
+
+sys.exit(main())
@@ -32,7 +32,7 @@ setup(
     ],
     rust_extensions=[
         RustExtension(
-            target="mft",
+            target="mft.mft",
             path="Cargo.toml",
             debug=os.getenv("MFT_DEBUG", False),
             rustc_flags=get_py_version_cfgs(),
@@ -43,6 +43,7 @@ setup(
             'pytest'
         ]
     },
+    packages=["mft"],
     install_requires=install_requires,
     include_package_data=True,
     zip_safe=False,
@@ -0,0 +1,71 @@
+//! Asynchronous iteration over entries, for asyncio.
+use crate::PyMftEntriesIterator;
+
+use pyo3::exceptions::PyStopAsyncIteration;
+use pyo3::prelude::*;
+use pyo3::PyAsyncProtocol;
+
+use std::collections::VecDeque;
+
+#[pyclass]
+/// An asynchronous iterator over entries, as returned by `PyMftParser.aentries()`.
+/// Records are read in batches on a thread of the default executor of the running event loop,
+/// and the rest of a batch is yielded without waiting.
+pub struct PyMftAsyncEntriesIterator {
+    entries: Py<PyMftEntriesIterator>,
+    batch_size: usize,
+    pending: VecDeque<PyObject>,
+}
+
+impl PyMftAsyncEntriesIterator {
+    pub fn new(entries: Py<PyMftEntriesIterator>, batch_size: usize) -> Self {
+        PyMftAsyncEntriesIterator {
+            entries,
+            batch_size: batch_size.max(1),
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+#[pymethods]
+impl PyMftAsyncEntriesIterator {
+    /// Reads the next batch, and returns its first result. Runs on the executor thread.
+    fn _fetch(&mut self, py: Python) -> PyResult<PyObject> {
+        let batch = {
+            let entries = self.entries.borrow(py);
+            let mut state = entries.lock(py);
+            state.release_gil = true;
+            state.next_batch(py, self.batch_size)?
+        };
+
+        self.pending.extend(batch);
+        self.pending
+            .pop_front()
+            .ok_or_else(|| PyStopAsyncIteration::new_err(()))
+    }
+}
+
+#[pyproto]
+impl PyAsyncProtocol for PyMftAsyncEntriesIterator {
+    fn __aiter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __anext__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let event_loop = py.import("asyncio")?.call_method0("get_running_loop")?;
+
+        if let Some(obj) = slf.pending.pop_front() {
+            let future = event_loop.call_method0("create_future")?;
+            future.call_method1("set_result", (obj,))?;
+            return Ok(Some(future.to_object(py)));
+        }
+
+        let fetch = slf.into_py(py).getattr(py, "_fetch")?;
+        let future = event_loop.call_method1("run_in_executor", (py.None(), fetch))?;
+
+        Ok(Some(future.to_object(py)))
+    }
+}
@@ -0,0 +1,471 @@
+//! Encoding of flat entries as an Arrow IPC stream (https://arrow.apache.org/docs/format/Columnar.html#ipc-streaming-format).
+//! A stream is a schema message, followed by record batches, and an end-of-stream marker.
+//! Messages are encapsulated FlatBuffers, which are written by hand, since only a handful of tables are needed.
+use crate::flat::FlatMftEntry;
+use crate::timestamp::Timestamp;
+
+/// Precedes the metadata of every message.
+const CONTINUATION: [u8; 4] = [0xff; 4];
+
+/// `MetadataVersion.V5`
+const METADATA_VERSION: i16 = 4;
+
+// `MessageHeader` union.
+const HEADER_SCHEMA: u8 = 1;
+const HEADER_RECORD_BATCH: u8 = 3;
+
+// `Type` union.
+const TYPE_INT: u8 = 2;
+const TYPE_UTF8: u8 = 5;
+const TYPE_BOOL: u8 = 6;
+const TYPE_TIMESTAMP: u8 = 10;
+
+/// `TimeUnit.MICROSECOND`
+/// NTFS timestamps have a 100ns resolution, but nanoseconds since the epoch cannot represent dates before 1677.
+const TIME_UNIT_MICROSECOND: i16 = 2;
+
+/// A node of a FlatBuffer, written in order (children after their parent).
+enum Node {
+    /// The fields of a table, by field id. Absent fields take their default value.
+    Table(Vec<Option<Scalar>>),
+    String(String),
+    Tables(Vec<Node>),
+    /// A vector of structs made of two longs (`FieldNode` and `Buffer`).
+    LongPairs(Vec<(i64, i64)>),
+}
+
+enum Scalar {
+    Bool(bool),
+    U8(u8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    Offset(Node),
+}
+
+impl Scalar {
+    fn size(&self) -> usize {
+        match self {
+            Scalar::Bool(_) | Scalar::U8(_) => 1,
+            Scalar::I16(_) => 2,
+            Scalar::I32(_) | Scalar::Offset(_) => 4,
+            Scalar::I64(_) => 8,
+        }
+    }
+}
+
+#[derive(Default)]
+struct FlatBufferWriter {
+    buf: Vec<u8>,
+}
+
+impl FlatBufferWriter {
+    fn finish(root: &Node) -> Vec<u8> {
+        let mut writer = FlatBufferWriter::default();
+        writer.buf.extend_from_slice(&[0; 4]);
+
+        let position = writer.write(root);
+        writer.patch_offset(0, position);
+        writer.buf
+    }
+
+    fn align(&mut self, alignment: usize) {
+        while !self.buf.len().is_multiple_of(alignment) {
+            self.buf.push(0);
+        }
+    }
+
+    /// Offsets are unsigned, relative to their own position.
+    fn patch_offset(&mut self, at: usize, target: usize) {
+        let offset = (target - at) as u32;
+        self.buf[at..at + 4].copy_from_slice(&offset.to_le_bytes());
+    }
+
+    /// Writes a node and its children, and returns the position of the node.
+    fn write(&mut self, node: &Node) -> usize {
+        match node {
+            Node::Table(fields) => self.write_table(fields),
+            Node::String(s) => {
+                self.align(4);
+                let position = self.buf.len();
+                self.buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+                self.buf.extend_from_slice(s.as_bytes());
+                self.buf.push(0);
+                position
+            }
+            Node::Tables(tables) => {
+                self.align(4);
+                let position = self.buf.len();
+                self.buf.extend_from_slice(&(tables.len() as u32).to_le_bytes());
+                let offsets = self.buf.len();
+                self.buf.resize(offsets + 4 * tables.len(), 0);
+
+                for (i, table) in tables.iter().enumerate() {
+                    let table_position = self.write(table);
+                    self.patch_offset(offsets + 4 * i, table_position);
+                }
+                position
+            }
+            Node::LongPairs(pairs) => {
+                // The elements are aligned on 8 bytes, after the length.
+                while self.buf.len() % 8 != 4 {
+                    self.buf.push(0);
+                }
+                let position = self.buf.len();
+                self.buf.extend_from_slice(&(pairs.len() as u32).to_le_bytes());
+                for (first, second) in pairs {
+                    self.buf.extend_from_slice(&first.to_le_bytes());
+                    self.buf.extend_from_slice(&second.to_le_bytes());
+                }
+                position
+            }
+        }
+    }
+
+    /// The vtable is written right before the table.
+    fn write_table(&mut self, fields: &[Option<Scalar>]) -> usize {
+        self.align(2);
+        let vtable = self.buf.len();
+        self.buf.resize(vtable + 4 + 2 * fields.len(), 0);
+
+        let alignment = fields.iter().flatten().map(Scalar::size).max().unwrap_or(4).max(4);
+        self.align(alignment);
+        let table = self.buf.len();
+        self.buf.extend_from_slice(&((table - vtable) as i32).to_le_bytes());
+
+        let mut field_offsets = vec![0_u16; fields.len()];
+        let mut children = vec![];
+
+        for (i, field) in fields.iter().enumerate() {
+            let field = match field {
+                Some(field) => field,
+                None => continue,
+            };
+
+            self.align(field.size());
+            field_offsets[i] = (self.buf.len() - table) as u16;
+
+            match field {
+                Scalar::Bool(v) => self.buf.push(u8::from(*v)),
+                Scalar::U8(v) => self.buf.push(*v),
+                Scalar::I16(v) => self.buf.extend_from_slice(&v.to_le_bytes()),
+                Scalar::I32(v) => self.buf.extend_from_slice(&v.to_le_bytes()),
+                Scalar::I64(v) => self.buf.extend_from_slice(&v.to_le_bytes()),
+                Scalar::Offset(child) => {
+                    children.push((self.buf.len(), child));
+                    self.buf.extend_from_slice(&[0; 4]);
+                }
+            }
+        }
+
+        let table_size = (self.buf.len() - table) as u16;
+        let vtable_size = (4 + 2 * fields.len()) as u16;
+        self.buf[vtable..vtable + 2].copy_from_slice(&vtable_size.to_le_bytes());
+        self.buf[vtable + 2..vtable + 4].copy_from_slice(&table_size.to_le_bytes());
+        for (i, offset) in field_offsets.iter().enumerate() {
+            let at = vtable + 4 + 2 * i;
+            self.buf[at..at + 2].copy_from_slice(&offset.to_le_bytes());
+        }
+
+        for (at, child) in children {
+            let position = self.write(child);
+            self.patch_offset(at, position);
+        }
+
+        table
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataType {
+    Bool,
+    UInt16,
+    UInt32,
+    UInt64,
+    Utf8,
+    /// Microseconds since the Unix epoch, in UTC.
+    Timestamp,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Field {
+    pub name: &'static str,
+    pub data_type: DataType,
+    pub nullable: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    UInt(u64),
+    Utf8(String),
+    Timestamp(i64),
+}
+
+impl Field {
+    fn to_node(self) -> Node {
+        let (type_type, type_table) = match self.data_type {
+            DataType::Bool => (TYPE_BOOL, vec![]),
+            DataType::UInt16 => (TYPE_INT, vec![Some(Scalar::I32(16)), Some(Scalar::Bool(false))]),
+            DataType::UInt32 => (TYPE_INT, vec![Some(Scalar::I32(32)), Some(Scalar::Bool(false))]),
+            DataType::UInt64 => (TYPE_INT, vec![Some(Scalar::I32(64)), Some(Scalar::Bool(false))]),
+            DataType::Utf8 => (TYPE_UTF8, vec![]),
+            DataType::Timestamp => (
+                TYPE_TIMESTAMP,
+                vec![
+                    Some(Scalar::I16(TIME_UNIT_MICROSECOND)),
+                    Some(Scalar::Offset(Node::String("UTC".to_string()))),
+                ],
+            ),
+        };
+
+        Node::Table(vec![
+            Some(Scalar::Offset(Node::String(self.name.to_string()))),
+            Some(Scalar::Bool(self.nullable)),
+            Some(Scalar::U8(type_type)),
+            Some(Scalar::Offset(Node::Table(type_table))),
+            None,
+            // Readers expect the children to be present, even for primitive types.
+            Some(Scalar::Offset(Node::Tables(vec![]))),
+        ])
+    }
+}
+
+/// Encapsulates a message: continuation marker, metadata length, metadata (padded to 8 bytes), and body.
+fn encapsulate(header_type: u8, header: Node, body: Vec<u8>) -> Vec<u8> {
+    let message = Node::Table(vec![
+        Some(Scalar::I16(METADATA_VERSION)),
+        Some(Scalar::U8(header_type)),
+        Some(Scalar::Offset(header)),
+        Some(Scalar::I64(body.len() as i64)),
+    ]);
+
+    let mut metadata = FlatBufferWriter::finish(&message);
+    while !metadata.len().is_multiple_of(8) {
+        metadata.push(0);
+    }
+
+    let mut encapsulated = Vec::with_capacity(8 + metadata.len() + body.len());
+    encapsulated.extend_from_slice(&CONTINUATION);
+    encapsulated.extend_from_slice(&(metadata.len() as i32).to_le_bytes());
+    encapsulated.extend_from_slice(&metadata);
+    encapsulated.extend_from_slice(&body);
+    encapsulated
+}
+
+pub fn schema_message(schema: &[Field]) -> Vec<u8> {
+    let header = Node::Table(vec![
+        // Little endian.
+        Some(Scalar::I16(0)),
+        Some(Scalar::Offset(Node::Tables(schema.iter().map(|f| f.to_node()).collect()))),
+    ]);
+
+    encapsulate(HEADER_SCHEMA, header, vec![])
+}
+
+/// Marks the end of the stream.
+pub fn end_of_stream() -> Vec<u8> {
+    let mut marker = CONTINUATION.to_vec();
+    marker.extend_from_slice(&[0; 4]);
+    marker
+}
+
+/// Bits packed in bytes, least significant bit first.
+pub fn bitmap(bits: impl Iterator<Item = bool>) -> Vec<u8> {
+    let mut bitmap = vec![];
+    for (i, bit) in bits.enumerate() {
+        if i.is_multiple_of(8) {
+            bitmap.push(0);
+        }
+        if bit {
+            *bitmap.last_mut().expect("pushed above") |= 1 << (i % 8);
+        }
+    }
+    bitmap
+}
+
+/// Encodes rows (whose values match the types of the schema) as a record batch message.
+pub fn record_batch_message(schema: &[Field], rows: &[Vec<Option<Value>>]) -> Vec<u8> {
+    let mut body = vec![];
+    let mut nodes = vec![];
+    let mut buffers = vec![];
+
+    let mut push_buffer = |body: &mut Vec<u8>, data: Vec<u8>| {
+        buffers.push((body.len() as i64, data.len() as i64));
+        body.extend_from_slice(&data);
+        while !body.len().is_multiple_of(8) {
+            body.push(0);
+        }
+    };
+
+    for (column, field) in schema.iter().enumerate() {
+        let values: Vec<Option<&Value>> = rows.iter().map(|row| row[column].as_ref()).collect();
+        let null_count = values.iter().filter(|v| v.is_none()).count();
+        nodes.push((rows.len() as i64, null_count as i64));
+
+        push_buffer(&mut body, bitmap(values.iter().map(Option::is_some)));
+
+        match field.data_type {
+            DataType::Bool => push_buffer(
+                &mut body,
+                bitmap(values.iter().map(|v| matches!(v, Some(Value::Bool(true))))),
+            ),
+            DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => {
+                let width = match field.data_type {
+                    DataType::UInt16 => 2,
+                    DataType::UInt32 => 4,
+                    _ => 8,
+                };
+
+                let mut data = Vec::with_capacity(width * values.len());
+                for value in &values {
+                    let value = match value {
+                        Some(Value::UInt(v)) => *v,
+                        _ => 0,
+                    };
+                    data.extend_from_slice(&value.to_le_bytes()[..width]);
+                }
+                push_buffer(&mut body, data);
+            }
+            DataType::Utf8 => {
+                let mut offsets = Vec::with_capacity(4 * (values.len() + 1));
+                let mut data = vec![];
+                offsets.extend_from_slice(&0_i32.to_le_bytes());
+
+                for value in &values {
+                    if let Some(Value::Utf8(s)) = value {
+                        data.extend_from_slice(s.as_bytes());
+                    }
+                    offsets.extend_from_slice(&(data.len() as i32).to_le_bytes());
+                }
+                push_buffer(&mut body, offsets);
+                push_buffer(&mut body, data);
+            }
+            DataType::Timestamp => {
+                let mut data = Vec::with_capacity(8 * values.len());
+                for value in &values {
+                    let value = match value {
+                        Some(Value::Timestamp(v)) => *v,
+                        _ => 0,
+                    };
+                    data.extend_from_slice(&value.to_le_bytes());
+                }
+                push_buffer(&mut body, data);
+            }
+        }
+    }
+
+    let header = Node::Table(vec![
+        Some(Scalar::I64(rows.len() as i64)),
+        Some(Scalar::Offset(Node::LongPairs(nodes))),
+        Some(Scalar::Offset(Node::LongPairs(buffers))),
+    ]);
+
+    encapsulate(HEADER_RECORD_BATCH, header, body)
+}
+
+const fn field(name: &'static str, data_type: DataType, nullable: bool) -> Field {
+    Field {
+        name,
+        data_type,
+        nullable,
+    }
+}
+
+/// The columns of `FlatMftEntry`, with the same names as in CSV output.
+pub const FLAT_SCHEMA: [Field; 38] = [
+    field("Signature", DataType::Utf8, false),
+    field("EntryId", DataType::UInt64, false),
+    field("Sequence", DataType::UInt16, false),
+    field("BaseEntryId", DataType::UInt64, false),
+    field("BaseEntrySequence", DataType::UInt16, false),
+    field("HardLinkCount", DataType::UInt16, false),
+    field("Flags", DataType::Utf8, false),
+    field("UsedEntrySize", DataType::UInt32, false),
+    field("TotalEntrySize", DataType::UInt32, false),
+    field("FileSize", DataType::UInt64, false),
+    field("AllocatedSize", DataType::UInt64, false),
+    field("ValidDataLength", DataType::UInt64, false),
+    field("DataFragments", DataType::UInt64, true),
+    field("IsADirectory", DataType::Bool, false),
+    field("IsDeleted", DataType::Bool, false),
+    field("IsCompressed", DataType::Bool, false),
+    field("IsEncrypted", DataType::Bool, false),
+    field("IsSparse", DataType::Bool, false),
+    field("HasAlternateDataStreams", DataType::Bool, false),
+    field("StandardInfoFlags", DataType::Utf8, true),
+    field("StandardInfoFlagsValue", DataType::UInt32, true),
+    field("StandardInfoFlagNames", DataType::Utf8, true),
+    field("StandardInfoLastModified", DataType::Timestamp, true),
+    field("StandardInfoLastAccess", DataType::Timestamp, true),
+    field("StandardInfoCreated", DataType::Timestamp, true),
+    field("FileNameFlags", DataType::Utf8, true),
+    field("FileNameFlagsValue", DataType::UInt32, true),
+    field("FileNameFlagNames", DataType::Utf8, true),
+    field("FileNameLastModified", DataType::Timestamp, true),
+    field("FileNameLastAccess", DataType::Timestamp, true),
+    field("FileNameCreated", DataType::Timestamp, true),
+    field("StandardInfoZeroFraction", DataType::Bool, false),
+    field("EpochTimestamp", DataType::Bool, false),
+    field("FullPath", DataType::Utf8, false),
+    field("LinkTarget", DataType::Utf8, true),
+    field("ResidentDataSha256", DataType::Utf8, true),
+    field("OwnerSid", DataType::Utf8, true),
+    field("GroupSid", DataType::Utf8, true),
+];
+
+fn timestamp(timestamp: &Option<Timestamp>) -> Option<Value> {
+    timestamp.map(|t| {
+        let t = t.datetime();
+        Value::Timestamp(t.timestamp() * 1_000_000 + i64::from(t.timestamp_subsec_micros()))
+    })
+}
+
+fn utf8(s: &Option<String>) -> Option<Value> {
+    s.clone().map(Value::Utf8)
+}
+
+/// The values of an entry, in the order of `FLAT_SCHEMA`.
+pub fn flat_row(entry: &FlatMftEntry) -> Vec<Option<Value>> {
+    vec![
+        Some(Value::Utf8(entry.signature.clone())),
+        Some(Value::UInt(entry.entry_id)),
+        Some(Value::UInt(u64::from(entry.sequence))),
+        Some(Value::UInt(entry.base_entry_id)),
+        Some(Value::UInt(u64::from(entry.base_entry_sequence))),
+        Some(Value::UInt(u64::from(entry.hard_link_count))),
+        // Flags are formatted like in serialized output.
+        Some(Value::Utf8(format!("{:?}", entry.flags))),
+        Some(Value::UInt(u64::from(entry.used_entry_size))),
+        Some(Value::UInt(u64::from(entry.total_entry_size))),
+        Some(Value::UInt(entry.file_size)),
+        Some(Value::UInt(entry.allocated_size)),
+        Some(Value::UInt(entry.valid_data_length)),
+        entry.data_fragments.map(Value::UInt),
+        Some(Value::Bool(entry.is_a_directory)),
+        Some(Value::Bool(entry.is_deleted)),
+        Some(Value::Bool(entry.is_compressed)),
+        Some(Value::Bool(entry.is_encrypted)),
+        Some(Value::Bool(entry.is_sparse)),
+        Some(Value::Bool(entry.has_alternate_data_streams)),
+        entry.standard_info_flags.map(|f| Value::Utf8(format!("{:?}", f))),
+        entry.standard_info_flags_value.map(|v| Value::UInt(u64::from(v))),
+        utf8(&entry.standard_info_flag_names),
+        timestamp(&entry.standard_info_last_modified),
+        timestamp(&entry.standard_info_last_access),
+        timestamp(&entry.standard_info_created),
+        entry.file_name_flags.map(|f| Value::Utf8(format!("{:?}", f))),
+        entry.file_name_flags_value.map(|v| Value::UInt(u64::from(v))),
+        utf8(&entry.file_name_flag_names),
+        timestamp(&entry.file_name_last_modified),
+        timestamp(&entry.file_name_last_access),
+        timestamp(&entry.file_name_created),
+        Some(Value::Bool(entry.standard_info_zero_fraction)),
+        Some(Value::Bool(entry.epoch_timestamp)),
+        Some(Value::Utf8(entry.full_path.to_string_lossy().to_string())),
+        utf8(&entry.link_target),
+        utf8(&entry.resident_data_sha256),
+        utf8(&entry.owner_sid),
+        utf8(&entry.group_sid),
+    ]
+}
@@ -1,21 +1,64 @@
 use mft::attribute::header::ResidentialHeader;
-use mft::attribute::raw::RawAttribute;
 use mft::attribute::x40::ObjectIdAttr;
-use mft::attribute::x80::DataAttr;
 use mft::attribute::x90::IndexRootAttr;
-use mft::attribute::MftAttributeContent;
+use mft::attribute::{AttributeDataFlags, MftAttributeContent, MftAttributeType};
 use mft::{FileNameAttr, MftAttribute, StandardInfoAttr};
 
 use num_traits::cast::ToPrimitive;
 
 use mft::attribute::x20::{AttributeListAttr, AttributeListEntry};
 use pyo3::prelude::*;
-use pyo3::{ffi, Py, PyIterProtocol, PyResult, Python, ToPyObject};
+use pyo3::types::{PyBytes, PyDict};
+use pyo3::{ffi, AsPyPointer, Py, PyBufferProtocol, PyIterProtocol, PyObjectProtocol, PyResult, Python, ToPyObject};
 
-use crate::utils::date_to_pyobject;
+use crate::ea::{parse_extended_attributes, EaInformation};
+use crate::entry::{file_name_bytes, name_was_truncated};
+use crate::flags::file_attribute_names;
+use crate::utils::{date_to_pyobject, fill_read_only_buffer, py_repr, utf16_to_string};
+
+use std::ops::Range;
+use std::os::raw::c_int;
+use std::sync::Arc;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+/// A range of a buffer which is shared by an attribute and its content,
+/// so that the content is exported in place by the buffer protocol, without copies.
+#[derive(Clone)]
+struct SharedBytes {
+    buffer: Arc<[u8]>,
+    range: Range<usize>,
+}
+
+impl SharedBytes {
+    fn new(data: &[u8]) -> Self {
+        SharedBytes {
+            buffer: Arc::from(data),
+            range: 0..data.len(),
+        }
+    }
+
+    fn empty() -> Self {
+        SharedBytes::new(&[])
+    }
+
+    /// A range of these bytes, sharing their buffer.
+    fn slice(&self, range: Range<usize>) -> Self {
+        SharedBytes {
+            buffer: Arc::clone(&self.buffer),
+            range: self.range.start + range.start..self.range.start + range.end,
+        }
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.buffer[self.range.clone()]
+    }
+}
 
 #[pyclass]
 pub struct PyMftAttribute {
+    /// The parsed attribute, without the copy of the content of $DATA and unparsed attributes,
+    /// which is read from `raw` instead.
     inner: MftAttribute,
     /// Hex value of attribute type
     #[pyo3(get)]
@@ -28,14 +71,71 @@ pub struct PyMftAttribute {
     pub name: String,
     #[pyo3(get)]
     pub data_flags: String,
+    /// Raw attribute header flags (compression unit, encrypted, sparse)
+    #[pyo3(get)]
+    pub flags: u16,
+    #[pyo3(get)]
+    pub is_compressed: bool,
+    #[pyo3(get)]
+    pub is_encrypted: bool,
+    #[pyo3(get)]
+    pub is_sparse: bool,
     #[pyo3(get)]
     pub is_resident: bool,
     #[pyo3(get)]
     pub data_size: u32,
+    /// Attribute instance id, unique within the entry
+    #[pyo3(get)]
+    pub instance: u16,
+    /// Offset of the resident content from the start of the attribute, or None if it is non-resident
+    #[pyo3(get)]
+    pub content_offset: Option<u16>,
+    /// Length of the resident content, or None if it is non-resident
+    #[pyo3(get)]
+    pub content_length: Option<u32>,
+    /// When parsing leniently, the error which prevented parsing the attribute content.
+    /// Its content is then returned as `PyMftAttributeOther`.
+    #[pyo3(get)]
+    pub parse_error: Option<String>,
+    /// The bytes of the attribute header, followed by the resident content or the run list.
+    raw: SharedBytes,
+    /// Where the content starts in `raw`.
+    raw_content_offset: usize,
+    lossy_names: bool,
 }
 
 impl PyMftAttribute {
-    pub fn from_mft_attribute(py: Python, attr: MftAttribute) -> PyResult<Py<Self>> {
+    /// The resident content, or the run list, sharing the buffer of the attribute.
+    fn shared_content(&self) -> SharedBytes {
+        self.raw.slice(self.raw_content_offset..self.raw.as_slice().len())
+    }
+
+    pub fn from_mft_attribute(
+        py: Python,
+        mut attr: MftAttribute,
+        parse_error: Option<String>,
+        record: &[u8],
+        lossy_names: bool,
+    ) -> PyResult<Py<Self>> {
+        let (content_offset, content_size) = match &attr.header.residential_header {
+            ResidentialHeader::Resident(resident) => {
+                (resident.data_offset as usize, resident.data_size as usize)
+            }
+            ResidentialHeader::NonResident(non_resident) => {
+                let offset = non_resident.datarun_offset as usize;
+                (offset, record.len().saturating_sub(offset))
+            }
+        };
+        let content_offset = content_offset.min(record.len());
+        let content_end = (content_offset + content_size).min(record.len());
+
+        match &mut attr.data {
+            // Resident $DATA is read from `raw` (and non-resident $DATA has no content).
+            MftAttributeContent::AttrX80(_) => attr.data = MftAttributeContent::None,
+            MftAttributeContent::Raw(raw) => raw.data = vec![],
+            _ => {}
+        }
+
         Py::new(
             py,
             PyMftAttribute {
@@ -43,6 +143,10 @@ impl PyMftAttribute {
                 type_code: attr.header.type_code.to_u32().unwrap(),
                 name: attr.header.name.clone(),
                 data_flags: format!("{:?}", &attr.header.data_flags),
+                flags: attr.header.data_flags.bits(),
+                is_compressed: attr.header.data_flags.intersects(AttributeDataFlags::COMPRESSION_MASK),
+                is_encrypted: attr.header.data_flags.contains(AttributeDataFlags::ENCRYPTED),
+                is_sparse: attr.header.data_flags.contains(AttributeDataFlags::SPARSE),
                 is_resident: {
                     matches!(
                         attr.header.residential_header,
@@ -50,6 +154,19 @@ impl PyMftAttribute {
                     )
                 },
                 data_size: attr.header.record_length,
+                instance: attr.header.instance,
+                content_offset: match &attr.header.residential_header {
+                    ResidentialHeader::Resident(resident) => Some(resident.data_offset),
+                    ResidentialHeader::NonResident(_) => None,
+                },
+                content_length: match &attr.header.residential_header {
+                    ResidentialHeader::Resident(resident) => Some(resident.data_size),
+                    ResidentialHeader::NonResident(_) => None,
+                },
+                parse_error,
+                raw: SharedBytes::new(&record[..content_end]),
+                raw_content_offset: content_offset,
+                lossy_names,
                 inner: attr,
             },
         )
@@ -65,71 +182,146 @@ impl PyMftAttribute {
     /// - `PyMftAttributeX40`
     /// - `PyMftAttributeX80`
     /// - `PyMftAttributeX90`
+    /// - `PyMftAttributeXD0`
+    /// - `PyMftAttributeXE0`
     /// - `PyMftAttributeOther` (Currently unparsed in rust)
-    /// - `None` (if attribute content is non-resident)
+    /// - `None` (if attribute content is non-resident, except for $DATA which is a `PyMftAttributeX80` without data)
     #[getter]
     pub fn attribute_content(&self) -> PyResult<PyObject> {
         let gil = Python::acquire_gil();
         let py = gil.python();
 
+        let content = self.raw_content();
+
         Ok(match &self.inner.data {
             MftAttributeContent::AttrX10(info) => {
-                PyMftAttributeX10::from_x10(py, info.clone())?.to_object(py)
+                PyMftAttributeX10::from_x10(py, info.clone(), content)?.to_object(py)
             }
             MftAttributeContent::AttrX20(info) => {
                 PyMftAttributeX20::from_x20(py, info.clone())?.to_object(py)
             }
             MftAttributeContent::AttrX30(info) => {
-                PyMftAttributeX30::from_x30(py, info.clone())?.to_object(py)
+                PyMftAttributeX30::from_x30(py, info.clone(), content, self.lossy_names)?.to_object(py)
             }
             MftAttributeContent::AttrX40(info) => {
-                PyMftAttributeX40::from_x40(py, info.clone())?.to_object(py)
-            }
-            MftAttributeContent::AttrX80(info) => {
-                PyMftAttributeX80::from_x80(py, info.clone())?.to_object(py)
+                PyMftAttributeX40::from_x40(py, info.clone(), content)?.to_object(py)
             }
             MftAttributeContent::AttrX90(info) => {
                 PyMftAttributeX90::from_x90(py, info.clone())?.to_object(py)
             }
-            MftAttributeContent::Raw(raw) => {
-                PyMftAttributeOther::from_raw(py, raw.clone())?.to_object(py)
+            MftAttributeContent::Raw(raw) => match raw.attribute_type {
+                MftAttributeType::EaInformation => match EaInformation::from_buffer(content) {
+                    Some(info) => PyMftAttributeXD0::from_xd0(py, info)?.to_object(py),
+                    None => {
+                        PyMftAttributeOther::from_raw(py, &raw.attribute_type, self.shared_content())?.to_object(py)
+                    }
+                },
+                MftAttributeType::EA => PyMftAttributeXE0::from_xe0(py, content)?.to_object(py),
+                _ => PyMftAttributeOther::from_raw(py, &raw.attribute_type, self.shared_content())?.to_object(py),
+            },
+            // $DATA content is read from `raw`, non-resident $DATA has none but still has the sizes of the stream.
+            MftAttributeContent::AttrX80(_) | MftAttributeContent::None
+                if self.inner.header.type_code == MftAttributeType::DATA =>
+            {
+                let data = if self.is_resident { self.shared_content() } else { SharedBytes::empty() };
+                PyMftAttributeX80::from_x80(py, data, &self.inner.header.residential_header)?.to_object(py)
             }
-            MftAttributeContent::None => unsafe { PyObject::from_borrowed_ptr(py, ffi::Py_None()) },
+            MftAttributeContent::AttrX80(_) | MftAttributeContent::None => unsafe {
+                PyObject::from_borrowed_ptr(py, ffi::Py_None())
+            },
         })
     }
+
+    /// The bytes of the attribute header, including the attribute name.
+    #[getter]
+    pub fn raw_header(&self) -> &[u8] {
+        &self.raw.as_slice()[..self.raw_content_offset]
+    }
+
+    /// The bytes of the attribute content if it is resident, or of its run list (mapping pairs) if it is not.
+    #[getter]
+    pub fn raw_content(&self) -> &[u8] {
+        &self.raw.as_slice()[self.raw_content_offset..]
+    }
+
+    /// to_dict(self, /)
+    /// --
+    ///
+    /// Returns the header fields as a dict, with the content (`attribute_content.to_dict()`, or None) under `attribute_content`.
+    pub fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("type_code", self.type_code)?;
+        dict.set_item("type_name", &self.type_name)?;
+        dict.set_item("name", &self.name)?;
+        dict.set_item("data_flags", &self.data_flags)?;
+        dict.set_item("flags", self.flags)?;
+        dict.set_item("is_compressed", self.is_compressed)?;
+        dict.set_item("is_encrypted", self.is_encrypted)?;
+        dict.set_item("is_sparse", self.is_sparse)?;
+        dict.set_item("is_resident", self.is_resident)?;
+        dict.set_item("data_size", self.data_size)?;
+        dict.set_item("instance", self.instance)?;
+        dict.set_item("content_offset", self.content_offset)?;
+        dict.set_item("content_length", self.content_length)?;
+        dict.set_item("parse_error", &self.parse_error)?;
+        dict.set_item("raw_header", PyBytes::new(py, self.raw_header()))?;
+        dict.set_item("raw_content", PyBytes::new(py, self.raw_content()))?;
+
+        let content = self.attribute_content()?;
+        let content = if content.is_none(py) {
+            content
+        } else {
+            content.call_method0(py, "to_dict")?
+        };
+        dict.set_item("attribute_content", content)?;
+        Ok(dict.to_object(py))
+    }
 }
 
 #[pyclass]
 pub struct PyMftAttributeX10 {
     inner: StandardInfoAttr,
+    /// Maximum number of versions of the file (0 if versioning is disabled)
     #[pyo3(get)]
     pub max_version: u32,
+    /// Version number of the file
     #[pyo3(get)]
     pub version: u32,
     #[pyo3(get)]
     pub class_id: u32,
+    /// Owner id, an index into the quota table ($Extend\$Quota)
     #[pyo3(get)]
     pub owner_id: u32,
+    /// Security id, an index into the security descriptors of $Secure
     #[pyo3(get)]
     pub security_id: u32,
+    /// Number of bytes charged to the quota of the owner
     #[pyo3(get)]
     pub quota: u64,
+    /// Update sequence number of the last change, an offset into $Extend\$UsnJrnl:$J
     #[pyo3(get)]
     pub usn: u64,
 }
 
+/// Size of the content of $STANDARD_INFORMATION before NTFS 3.0, without the owner id, security id, quota and USN.
+const STANDARD_INFO_V1_SIZE: usize = 48;
+
 impl PyMftAttributeX10 {
-    pub fn from_x10(py: Python, attr: StandardInfoAttr) -> PyResult<Py<Self>> {
+    /// The fields added in NTFS 3.0 are 0 when the content has the size of NTFS 1.2,
+    /// because the mft crate reads them from the bytes which follow it.
+    pub fn from_x10(py: Python, attr: StandardInfoAttr, content: &[u8]) -> PyResult<Py<Self>> {
+        let extended = content.len() > STANDARD_INFO_V1_SIZE;
+
         Py::new(
             py,
             PyMftAttributeX10 {
                 max_version: attr.max_version,
                 version: attr.version,
                 class_id: attr.class_id,
-                owner_id: attr.owner_id,
-                security_id: attr.security_id,
-                quota: attr.quota,
-                usn: attr.usn,
+                owner_id: if extended { attr.owner_id } else { 0 },
+                security_id: if extended { attr.security_id } else { 0 },
+                quota: if extended { attr.quota } else { 0 },
+                usn: if extended { attr.usn } else { 0 },
                 inner: attr,
             },
         )
@@ -156,9 +348,64 @@ impl PyMftAttributeX10 {
         date_to_pyobject(&self.inner.accessed)
     }
 
+    /// The file attribute flags, as an integer (compatible with `enum.IntFlag`)
+    #[getter]
+    pub fn file_flags(&self) -> u32 {
+        self.inner.file_flags.bits()
+    }
+
+    /// flags_list(self, /)
+    /// --
+    ///
+    /// Returns the names of the file attribute flags which are set (`HIDDEN`, `SYSTEM`, `ARCHIVE`, ...).
+    pub fn flags_list(&self) -> Vec<&'static str> {
+        file_attribute_names(self.inner.file_flags)
+    }
+
+    /// Same as `max_version`
+    #[getter]
+    pub fn max_versions(&self) -> u32 {
+        self.max_version
+    }
+
+    /// Same as `version`
+    #[getter]
+    pub fn version_number(&self) -> u32 {
+        self.version
+    }
+
+    /// Same as `quota`
+    #[getter]
+    pub fn quota_charged(&self) -> u64 {
+        self.quota
+    }
+
+    /// Same as `usn`
     #[getter]
-    pub fn file_flags(&self) -> PyResult<String> {
-        Ok(format!("{:?}", self.inner.file_flags))
+    pub fn update_sequence_number(&self) -> u64 {
+        self.usn
+    }
+
+    /// to_dict(self, /)
+    /// --
+    ///
+    /// Returns the fields as a dict, with the names of the file attribute flags under `flags_list`.
+    pub fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("max_version", self.max_version)?;
+        dict.set_item("version", self.version)?;
+        dict.set_item("class_id", self.class_id)?;
+        dict.set_item("owner_id", self.owner_id)?;
+        dict.set_item("security_id", self.security_id)?;
+        dict.set_item("quota", self.quota)?;
+        dict.set_item("usn", self.usn)?;
+        dict.set_item("created", self.created()?)?;
+        dict.set_item("modified", self.modified()?)?;
+        dict.set_item("mft_modified", self.mft_modified()?)?;
+        dict.set_item("accessed", self.accessed()?)?;
+        dict.set_item("file_flags", self.file_flags())?;
+        dict.set_item("flags_list", self.flags_list())?;
+        Ok(dict.to_object(py))
     }
 }
 
@@ -166,10 +413,19 @@ impl PyMftAttributeX10 {
 pub struct PyMftAttributeX20Entry {
     #[pyo3(get)]
     pub attribute_type: u32,
+    /// First VCN of the attribute segment (0 for resident attributes, and for the first segment)
     #[pyo3(get)]
     pub lowest_vcn: u64,
     #[pyo3(get)]
     pub name: String,
+    /// Record holding the attribute, either the base record or an extension record
+    #[pyo3(get)]
+    pub segment_entry_id: u64,
+    #[pyo3(get)]
+    pub segment_entry_sequence: u16,
+    /// Instance (attribute id) of the attribute in that record
+    #[pyo3(get)]
+    pub attribute_id: u16,
 }
 
 impl PyMftAttributeX20Entry {
@@ -180,11 +436,33 @@ impl PyMftAttributeX20Entry {
                 attribute_type: attr.attribute_type,
                 lowest_vcn: attr.lowest_vcn,
                 name: attr.name.clone(),
+                segment_entry_id: attr.segment_reference.entry,
+                segment_entry_sequence: attr.segment_reference.sequence,
+                // Documented as reserved by the mft crate, this is the attribute id.
+                attribute_id: attr.reserved,
             },
         )
     }
 }
 
+#[pymethods]
+impl PyMftAttributeX20Entry {
+    /// to_dict(self, /)
+    /// --
+    ///
+    /// Returns the fields as a dict.
+    pub fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("attribute_type", self.attribute_type)?;
+        dict.set_item("lowest_vcn", self.lowest_vcn)?;
+        dict.set_item("name", &self.name)?;
+        dict.set_item("segment_entry_id", self.segment_entry_id)?;
+        dict.set_item("segment_entry_sequence", self.segment_entry_sequence)?;
+        dict.set_item("attribute_id", self.attribute_id)?;
+        Ok(dict.to_object(py))
+    }
+}
+
 #[pyclass]
 pub struct PyMftAttributeX20 {
     inner: AttributeListAttr,
@@ -242,6 +520,23 @@ impl PyMftAttributeX20 {
             },
         )
     }
+
+    /// to_dict(self, /)
+    /// --
+    ///
+    /// Returns the entries of the list as dicts, under `entries`.
+    pub fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let entries = self
+            .inner
+            .entries
+            .iter()
+            .map(|entry| PyMftAttributeX20Entry::from_x20_entry(py, entry)?.borrow(py).to_dict(py))
+            .collect::<PyResult<Vec<_>>>()?;
+
+        let dict = PyDict::new(py);
+        dict.set_item("entries", entries)?;
+        Ok(dict.to_object(py))
+    }
 }
 
 #[pyclass]
@@ -261,10 +556,13 @@ pub struct PyMftAttributeX30 {
     pub namespace: String,
     #[pyo3(get)]
     pub name: String,
+    name_bytes: Vec<u8>,
 }
 
 impl PyMftAttributeX30 {
-    pub fn from_x30(py: Python, attr: FileNameAttr) -> PyResult<Py<Self>> {
+    pub fn from_x30(py: Python, attr: FileNameAttr, content: &[u8], lossy_names: bool) -> PyResult<Py<Self>> {
+        let name_bytes = file_name_bytes(content).to_vec();
+
         Py::new(
             py,
             PyMftAttributeX30 {
@@ -274,7 +572,12 @@ impl PyMftAttributeX30 {
                 namespace: format!("{:?}", &attr.namespace),
                 parent_entry_id: attr.parent.entry,
                 parent_entry_sequence: attr.parent.sequence,
-                name: attr.name.clone(),
+                name: if lossy_names && name_was_truncated(&attr) {
+                    utf16_to_string(&name_bytes)
+                } else {
+                    attr.name.clone()
+                },
+                name_bytes,
                 inner: attr,
             },
         )
@@ -305,6 +608,57 @@ impl PyMftAttributeX30 {
     pub fn flags(&self) -> PyResult<String> {
         Ok(format!("{:?}", self.inner.flags))
     }
+
+    /// The file attribute flags, as an integer (compatible with `enum.IntFlag`)
+    #[getter]
+    pub fn file_flags(&self) -> u32 {
+        self.inner.flags.bits()
+    }
+
+    /// flags_list(self, /)
+    /// --
+    ///
+    /// Returns the names of the file attribute flags which are set (`HIDDEN`, `SYSTEM`, `ARCHIVE`, ...).
+    pub fn flags_list(&self) -> Vec<&'static str> {
+        file_attribute_names(self.inner.flags)
+    }
+
+    /// The reference to the parent directory, as a `(record_number, sequence_number)` tuple.
+    #[getter]
+    pub fn parent(&self) -> (u64, u16) {
+        (self.parent_entry_id, self.parent_entry_sequence)
+    }
+
+    /// The raw UTF-16LE bytes of the name, as stored in the record.
+    #[getter]
+    pub fn name_bytes(&self) -> &[u8] {
+        &self.name_bytes
+    }
+
+    /// to_dict(self, /)
+    /// --
+    ///
+    /// Returns the fields as a dict.
+    pub fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("parent_entry_id", self.parent_entry_id)?;
+        dict.set_item("parent_entry_sequence", self.parent_entry_sequence)?;
+        dict.set_item("logical_size", self.logical_size)?;
+        dict.set_item("physical_size", self.physical_size)?;
+        dict.set_item("reparse_value", self.reparse_value)?;
+        dict.set_item("namespace", &self.namespace)?;
+        dict.set_item("name", &self.name)?;
+        dict.set_item("name_bytes", PyBytes::new(py, &self.name_bytes))?;
+        dict.set_item("created", self.created()?)?;
+        dict.set_item("modified", self.modified()?)?;
+        dict.set_item("mft_modified", self.mft_modified()?)?;
+        dict.set_item("accessed", self.accessed()?)?;
+        dict.set_item("flags", self.flags()?)?;
+        dict.set_item("file_flags", self.file_flags())?;
+        dict.set_item("flags_list", self.flags_list())?;
+        dict.set_item("parent", self.parent())?;
+        Ok(dict.to_object(py))
+    }
 }
 
 #[pyclass]
@@ -321,10 +675,18 @@ pub struct PyMftAttributeX40 {
     #[pyo3(get)]
     /// Domain in which object was created
     pub domain_id: String,
+    object_id_bytes: Vec<u8>,
+    birth_volume_id_bytes: Option<Vec<u8>>,
+    birth_object_id_bytes: Option<Vec<u8>>,
+    domain_id_bytes: Option<Vec<u8>>,
 }
 
 impl PyMftAttributeX40 {
-    pub fn from_x40(py: Python, attr: ObjectIdAttr) -> PyResult<Py<Self>> {
+    /// The bytes of the GUIDs are sliced from `content` (the resident content of the attribute),
+    /// where they are stored one after the other.
+    pub fn from_x40(py: Python, attr: ObjectIdAttr, content: &[u8]) -> PyResult<Py<Self>> {
+        let guid_bytes = |index: usize| content.get(index * 16..(index + 1) * 16).map(<[u8]>::to_vec);
+
         Py::new(
             py,
             PyMftAttributeX40 {
@@ -344,27 +706,122 @@ impl PyMftAttributeX40 {
                     .as_ref()
                     .map(|a| a.to_string())
                     .unwrap_or_default(),
+                object_id_bytes: guid_bytes(0).unwrap_or_default(),
+                birth_volume_id_bytes: attr.birth_volume_id.as_ref().and(guid_bytes(1)),
+                birth_object_id_bytes: attr.birth_object_id.as_ref().and(guid_bytes(2)),
+                domain_id_bytes: attr.domain_id.as_ref().and(guid_bytes(3)),
             },
         )
     }
 }
 
+#[pymethods]
+impl PyMftAttributeX40 {
+    /// The 16 bytes of `object_id`, as stored on disk.
+    #[getter]
+    pub fn object_id_bytes(&self) -> &[u8] {
+        &self.object_id_bytes
+    }
+
+    /// The 16 bytes of `birth_volume_id`, or None if the attribute does not have it.
+    #[getter]
+    pub fn birth_volume_id_bytes(&self) -> Option<&[u8]> {
+        self.birth_volume_id_bytes.as_deref()
+    }
+
+    /// The 16 bytes of `birth_object_id`, or None if the attribute does not have it.
+    #[getter]
+    pub fn birth_object_id_bytes(&self) -> Option<&[u8]> {
+        self.birth_object_id_bytes.as_deref()
+    }
+
+    /// The 16 bytes of `domain_id`, or None if the attribute does not have it.
+    #[getter]
+    pub fn domain_id_bytes(&self) -> Option<&[u8]> {
+        self.domain_id_bytes.as_deref()
+    }
+
+    /// to_dict(self, /)
+    /// --
+    ///
+    /// Returns the fields as a dict.
+    pub fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let bytes = |bytes: &Option<Vec<u8>>| bytes.as_ref().map(|bytes| PyBytes::new(py, bytes));
+
+        let dict = PyDict::new(py);
+        dict.set_item("object_id", &self.object_id)?;
+        dict.set_item("birth_volume_id", &self.birth_volume_id)?;
+        dict.set_item("birth_object_id", &self.birth_object_id)?;
+        dict.set_item("domain_id", &self.domain_id)?;
+        dict.set_item("object_id_bytes", PyBytes::new(py, &self.object_id_bytes))?;
+        dict.set_item("birth_volume_id_bytes", bytes(&self.birth_volume_id_bytes))?;
+        dict.set_item("birth_object_id_bytes", bytes(&self.birth_object_id_bytes))?;
+        dict.set_item("domain_id_bytes", bytes(&self.domain_id_bytes))?;
+        Ok(dict.to_object(py))
+    }
+}
+
 #[pyclass]
 pub struct PyMftAttributeX80 {
-    inner: DataAttr,
+    data: SharedBytes,
+    /// Size of the stream
+    #[pyo3(get)]
+    pub file_size: u64,
+    /// Size of the clusters allocated to the stream, 0 if it is resident
+    #[pyo3(get)]
+    pub allocated_size: u64,
+    /// Valid data length (initialized size) of the stream, reads past it return zeros.
+    /// Sizes are only valid on the first segment of a non-resident stream (whose `lowest_vcn` is 0).
+    #[pyo3(get)]
+    pub valid_data_length: u64,
 }
 
 impl PyMftAttributeX80 {
-    pub fn from_x80(py: Python, attr: DataAttr) -> PyResult<Py<Self>> {
-        Py::new(py, PyMftAttributeX80 { inner: attr })
+    fn from_x80(py: Python, data: SharedBytes, header: &ResidentialHeader) -> PyResult<Py<Self>> {
+        let (file_size, allocated_size, valid_data_length) = match header {
+            ResidentialHeader::Resident(resident) => {
+                let size = u64::from(resident.data_size);
+                (size, 0, size)
+            }
+            ResidentialHeader::NonResident(non_resident) => (
+                non_resident.file_size,
+                non_resident.allocated_length,
+                non_resident.valid_data_length,
+            ),
+        };
+
+        Py::new(
+            py,
+            PyMftAttributeX80 {
+                data,
+                file_size,
+                allocated_size,
+                valid_data_length,
+            },
+        )
     }
 }
 
 #[pymethods]
 impl PyMftAttributeX80 {
+    /// The content of the stream if it is resident, empty otherwise.
+    /// `memoryview(attribute)` reads the content in place, without the copy of this function.
     #[getter]
     pub fn data(&self) -> &[u8] {
-        self.inner.data()
+        self.data.as_slice()
+    }
+
+    /// to_dict(self, /)
+    /// --
+    ///
+    /// Returns the fields as a dict, with a copy of the content under `data`.
+    pub fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("data", PyBytes::new(py, self.data()))?;
+        dict.set_item("file_size", self.file_size)?;
+        dict.set_item("allocated_size", self.allocated_size)?;
+        dict.set_item("valid_data_length", self.valid_data_length)?;
+        Ok(dict.to_object(py))
     }
 }
 
@@ -399,21 +856,323 @@ impl PyMftAttributeX90 {
     }
 }
 
+#[pymethods]
+impl PyMftAttributeX90 {
+    /// to_dict(self, /)
+    /// --
+    ///
+    /// Returns the fields as a dict.
+    pub fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("attribute_type", self.attribute_type)?;
+        dict.set_item("collation_rule", self.collation_rule)?;
+        dict.set_item("index_entry_size", self.index_entry_size)?;
+        dict.set_item("index_entry_number_of_cluster_blocks", self.index_entry_number_of_cluster_blocks)?;
+        Ok(dict.to_object(py))
+    }
+}
+
+#[pyclass]
+pub struct PyMftAttributeXD0 {
+    /// Size of the packed extended attributes
+    #[pyo3(get)]
+    pub packed_ea_size: u16,
+    /// Number of extended attributes with the NEED_EA flag
+    #[pyo3(get)]
+    pub need_ea_count: u16,
+    /// Size of the unpacked extended attributes
+    #[pyo3(get)]
+    pub unpacked_ea_size: u32,
+}
+
+impl PyMftAttributeXD0 {
+    pub fn from_xd0(py: Python, info: EaInformation) -> PyResult<Py<Self>> {
+        Py::new(
+            py,
+            PyMftAttributeXD0 {
+                packed_ea_size: info.packed_ea_size,
+                need_ea_count: info.need_ea_count,
+                unpacked_ea_size: info.unpacked_ea_size,
+            },
+        )
+    }
+}
+
+#[pymethods]
+impl PyMftAttributeXD0 {
+    /// to_dict(self, /)
+    /// --
+    ///
+    /// Returns the fields as a dict.
+    pub fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("packed_ea_size", self.packed_ea_size)?;
+        dict.set_item("need_ea_count", self.need_ea_count)?;
+        dict.set_item("unpacked_ea_size", self.unpacked_ea_size)?;
+        Ok(dict.to_object(py))
+    }
+}
+
+#[pyclass]
+pub struct PyMftAttributeXE0 {
+    /// List of `PyExtendedAttribute`
+    #[pyo3(get)]
+    pub extended_attributes: Vec<Py<PyExtendedAttribute>>,
+}
+
+impl PyMftAttributeXE0 {
+    pub fn from_xe0(py: Python, data: &[u8]) -> PyResult<Py<Self>> {
+        let extended_attributes = parse_extended_attributes(data)
+            .into_iter()
+            .map(|ea| {
+                Py::new(
+                    py,
+                    PyExtendedAttribute {
+                        name: ea.name,
+                        flags: ea.flags,
+                        value: ea.value,
+                    },
+                )
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+
+        Py::new(py, PyMftAttributeXE0 { extended_attributes })
+    }
+}
+
+#[pymethods]
+impl PyMftAttributeXE0 {
+    /// to_dict(self, /)
+    /// --
+    ///
+    /// Returns the extended attributes as dicts, under `extended_attributes`.
+    pub fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let extended_attributes = self
+            .extended_attributes
+            .iter()
+            .map(|ea| ea.borrow(py).to_dict(py))
+            .collect::<PyResult<Vec<_>>>()?;
+
+        let dict = PyDict::new(py);
+        dict.set_item("extended_attributes", extended_attributes)?;
+        Ok(dict.to_object(py))
+    }
+}
+
+#[pyclass]
+pub struct PyExtendedAttribute {
+    #[pyo3(get)]
+    pub name: String,
+    /// 0x80 if the file cannot be interpreted without understanding this extended attribute (NEED_EA)
+    #[pyo3(get)]
+    pub flags: u8,
+    value: Vec<u8>,
+}
+
+#[pymethods]
+impl PyExtendedAttribute {
+    /// The value of the extended attribute, also readable in place with `memoryview(attribute)`.
+    #[getter]
+    pub fn value(&self) -> &[u8] {
+        &self.value
+    }
+
+    /// to_dict(self, /)
+    /// --
+    ///
+    /// Returns the fields as a dict, with a copy of the value under `value`.
+    pub fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("name", &self.name)?;
+        dict.set_item("flags", self.flags)?;
+        dict.set_item("value", PyBytes::new(py, &self.value))?;
+        Ok(dict.to_object(py))
+    }
+}
+
 #[pyclass]
 pub struct PyMftAttributeOther {
-    inner: RawAttribute,
+    attribute_type: MftAttributeType,
+    data: SharedBytes,
 }
 
 impl PyMftAttributeOther {
-    pub fn from_raw(py: Python, attr: RawAttribute) -> PyResult<Py<Self>> {
-        Py::new(py, PyMftAttributeOther { inner: attr })
+    fn from_raw(py: Python, attribute_type: &MftAttributeType, data: SharedBytes) -> PyResult<Py<Self>> {
+        Py::new(
+            py,
+            PyMftAttributeOther {
+                attribute_type: attribute_type.clone(),
+                data,
+            },
+        )
     }
 }
 
 #[pymethods]
 impl PyMftAttributeOther {
+    /// The raw content of the attribute, also readable in place with `memoryview(attribute)`.
     #[getter]
     pub fn data(&self) -> &[u8] {
-        &self.inner.data
+        self.data.as_slice()
+    }
+
+    /// to_dict(self, /)
+    /// --
+    ///
+    /// Returns the type code of the attribute under `attribute_type`, and a copy of its content under `data`.
+    pub fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("attribute_type", self.attribute_type.to_u32())?;
+        dict.set_item("data", PyBytes::new(py, self.data()))?;
+        Ok(dict.to_object(py))
+    }
+}
+
+fn format_date(date: &DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+#[pyproto]
+impl PyObjectProtocol for PyMftAttribute {
+    fn __repr__(&self) -> String {
+        let name = if self.name.is_empty() {
+            String::new()
+        } else {
+            format!(" {}", py_repr(&self.name))
+        };
+        let residence = if self.is_resident { "resident" } else { "non-resident" };
+
+        format!("<MftAttribute {}{} {}>", self.type_name, name, residence)
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for PyMftAttributeX10 {
+    fn __repr__(&self) -> String {
+        format!(
+            "<MftAttributeX10 created={} modified={} flags={}>",
+            format_date(&self.inner.created),
+            format_date(&self.inner.modified),
+            file_attribute_names(self.inner.file_flags).join("|")
+        )
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for PyMftAttributeX20Entry {
+    fn __repr__(&self) -> String {
+        format!(
+            "<MftAttributeX20Entry type=0x{:x} name={} segment={}-{} lowest_vcn={}>",
+            self.attribute_type,
+            py_repr(&self.name),
+            self.segment_entry_id,
+            self.segment_entry_sequence,
+            self.lowest_vcn
+        )
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for PyMftAttributeX20 {
+    fn __repr__(&self) -> String {
+        format!("<MftAttributeX20 {} entries>", self.inner.entries.len())
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for PyMftAttributeX30 {
+    fn __repr__(&self) -> String {
+        format!(
+            "<MftAttributeX30 {} parent={}-{} {}>",
+            py_repr(&self.name),
+            self.parent_entry_id,
+            self.parent_entry_sequence,
+            self.namespace
+        )
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for PyMftAttributeX40 {
+    fn __repr__(&self) -> String {
+        format!("<MftAttributeX40 {}>", self.object_id)
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for PyMftAttributeX80 {
+    fn __repr__(&self) -> String {
+        format!(
+            "<MftAttributeX80 file_size={} allocated_size={}>",
+            self.file_size, self.allocated_size
+        )
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for PyMftAttributeX90 {
+    fn __repr__(&self) -> String {
+        format!(
+            "<MftAttributeX90 type=0x{:x} index_entry_size={}>",
+            self.attribute_type, self.index_entry_size
+        )
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for PyMftAttributeXD0 {
+    fn __repr__(&self) -> String {
+        format!(
+            "<MftAttributeXD0 packed_ea_size={} need_ea_count={}>",
+            self.packed_ea_size, self.need_ea_count
+        )
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for PyMftAttributeXE0 {
+    fn __repr__(&self) -> String {
+        format!("<MftAttributeXE0 {} extended attributes>", self.extended_attributes.len())
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for PyExtendedAttribute {
+    fn __repr__(&self) -> String {
+        format!("<ExtendedAttribute {} {} bytes>", py_repr(&self.name), self.value.len())
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for PyMftAttributeOther {
+    fn __repr__(&self) -> String {
+        format!("<MftAttributeOther {:?} {} bytes>", self.attribute_type, self.data().len())
     }
 }
+
+#[pyproto]
+impl PyBufferProtocol for PyMftAttributeX80 {
+    fn bf_getbuffer(slf: PyRefMut<Self>, view: *mut ffi::Py_buffer, flags: c_int) -> PyResult<()> {
+        fill_read_only_buffer(slf.as_ptr(), slf.data(), view, flags)
+    }
+
+    fn bf_releasebuffer(_slf: PyRefMut<Self>, _view: *mut ffi::Py_buffer) {}
+}
+
+#[pyproto]
+impl PyBufferProtocol for PyExtendedAttribute {
+    fn bf_getbuffer(slf: PyRefMut<Self>, view: *mut ffi::Py_buffer, flags: c_int) -> PyResult<()> {
+        fill_read_only_buffer(slf.as_ptr(), &slf.value, view, flags)
+    }
+
+    fn bf_releasebuffer(_slf: PyRefMut<Self>, _view: *mut ffi::Py_buffer) {}
+}
+
+#[pyproto]
+impl PyBufferProtocol for PyMftAttributeOther {
+    fn bf_getbuffer(slf: PyRefMut<Self>, view: *mut ffi::Py_buffer, flags: c_int) -> PyResult<()> {
+        fill_read_only_buffer(slf.as_ptr(), slf.data(), view, flags)
+    }
+
+    fn bf_releasebuffer(_slf: PyRefMut<Self>, _view: *mut ffi::Py_buffer) {}
+}
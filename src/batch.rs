@@ -0,0 +1,205 @@
+//! Export of several MFTs (e.g. collected from many hosts) into a single output.
+//! The MFTs are exported by a single pool of worker threads (see `export_batch`),
+//! and every row is tagged with the source it was read from.
+use crate::export::{export_batch, ExportFormat, ExportInput, ExportOutput};
+use crate::path::{PathFormat, ORPHAN_PREFIX};
+use crate::PyMftParser;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use std::io;
+use std::sync::{Arc, Mutex};
+
+#[pyclass]
+pub struct PyMftBatch {
+    paths: Vec<PyObject>,
+    /// The identifiers of the MFTs, written in the `Source` column of every row
+    #[pyo3(get)]
+    sources: Vec<String>,
+    threads: Option<usize>,
+    strict: bool,
+}
+
+/// The path of a `str` or `os.PathLike` (such as `pathlib.Path`), None for file-like objects.
+fn fspath(py: Python, path: &PyObject) -> Option<String> {
+    let path = py.import("os").ok()?.call_method1("fspath", (path,)).ok()?;
+    path.extract().ok()
+}
+
+#[pymethods]
+impl PyMftBatch {
+    /// PyMftBatch(paths, sources=None, threads=None, strict=False, /)
+    /// --
+    ///
+    /// A batch of MFTs (paths, `pathlib.Path`s or file-like objects), which are exported together as a single output.
+    /// Every row starts with a `Source` column (a `source` key in JSON): the matching item of `sources`
+    /// (e.g. host names), or the path of the MFT if `sources` is not given.
+    /// The MFTs are parsed by a pool of `threads` worker threads (all the available cores by default),
+    /// which move on to the next MFT as soon as every chunk of the current one is taken, so that several MFTs
+    /// are parsed at once. An MFT is only open while it is being parsed. `strict` is the option of `PyMftParser`.
+    #[new]
+    #[args(sources = "None", threads = "None", strict = "false")]
+    fn new(
+        py: Python,
+        paths: Vec<PyObject>,
+        sources: Option<Vec<String>>,
+        threads: Option<usize>,
+        strict: bool,
+    ) -> PyResult<Self> {
+        if paths.is_empty() {
+            return Err(PyValueError::new_err("at least one MFT must be given"));
+        }
+
+        let sources = match sources {
+            Some(sources) if sources.len() != paths.len() => {
+                return Err(PyValueError::new_err(format!(
+                    "got {} sources for {} MFTs",
+                    sources.len(),
+                    paths.len()
+                )))
+            }
+            Some(sources) => sources,
+            None => paths
+                .iter()
+                .map(|path| match fspath(py, path) {
+                    Some(path) => Ok(path),
+                    None => Ok(path.as_ref(py).str()?.to_string()),
+                })
+                .collect::<PyResult<_>>()?,
+        };
+
+        Ok(PyMftBatch {
+            paths,
+            sources,
+            threads,
+            strict,
+        })
+    }
+
+    /// export_csv(self, path_or_file_like, compress=None, timestamp_format='iso', columns=None, /)
+    /// --
+    ///
+    /// Writes the entries of every MFT to a single CSV file, in the order of the MFTs, with a single header.
+    /// The options are the same as in `PyMftParser.export_csv`. Returns the number of written rows.
+    #[args(compress = "None", timestamp_format = "\"iso\"", columns = "None")]
+    fn export_csv(
+        &self,
+        py: Python,
+        path_or_file_like: PyObject,
+        compress: Option<String>,
+        timestamp_format: &str,
+        columns: Option<Vec<String>>,
+    ) -> PyResult<u64> {
+        self.export(py, ExportFormat::Csv, path_or_file_like, compress.as_deref(), timestamp_format, columns)
+    }
+
+    /// export_json(self, path_or_file_like, compress=None, timestamp_format='iso', /)
+    /// --
+    ///
+    /// Writes the entries of every MFT as JSON lines, as in `PyMftParser.export_json`.
+    /// Returns the number of written rows.
+    #[args(compress = "None", timestamp_format = "\"iso\"")]
+    fn export_json(
+        &self,
+        py: Python,
+        path_or_file_like: PyObject,
+        compress: Option<String>,
+        timestamp_format: &str,
+    ) -> PyResult<u64> {
+        self.export(py, ExportFormat::Json, path_or_file_like, compress.as_deref(), timestamp_format, None)
+    }
+
+    /// export_arrow_ipc(self, path_or_file_like, columns=None, /)
+    /// --
+    ///
+    /// Writes the entries of every MFT as a single Arrow IPC stream, as in `PyMftParser.export_arrow_ipc`,
+    /// with a first `Source` column. Returns the number of written rows.
+    #[args(columns = "None")]
+    fn export_arrow_ipc(&self, py: Python, path_or_file_like: PyObject, columns: Option<Vec<String>>) -> PyResult<u64> {
+        self.export(py, ExportFormat::ArrowIpc, path_or_file_like, None, "iso", columns)
+    }
+
+    /// export_parquet(self, path_or_file_like, columns=None, /)
+    /// --
+    ///
+    /// Writes the entries of every MFT as a single (uncompressed) Parquet file, with a first `Source` column,
+    /// and the columns and types of `export_arrow_ipc`. A row group is written every 1024 records.
+    /// Returns the number of written rows.
+    #[args(columns = "None")]
+    fn export_parquet(&self, py: Python, path_or_file_like: PyObject, columns: Option<Vec<String>>) -> PyResult<u64> {
+        self.export(py, ExportFormat::Parquet, path_or_file_like, None, "iso", columns)
+    }
+}
+
+impl PyMftBatch {
+    /// Opens the MFT at `index`, and returns it with the options to export it with.
+    fn input(
+        &self,
+        py: Python,
+        index: usize,
+        format: ExportFormat,
+        timestamp_format: &str,
+        columns: Option<Vec<String>>,
+    ) -> PyResult<ExportInput> {
+        let path = match fspath(py, &self.paths[index]) {
+            Some(path) => path.to_object(py),
+            None => self.paths[index].clone_ref(py),
+        };
+        let parser = PyMftParser::new(
+            path,
+            None,
+            "win32",
+            self.strict,
+            false,
+            None,
+            false,
+            false,
+            0,
+            None,
+            ORPHAN_PREFIX,
+        )?;
+        let context = parser.flat_context(timestamp_format, columns, PathFormat::default())?;
+        let (source, mut options) = parser.export_data(format, self.threads, context);
+        options.source = Some(self.sources[index].clone());
+
+        Ok(ExportInput { source, options })
+    }
+
+    fn export(
+        &self,
+        py: Python,
+        format: ExportFormat,
+        path_or_file_like: PyObject,
+        compress: Option<&str>,
+        timestamp_format: &str,
+        columns: Option<Vec<String>>,
+    ) -> PyResult<u64> {
+        let mut output = ExportOutput::open(path_or_file_like, compress)?;
+        let first = Arc::new(self.input(py, 0, format, timestamp_format, columns.clone())?);
+        let threads = first.options.threads;
+
+        // The first error opening one of the next MFTs (in a worker), raised as is rather than as an `OSError`.
+        let open_error = Mutex::new(None);
+        let open = |index| {
+            Python::with_gil(|py| self.input(py, index, format, timestamp_format, columns.clone())).map_err(|e| {
+                let message = format!("failed to open `{}`: {}", self.sources[index], e);
+                open_error
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .get_or_insert(e);
+                io::Error::other(message)
+            })
+        };
+
+        let writer = &mut output.writer;
+        let rows = py.allow_threads(|| export_batch(first, self.paths.len(), &open, threads, writer));
+        if let Some(e) = open_error.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner()) {
+            return Err(e);
+        }
+
+        let rows = rows?;
+        output.close()?;
+        Ok(rows)
+    }
+}
@@ -0,0 +1,187 @@
+//! Correlation of data runs with the cluster allocation bitmap of the volume ($Bitmap).
+use crate::entry::PyMftEntry;
+use crate::runlist::stream_runs;
+use crate::utils::FileOrFileLike;
+use crate::volume::{open_file_stream, SharedReader};
+use crate::ReadSeek;
+
+use pyo3::prelude::*;
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{BufReader, Read};
+
+const BITMAP_ENTRY: u64 = 6;
+
+/// The allocation status of every cluster of the volume, one bit per cluster.
+pub struct ClusterBitmap {
+    bits: Vec<u8>,
+}
+
+impl ClusterBitmap {
+    pub fn from_reader(mut reader: impl Read) -> std::io::Result<Self> {
+        let mut bits = vec![];
+        reader.read_to_end(&mut bits)?;
+
+        Ok(ClusterBitmap { bits })
+    }
+
+    pub fn cluster_count(&self) -> u64 {
+        self.bits.len() as u64 * 8
+    }
+
+    /// Returns None if the cluster is past the end of the bitmap.
+    pub fn is_allocated(&self, lcn: u64) -> Option<bool> {
+        let byte = self.bits.get(usize::try_from(lcn / 8).ok()?)?;
+        Some(byte & (1 << (lcn % 8)) != 0)
+    }
+
+    /// Counts the allocated clusters in `lcn..lcn + length`, ignoring those past the end of the bitmap.
+    pub fn count_allocated(&self, lcn: u64, length: u64) -> u64 {
+        let end = lcn.saturating_add(length).min(self.cluster_count());
+        let mut count = 0;
+        let mut cluster = lcn;
+
+        while cluster < end {
+            // Whole bytes at once.
+            if cluster.is_multiple_of(8) && end - cluster >= 8 {
+                count += u64::from(self.bits[(cluster / 8) as usize].count_ones());
+                cluster += 8;
+                continue;
+            }
+
+            if self.is_allocated(cluster) == Some(true) {
+                count += 1;
+            }
+            cluster += 1;
+        }
+
+        count
+    }
+}
+
+#[pyclass]
+/// PyVolumeBitmap(self, path_or_file_like, /)
+/// --
+///
+/// Parses an extracted $Bitmap stream, which holds the allocation status of every cluster of the volume.
+/// Works on both a path (string), or a file-like object.
+pub struct PyVolumeBitmap {
+    inner: ClusterBitmap,
+}
+
+#[pymethods]
+impl PyVolumeBitmap {
+    #[new]
+    fn new(path_or_file_like: PyObject) -> PyResult<Self> {
+        let inner = match FileOrFileLike::from_pyobject(path_or_file_like)? {
+            FileOrFileLike::File(s) => ClusterBitmap::from_reader(BufReader::new(File::open(s)?))?,
+            FileOrFileLike::FileLike(f) => ClusterBitmap::from_reader(f)?,
+            FileOrFileLike::Buffer(b) => ClusterBitmap::from_reader(b)?,
+        };
+
+        Ok(PyVolumeBitmap { inner })
+    }
+
+    /// from_volume(path_or_file_like, /)
+    /// --
+    ///
+    /// Reads the $Bitmap stream from a raw NTFS volume image.
+    #[staticmethod]
+    fn from_volume(path_or_file_like: PyObject) -> PyResult<Self> {
+        let boxed_read_seek = match FileOrFileLike::from_pyobject(path_or_file_like)? {
+            FileOrFileLike::File(s) => {
+                Box::new(BufReader::with_capacity(4096, File::open(s)?)) as Box<dyn ReadSeek + Send>
+            }
+            FileOrFileLike::FileLike(f) => Box::new(f) as Box<dyn ReadSeek + Send>,
+            FileOrFileLike::Buffer(b) => Box::new(b) as Box<dyn ReadSeek + Send>,
+        };
+
+        let (bitmap, _size) = open_file_stream(SharedReader::new(boxed_read_seek)?, BITMAP_ENTRY, "")?;
+
+        Ok(PyVolumeBitmap {
+            inner: ClusterBitmap::from_reader(bitmap)?,
+        })
+    }
+
+    /// Number of clusters described by the bitmap
+    #[getter]
+    fn cluster_count(&self) -> u64 {
+        self.inner.cluster_count()
+    }
+
+    /// is_allocated(self, lcn, /)
+    /// --
+    ///
+    /// Returns whether the cluster is allocated, or None if it is past the end of the bitmap.
+    fn is_allocated(&self, lcn: u64) -> Option<bool> {
+        self.inner.is_allocated(lcn)
+    }
+
+    /// check_entry(self, entry, stream_name='', /)
+    /// --
+    ///
+    /// Returns the current allocation status (`PyClusterAllocation`) of the clusters referenced by
+    /// the run list of a $DATA stream of the entry, or None if the stream is not non-resident.
+    /// If the entry is deleted, allocated clusters were reused by another file, so its content cannot be fully recovered.
+    /// The attributes of extension records are included if they were loaded with `PyMftEntry.resolve_extensions`.
+    #[args(stream_name = "\"\"")]
+    fn check_entry(&self, entry: PyRef<PyMftEntry>, stream_name: &str) -> Option<PyClusterAllocation> {
+        let runs = stream_runs(entry.records(), stream_name)?;
+
+        let mut allocation = PyClusterAllocation {
+            clusters: 0,
+            allocated_clusters: 0,
+            unallocated_clusters: 0,
+            out_of_range_clusters: 0,
+            runs: vec![],
+        };
+
+        for run in runs {
+            // Sparse runs do not reference any cluster.
+            let lcn = match run.lcn {
+                Some(lcn) => lcn,
+                None => continue,
+            };
+
+            let allocated = self.inner.count_allocated(lcn, run.length);
+            let in_range = lcn.saturating_add(run.length).min(self.inner.cluster_count()).saturating_sub(lcn);
+
+            allocation.clusters += run.length;
+            allocation.allocated_clusters += allocated;
+            allocation.unallocated_clusters += in_range - allocated;
+            allocation.out_of_range_clusters += run.length - in_range;
+            allocation.runs.push((lcn, run.length, allocated));
+        }
+
+        Some(allocation)
+    }
+}
+
+#[pyclass]
+pub struct PyClusterAllocation {
+    /// Number of clusters referenced by the run list, excluding sparse runs
+    #[pyo3(get)]
+    pub clusters: u64,
+    /// Clusters which are currently allocated
+    #[pyo3(get)]
+    pub allocated_clusters: u64,
+    /// Clusters which are currently free
+    #[pyo3(get)]
+    pub unallocated_clusters: u64,
+    /// Clusters past the end of the bitmap, which indicates a corrupted run list
+    #[pyo3(get)]
+    pub out_of_range_clusters: u64,
+    /// `(lcn, length, allocated_clusters)` of every run, excluding sparse runs
+    #[pyo3(get)]
+    pub runs: Vec<(u64, u64, u64)>,
+}
+
+#[pymethods]
+impl PyClusterAllocation {
+    /// Whether none of the clusters were reused, i.e. whether the content of a deleted entry can plausibly be recovered.
+    #[getter]
+    fn is_recoverable(&self) -> bool {
+        self.clusters > 0 && self.allocated_clusters == 0 && self.out_of_range_clusters == 0
+    }
+}
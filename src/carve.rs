@@ -0,0 +1,183 @@
+//! Carving of MFT entries from arbitrary data (memory dumps, unallocated space, ...).
+use crate::parser::{check_record_header, entry_from_buffer};
+use crate::path::NamePreference;
+use crate::utils::{u16_at, u32_at, FileOrFileLike};
+use crate::PyMftEntry;
+
+use mft::entry::{BAAD_HEADER, FILE_HEADER};
+use mft::MftEntry;
+use pyo3::prelude::*;
+use pyo3::PyIterProtocol;
+
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+
+/// Largest FILE record size, a full record must be available before trying to parse it.
+const MAX_RECORD_SIZE: usize = 4096;
+const READ_SIZE: usize = 1 << 20;
+
+/// Scans a stream for FILE/BAAD records.
+pub struct Carver<R: Read> {
+    reader: R,
+    window: Vec<u8>,
+    /// Offset in the stream of the first byte of `window`.
+    window_offset: u64,
+    position: usize,
+    eof: bool,
+}
+
+impl<R: Read> Carver<R> {
+    pub fn new(reader: R) -> Self {
+        Carver {
+            reader,
+            window: vec![],
+            window_offset: 0,
+            position: 0,
+            eof: false,
+        }
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        self.window.drain(..self.position);
+        self.window_offset += self.position as u64;
+        self.position = 0;
+
+        let mut chunk = vec![0_u8; READ_SIZE];
+        while !self.eof && self.window.len() < READ_SIZE + MAX_RECORD_SIZE {
+            match self.reader.read(&mut chunk) {
+                Ok(0) => self.eof = true,
+                Ok(n) => self.window.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    // Yield whatever was carved so far, and stop after the error.
+                    self.eof = true;
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the size of the record starting at `start`, if its header looks sane.
+    fn record_size(record: &[u8]) -> Option<usize> {
+        if record.len() < 0x30 {
+            return None;
+        }
+
+        let total_size = u32_at(record, 28) as usize;
+
+        let sane = (total_size == 1024 || total_size == 4096)
+            && total_size <= record.len()
+            && check_record_header(&record[..total_size]).is_ok();
+
+        if sane {
+            Some(total_size)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the next carved entry, and its offset in the stream.
+    pub fn next_entry(&mut self) -> io::Result<Option<(u64, MftEntry)>> {
+        loop {
+            let available = self.window.len().saturating_sub(3);
+            // Unless the stream is exhausted, keep enough data after a match to hold a full record.
+            let search_end = if self.eof {
+                available
+            } else {
+                self.window.len().saturating_sub(MAX_RECORD_SIZE)
+            };
+
+            while self.position < search_end {
+                let start = self.position;
+                let signature = &self.window[start..start + 4];
+
+                if signature == FILE_HEADER || signature == BAAD_HEADER {
+                    if let Some(size) = Self::record_size(&self.window[start..]) {
+                        let record = self.window[start..start + size].to_vec();
+                        // XP and later store the record number in the header.
+                        let entry_number = if u16_at(&record, 4) >= 0x30 {
+                            u64::from(u32_at(&record, 0x2C))
+                        } else {
+                            0
+                        };
+
+                        if let Ok(entry) = entry_from_buffer(record, entry_number) {
+                            self.position = start + size;
+                            return Ok(Some((self.window_offset + start as u64, entry)));
+                        }
+                    }
+                }
+
+                self.position += 1;
+            }
+
+            if self.eof {
+                return Ok(None);
+            }
+
+            self.fill()?;
+        }
+    }
+}
+
+/// carve_entries(buffer_or_file, /)
+/// --
+///
+/// Scans any data (a buffer such as bytes or a memoryview, a path, or a file-like object) for FILE/BAAD records, and returns an iterator
+/// that yields `(offset, PyMftEntry)` tuples for every record which parses.
+/// Carved entries have no parent information, so their `full_path` is only their own name.
+/// Since carved records are often partially overwritten, their attributes are parsed leniently.
+#[pyfunction]
+pub fn carve_entries(buffer_or_file: PyObject) -> PyResult<PyCarvedEntriesIterator> {
+    let reader = match FileOrFileLike::from_pyobject(buffer_or_file)? {
+        FileOrFileLike::File(s) => Box::new(BufReader::new(File::open(s)?)) as Box<dyn Read + Send>,
+        FileOrFileLike::FileLike(f) => Box::new(f) as Box<dyn Read + Send>,
+        FileOrFileLike::Buffer(b) => Box::new(b) as Box<dyn Read + Send>,
+    };
+
+    Ok(PyCarvedEntriesIterator {
+        inner: Carver::new(reader),
+    })
+}
+
+#[pyclass]
+pub struct PyCarvedEntriesIterator {
+    inner: Carver<Box<dyn Read + Send>>,
+}
+
+impl PyCarvedEntriesIterator {
+    fn next(&mut self) -> PyResult<Option<PyObject>> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        match self.inner.next_entry() {
+            Ok(Some((offset, entry))) => {
+                let name = NamePreference::Win32
+                    .pick(&entry)
+                    .map_or_else(|| "[UNKNOWN]".to_string(), |name| name.name);
+
+                let obj = match PyMftEntry::from_entry_with_path(py, entry, name, None, true) {
+                    Ok(entry) => (offset, entry).to_object(py),
+                    Err(e) => e.to_object(py),
+                };
+
+                Ok(Some(obj))
+            }
+            Ok(None) => Ok(None),
+            Err(e) => Ok(Some(PyErr::from(e).to_object(py))),
+        }
+    }
+}
+
+#[pyproto]
+impl PyIterProtocol for PyCarvedEntriesIterator {
+    fn __iter__(slf: PyRefMut<Self>) -> PyResult<Py<PyCarvedEntriesIterator>> {
+        Ok(slf.into())
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        slf.next()
+    }
+}
@@ -0,0 +1,155 @@
+//! Checkpoints of entry iterators, so that long iterations can be resumed (for example, after a crash).
+use crate::fields::FieldSelection;
+use crate::flat::FlatColumns;
+use crate::path::PathFormat;
+use crate::timestamp::TimestampFormat;
+use crate::{EntriesIteratorState, Output};
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// Incremented when the layout of the state changes.
+const CHECKPOINT_VERSION: u32 = 1;
+
+/// Everything needed to recreate an iterator over the same MFT, at the same position.
+pub struct Checkpoint {
+    pub number_of_entries: u64,
+    /// Position of the iterator, the record number of the next entry.
+    pub position: u64,
+    /// Results of the last entry which were not yielded yet, for outputs with several results per entry.
+    pub pending: usize,
+    pub output: Output,
+    pub csv_header_written: bool,
+    pub skip_empty: bool,
+    pub timestamps: TimestampFormat,
+    pub columns: Option<FlatColumns>,
+    pub path_format: PathFormat,
+}
+
+fn output_name(output: &Output) -> &'static str {
+    match output {
+        Output::Python => "python",
+        Output::CSV => "csv",
+        Output::JSON { .. } => "json",
+        Output::MsgPack { .. } => "msgpack",
+        Output::Timeline => "timeline",
+        Output::L2tCsv => "l2t_csv",
+        Output::Findings => "findings",
+        Output::TriageCsv => "triage_csv",
+        Output::Xml => "xml",
+    }
+}
+
+fn get_item<'a, T: FromPyObject<'a>>(state: &'a PyDict, key: &str) -> PyResult<T> {
+    state
+        .get_item(key)
+        .ok_or_else(|| PyValueError::new_err(format!("invalid iterator state, `{}` is missing", key)))?
+        .extract()
+}
+
+impl Checkpoint {
+    pub fn from_state(state: &EntriesIteratorState) -> PyResult<Self> {
+        if state.selection.is_some() || !state.filters.is_empty() {
+            return Err(PyValueError::new_err(
+                "the state of iterators with filters or over a selection of records cannot be saved",
+            ));
+        }
+
+        Ok(Checkpoint {
+            number_of_entries: state.total_number_of_records,
+            position: state.current_record,
+            pending: state.pending.len(),
+            output: state.output_format.clone(),
+            csv_header_written: state.csv_header_written,
+            skip_empty: state.skip_empty,
+            timestamps: state.context.timestamps,
+            columns: state.context.columns.clone(),
+            path_format: state.context.path_format.clone(),
+        })
+    }
+
+    pub fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("version", CHECKPOINT_VERSION)?;
+        dict.set_item("number_of_entries", self.number_of_entries)?;
+        dict.set_item("position", self.position)?;
+        dict.set_item("pending", self.pending)?;
+        dict.set_item("output", output_name(&self.output))?;
+        dict.set_item("csv_header_written", self.csv_header_written)?;
+        dict.set_item("skip_empty", self.skip_empty)?;
+        dict.set_item("timestamp_format", self.timestamps.name())?;
+        dict.set_item("columns", self.columns.as_ref().map(FlatColumns::names))?;
+        dict.set_item("path_separator", &self.path_format.separator)?;
+        dict.set_item("path_root", &self.path_format.root)?;
+        dict.set_item("path_include_name", self.path_format.include_name)?;
+
+        if let Output::JSON { fields, indent } = &self.output {
+            dict.set_item("include", fields.include())?;
+            dict.set_item("exclude", fields.exclude())?;
+            dict.set_item("indent", *indent)?;
+        }
+
+        if let Output::MsgPack { fields } = &self.output {
+            dict.set_item("include", fields.include())?;
+            dict.set_item("exclude", fields.exclude())?;
+        }
+
+        Ok(dict.to_object(py))
+    }
+
+    pub fn from_dict(state: &PyDict) -> PyResult<Self> {
+        let version: u32 = get_item(state, "version")?;
+        if version != CHECKPOINT_VERSION {
+            return Err(PyValueError::new_err(format!(
+                "unsupported iterator state version {}, expected {}",
+                version, CHECKPOINT_VERSION
+            )));
+        }
+
+        let output = match get_item::<&str>(state, "output")? {
+            "python" => Output::Python,
+            "csv" => Output::CSV,
+            "json" => Output::JSON {
+                fields: FieldSelection::new(get_item(state, "include")?, get_item(state, "exclude")?)?,
+                indent: get_item(state, "indent")?,
+            },
+            "msgpack" => Output::MsgPack {
+                fields: FieldSelection::new(get_item(state, "include")?, get_item(state, "exclude")?)?,
+            },
+            "timeline" => Output::Timeline,
+            "l2t_csv" => Output::L2tCsv,
+            "findings" => Output::Findings,
+            "triage_csv" => Output::TriageCsv,
+            "xml" => Output::Xml,
+            name => return Err(PyValueError::new_err(format!("invalid iterator state output `{}`", name))),
+        };
+
+        Ok(Checkpoint {
+            number_of_entries: get_item(state, "number_of_entries")?,
+            position: get_item(state, "position")?,
+            pending: get_item(state, "pending")?,
+            output,
+            csv_header_written: get_item(state, "csv_header_written")?,
+            skip_empty: get_item(state, "skip_empty")?,
+            timestamps: TimestampFormat::from_name(get_item(state, "timestamp_format")?)?,
+            // Absent from the states saved before columns could be selected.
+            columns: match state.get_item("columns") {
+                Some(columns) => columns.extract::<Option<Vec<String>>>()?,
+                None => None,
+            }
+            .as_deref()
+            .map(FlatColumns::new)
+            .transpose()?,
+            // Absent from the states saved before paths could be formatted.
+            path_format: match state.get_item("path_separator") {
+                Some(_) => PathFormat::new(
+                    get_item(state, "path_separator")?,
+                    get_item(state, "path_root")?,
+                    get_item(state, "path_include_name")?,
+                )?,
+                None => PathFormat::default(),
+            },
+        })
+    }
+}
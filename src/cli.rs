@@ -0,0 +1,237 @@
+//! The command line interface, run by `python -m mft`.
+//!
+//! ```text
+//! python -m mft dump --csv out.csv --threads 4 $MFT
+//! python -m mft dump --json - --min-size 1048576 $MFT
+//! ```
+use crate::export::ExportOutput;
+use crate::path::ORPHAN_PREFIX;
+use crate::xml::{XML_EPILOGUE, XML_PROLOGUE};
+use crate::PyMftParser;
+
+use clap::{App, AppSettings, Arg, ArgGroup, ArgMatches, ErrorKind, SubCommand};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyIterator;
+
+use std::io::Write;
+
+/// The output formats of `dump`, by the name of their option.
+const FORMATS: [&str; 4] = ["csv", "json", "xml", "arrow"];
+
+fn app() -> App<'static, 'static> {
+    App::new("mft")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Parses NTFS Master File Tables")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .setting(AppSettings::VersionlessSubcommands)
+        .subcommand(
+            SubCommand::with_name("dump")
+                .about("Writes the entries of an MFT (as CSV to the standard output by default)")
+                .arg(Arg::with_name("MFT").required(true).help("The path of the MFT"))
+                .args(
+                    &FORMATS
+                        .iter()
+                        .map(|format| {
+                            Arg::with_name(format)
+                                .long(format)
+                                .value_name("PATH")
+                                .help("Writes this format to PATH (`-` for the standard output)")
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .group(ArgGroup::with_name("format").args(&FORMATS))
+                .arg(
+                    Arg::with_name("threads")
+                        .short("t")
+                        .long("threads")
+                        .value_name("N")
+                        .help("The number of worker threads (one per core by default)"),
+                )
+                .arg(
+                    Arg::with_name("timestamp-format")
+                        .long("timestamp-format")
+                        .value_name("FORMAT")
+                        .possible_values(&["iso", "epoch", "epoch_ms"])
+                        .default_value("iso")
+                        .help("How timestamps are written"),
+                )
+                .arg(
+                    Arg::with_name("columns")
+                        .long("columns")
+                        .value_name("COLUMNS")
+                        .use_delimiter(true)
+                        .help("The columns of CSV, XML and Arrow output, separated by commas"),
+                )
+                .arg(
+                    Arg::with_name("compress")
+                        .long("compress")
+                        .value_name("METHOD")
+                        .possible_values(&["gzip"])
+                        .help("Compresses the output (also done for paths ending with `.gz`)"),
+                )
+                .arg(
+                    Arg::with_name("min-size")
+                        .long("min-size")
+                        .value_name("BYTES")
+                        .help("Only writes the entries whose logical size is at least BYTES"),
+                )
+                .arg(
+                    Arg::with_name("max-size")
+                        .long("max-size")
+                        .value_name("BYTES")
+                        .help("Only writes the entries whose logical size is at most BYTES"),
+                )
+                .arg(
+                    Arg::with_name("strict")
+                        .long("strict")
+                        .help("Skips the entries with mismatching fixup values"),
+                ),
+        )
+}
+
+/// Parses the value of an option as a number.
+fn number<T: std::str::FromStr>(matches: &ArgMatches, name: &str) -> PyResult<Option<T>> {
+    matches
+        .value_of(name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| PyValueError::new_err(format!("--{} must be a number, got `{}`", name, value)))
+        })
+        .transpose()
+}
+
+/// Writes a message to `sys.stdout` or `sys.stderr`, so that it goes wherever python's output is redirected.
+fn print(py: Python, stream: &str, message: &str) -> PyResult<()> {
+    let stream = py.import("sys")?.getattr(stream)?;
+    stream.call_method1("write", (format!("{}\n", message),))?;
+    stream.call_method0("flush")?;
+    Ok(())
+}
+
+fn dump(py: Python, matches: &ArgMatches) -> PyResult<()> {
+    let (format, output) = FORMATS
+        .iter()
+        .find_map(|format| matches.value_of(format).map(|output| (*format, output)))
+        .unwrap_or(("csv", "-"));
+    let output = match output {
+        "-" => py.import("sys")?.getattr("stdout")?.getattr("buffer")?.to_object(py),
+        path => path.to_object(py),
+    };
+
+    let threads = number(matches, "threads")?;
+    let min_size = number(matches, "min-size")?;
+    let max_size = number(matches, "max-size")?;
+    let timestamp_format = matches.value_of("timestamp-format").unwrap_or("iso");
+    let columns: Option<Vec<String>> = matches
+        .values_of("columns")
+        .map(|columns| columns.map(String::from).collect());
+    let compress = matches.value_of("compress").map(String::from);
+
+    let mut parser = PyMftParser::new(
+        matches.value_of("MFT").unwrap_or_default().to_object(py),
+        None,
+        "win32",
+        matches.is_present("strict"),
+        false,
+        None,
+        false,
+        false,
+        0,
+        None,
+        ORPHAN_PREFIX,
+    )?;
+
+    if min_size.is_none() && max_size.is_none() {
+        return match format {
+            "csv" => parser.export_csv(
+                py,
+                output,
+                threads,
+                compress,
+                timestamp_format,
+                columns,
+                "utf-8",
+                "/",
+                "",
+                true,
+                false,
+            ),
+            "json" => parser.export_json(py, output, threads, compress, timestamp_format, false),
+            "xml" => parser.export_xml(py, output, threads, compress, timestamp_format, columns, "/", "", true, false),
+            _ => parser.export_arrow_ipc(py, output, threads, columns, "/", "", true, false),
+        }
+        .map(|_| ());
+    }
+
+    // The size filters are evaluated while iterating, on a single thread.
+    let (entries, prologue, epilogue) = match format {
+        "csv" => (
+            parser.entries_csv(None, min_size, max_size, timestamp_format, columns, "/", "", true)?,
+            "",
+            "",
+        ),
+        "json" => (
+            parser.entries_json(None, min_size, max_size, None, None, None, timestamp_format)?,
+            "",
+            "",
+        ),
+        "xml" => (
+            parser.entries_xml(None, min_size, max_size, timestamp_format, columns, "/", "", true)?,
+            XML_PROLOGUE,
+            XML_EPILOGUE,
+        ),
+        _ => return Err(PyValueError::new_err("--min-size and --max-size are not supported with --arrow")),
+    };
+
+    let mut output = ExportOutput::open(output, compress.as_deref())?;
+    output.writer.write_all(prologue.as_bytes())?;
+    for entry in PyIterator::from_object(py, &entries)? {
+        let entry = entry?;
+        match entry.extract::<&[u8]>() {
+            Ok(line) => output.writer.write_all(line)?,
+            Err(_) => writeln!(output.writer, "{}", entry.extract::<&str>()?)?,
+        }
+    }
+    output.writer.write_all(epilogue.as_bytes())?;
+    Ok(output.close()?)
+}
+
+/// main(argv=None, /)
+/// --
+///
+/// Runs the command line interface with the arguments `argv` (`sys.argv[1:]` by default), as `python -m mft` does,
+/// and returns its exit status. Run `python -m mft --help` for the commands and their options.
+#[pyfunction]
+pub fn main(py: Python, argv: Option<Vec<String>>) -> PyResult<i32> {
+    let argv = match argv {
+        Some(argv) => argv,
+        None => py.import("sys")?.getattr("argv")?.extract::<Vec<String>>()?.into_iter().skip(1).collect(),
+    };
+
+    let matches = match app().get_matches_from_safe(std::iter::once("mft".to_string()).chain(argv)) {
+        Ok(matches) => matches,
+        Err(e) if matches!(e.kind, ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed) => {
+            print(py, "stdout", &e.message)?;
+            return Ok(0);
+        }
+        Err(e) => {
+            print(py, "stderr", &e.message)?;
+            return Ok(2);
+        }
+    };
+
+    let result = match matches.subcommand() {
+        ("dump", Some(matches)) => dump(py, matches),
+        _ => unreachable!("a subcommand is required"),
+    };
+
+    match result {
+        Ok(()) => Ok(0),
+        Err(e) => {
+            print(py, "stderr", &format!("error: {}", e.pvalue(py)))?;
+            Ok(1)
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Detections of anomalies which indicate tampering with the metadata of files (such as timestomping).
+use crate::entry::EntryContext;
+use crate::parser::MftParser;
+use crate::timeline::{attribute_timestamps, resolved_path, AttributeTimestamps, MACB};
+use crate::ReadSeek;
+
+use chrono::{DateTime, Utc};
+use mft::MftEntry;
+use pyo3::prelude::*;
+
+/// An anomaly of an entry, without the path of the entry.
+#[derive(Debug, Clone)]
+pub struct Detection {
+    /// The name of the detection, e.g. `fn_after_si`
+    pub kind: &'static str,
+    pub message: String,
+}
+
+/// $FILE_NAME timestamps which are later than the same timestamp of $STANDARD_INFORMATION.
+/// Tools which set timestamps (through `SetFileTime`) only change $STANDARD_INFORMATION,
+/// while $FILE_NAME timestamps are usually set when the file is created, moved or renamed.
+fn fn_after_si(timestamps: &[AttributeTimestamps], detections: &mut Vec<Detection>) {
+    let standard_info = match timestamps.iter().find(|t| t.source == "$SI") {
+        Some(standard_info) => standard_info,
+        None => return,
+    };
+
+    for file_name in timestamps.iter().filter(|t| t.source == "$FN") {
+        for ((macb, fn_time), si_time) in MACB.iter().zip(file_name.times.iter()).zip(standard_info.times.iter()) {
+            if fn_time > si_time {
+                detections.push(Detection {
+                    kind: "fn_after_si",
+                    message: format!(
+                        "$FN {} timestamp {} of `{}` is later than the $SI {} timestamp {}",
+                        macb,
+                        fn_time.to_rfc3339(),
+                        file_name.name,
+                        macb,
+                        si_time.to_rfc3339()
+                    ),
+                });
+            }
+        }
+    }
+}
+
+/// Timestamps which are the epoch of FILETIMEs (1601-01-01), of Unix time (1970-01-01) or of DOS dates (1980-01-01),
+/// which are what tools write when a timestamp is cleared or defaulted.
+fn is_epoch(timestamp: &DateTime<Utc>) -> bool {
+    let seconds = timestamp.timestamp();
+    timestamp.timestamp_subsec_nanos() == 0
+        && [-11_644_473_600, 0, 315_532_800].contains(&seconds)
+}
+
+/// Whether a timestamp has no fractional seconds, which real file system activity almost never produces,
+/// but setting timestamps with a precision of seconds does.
+fn has_zero_fraction(timestamp: &DateTime<Utc>) -> bool {
+    timestamp.timestamp_subsec_nanos() == 0 && !is_epoch(timestamp)
+}
+
+/// $STANDARD_INFORMATION timestamps with exactly zero fractional seconds.
+fn zero_fraction(timestamps: &[AttributeTimestamps], detections: &mut Vec<Detection>) {
+    for standard_info in timestamps.iter().filter(|t| t.source == "$SI") {
+        for (macb, time) in MACB.iter().zip(standard_info.times.iter()) {
+            if has_zero_fraction(time) {
+                detections.push(Detection {
+                    kind: "zero_fraction",
+                    message: format!("$SI {} timestamp {} has no fractional seconds", macb, time.to_rfc3339()),
+                });
+            }
+        }
+    }
+}
+
+/// Timestamps equal to a well-known epoch, in $STANDARD_INFORMATION or $FILE_NAME.
+fn epoch_timestamp(timestamps: &[AttributeTimestamps], detections: &mut Vec<Detection>) {
+    for attribute in timestamps {
+        for (macb, time) in MACB.iter().zip(attribute.times.iter()) {
+            if is_epoch(time) {
+                detections.push(Detection {
+                    kind: "epoch_timestamp",
+                    message: format!("{} {} timestamp is the epoch {}", attribute.source, macb, time.to_rfc3339()),
+                });
+            }
+        }
+    }
+}
+
+/// Runs every detection on an entry.
+pub fn detections(entry: &MftEntry) -> Vec<Detection> {
+    let timestamps = attribute_timestamps(entry);
+
+    let mut detections = vec![];
+    fn_after_si(&timestamps, &mut detections);
+    zero_fraction(&timestamps, &mut detections);
+    epoch_timestamp(&timestamps, &mut detections);
+    detections
+}
+
+/// Whether any $STANDARD_INFORMATION timestamp has zero fractional seconds,
+/// and whether any timestamp is a well-known epoch, for the boolean columns of flat output.
+pub fn timestamp_anomalies(entry: &MftEntry) -> (bool, bool) {
+    let timestamps = attribute_timestamps(entry);
+
+    let zero_fraction = timestamps
+        .iter()
+        .filter(|t| t.source == "$SI")
+        .any(|t| t.times.iter().any(has_zero_fraction));
+    let epoch = timestamps.iter().any(|t| t.times.iter().any(is_epoch));
+    (zero_fraction, epoch)
+}
+
+/// A detection for an entry.
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub entry_id: u64,
+    pub sequence: u16,
+    pub full_path: String,
+    pub detection: Detection,
+}
+
+/// Runs every detection on an entry, and resolves its path if any of them matches.
+pub fn findings(entry: &MftEntry, parser: &mut MftParser<impl ReadSeek>, context: &mut EntryContext) -> Vec<Finding> {
+    let detections = detections(entry);
+    if detections.is_empty() {
+        return vec![];
+    }
+
+    let full_path = resolved_path(entry, parser, context);
+    detections
+        .into_iter()
+        .map(|detection| Finding {
+            entry_id: entry.header.record_number,
+            sequence: entry.header.sequence,
+            full_path: full_path.clone(),
+            detection,
+        })
+        .collect()
+}
+
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct PyMftFinding {
+    /// The name of the detection, one of `fn_after_si` ($FILE_NAME timestamp later than $STANDARD_INFORMATION),
+    /// `zero_fraction` ($STANDARD_INFORMATION timestamp without fractional seconds),
+    /// or `epoch_timestamp` (timestamp equal to the FILETIME, Unix or DOS epoch)
+    #[pyo3(get)]
+    pub kind: String,
+    #[pyo3(get)]
+    pub entry_id: u64,
+    #[pyo3(get)]
+    pub sequence: u16,
+    #[pyo3(get)]
+    pub full_path: String,
+    /// A description of the anomaly, with the values which triggered the detection
+    #[pyo3(get)]
+    pub message: String,
+}
+
+impl PyMftFinding {
+    pub fn from_finding(py: Python, finding: Finding) -> PyResult<Py<Self>> {
+        Py::new(
+            py,
+            PyMftFinding {
+                kind: finding.detection.kind.to_string(),
+                entry_id: finding.entry_id,
+                sequence: finding.sequence,
+                full_path: finding.full_path,
+                message: finding.detection.message,
+            },
+        )
+    }
+}
@@ -0,0 +1,285 @@
+//! Comparison of two captures of an MFT.
+use crate::entry::{data_sizes, EntryContext};
+use crate::err::PyMftError;
+use crate::parser::MftParser;
+use crate::{PyMftEntry, PyMftParser, ReadSeek};
+
+use mft::attribute::{MftAttributeContent, MftAttributeType};
+use mft::entry::ZERO_HEADER;
+use mft::MftEntry;
+use pyo3::prelude::*;
+use pyo3::PyIterProtocol;
+
+use std::collections::VecDeque;
+
+/// The fields which are compared between the two versions of a record, in the order they are reported.
+fn comparable_fields(entry: &MftEntry, full_path: &str) -> Vec<(&'static str, String)> {
+    let header = &entry.header;
+
+    let mut fields = vec![
+        ("flags", format!("{:?}", header.flags)),
+        ("hard_link_count", header.hard_link_count.to_string()),
+        ("base_entry_id", header.base_reference.entry.to_string()),
+        ("log_sequence_number", header.metadata_transaction_journal.to_string()),
+        ("used_entry_size", header.used_entry_size.to_string()),
+        ("full_path", full_path.to_string()),
+    ];
+
+    let (file_size, allocated_size) = data_sizes(entry);
+    fields.push(("file_size", file_size.to_string()));
+    fields.push(("allocated_size", allocated_size.to_string()));
+
+    let standard_info = entry
+        .iter_attributes_matching(Some(vec![MftAttributeType::StandardInformation]))
+        .find_map(|a| match a.ok()?.data {
+            MftAttributeContent::AttrX10(info) => Some(info),
+            _ => None,
+        });
+
+    if let Some(info) = standard_info {
+        fields.push(("standard_info_created", info.created.to_rfc3339()));
+        fields.push(("standard_info_modified", info.modified.to_rfc3339()));
+        fields.push(("standard_info_mft_modified", info.mft_modified.to_rfc3339()));
+        fields.push(("standard_info_accessed", info.accessed.to_rfc3339()));
+        fields.push(("standard_info_flags", format!("{:?}", info.file_flags)));
+    }
+
+    fields
+}
+
+/// A single record, as read from one side of the comparison.
+struct Record {
+    entry: Py<PyMftEntry>,
+    sequence: u16,
+    fields: Vec<(&'static str, String)>,
+}
+
+struct Side {
+    parser: MftParser<Box<dyn ReadSeek + Send>>,
+    context: EntryContext,
+}
+
+impl Side {
+    fn from_parser(parser: &mut PyMftParser) -> PyResult<Self> {
+        let context = parser.entry_context();
+        let inner = parser.new_parser();
+
+        Ok(Side {
+            parser: inner,
+            context,
+        })
+    }
+
+    /// Reads a record, `None` if it is beyond the end of the MFT or zeroed.
+    fn record(&mut self, py: Python, entry_id: u64) -> PyResult<Option<Record>> {
+        if entry_id >= self.parser.get_entry_count() {
+            return Ok(None);
+        }
+
+        let entry = self.parser.read_entry(entry_id).map_err(PyMftError)?;
+        if &entry.header.signature == ZERO_HEADER {
+            return Ok(None);
+        }
+
+        let full_path = self
+            .context
+            .paths
+            .full_path(&entry, &mut self.parser)
+            .map(|path| path.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let sequence = entry.header.sequence;
+        let fields = comparable_fields(&entry, &full_path);
+        let entry = PyMftEntry::from_entry_with_path(
+            py,
+            entry,
+            full_path,
+            self.context.security.as_ref(),
+            self.context.lenient,
+        )?;
+
+        Ok(Some(Record {
+            entry,
+            sequence,
+            fields,
+        }))
+    }
+}
+
+/// diff(parser_a, parser_b, /)
+/// --
+///
+/// Compares two captures of an MFT (for example, a baseline and a post-incident capture, or the live MFT and
+/// the MFT of a shadow copy), and returns an iterator that yields a `PyMftDiff` for every record which was
+/// added, removed, or modified from `parser_a` to `parser_b`.
+/// Records are keyed by entry number and sequence, so a reused record is reported as removed and added.
+#[pyfunction]
+pub fn diff(mut parser_a: PyRefMut<PyMftParser>, mut parser_b: PyRefMut<PyMftParser>) -> PyResult<PyMftDiffIterator> {
+    let a = Side::from_parser(&mut parser_a)?;
+    let b = Side::from_parser(&mut parser_b)?;
+    let total_number_of_records = a.parser.get_entry_count().max(b.parser.get_entry_count());
+
+    Ok(PyMftDiffIterator {
+        a,
+        b,
+        current_record: 0,
+        total_number_of_records,
+        pending: VecDeque::new(),
+    })
+}
+
+#[pyclass]
+pub struct PyMftDiff {
+    /// One of `added`, `removed`, `modified`
+    #[pyo3(get)]
+    pub kind: String,
+    #[pyo3(get)]
+    pub entry_id: u64,
+    #[pyo3(get)]
+    pub sequence: u16,
+    /// Names of the fields which differ (only for modified records)
+    #[pyo3(get)]
+    pub changed_fields: Vec<String>,
+    /// The record in `parser_a`, None if it was added
+    #[pyo3(get)]
+    pub old: Option<Py<PyMftEntry>>,
+    /// The record in `parser_b`, None if it was removed
+    #[pyo3(get)]
+    pub new: Option<Py<PyMftEntry>>,
+}
+
+impl PyMftDiff {
+    fn new(
+        kind: &str,
+        entry_id: u64,
+        sequence: u16,
+        changed_fields: Vec<String>,
+        old: Option<Py<PyMftEntry>>,
+        new: Option<Py<PyMftEntry>>,
+    ) -> Self {
+        PyMftDiff {
+            kind: kind.to_string(),
+            entry_id,
+            sequence,
+            changed_fields,
+            old,
+            new,
+        }
+    }
+}
+
+#[pyclass]
+pub struct PyMftDiffIterator {
+    a: Side,
+    b: Side,
+    current_record: u64,
+    total_number_of_records: u64,
+    /// Differences of the current record, which were not yielded yet.
+    pending: VecDeque<PyMftDiff>,
+}
+
+impl PyMftDiffIterator {
+    fn compare(&mut self, py: Python, entry_id: u64) -> PyResult<()> {
+        let old = self.a.record(py, entry_id)?;
+        let new = self.b.record(py, entry_id)?;
+
+        match (old, new) {
+            (None, None) => {}
+            (Some(old), None) => self.pending.push_back(PyMftDiff::new(
+                "removed",
+                entry_id,
+                old.sequence,
+                vec![],
+                Some(old.entry),
+                None,
+            )),
+            (None, Some(new)) => self.pending.push_back(PyMftDiff::new(
+                "added",
+                entry_id,
+                new.sequence,
+                vec![],
+                None,
+                Some(new.entry),
+            )),
+            (Some(old), Some(new)) if old.sequence != new.sequence => {
+                self.pending.push_back(PyMftDiff::new(
+                    "removed",
+                    entry_id,
+                    old.sequence,
+                    vec![],
+                    Some(old.entry),
+                    None,
+                ));
+                self.pending.push_back(PyMftDiff::new(
+                    "added",
+                    entry_id,
+                    new.sequence,
+                    vec![],
+                    None,
+                    Some(new.entry),
+                ));
+            }
+            (Some(old), Some(new)) => {
+                let mut changed_fields: Vec<String> = vec![];
+                for (name, value) in &new.fields {
+                    let old_value = old.fields.iter().find(|(n, _)| n == name).map(|(_, v)| v);
+                    if old_value != Some(value) {
+                        changed_fields.push(name.to_string());
+                    }
+                }
+                for (name, _) in &old.fields {
+                    if !new.fields.iter().any(|(n, _)| n == name) {
+                        changed_fields.push(name.to_string());
+                    }
+                }
+
+                if !changed_fields.is_empty() {
+                    self.pending.push_back(PyMftDiff::new(
+                        "modified",
+                        entry_id,
+                        new.sequence,
+                        changed_fields,
+                        Some(old.entry),
+                        Some(new.entry),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn next(&mut self) -> PyResult<Option<PyObject>> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        loop {
+            if let Some(difference) = self.pending.pop_front() {
+                return Ok(Some(Py::new(py, difference)?.to_object(py)));
+            }
+
+            if self.current_record >= self.total_number_of_records {
+                return Ok(None);
+            }
+
+            let entry_id = self.current_record;
+            self.current_record += 1;
+
+            // Yield the error, so iteration can continue with the next record.
+            if let Err(e) = self.compare(py, entry_id) {
+                return Ok(Some(e.to_object(py)));
+            }
+        }
+    }
+}
+
+#[pyproto]
+impl PyIterProtocol for PyMftDiffIterator {
+    fn __iter__(slf: PyRefMut<Self>) -> PyResult<Py<PyMftDiffIterator>> {
+        Ok(slf.into())
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        slf.next()
+    }
+}
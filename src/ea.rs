@@ -0,0 +1,204 @@
+//! $EA (extended attributes) support, and the WSL metadata which is stored in extended attributes.
+//! https://docs.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/ns-wdm-_file_full_ea_information
+use crate::utils::{date_to_pyobject, u16_at, u32_at, u64_at};
+
+use chrono::{DateTime, TimeZone, Utc};
+use mft::attribute::{MftAttributeContent, MftAttributeType};
+use mft::MftEntry;
+use pyo3::prelude::*;
+
+const EA_HEADER_SIZE: usize = 8;
+/// Size of the `LXATTRB` structure.
+const LXATTRB_SIZE: usize = 56;
+
+/// A single extended attribute.
+#[derive(Debug, Clone)]
+pub struct ExtendedAttribute {
+    pub flags: u8,
+    pub name: String,
+    pub value: Vec<u8>,
+}
+
+/// Decodes the content of an $EA attribute.
+/// Decoding stops at the first malformed entry.
+pub fn parse_extended_attributes(data: &[u8]) -> Vec<ExtendedAttribute> {
+    let mut attributes = vec![];
+    let mut offset = 0;
+
+    while offset + EA_HEADER_SIZE <= data.len() {
+        let next_entry_offset = u32_at(data, offset) as usize;
+        let flags = data[offset + 4];
+        let name_length = data[offset + 5] as usize;
+        let value_length = u16_at(data, offset + 6) as usize;
+
+        let name_start = offset + EA_HEADER_SIZE;
+        // The name is NUL terminated.
+        let value_start = name_start + name_length + 1;
+        let value = match data.get(value_start..value_start + value_length) {
+            Some(value) => value.to_vec(),
+            None => break,
+        };
+
+        attributes.push(ExtendedAttribute {
+            flags,
+            name: String::from_utf8_lossy(&data[name_start..name_start + name_length]).to_string(),
+            value,
+        });
+
+        if next_entry_offset == 0 {
+            break;
+        }
+        offset += next_entry_offset;
+    }
+
+    attributes
+}
+
+/// Content of an $EA_INFORMATION attribute.
+#[derive(Debug, Clone)]
+pub struct EaInformation {
+    pub packed_ea_size: u16,
+    pub need_ea_count: u16,
+    pub unpacked_ea_size: u32,
+}
+
+impl EaInformation {
+    pub fn from_buffer(data: &[u8]) -> Option<Self> {
+        if data.len() < 8 {
+            return None;
+        }
+
+        Some(EaInformation {
+            packed_ea_size: u16_at(data, 0),
+            need_ea_count: u16_at(data, 2),
+            unpacked_ea_size: u32_at(data, 4),
+        })
+    }
+}
+
+/// Returns the extended attributes of an entry, if it has a resident $EA attribute.
+pub fn extended_attributes(entry: &MftEntry) -> Option<Vec<ExtendedAttribute>> {
+    entry
+        .iter_attributes_matching(Some(vec![MftAttributeType::EA]))
+        .filter_map(Result::ok)
+        .find_map(|a| match a.data {
+            MftAttributeContent::Raw(raw) => Some(parse_extended_attributes(&raw.data)),
+            _ => None,
+        })
+}
+
+/// Linux metadata of files created by WSL.
+/// WSL 1 (lxfs) stores it in a single `LXATTRB` extended attribute,
+/// while DrvFs "metadata" mounts use one extended attribute per field (`$LXUID`, `$LXGID`, `$LXMOD`, `$LXDEV`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WslMetadata {
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// `(major, minor)` device numbers of device files
+    pub device: Option<(u32, u32)>,
+    pub access_time: Option<DateTime<Utc>>,
+    pub modification_time: Option<DateTime<Utc>>,
+    pub change_time: Option<DateTime<Utc>>,
+}
+
+fn unix_time(seconds: u64, nanoseconds: u32) -> Option<DateTime<Utc>> {
+    Utc.timestamp_opt(seconds as i64, nanoseconds).single()
+}
+
+impl WslMetadata {
+    /// Returns `None` if none of the WSL extended attributes are present.
+    pub fn from_extended_attributes(attributes: &[ExtendedAttribute]) -> Option<Self> {
+        let mut metadata = WslMetadata::default();
+        let mut found = false;
+
+        for attribute in attributes {
+            let value = &attribute.value;
+            let as_u32 = || if value.len() >= 4 { Some(u32_at(value, 0)) } else { None };
+
+            match attribute.name.trim_start_matches('$').to_uppercase().as_str() {
+                "LXATTRB" if value.len() >= LXATTRB_SIZE => {
+                    metadata.mode = Some(u32_at(value, 4));
+                    metadata.uid = Some(u32_at(value, 8));
+                    metadata.gid = Some(u32_at(value, 12));
+                    let rdev = u32_at(value, 16);
+                    if rdev != 0 {
+                        // Encoded as in the Linux kernel (`new_encode_dev`).
+                        metadata.device = Some(((rdev >> 8) & 0xFFF, (rdev & 0xFF) | ((rdev >> 12) & 0xFFF00)));
+                    }
+                    metadata.access_time = unix_time(u64_at(value, 32), u32_at(value, 20));
+                    metadata.modification_time = unix_time(u64_at(value, 40), u32_at(value, 24));
+                    metadata.change_time = unix_time(u64_at(value, 48), u32_at(value, 28));
+                }
+                "LXUID" => metadata.uid = as_u32(),
+                "LXGID" => metadata.gid = as_u32(),
+                "LXMOD" => metadata.mode = as_u32(),
+                "LXDEV" if value.len() >= 8 => metadata.device = Some((u32_at(value, 0), u32_at(value, 4))),
+                _ => continue,
+            }
+
+            found = true;
+        }
+
+        if found {
+            Some(metadata)
+        } else {
+            None
+        }
+    }
+}
+
+#[pyclass]
+pub struct PyWslMetadata {
+    /// Linux file mode (type and permission bits)
+    #[pyo3(get)]
+    pub mode: Option<u32>,
+    #[pyo3(get)]
+    pub uid: Option<u32>,
+    #[pyo3(get)]
+    pub gid: Option<u32>,
+    /// `(major, minor)` device numbers, for device files
+    #[pyo3(get)]
+    pub device: Option<(u32, u32)>,
+    inner: WslMetadata,
+}
+
+impl PyWslMetadata {
+    pub fn from_metadata(py: Python, metadata: WslMetadata) -> PyResult<Py<Self>> {
+        Py::new(
+            py,
+            PyWslMetadata {
+                mode: metadata.mode,
+                uid: metadata.uid,
+                gid: metadata.gid,
+                device: metadata.device,
+                inner: metadata,
+            },
+        )
+    }
+}
+
+fn optional_date(date: &Option<DateTime<Utc>>) -> PyResult<Option<PyObject>> {
+    date.as_ref().map(date_to_pyobject).transpose()
+}
+
+#[pymethods]
+impl PyWslMetadata {
+    /// Only set by WSL 1 (`LXATTRB`)
+    #[getter]
+    pub fn access_time(&self) -> PyResult<Option<PyObject>> {
+        optional_date(&self.inner.access_time)
+    }
+
+    /// Only set by WSL 1 (`LXATTRB`)
+    #[getter]
+    pub fn modification_time(&self) -> PyResult<Option<PyObject>> {
+        optional_date(&self.inner.modification_time)
+    }
+
+    /// Only set by WSL 1 (`LXATTRB`)
+    #[getter]
+    pub fn change_time(&self) -> PyResult<Option<PyObject>> {
+        optional_date(&self.inner.change_time)
+    }
+}
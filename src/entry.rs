@@ -1,18 +1,102 @@
-use crate::ReadSeek;
 use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
 
 use crate::attribute::PyMftAttribute;
+use crate::detect::{detections, Finding, PyMftFinding};
+use crate::digest::Algorithm;
+use crate::ea::{extended_attributes, PyWslMetadata, WslMetadata};
 use crate::err::PyMftError;
-use mft::{MftEntry, MftParser};
-use mft::attribute::MftAttributeType;
-use mft::attribute::header::ResidentialHeader;
-use pyo3::{Py, PyIterProtocol, PyResult, Python};
+use crate::flags::file_attributes_json;
+use crate::flat::{FlatColumns, FlatMftEntry};
+use crate::index::{index_root_entries, PyIndexEntry};
+use crate::parser::entry_from_buffer;
+use crate::path::{NamePreference, PathFormat, PathResolver, SharedParser};
+use crate::reparse::{link_target, reparse_data};
+use crate::runlist::{attribute_record, data_runs_for_instance, fragment_count, sparse_ranges, stream_runs};
+use crate::secure::{owner_and_group, SecurityDescriptors};
+use crate::timestamp::TimestampFormat;
+use crate::utils::{py_repr, u16_at, u32_at, utf16_to_string};
+use mft::{MftAttribute, MftEntry};
+use num_traits::FromPrimitive;
+use mft::attribute::{AttributeDataFlags, FileAttributeFlags, MftAttributeType};
+use mft::attribute::header::{MftAttributeHeader, ResidentialHeader};
+use mft::attribute::raw::RawAttribute;
+use mft::attribute::MftAttributeContent;
+use mft::entry::{EntryFlags, ZERO_HEADER};
+use mft::FileNameAttr;
+use winstructs::ntfs::mft_reference::MftReference;
+use pyo3::basic::CompareOp;
+use pyo3::{Py, PyIterProtocol, PyObjectProtocol, PyResult, Python};
+use std::cell::OnceCell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{Cursor, Seek, SeekFrom};
 use std::path::PathBuf;
 
+/// Parser level options and state, which are needed to build entries.
+#[derive(Clone)]
+pub struct EntryContext {
+    pub security: Option<SecurityDescriptors>,
+    pub paths: PathResolver,
+    /// Resolves the paths of the `PyMftEntry`s, on demand.
+    pub shared_parser: SharedParser,
+    pub lenient: bool,
+    /// Whether names with invalid UTF-16 are decoded with replacement characters.
+    pub lossy_names: bool,
+    /// Whether resident $DATA is hashed in flat output.
+    pub hash_resident_data: bool,
+    /// How timestamps are written in JSON and CSV output.
+    pub timestamps: TimestampFormat,
+    /// The columns of flat output (CSV, XML and Arrow), all of them if None.
+    pub columns: Option<FlatColumns>,
+    /// How paths are written in flat output.
+    pub path_format: PathFormat,
+}
+
+impl EntryContext {
+    pub fn entry_options(&self) -> EntryOptions {
+        EntryOptions {
+            security: self.security.clone(),
+            lenient: self.lenient,
+            lossy_names: self.lossy_names,
+            hash_resident_data: self.hash_resident_data,
+        }
+    }
+}
+
+/// How the attributes of a `PyMftEntry` are parsed and resolved.
+#[derive(Clone, Default)]
+pub struct EntryOptions {
+    pub security: Option<SecurityDescriptors>,
+    pub lenient: bool,
+    pub lossy_names: bool,
+    /// Whether resident $DATA is hashed in flat output.
+    pub hash_resident_data: bool,
+}
+
 #[pyclass]
 pub struct PyMftEntry {
     // We need to keep inner entry to access it's attributes.
     inner: MftEntry,
+    /// Records holding the attributes which do not fit in the base record, loaded by `resolve_extensions`.
+    extensions: Vec<MftEntry>,
+    options: EntryOptions,
+    /// The parser which read the entry, to resolve paths and parents.
+    parser: Option<SharedParser>,
+    /// Cached once resolved.
+    resolved_path: Option<String>,
+    /// Either `FILE` or `BAAD`, or empty for zeroed records
+    #[pyo3(get)]
+    pub signature: String,
+    /// Offset of the update sequence array
+    #[pyo3(get)]
+    pub usa_offset: u16,
+    /// Number of entries in the update sequence array
+    #[pyo3(get)]
+    pub usa_size: u16,
+    /// $LogFile sequence number (LSN) of the last change to the record
+    #[pyo3(get)]
+    pub log_sequence_number: u64,
     #[pyo3(get)]
     pub entry_id: u64,
     #[pyo3(get)]
@@ -23,35 +107,105 @@ pub struct PyMftEntry {
     pub base_entry_sequence: u16,
     #[pyo3(get)]
     pub hard_link_count: u16,
+    /// Offset of the first attribute record
+    #[pyo3(get)]
+    pub first_attribute_offset: u16,
+    /// Next attribute instance id
+    #[pyo3(get)]
+    pub first_attribute_id: u16,
     #[pyo3(get)]
     pub flags: String,
     #[pyo3(get)]
     pub used_entry_size: u32,
+    /// Allocated size of the record
     #[pyo3(get)]
     pub total_entry_size: u32,
+    /// Whether the fixup values of every sector matched the update sequence array,
+    /// or None if the entry has no valid header
     #[pyo3(get)]
-    pub full_path: String,
+    pub fixup_ok: Option<bool>,
     #[pyo3(get)]
-    pub file_size: u64,
+    pub is_directory: bool,
+    /// Properties which need the attributes to be parsed, computed when first accessed.
+    sizes: OnceCell<(u64, u64)>,
+    sids: OnceCell<(Option<String>, Option<String>)>,
+    storage: OnceCell<StorageFlags>,
 }
 
 #[pymethods]
 impl PyMftEntry {
-    pub fn attributes(&self) -> PyResult<Py<PyMftAttributesIter>> {
+    /// from_bytes(data, entry_id=None, lenient=False, /)
+    /// --
+    ///
+    /// Parses a single FILE (or BAAD) record of 1024 or 4096 bytes into an entry, independently of any parser.
+    /// Fixups are applied, `fixup_ok` tells whether they matched.
+    /// The record number is read from the header (of records written by XP and later), unless `entry_id` is given.
+    /// Without a parser, the `full_path` of the entry is only its own name.
+    /// `lenient` is the option of `PyMftParser`.
+    #[staticmethod]
+    #[args(entry_id = "None", lenient = "false")]
+    pub fn from_bytes(py: Python, data: &[u8], entry_id: Option<u64>, lenient: bool) -> PyResult<Py<PyMftEntry>> {
+        if data.len() != 1024 && data.len() != 4096 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "a record is 1024 or 4096 bytes, got {} bytes",
+                data.len()
+            )));
+        }
+
+        let entry_id = entry_id.unwrap_or_else(|| {
+            if u16_at(data, 4) >= 0x30 {
+                u64::from(u32_at(data, 0x2C))
+            } else {
+                0
+            }
+        });
+        let entry = entry_from_buffer(data.to_vec(), entry_id).map_err(PyMftError)?;
+        let name = NamePreference::Win32
+            .pick(&entry)
+            .map_or_else(|| "[UNKNOWN]".to_string(), |name| name.name);
+
+        PyMftEntry::from_entry_with_path(py, entry, name, None, lenient)
+    }
+
+    /// attributes(self, types=None, /)
+    /// --
+    ///
+    /// Returns an iterator over the attributes of the record,
+    /// followed by those of its extension records if they were loaded with `resolve_extensions`.
+    /// If `types` is given (type codes such as `0x30`, or type names such as `'FileName'`, case insensitive),
+    /// only the attributes of these types are parsed and returned.
+    #[args(types = "None")]
+    pub fn attributes(&self, types: Option<Vec<&PyAny>>) -> PyResult<Py<PyMftAttributesIter>> {
         let gil = Python::acquire_gil();
         let py = gil.python();
 
+        let types = types.map(|types| attribute_types(&types)).transpose()?;
         let mut attributes = vec![];
 
-        for attribute_result in self.inner.iter_attributes() {
-            match attribute_result {
-                Ok(attribute) => match PyMftAttribute::from_mft_attribute(py, attribute)
-                    .map(|entry| entry.to_object(py))
-                {
-                    Ok(obj) => attributes.push(obj),
-                    Err(e) => attributes.push(e.to_object(py)),
-                },
-                Err(e) => attributes.push(PyErr::from(PyMftError(e)).to_object(py)),
+        for record in std::iter::once(&self.inner).chain(self.extensions.iter()) {
+            let attribute_results = if self.options.lenient {
+                iter_attributes_lenient(record, types.as_deref())
+            } else {
+                record
+                    .iter_attributes_matching(types.clone())
+                    .map(|result| result.map(|attribute| (attribute, None)))
+                    .collect()
+            };
+
+            for attribute_result in attribute_results {
+                match attribute_result {
+                    Ok((attribute, parse_error)) => {
+                        let raw = attribute_record(record, attribute.header.instance).unwrap_or_default();
+
+                        match PyMftAttribute::from_mft_attribute(py, attribute, parse_error, raw, self.options.lossy_names)
+                            .map(|entry| entry.to_object(py))
+                        {
+                            Ok(obj) => attributes.push(obj),
+                            Err(e) => attributes.push(e.to_object(py)),
+                        }
+                    }
+                    Err(e) => attributes.push(PyErr::from(PyMftError(e)).to_object(py)),
+                }
             }
         }
 
@@ -62,52 +216,923 @@ impl PyMftEntry {
             },
         )
     }
+
+    /// Logical size of the unnamed $DATA stream
+    #[getter]
+    pub fn file_size(&self) -> u64 {
+        self.sizes().0
+    }
+
+    /// Size of the clusters allocated to the unnamed $DATA stream (0 if it is resident)
+    #[getter]
+    pub fn allocated_size(&self) -> u64 {
+        self.sizes().1
+    }
+
+    /// Owner SID, if the security descriptor of the entry could be resolved
+    #[getter]
+    pub fn owner_sid(&self) -> Option<String> {
+        self.sids().0.clone()
+    }
+
+    /// Group SID, if the security descriptor of the entry could be resolved
+    #[getter]
+    pub fn group_sid(&self) -> Option<String> {
+        self.sids().1.clone()
+    }
+
+    /// Whether the file is compressed, by its $STANDARD_INFORMATION or $DATA flags
+    #[getter]
+    pub fn is_compressed(&self) -> bool {
+        self.storage().is_compressed
+    }
+
+    /// Whether the file is encrypted (EFS), by its $STANDARD_INFORMATION or $DATA flags
+    #[getter]
+    pub fn is_encrypted(&self) -> bool {
+        self.storage().is_encrypted
+    }
+
+    /// Whether the file is sparse, by its $STANDARD_INFORMATION or $DATA flags
+    #[getter]
+    pub fn is_sparse(&self) -> bool {
+        self.storage().is_sparse
+    }
+
+    /// Full path of the entry, resolved when first accessed, then cached
+    #[getter]
+    pub fn full_path(&mut self, py: Python) -> String {
+        if let Some(full_path) = &self.resolved_path {
+            return full_path.clone();
+        }
+
+        let entry = &self.inner;
+        let full_path = match &self.parser {
+            // Reading parents may need the GIL (for file-like objects), and another thread may hold the parser.
+            Some(parser) => py.allow_threads(|| parser.full_path(entry)),
+            None => None,
+        }
+        .unwrap_or_else(|| PathBuf::from("[UNKNOWN]"))
+        .to_string_lossy()
+        .to_string();
+
+        self.resolved_path = Some(full_path.clone());
+        full_path
+    }
+
+    /// to_json(self, flat=False, /)
+    /// --
+    ///
+    /// Serializes the entry as a JSON document, like `PyMftParser.entries_json`,
+    /// or if `flat` is set, as an object with the columns of CSV output (like `PyMftParser.entries_csv`).
+    /// The MFT is not read again, except to resolve the path of the entry if it was not yet resolved.
+    #[args(flat = "false")]
+    pub fn to_json(&mut self, py: Python, flat: bool) -> PyResult<String> {
+        let json = if flat {
+            // Unresolved paths are empty, like in CSV output.
+            let entry = &self.inner;
+            let full_path = match &self.parser {
+                Some(parser) => py.allow_threads(|| parser.full_path(entry)).unwrap_or_default(),
+                None => self.resolved_path.clone().map(PathBuf::from).unwrap_or_default(),
+            };
+            let flat = FlatMftEntry::with_path(
+                &self.inner,
+                full_path,
+                self.options.security.as_ref(),
+                self.options.hash_resident_data,
+                TimestampFormat::Iso,
+                None,
+            );
+            serde_json::to_string(&flat)
+        } else {
+            entry_to_json_value(&self.inner, &self.options, TimestampFormat::Iso)
+                .and_then(|value| serde_json::to_string(&value))
+        };
+
+        json.map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("JSON Serialization failed: {}", e)))
+    }
+
+    /// findings(self, /)
+    /// --
+    ///
+    /// Returns a list of `PyMftFinding`, one for every anomaly of the entry which indicates tampering:
+    /// `fn_after_si` for every $FILE_NAME timestamp which is later than the same $STANDARD_INFORMATION timestamp
+    /// (timestomping tools usually only change $STANDARD_INFORMATION),
+    /// `zero_fraction` for every $STANDARD_INFORMATION timestamp with exactly zero fractional seconds
+    /// (typical of setting timestamps with `SetFileTime`),
+    /// and `epoch_timestamp` for every timestamp equal to the FILETIME (1601), Unix (1970) or DOS (1980) epoch.
+    pub fn findings(&mut self, py: Python) -> PyResult<Vec<Py<PyMftFinding>>> {
+        let detections = detections(&self.inner);
+        if detections.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let full_path = self.full_path(py);
+        detections
+            .into_iter()
+            .map(|detection| {
+                let finding = Finding {
+                    entry_id: self.entry_id,
+                    sequence: self.sequence,
+                    full_path: full_path.clone(),
+                    detection,
+                };
+                PyMftFinding::from_finding(py, finding)
+            })
+            .collect()
+    }
+
+    /// parent(self, /)
+    /// --
+    ///
+    /// Returns the `PyMftEntry` of the parent directory, following the parent reference of the $FILE_NAME
+    /// used for the path. Returns None for orphans: entries without a name, the root directory,
+    /// and entries whose parent record was reused since (its sequence number does not match the reference,
+    /// unless the parent was deleted and not reused).
+    pub fn parent(&self, py: Python) -> PyResult<Option<Py<PyMftEntry>>> {
+        let parser = self.parser.as_ref().ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("entry was not read by a PyMftParser")
+        })?;
+
+        let entry = &self.inner;
+        match py.allow_threads(|| parser.parent(entry)) {
+            Some(parent) => Ok(Some(PyMftEntry::new(
+                py,
+                parent,
+                vec![],
+                Some(parser.clone()),
+                None,
+                self.options.clone(),
+            )?)),
+            None => Ok(None),
+        }
+    }
+
+    /// resolve_extensions(self, /)
+    /// --
+    ///
+    /// Returns a copy of the entry which also has the attributes of its extension records,
+    /// as referenced by its (resident) $ATTRIBUTE_LIST: `attributes()` yields the attributes of every record,
+    /// and `file_size` and `allocated_size` are those of the $DATA attribute wherever it is stored.
+    /// Extension records whose sequence number or base reference does not match are ignored.
+    pub fn resolve_extensions(&self, py: Python) -> PyResult<Py<PyMftEntry>> {
+        let parser = self.parser.as_ref().ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("entry was not read by a PyMftParser")
+        })?;
+
+        let entry = &self.inner;
+        let extensions = py.allow_threads(|| parser.extension_records(entry));
+
+        PyMftEntry::new(
+            py,
+            self.inner.clone(),
+            extensions,
+            Some(parser.clone()),
+            self.resolved_path.clone(),
+            self.options.clone(),
+        )
+    }
+
+    /// walk(self, /)
+    /// --
+    ///
+    /// Returns an iterator that yields every entry below this directory, depth first
+    /// (with the children of a directory ordered by name), including deleted entries.
+    /// Children are looked up in an index of the parents of every $FILE_NAME, which is built on first use.
+    pub fn walk(&self, py: Python) -> PyResult<Py<PyMftWalkIterator>> {
+        let parser = self.parser.as_ref().ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("entry was not read by a PyMftParser")
+        })?;
+
+        if !self.is_directory {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "entry {} is not a directory",
+                self.entry_id
+            )));
+        }
+
+        let entry_id = self.entry_id;
+        let descendants = py.allow_threads(|| parser.descendants(entry_id));
+
+        Py::new(
+            py,
+            PyMftWalkIterator {
+                parser: parser.clone(),
+                descendants: descendants.into_iter(),
+                options: self.options.clone(),
+            },
+        )
+    }
+
+    /// The bytes of the whole record, after the fixups were applied.
+    #[getter]
+    pub fn raw(&self) -> &[u8] {
+        &self.inner.data
+    }
+
+    /// The bytes of the whole record as stored on disk, before the fixups were applied.
+    /// If `fixup_ok` is False, the original bytes at the end of the mismatching sectors are lost,
+    /// and the update sequence number is put in their place.
+    #[getter]
+    pub fn raw_pre_fixup(&self, py: Python) -> PyObject {
+        PyBytes::new(py, &without_fixups(&self.inner)).to_object(py)
+    }
+
+    /// to_bytes(self, apply_fixups=True, /)
+    /// --
+    ///
+    /// Returns the bytes of the record, for writing it back to a file (for example, a subset of the entries of an MFT
+    /// as a smaller $MFT which other tools can parse).
+    /// If `apply_fixups` is set, the update sequence number is put back at the end of every sector, as NTFS does
+    /// before writing a record, so the bytes are those stored on disk (`raw_pre_fixup`). Otherwise they are `raw`.
+    /// Extension records loaded with `resolve_extensions` are not included.
+    #[args(apply_fixups = "true")]
+    pub fn to_bytes(&self, py: Python, apply_fixups: bool) -> PyObject {
+        if apply_fixups {
+            PyBytes::new(py, &without_fixups(&self.inner)).to_object(py)
+        } else {
+            PyBytes::new(py, &self.inner.data).to_object(py)
+        }
+    }
+
+    /// The bytes between the end of the used portion of the record and its allocated size.
+    /// Slack often contains remnants of older attributes.
+    #[getter]
+    pub fn slack(&self) -> &[u8] {
+        let end = (self.inner.header.total_entry_size as usize).min(self.inner.data.len());
+        let start = (self.inner.header.used_entry_size as usize).min(end);
+
+        &self.inner.data[start..end]
+    }
+
+    /// resident_data_hashes(self, /)
+    /// --
+    ///
+    /// Returns a dict of the `md5`, `sha1` and `sha256` hex digests of the content of the unnamed $DATA attribute,
+    /// or None if the entry has no resident $DATA.
+    pub fn resident_data_hashes(&self, py: Python) -> PyResult<Option<PyObject>> {
+        let data = match resident_data(&self.inner) {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+
+        let dict = PyDict::new(py);
+        for algorithm in Algorithm::ALL {
+            dict.set_item(algorithm.name(), algorithm.hexdigest(&data))?;
+        }
+
+        Ok(Some(dict.to_object(py)))
+    }
+
+    /// index_entries(self, /)
+    /// --
+    ///
+    /// Returns a list of `PyIndexEntry` for the files of a directory which are indexed in its $I30 $INDEX_ROOT,
+    /// including stale entries recovered from the index slack space.
+    /// Returns None if the entry has no $I30 $INDEX_ROOT attribute.
+    /// Large directories also store entries in $INDEX_ALLOCATION, which is outside of the MFT.
+    pub fn index_entries(&self, py: Python) -> PyResult<Option<Vec<Py<PyIndexEntry>>>> {
+        index_root_entries(&self.inner)
+            .map(|entries| {
+                entries
+                    .into_iter()
+                    .map(|entry| PyIndexEntry::from_index_entry(py, entry))
+                    .collect()
+            })
+            .transpose()
+    }
+
+    /// wsl_metadata(self, /)
+    /// --
+    ///
+    /// Returns the Linux metadata (`PyWslMetadata`) of files written by WSL, which is stored in
+    /// extended attributes, or None if the entry has none.
+    pub fn wsl_metadata(&self, py: Python) -> PyResult<Option<Py<PyWslMetadata>>> {
+        extended_attributes(&self.inner)
+            .and_then(|attributes| WslMetadata::from_extended_attributes(&attributes))
+            .map(|metadata| PyWslMetadata::from_metadata(py, metadata))
+            .transpose()
+    }
+
+    /// link_target(self, /)
+    /// --
+    ///
+    /// Returns the target path of a symbolic link, a mount point (junction), or a WSL symbolic link,
+    /// or None if the entry is not a link.
+    pub fn link_target(&self) -> Option<String> {
+        reparse_data(&self.inner).and_then(|data| link_target(&data))
+    }
+
+    /// sparse_ranges(self, stream_name='', /)
+    /// --
+    ///
+    /// Returns the holes of a sparse $DATA stream as a list of `(vcn, length)` tuples (in clusters),
+    /// computed from its run list. The list is empty if the stream is not sparse,
+    /// and None if the entry has no non-resident $DATA stream with that name.
+    #[args(stream_name = "\"\"")]
+    pub fn sparse_ranges(&self, stream_name: &str) -> Option<Vec<(u64, u64)>> {
+        let attribute = self
+            .inner
+            .iter_attributes_matching(Some(vec![MftAttributeType::DATA]))
+            .filter_map(Result::ok)
+            .find(|a| a.header.form_code == 1 && a.header.name == stream_name)?;
+
+        if !attribute.header.data_flags.contains(AttributeDataFlags::SPARSE) {
+            return Some(vec![]);
+        }
+
+        data_runs_for_instance(&self.inner, attribute.header.instance).map(|runs| sparse_ranges(&runs))
+    }
+
+    /// data_runs(self, stream_name='', /)
+    /// --
+    ///
+    /// Returns the run list of a non-resident $DATA stream as a list of `(vcn, lcn, length)` tuples (in clusters),
+    /// `lcn` is None for sparse runs. Segments in extension records are included once they are resolved
+    /// (see `resolve_extensions`). Returns None if the entry has no non-resident $DATA stream with that name.
+    #[args(stream_name = "\"\"")]
+    pub fn data_runs(&self, stream_name: &str) -> Option<Vec<(u64, Option<u64>, u64)>> {
+        let runs = stream_runs(self.records(), stream_name)?;
+
+        Some(runs.into_iter().map(|run| (run.vcn, run.lcn, run.length)).collect())
+    }
+
+    /// fragment_count(self, stream_name='', /)
+    /// --
+    ///
+    /// Returns the number of fragments of a non-resident $DATA stream: its runs which are not contiguous
+    /// on the volume, sparse runs excluded. Returns None if the entry has no non-resident $DATA stream with that name.
+    #[args(stream_name = "\"\"")]
+    pub fn fragment_count(&self, stream_name: &str) -> Option<u64> {
+        stream_runs(self.records(), stream_name).map(|runs| fragment_count(&runs))
+    }
+
+    /// data_stream_names(self, /)
+    /// --
+    ///
+    /// Returns the names of the $DATA streams of the entry, in order of appearance.
+    /// The unnamed (default) stream is named by an empty string, every other name is an alternate data stream.
+    pub fn data_stream_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = vec![];
+
+        for attribute in self
+            .inner
+            .iter_attributes_matching(Some(vec![MftAttributeType::DATA]))
+            .filter_map(Result::ok)
+        {
+            // Non-resident streams can be split across several attributes.
+            if !names.contains(&attribute.header.name) {
+                names.push(attribute.header.name);
+            }
+        }
+
+        names
+    }
+
+    /// hard_links(self, /)
+    /// --
+    ///
+    /// Returns a list of `((parent_entry_id, parent_entry_sequence), name, namespace)` tuples,
+    /// one for every $FILE_NAME attribute of the entry.
+    pub fn hard_links(&self) -> Vec<((u64, u16), String, String)> {
+        file_names(&self.inner)
+            .map(|name| {
+                (
+                    (name.parent.entry, name.parent.sequence),
+                    name.name,
+                    format!("{:?}", name.namespace),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Parses attribute types given as type codes (e.g. `0x30`) or as names (e.g. `FileName`, case insensitive).
+fn attribute_types(types: &[&PyAny]) -> PyResult<Vec<MftAttributeType>> {
+    types
+        .iter()
+        .map(|attribute_type| {
+            let parsed = match attribute_type.extract::<u32>() {
+                Ok(type_code) => MftAttributeType::from_u32(type_code),
+                Err(_) => {
+                    let name = attribute_type.extract::<&str>()?;
+                    (0x10..=0x100)
+                        .step_by(0x10)
+                        .filter_map(MftAttributeType::from_u32)
+                        .find(|known| format!("{:?}", known).eq_ignore_ascii_case(name))
+                }
+            };
+
+            parsed.ok_or_else(|| {
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "unknown attribute type {}",
+                    attribute_type.repr().map(|repr| repr.to_string()).unwrap_or_default()
+                ))
+            })
+        })
+        .collect()
+}
+
+/// Reads the attributes of a record (those of `types` only, if given) without failing on bad attribute contents.
+/// Like `MftEntry::iter_attributes`, but an attribute whose content fails to parse is returned as a raw
+/// attribute along with the parse error.
+/// A broken attribute header does not hide the following attributes.
+pub fn iter_attributes_lenient(
+    entry: &MftEntry,
+    types: Option<&[MftAttributeType]>,
+) -> Vec<Result<(MftAttribute, Option<String>), mft::err::Error>> {
+    let mut cursor = Cursor::new(&entry.data);
+    let mut offset = u64::from(entry.header.first_attribute_record_offset);
+    let mut attributes = vec![];
+
+    while offset + 8 <= entry.data.len() as u64 {
+        let record_length = u64::from(u32_at(&entry.data, offset as usize + 4));
+
+        if let Err(e) = cursor.seek(SeekFrom::Start(offset)) {
+            attributes.push(Err(e.into()));
+            break;
+        }
+
+        match MftAttributeHeader::from_stream(&mut cursor) {
+            // $END marker.
+            Ok(None) => break,
+            // Attributes which are not wanted are skipped before reading their content.
+            Ok(Some(header)) if types.is_some_and(|types| !types.contains(&header.type_code)) => {}
+            Ok(Some(header)) => {
+                let (data, parse_error) = match header.residential_header {
+                    ResidentialHeader::Resident(ref resident) => {
+                        match MftAttributeContent::from_stream_resident(&mut cursor, &header, resident) {
+                            Ok(content) => (content, None),
+                            Err(e) => {
+                                let len = entry.data.len();
+                                let start = (offset as usize + resident.data_offset as usize).min(len);
+                                let end = (start + resident.data_size as usize).min(len);
+
+                                let raw = RawAttribute {
+                                    attribute_type: header.type_code.clone(),
+                                    data: entry.data[start..end].to_vec(),
+                                };
+
+                                (MftAttributeContent::Raw(raw), Some(e.to_string()))
+                            }
+                        }
+                    }
+                    ResidentialHeader::NonResident(_) => (MftAttributeContent::None, None),
+                };
+
+                attributes.push(Ok((MftAttribute { header, data }, parse_error)));
+            }
+            Err(e) => attributes.push(Err(e)),
+        }
+
+        // Without a usable length there is no way to find the next attribute.
+        if record_length == 0 || offset + record_length > entry.data.len() as u64 {
+            break;
+        }
+
+        offset += record_length;
+    }
+
+    attributes
+}
+
+/// Reverts the fixups of an entry, by putting the update sequence number back at the end of every sector.
+pub fn without_fixups(entry: &MftEntry) -> Vec<u8> {
+    let mut data = entry.data.clone();
+
+    // Fixups are only applied to entries with a valid header.
+    if entry.valid_fixup.is_none() {
+        return data;
+    }
+
+    let usa_offset = entry.header.usa_offset as usize;
+    if usa_offset + 2 > data.len() {
+        return data;
+    }
+
+    let update_sequence = [data[usa_offset], data[usa_offset + 1]];
+    for sector in 1..entry.header.usa_size as usize {
+        let end = sector * 512;
+        if end > data.len() {
+            break;
+        }
+
+        data[end - 2..end].copy_from_slice(&update_sequence);
+    }
+
+    data
+}
+
+/// Serializes an entry, along with the values resolved by pymft.
+pub fn entry_to_json_value(
+    entry: &MftEntry,
+    options: &EntryOptions,
+    timestamps: TimestampFormat,
+) -> serde_json::Result<serde_json::Value> {
+    let mut value = serde_json::to_value(entry)?;
+
+    if let Some(object) = value.as_object_mut() {
+        let (owner_sid, group_sid) = owner_and_group(entry, options.security.as_ref());
+        object.insert("owner_sid".to_string(), serde_json::json!(owner_sid));
+        object.insert("group_sid".to_string(), serde_json::json!(group_sid));
+
+        let storage = StorageFlags::from_entry(entry);
+        object.insert("is_directory".to_string(), serde_json::json!(storage.is_directory));
+        object.insert("is_compressed".to_string(), serde_json::json!(storage.is_compressed));
+        object.insert("is_encrypted".to_string(), serde_json::json!(storage.is_encrypted));
+        object.insert("is_sparse".to_string(), serde_json::json!(storage.is_sparse));
+
+        let standard_info_flags = entry
+            .iter_attributes_matching(Some(vec![MftAttributeType::StandardInformation]))
+            .filter_map(Result::ok)
+            .find_map(|a| a.data.into_standard_info())
+            .map(|info| file_attributes_json(info.file_flags));
+        let file_name_flags = file_names(entry).next().map(|name| file_attributes_json(name.flags));
+        object.insert("standard_info_flags".to_string(), serde_json::json!(standard_info_flags));
+        object.insert("file_name_flags".to_string(), serde_json::json!(file_name_flags));
+
+        if options.lenient {
+            let attributes: Vec<MftAttribute> = iter_attributes_lenient(entry, None)
+                .into_iter()
+                .filter_map(Result::ok)
+                .map(|(attribute, _)| attribute)
+                .collect();
+            object.insert("attributes".to_string(), serde_json::to_value(attributes)?);
+        }
+    }
+
+    timestamps.convert_json(&mut value);
+
+    Ok(value)
+}
+
+/// Storage properties of an entry, which are set either in the $STANDARD_INFORMATION file attributes,
+/// or in the flags of the unnamed $DATA attribute.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StorageFlags {
+    pub is_directory: bool,
+    pub is_compressed: bool,
+    pub is_encrypted: bool,
+    pub is_sparse: bool,
+}
+
+impl StorageFlags {
+    pub fn from_entry(entry: &MftEntry) -> Self {
+        let mut file_flags = FileAttributeFlags::empty();
+        let mut data_flags = AttributeDataFlags::empty();
+
+        for attribute in entry
+            .iter_attributes_matching(Some(vec![MftAttributeType::StandardInformation, MftAttributeType::DATA]))
+            .filter_map(Result::ok)
+        {
+            match attribute.data {
+                MftAttributeContent::AttrX10(info) => file_flags |= info.file_flags,
+                _ if attribute.header.type_code == MftAttributeType::DATA && attribute.header.name.is_empty() => {
+                    data_flags |= attribute.header.data_flags
+                }
+                _ => {}
+            }
+        }
+
+        StorageFlags {
+            is_directory: entry.is_dir(),
+            is_compressed: file_flags.contains(FileAttributeFlags::FILE_ATTRIBUTE_COMPRESSED)
+                || data_flags.intersects(AttributeDataFlags::COMPRESSION_MASK),
+            is_encrypted: file_flags.contains(FileAttributeFlags::FILE_ATTRIBUTE_ENCRYPTED)
+                || data_flags.contains(AttributeDataFlags::ENCRYPTED),
+            is_sparse: file_flags.contains(FileAttributeFlags::FILE_ATTRIBUTE_SPARSE_FILE)
+                || data_flags.contains(AttributeDataFlags::SPARSE),
+        }
+    }
+}
+
+/// Returns the `(file_size, allocated_size)` of the unnamed $DATA attribute.
+/// Resident content does not use any clusters, so its allocated size is 0.
+pub fn data_sizes(entry: &MftEntry) -> (u64, u64) {
+    entry
+        .iter_attributes_matching(Some(vec![MftAttributeType::DATA]))
+        .filter_map(Result::ok)
+        .filter(|a| a.header.name.is_empty())
+        .find_map(|a| match a.header.residential_header {
+            ResidentialHeader::Resident(r) => Some((u64::from(r.data_size), 0)),
+            // Only the first segment of a non-resident attribute has the stream sizes.
+            ResidentialHeader::NonResident(nr) if nr.vnc_first == 0 => Some((nr.file_size, nr.allocated_length)),
+            ResidentialHeader::NonResident(_) => None,
+        })
+        .unwrap_or((0, 0))
+}
+
+/// Returns the valid data length (initialized size) of the unnamed $DATA attribute.
+/// Resident content is always fully initialized.
+pub fn valid_data_length(entry: &MftEntry) -> u64 {
+    entry
+        .iter_attributes_matching(Some(vec![MftAttributeType::DATA]))
+        .filter_map(Result::ok)
+        .filter(|a| a.header.name.is_empty())
+        .find_map(|a| match a.header.residential_header {
+            ResidentialHeader::Resident(r) => Some(u64::from(r.data_size)),
+            ResidentialHeader::NonResident(nr) if nr.vnc_first == 0 => Some(nr.valid_data_length),
+            ResidentialHeader::NonResident(_) => None,
+        })
+        .unwrap_or(0)
+}
+
+/// Returns the content of the unnamed $DATA attribute, if it is resident.
+pub fn resident_data(entry: &MftEntry) -> Option<Vec<u8>> {
+    entry
+        .iter_attributes_matching(Some(vec![MftAttributeType::DATA]))
+        .filter_map(Result::ok)
+        .find(|a| a.header.name.is_empty())
+        .and_then(|a| a.data.into_data())
+        .map(|data| data.data().to_vec())
+}
+
+/// Iterates over the $FILE_NAME attributes of an entry.
+pub fn file_names(entry: &MftEntry) -> impl Iterator<Item = FileNameAttr> + '_ {
+    entry
+        .iter_attributes_matching(Some(vec![MftAttributeType::FileName]))
+        .filter_map(Result::ok)
+        .filter_map(|a| match a.data {
+            MftAttributeContent::AttrX30(name) => Some(name),
+            _ => None,
+        })
+}
+
+/// Offset of the name length in the content of a $FILE_NAME attribute, the UTF-16 name follows.
+const FILE_NAME_LENGTH_OFFSET: usize = 0x40;
+
+/// The UTF-16LE bytes of the name in the content of a $FILE_NAME attribute.
+pub fn file_name_bytes(content: &[u8]) -> &[u8] {
+    let length = match content.get(FILE_NAME_LENGTH_OFFSET) {
+        Some(length) => *length as usize * 2,
+        None => return &[],
+    };
+
+    let start = FILE_NAME_LENGTH_OFFSET + 2;
+    content.get(start..start + length).or_else(|| content.get(start..)).unwrap_or(&[])
+}
+
+/// Whether invalid code units were dropped when the name was decoded.
+pub fn name_was_truncated(name: &FileNameAttr) -> bool {
+    name.name.encode_utf16().count() != name.name_length as usize
+}
+
+/// Like `file_names`, but if `lossy` is set, names with invalid UTF-16 (whose invalid code units are dropped
+/// by the parser) are decoded again from the record, with replacement characters.
+pub fn decoded_file_names(entry: &MftEntry, lossy: bool) -> Vec<FileNameAttr> {
+    if !lossy {
+        return file_names(entry).collect();
+    }
+
+    entry
+        .iter_attributes_matching(Some(vec![MftAttributeType::FileName]))
+        .filter_map(Result::ok)
+        .filter_map(|a| {
+            let instance = a.header.instance;
+            let content_offset = match &a.header.residential_header {
+                ResidentialHeader::Resident(resident) => resident.data_offset as usize,
+                ResidentialHeader::NonResident(_) => return None,
+            };
+
+            let mut name = a.data.into_file_name()?;
+            if name_was_truncated(&name) {
+                if let Some(record) = attribute_record(entry, instance) {
+                    name.name = utf16_to_string(file_name_bytes(record.get(content_offset..).unwrap_or(&[])));
+                }
+            }
+
+            Some(name)
+        })
+        .collect()
+}
+
+/// References of the records listed in the (resident) $ATTRIBUTE_LIST of an entry, other than itself.
+/// Every record is listed once, in order of appearance.
+pub fn extension_references(entry: &MftEntry) -> Vec<MftReference> {
+    let mut references: Vec<MftReference> = vec![];
+
+    for attribute in entry
+        .iter_attributes_matching(Some(vec![MftAttributeType::AttributeList]))
+        .filter_map(Result::ok)
+    {
+        if let MftAttributeContent::AttrX20(list) = attribute.data {
+            for item in list.entries {
+                let reference = item.segment_reference;
+                if reference.entry != entry.header.record_number && !references.contains(&reference) {
+                    references.push(reference);
+                }
+            }
+        }
+    }
+
+    references
+}
+
+/// Number of names of an entry, as counted by the entry header hard link count.
+/// Separate DOS and Win32 names are counted as two links.
+pub fn link_count(entry: &MftEntry) -> usize {
+    file_names(entry).count()
 }
 
 impl PyMftEntry {
-    pub fn from_mft_entry(
+    /// The base record, followed by the extension records loaded by `resolve_extensions`.
+    pub fn records(&self) -> impl Iterator<Item = &MftEntry> {
+        std::iter::once(&self.inner).chain(self.extensions.iter())
+    }
+
+    fn sizes(&self) -> (u64, u64) {
+        // The $DATA attribute may be in an extension record.
+        *self.sizes.get_or_init(|| {
+            self.records()
+                .map(data_sizes)
+                .find(|sizes| *sizes != (0, 0))
+                .unwrap_or((0, 0))
+        })
+    }
+
+    fn sids(&self) -> &(Option<String>, Option<String>) {
+        self.sids
+            .get_or_init(|| owner_and_group(&self.inner, self.options.security.as_ref()))
+    }
+
+    fn storage(&self) -> StorageFlags {
+        *self.storage.get_or_init(|| StorageFlags::from_entry(&self.inner))
+    }
+
+    /// Builds an entry whose path is resolved when first accessed.
+    pub fn from_mft_entry(py: Python, entry: MftEntry, context: &EntryContext) -> PyResult<Py<PyMftEntry>> {
+        PyMftEntry::new(
+            py,
+            entry,
+            vec![],
+            Some(context.shared_parser.clone()),
+            None,
+            context.entry_options(),
+        )
+    }
+
+    /// Builds an entry whose path was already resolved (for example, a carved entry without a parser).
+    pub fn from_entry_with_path(
         py: Python,
         entry: MftEntry,
-        parser: &mut MftParser<impl ReadSeek>,
+        full_path: String,
+        security: Option<&SecurityDescriptors>,
+        lenient: bool,
     ) -> PyResult<Py<PyMftEntry>> {
-        let full_path = parser
-            .get_full_path_for_entry(&entry)
-            .expect("unreachable")
-            .unwrap_or_else(|| PathBuf::from("[UNKNOWN]"))
-            .to_string_lossy()
-            .to_string();
-
-        let file_size = entry
-            .iter_attributes_matching(Some(vec![
-                MftAttributeType::DATA,
-            ]))
-            .find_map(Result::ok)
-            .map_or(0, |attr| {
-                match &attr.header.residential_header {
-                    ResidentialHeader::Resident(r) => u64::from(r.data_size),
-                    ResidentialHeader::NonResident(nr) => nr.file_size,
-                }
-            });
+        let options = EntryOptions {
+            security: security.cloned(),
+            lenient,
+            lossy_names: false,
+            hash_resident_data: false,
+        };
 
+        PyMftEntry::new(py, entry, vec![], None, Some(full_path), options)
+    }
+
+    fn new(
+        py: Python,
+        entry: MftEntry,
+        extensions: Vec<MftEntry>,
+        parser: Option<SharedParser>,
+        resolved_path: Option<String>,
+        options: EntryOptions,
+    ) -> PyResult<Py<PyMftEntry>> {
         Py::new(
             py,
             PyMftEntry {
+                signature: if &entry.header.signature == ZERO_HEADER {
+                    String::new()
+                } else {
+                    String::from_utf8_lossy(&entry.header.signature).to_string()
+                },
+                usa_offset: entry.header.usa_offset,
+                usa_size: entry.header.usa_size,
+                log_sequence_number: entry.header.metadata_transaction_journal,
                 entry_id: entry.header.record_number,
                 sequence: entry.header.sequence,
                 base_entry_id: entry.header.base_reference.entry,
-                base_entry_sequence: 0,
-                hard_link_count: 0,
+                base_entry_sequence: entry.header.base_reference.sequence,
+                hard_link_count: entry.header.hard_link_count,
+                first_attribute_offset: entry.header.first_attribute_record_offset,
+                first_attribute_id: entry.header.first_attribute_id,
                 flags: format!("{:?}", entry.header.flags),
                 used_entry_size: entry.header.used_entry_size,
                 total_entry_size: entry.header.total_entry_size,
+                fixup_ok: entry.valid_fixup,
+                is_directory: entry.is_dir(),
                 inner: entry,
-                full_path,
-                file_size,
+                extensions,
+                options,
+                parser,
+                resolved_path,
+                sizes: OnceCell::new(),
+                sids: OnceCell::new(),
+                storage: OnceCell::new(),
             },
         )
     }
 }
 
+#[pyproto]
+impl PyObjectProtocol for PyMftEntry {
+    fn __repr__(&self) -> String {
+        if self.signature.is_empty() {
+            return format!("<MftEntry {} empty>", self.entry_id);
+        }
+
+        // Unlike `full_path`, the resolved path is not cached, as `__repr__` only borrows the entry.
+        let full_path = match (&self.resolved_path, &self.parser) {
+            (Some(full_path), _) => full_path.clone(),
+            (None, Some(parser)) => {
+                let entry = &self.inner;
+                Python::with_gil(|py| py.allow_threads(|| parser.full_path(entry)))
+                    .unwrap_or_else(|| PathBuf::from("[UNKNOWN]"))
+                    .to_string_lossy()
+                    .to_string()
+            }
+            (None, None) => "[UNKNOWN]".to_string(),
+        };
+        let status = if self.inner.header.flags.contains(EntryFlags::ALLOCATED) {
+            "in_use"
+        } else {
+            "deleted"
+        };
+        let kind = if self.is_directory { "directory" } else { "file" };
+
+        format!("<MftEntry {} {} {} {}>", self.entry_id, py_repr(&full_path), status, kind)
+    }
+
+    /// Entries are equal if they are the same version of a record: same entry id and sequence number,
+    /// like file references.
+    fn __richcmp__(&self, other: PyObject, op: CompareOp) -> PyObject {
+        Python::with_gil(|py| {
+            let other = match other.extract::<PyRef<PyMftEntry>>(py) {
+                Ok(other) => other,
+                Err(_) => return py.NotImplemented(),
+            };
+            let reference = (self.entry_id, self.sequence);
+            let other_reference = (other.entry_id, other.sequence);
+
+            match op {
+                CompareOp::Eq => (reference == other_reference).into_py(py),
+                CompareOp::Ne => (reference != other_reference).into_py(py),
+                _ => py.NotImplemented(),
+            }
+        })
+    }
+
+    fn __hash__(&self) -> isize {
+        let mut hasher = DefaultHasher::new();
+        (self.entry_id, self.sequence).hash(&mut hasher);
+        hasher.finish() as isize
+    }
+}
+
+/// An iterator over the entries below a directory.
+#[pyclass]
+pub struct PyMftWalkIterator {
+    parser: SharedParser,
+    descendants: std::vec::IntoIter<u64>,
+    options: EntryOptions,
+}
+
+#[pyproto]
+impl PyIterProtocol for PyMftWalkIterator {
+    fn __iter__(slf: PyRefMut<Self>) -> PyResult<Py<PyMftWalkIterator>> {
+        Ok(slf.into())
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        let gil = Python::acquire_gil();
+        slf.next(gil.python())
+    }
+}
+
+impl PyMftWalkIterator {
+    fn next(&mut self, py: Python) -> PyResult<Option<PyObject>> {
+        let entry_id = match self.descendants.next() {
+            Some(entry_id) => entry_id,
+            None => return Ok(None),
+        };
+
+        let parser = &self.parser;
+        match py.allow_threads(|| parser.get_entry(entry_id)) {
+            Ok(entry) => Ok(Some(
+                PyMftEntry::new(
+                    py,
+                    entry,
+                    vec![],
+                    Some(self.parser.clone()),
+                    None,
+                    self.options.clone(),
+                )?
+                .to_object(py),
+            )),
+            Err(e) => Ok(Some(PyErr::from(PyMftError(e)).to_object(py))),
+        }
+    }
+}
+
 #[pyclass]
 pub struct PyMftAttributesIter {
     inner: Box<dyn Iterator<Item = PyObject> + Send>,
@@ -0,0 +1,869 @@
+//! Multi-threaded export of one or more MFTs (as CSV, JSON lines, XML, an Arrow IPC stream, or a Parquet file).
+//! Records are read, parsed and serialized a chunk at a time by worker threads, each with its own parser over the MFT,
+//! and written in order by the calling thread. The workers of a batch move on to the next MFT as soon as
+//! every chunk of the current one is taken, so that several MFTs are exported at once.
+use crate::arrow::{
+    end_of_stream, flat_row, record_batch_message, schema_message, DataType, Field, Value as ArrowValue, FLAT_SCHEMA,
+};
+use crate::entry::{entry_to_json_value, EntryContext};
+use crate::flat::FlatMftEntry;
+use crate::gzip::GzEncoder;
+use crate::parquet::{self, RowGroup};
+use crate::parser::{entry_from_buffer, MftParser};
+use crate::utils::{with_warnings, BufferReader, FileOrFileLike};
+use crate::volume::SharedReader;
+use crate::xml::{flat_entry_to_xml, XML_EPILOGUE, XML_PROLOGUE};
+use crate::ReadSeek;
+
+use csv::WriterBuilder;
+use log::warn;
+use mft::entry::ZERO_HEADER;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Number of records a worker parses at once.
+const CHUNK_SIZE: u64 = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    /// One JSON document per line.
+    Json,
+    /// An `Mft` XML document, with one `Entry` element per line.
+    Xml,
+    /// An Arrow IPC stream, with a record batch per chunk.
+    ArrowIpc,
+    /// A Parquet file, with a row group per chunk.
+    Parquet,
+}
+
+impl ExportOptions {
+    /// The fields of Arrow and Parquet output.
+    fn arrow_schema(&self) -> Vec<Field> {
+        let fields = match &self.context.columns {
+            Some(columns) => columns.fields(),
+            None => FLAT_SCHEMA.to_vec(),
+        };
+
+        match self.source {
+            Some(_) => std::iter::once(SOURCE_FIELD).chain(fields).collect(),
+            None => fields,
+        }
+    }
+
+    /// Written before the first chunk.
+    fn prologue(&self) -> Vec<u8> {
+        match self.format {
+            ExportFormat::Csv | ExportFormat::Json => vec![],
+            ExportFormat::Xml => XML_PROLOGUE.as_bytes().to_vec(),
+            ExportFormat::ArrowIpc => schema_message(&self.arrow_schema()),
+            ExportFormat::Parquet => parquet::MAGIC.to_vec(),
+        }
+    }
+
+    /// Written after the last chunk. The footer of Parquet output describes the written row groups.
+    fn epilogue(&self, row_groups: &[(u64, RowGroup)]) -> Vec<u8> {
+        match self.format {
+            ExportFormat::Csv | ExportFormat::Json => vec![],
+            ExportFormat::Xml => XML_EPILOGUE.as_bytes().to_vec(),
+            ExportFormat::ArrowIpc => end_of_stream(),
+            ExportFormat::Parquet => parquet::footer(&self.arrow_schema(), row_groups),
+        }
+    }
+}
+
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    pub entry_size: u32,
+    pub strict: bool,
+    /// The `verbose_warnings` option of the parser, for the warnings of the workers.
+    pub verbose_warnings: Option<bool>,
+    pub threads: usize,
+    /// Cloned for every worker, so that every worker has its own path cache.
+    pub context: EntryContext,
+    /// Written in a first `Source` column (a `source` key in JSON) of every row, when exporting several MFTs.
+    pub source: Option<String>,
+    /// Collected while exporting, if set.
+    pub stats: Option<ExportStats>,
+}
+
+/// Counters of an export, updated by the workers and the writer.
+/// Times are in nanoseconds, summed over the workers.
+#[derive(Debug, Default)]
+pub struct ExportStats {
+    pub records: AtomicU64,
+    pub reading: AtomicU64,
+    pub path_resolution: AtomicU64,
+    pub parsing: AtomicU64,
+    pub serialization: AtomicU64,
+    pub writing: AtomicU64,
+}
+
+/// Runs `f`, and adds the time it took to `nanos` (if statistics are collected).
+fn timed<T>(nanos: Option<&AtomicU64>, f: impl FnOnce() -> T) -> T {
+    let nanos = match nanos {
+        Some(nanos) => nanos,
+        None => return f(),
+    };
+
+    let started = Instant::now();
+    let result = f();
+    nanos.fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    result
+}
+
+/// Statistics of an export, returned by the exports of `PyMftParser` when `stats` is set,
+/// to tune the number of threads and the output on a given machine.
+/// The time of each stage is summed over the worker threads, so it may exceed `elapsed`.
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct PyMftExportStats {
+    /// The number of written rows
+    #[pyo3(get)]
+    pub rows: u64,
+    /// The number of records which were read (including empty and skipped ones)
+    #[pyo3(get)]
+    pub records: u64,
+    /// The size of the records which were read
+    #[pyo3(get)]
+    pub bytes_read: u64,
+    #[pyo3(get)]
+    pub threads: usize,
+    /// The duration of the whole export, in seconds
+    #[pyo3(get)]
+    pub elapsed: f64,
+    /// Seconds spent reading records from the input
+    #[pyo3(get)]
+    pub read_seconds: f64,
+    /// Seconds spent resolving the paths of entries
+    #[pyo3(get)]
+    pub path_resolution_seconds: f64,
+    /// Seconds spent parsing the attributes of entries into rows
+    #[pyo3(get)]
+    pub parsing_seconds: f64,
+    /// Seconds spent serializing rows to the output format
+    #[pyo3(get)]
+    pub serialization_seconds: f64,
+    /// Seconds spent writing to the output (including compression)
+    #[pyo3(get)]
+    pub write_seconds: f64,
+}
+
+impl PyMftExportStats {
+    pub fn new(rows: u64, entry_size: u32, threads: usize, elapsed: Duration, stats: &ExportStats) -> Self {
+        let seconds = |nanos: &AtomicU64| nanos.load(Ordering::Relaxed) as f64 / 1e9;
+        let records = stats.records.load(Ordering::Relaxed);
+
+        PyMftExportStats {
+            rows,
+            records,
+            bytes_read: records * u64::from(entry_size),
+            threads,
+            elapsed: elapsed.as_secs_f64(),
+            read_seconds: seconds(&stats.reading),
+            path_resolution_seconds: seconds(&stats.path_resolution),
+            parsing_seconds: seconds(&stats.parsing),
+            serialization_seconds: seconds(&stats.serialization),
+            write_seconds: seconds(&stats.writing),
+        }
+    }
+}
+
+#[pymethods]
+impl PyMftExportStats {
+    /// Records read per second of the export
+    #[getter]
+    pub fn records_per_second(&self) -> f64 {
+        if self.elapsed > 0.0 {
+            self.records as f64 / self.elapsed
+        } else {
+            0.0
+        }
+    }
+
+    /// Bytes of the MFT read per second of the export
+    #[getter]
+    pub fn bytes_per_second(&self) -> f64 {
+        if self.elapsed > 0.0 {
+            self.bytes_read as f64 / self.elapsed
+        } else {
+            0.0
+        }
+    }
+}
+
+/// The column of the source of rows, in Arrow output.
+const SOURCE_FIELD: Field = Field {
+    name: "Source",
+    data_type: DataType::Utf8,
+    nullable: false,
+};
+
+/// The MFT to export, which every worker reads a chunk at a time.
+#[derive(Clone)]
+pub enum ExportSource {
+    /// An MFT in memory (an input supporting the buffer protocol), whose chunks are sliced in place.
+    Buffer(BufferReader),
+    /// Any other input, whose chunks are read into a buffer of the worker.
+    Reader(SharedReader),
+}
+
+impl ExportSource {
+    fn size(&self) -> u64 {
+        match self {
+            ExportSource::Buffer(buffer) => buffer.size(),
+            ExportSource::Reader(reader) => reader.size(),
+        }
+    }
+
+    /// Another handle to the input, for the lookups (e.g. of parent directories) of a worker.
+    fn reader(&self) -> Box<dyn ReadSeek + Send> {
+        match self {
+            ExportSource::Buffer(buffer) => Box::new(buffer.clone()),
+            ExportSource::Reader(reader) => Box::new(reader.clone()),
+        }
+    }
+
+    /// The records of the chunk at `index`, read into `buffer` unless the input is in memory.
+    fn read_chunk<'a>(&'a self, index: u64, entry_size: u32, buffer: &'a mut Vec<u8>) -> io::Result<&'a [u8]> {
+        let entry_size = u64::from(entry_size);
+        let start = index * CHUNK_SIZE * entry_size;
+        let end = ((index + 1) * CHUNK_SIZE * entry_size).min(self.size() / entry_size * entry_size);
+
+        match self {
+            ExportSource::Buffer(input) => Ok(&input.as_slice()[start as usize..end as usize]),
+            ExportSource::Reader(reader) => {
+                buffer.resize((end - start) as usize, 0);
+                reader.read_exact_at(start, buffer)?;
+                Ok(buffer)
+            }
+        }
+    }
+}
+
+/// An MFT to export, and the options to export it with.
+pub struct ExportInput {
+    pub source: ExportSource,
+    pub options: ExportOptions,
+}
+
+impl ExportInput {
+    fn entry_count(&self) -> u64 {
+        self.source.size() / u64::from(self.options.entry_size)
+    }
+}
+
+/// Opens the MFT at an index of a batch, once the workers reach it.
+pub type OpenInput<'a> = dyn Fn(usize) -> io::Result<ExportInput> + Sync + 'a;
+
+/// A chunk for a worker to serialize.
+struct Task {
+    /// The index of the MFT in the batch.
+    input: usize,
+    index: u64,
+    /// Whether this is the last chunk of the MFT.
+    last: bool,
+    export: Arc<ExportInput>,
+}
+
+struct ScheduleState {
+    input: usize,
+    current: Arc<ExportInput>,
+    next_chunk: u64,
+    total_chunks: u64,
+}
+
+impl ScheduleState {
+    fn new(input: usize, current: Arc<ExportInput>) -> Self {
+        // An empty MFT still has a (empty) chunk, which tells the writer to move on to the next MFT.
+        let total_chunks = current.entry_count().div_ceil(CHUNK_SIZE).max(1);
+
+        ScheduleState {
+            input,
+            current,
+            next_chunk: 0,
+            total_chunks,
+        }
+    }
+}
+
+/// Hands out the chunks of the MFTs of an export in order. An MFT is opened when its first chunk is handed out,
+/// and closed once its last chunk is serialized, so that only the MFTs which are being exported are open.
+struct Schedule<'a> {
+    count: usize,
+    open: &'a OpenInput<'a>,
+    state: Mutex<ScheduleState>,
+}
+
+impl Schedule<'_> {
+    /// The next chunk to serialize, None once every chunk was handed out.
+    fn next(&self) -> io::Result<Option<Task>> {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        while state.next_chunk == state.total_chunks {
+            let input = state.input + 1;
+            if input >= self.count {
+                return Ok(None);
+            }
+
+            match (self.open)(input) {
+                Ok(opened) => *state = ScheduleState::new(input, Arc::new(opened)),
+                Err(e) => {
+                    // So that the other workers stop, rather than opening the MFT again.
+                    state.input = self.count;
+                    return Err(e);
+                }
+            }
+        }
+
+        let task = Task {
+            input: state.input,
+            index: state.next_chunk,
+            last: state.next_chunk + 1 == state.total_chunks,
+            export: state.current.clone(),
+        };
+        state.next_chunk += 1;
+        Ok(Some(task))
+    }
+}
+
+/// The state of a worker for the MFT it is exporting.
+struct Worker {
+    input: usize,
+    /// Looks up other records (e.g. parent directories) of the MFT.
+    parser: MftParser<Box<dyn ReadSeek + Send>>,
+    context: EntryContext,
+}
+
+impl Worker {
+    fn new(task: &Task) -> Self {
+        let ExportInput { source, options } = &*task.export;
+
+        let mut parser = MftParser::with_layout(
+            source.reader(),
+            task.export.entry_count() * u64::from(options.entry_size),
+            options.entry_size,
+        );
+        parser.set_verbose_warnings(options.verbose_warnings);
+
+        Worker {
+            input: task.input,
+            parser,
+            context: options.context.clone(),
+        }
+    }
+}
+
+/// A serialized chunk of records.
+struct Chunk {
+    input: usize,
+    index: u64,
+    last: bool,
+    rows: u64,
+    data: Vec<u8>,
+    /// The layout of the data, in Parquet output.
+    row_group: Option<RowGroup>,
+}
+
+/// Serializes the records of the chunk of `task`.
+fn serialize_chunk(worker: &mut Worker, task: &Task, records: &[u8]) -> io::Result<Chunk> {
+    let Worker { parser, context, .. } = worker;
+    let options = &task.export.options;
+
+    // A CSV chunk is serialized with its header, which is only kept for the first written chunk.
+    let mut writer = WriterBuilder::new().from_writer(Vec::new());
+    let mut lines = Vec::new();
+    let mut arrow_rows = vec![];
+    let mut rows = 0;
+
+    let start = task.index * CHUNK_SIZE;
+    let stats = options.stats.as_ref();
+
+    for (i, record) in (start..).zip(records.chunks_exact(options.entry_size as usize)) {
+        let entry = match entry_from_buffer(record.to_vec(), i) {
+            Ok(entry) if &entry.header.signature == ZERO_HEADER => continue,
+            Ok(entry) if options.strict && entry.valid_fixup == Some(false) => {
+                warn!("Skipping entry {}, it has mismatching fixup values", i);
+                continue;
+            }
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!("Skipping entry {}: {}", i, e);
+                continue;
+            }
+        };
+
+        // Paths are resolved ahead of flat entries only to be timed, flat entries then hit the path cache.
+        if let Some(stats) = stats {
+            if options.format != ExportFormat::Json && context.columns.as_ref().is_none_or(|c| c.contains("FullPath")) {
+                timed(Some(&stats.path_resolution), || context.paths.full_path(&entry, parser));
+            }
+        }
+        let parsing = stats.map(|s| &s.parsing);
+        let serialization = stats.map(|s| &s.serialization);
+
+        match options.format {
+            ExportFormat::Csv => {
+                let flat = timed(parsing, || FlatMftEntry::from_entry(&entry, parser, context));
+                timed(serialization, || match &options.source {
+                    Some(source) => flat.write_csv_with_source(&mut writer, context.columns.as_ref(), rows == 0, source),
+                    None => flat.write_csv(&mut writer, context.columns.as_ref(), rows == 0),
+                })?
+            }
+            ExportFormat::Json => {
+                let mut value = timed(parsing, || {
+                    entry_to_json_value(&entry, &context.entry_options(), context.timestamps)
+                })?;
+                if let (Some(source), Some(object)) = (&options.source, value.as_object_mut()) {
+                    object.insert("source".to_string(), serde_json::Value::String(source.clone()));
+                }
+                timed(serialization, || serde_json::to_writer(&mut lines, &value))?;
+                lines.push(b'\n');
+            }
+            ExportFormat::Xml => {
+                let flat = timed(parsing, || FlatMftEntry::from_entry(&entry, parser, context));
+                let xml = timed(serialization, || flat_entry_to_xml(&flat, context.columns.as_ref()))?;
+                lines.extend_from_slice(xml.as_bytes());
+                lines.push(b'\n');
+            }
+            ExportFormat::ArrowIpc | ExportFormat::Parquet => {
+                let row = timed(parsing, || flat_row(&FlatMftEntry::from_entry(&entry, parser, context)));
+                let row = match &context.columns {
+                    Some(columns) => columns.project(&row),
+                    None => row,
+                };
+                arrow_rows.push(match &options.source {
+                    Some(source) => std::iter::once(Some(ArrowValue::Utf8(source.clone()))).chain(row).collect(),
+                    None => row,
+                });
+            }
+        }
+        rows += 1;
+    }
+
+    if let Some(stats) = stats {
+        stats
+            .records
+            .fetch_add((records.len() / options.entry_size as usize) as u64, Ordering::Relaxed);
+    }
+
+    let mut row_group = None;
+    let data = match options.format {
+        ExportFormat::Csv => writer
+            .into_inner()
+            .map_err(|e| io::Error::other(e.to_string()))?,
+        ExportFormat::Json | ExportFormat::Xml => lines,
+        ExportFormat::ArrowIpc | ExportFormat::Parquet if arrow_rows.is_empty() => vec![],
+        ExportFormat::ArrowIpc => timed(stats.map(|s| &s.serialization), || {
+            record_batch_message(&options.arrow_schema(), &arrow_rows)
+        }),
+        ExportFormat::Parquet => {
+            let (data, layout) = timed(stats.map(|s| &s.serialization), || {
+                parquet::row_group(&options.arrow_schema(), &arrow_rows)
+            });
+            row_group = Some(layout);
+            data
+        }
+    };
+
+    Ok(Chunk {
+        input: task.input,
+        index: task.index,
+        last: task.last,
+        rows,
+        data,
+        row_group,
+    })
+}
+
+/// Writes every entry of the MFT of `input`, and returns the number of written rows.
+pub fn export(input: &Arc<ExportInput>, writer: &mut dyn Write) -> io::Result<u64> {
+    let threads = input.options.threads;
+    export_batch(input.clone(), 1, &|_| unreachable!("a single MFT is exported"), threads, writer)
+}
+
+/// Writes every entry of `count` MFTs one after the other, as a single output, and returns the number of written rows.
+/// `first` is the first MFT, whose options (e.g. the format and the columns) apply to the whole output,
+/// and the next ones are opened by `open` once the workers reach them.
+pub fn export_batch(
+    first: Arc<ExportInput>,
+    count: usize,
+    open: &OpenInput,
+    threads: usize,
+    writer: &mut dyn Write,
+) -> io::Result<u64> {
+    let options = &first.options;
+    let schedule = Schedule {
+        count,
+        open,
+        state: Mutex::new(ScheduleState::new(0, first.clone())),
+    };
+
+    thread::scope(|scope| {
+        // Bounded, so that workers do not get too far ahead of the writer.
+        let (sender, receiver) = mpsc::sync_channel::<io::Result<Chunk>>(threads * 2);
+
+        for _ in 0..threads {
+            let sender = sender.clone();
+            let schedule = &schedule;
+
+            scope.spawn(move || {
+                let mut worker: Option<Worker> = None;
+                let mut buffer = vec![];
+
+                loop {
+                    let task = match schedule.next() {
+                        Ok(Some(task)) => task,
+                        Ok(None) => return,
+                        Err(e) => {
+                            let _ = sender.send(Err(e));
+                            return;
+                        }
+                    };
+
+                    if worker.as_ref().is_none_or(|w| w.input != task.input) {
+                        worker = Some(Worker::new(&task));
+                    }
+                    let worker = worker.as_mut().expect("set above");
+                    let ExportInput { source, options } = &*task.export;
+
+                    let chunk = with_warnings(options.verbose_warnings, || {
+                        let reading = options.stats.as_ref().map(|s| &s.reading);
+                        let records = timed(reading, || {
+                            source.read_chunk(task.index, options.entry_size, &mut buffer)
+                        })?;
+                        serialize_chunk(worker, &task, records)
+                    })
+                    .map_err(|e| match &options.source {
+                        Some(source) => io::Error::new(e.kind(), format!("failed to export `{}`: {}", source, e)),
+                        None => e,
+                    });
+
+                    // The writer hung up after an error.
+                    if sender.send(chunk).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        let prologue = options.prologue();
+        writer.write_all(&prologue)?;
+        let mut position = prologue.len() as u64;
+
+        let mut pending = BTreeMap::new();
+        let mut next = (0, 0);
+        let mut header_written = false;
+        let mut row_groups = vec![];
+        let mut rows = 0;
+
+        for chunk in receiver {
+            let chunk = chunk?;
+            pending.insert((chunk.input, chunk.index), chunk);
+
+            while let Some(chunk) = pending.remove(&next) {
+                next = match chunk.last {
+                    true => (next.0 + 1, 0),
+                    false => (next.0, next.1 + 1),
+                };
+
+                if chunk.rows == 0 {
+                    continue;
+                }
+
+                let data = if header_written && options.format == ExportFormat::Csv {
+                    let header_end = chunk.data.iter().position(|&b| b == b'\n').map_or(0, |i| i + 1);
+                    &chunk.data[header_end..]
+                } else {
+                    &chunk.data[..]
+                };
+
+                timed(options.stats.as_ref().map(|s| &s.writing), || writer.write_all(data))?;
+                header_written = true;
+                rows += chunk.rows;
+
+                if let Some(row_group) = chunk.row_group {
+                    row_groups.push((position, row_group));
+                }
+                position += data.len() as u64;
+
+                // So that readers of the stream can consume every batch as soon as it is written.
+                if options.format == ExportFormat::ArrowIpc {
+                    writer.flush()?;
+                }
+            }
+        }
+
+        writer.write_all(&options.epilogue(&row_groups))?;
+        writer.flush()?;
+        Ok(rows)
+    })
+}
+
+/// The destination of an export.
+pub struct ExportOutput {
+    pub writer: OutputWriter,
+}
+
+/// The writer of an export, which compresses the output with gzip if needed.
+pub enum OutputWriter {
+    Plain(Box<dyn Write + Send>),
+    /// Must be finished once the export is done, to write the end of the gzip stream.
+    Gzip(GzEncoder<Box<dyn Write + Send>>),
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputWriter::Plain(writer) => writer.write(buf),
+            OutputWriter::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputWriter::Plain(writer) => writer.flush(),
+            OutputWriter::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+impl ExportOutput {
+    /// Opens a path or a (binary) file-like object for writing.
+    /// `compress` is either None or `gzip`, paths ending with `.gz` are always compressed.
+    pub fn open(path_or_file_like: PyObject, compress: Option<&str>) -> PyResult<Self> {
+        let output = FileOrFileLike::from_pyobject_for_writing(path_or_file_like)?;
+
+        let gzip = match compress {
+            Some("gzip") => true,
+            Some(other) => {
+                return Err(PyValueError::new_err(format!(
+                    "compress must be None or `gzip`, got `{}`",
+                    other
+                )))
+            }
+            None => matches!(&output, FileOrFileLike::File(path) if path.ends_with(".gz")),
+        };
+
+        let writer = match output {
+            FileOrFileLike::File(path) => Box::new(BufWriter::new(File::create(path)?)) as Box<dyn Write + Send>,
+            FileOrFileLike::FileLike(f) => Box::new(f) as Box<dyn Write + Send>,
+            FileOrFileLike::Buffer(_) => unreachable!("outputs are never buffers"),
+        };
+
+        let writer = match gzip {
+            true => OutputWriter::Gzip(GzEncoder::new(writer)?),
+            false => OutputWriter::Plain(writer),
+        };
+
+        Ok(ExportOutput { writer })
+    }
+
+    /// Finishes the compressed stream (if any), and flushes the output.
+    /// A file-like object passed by the caller is left open.
+    pub fn close(self) -> io::Result<()> {
+        match self.writer {
+            OutputWriter::Plain(mut writer) => writer.flush(),
+            OutputWriter::Gzip(encoder) => encoder.finish()?.flush(),
+        }
+    }
+}
+
+/// Writes lines to numbered files in a directory (`part-0001.<extension>`, ...),
+/// starting a new file whenever the current one would exceed the size or line count limits.
+pub struct RotatingWriter {
+    directory: PathBuf,
+    extension: &'static str,
+    max_bytes: Option<u64>,
+    max_lines: Option<u64>,
+    current: Option<BufWriter<File>>,
+    bytes: u64,
+    lines: u64,
+    paths: Vec<PathBuf>,
+    /// The start of a line which was not entirely written yet.
+    partial: Vec<u8>,
+}
+
+impl RotatingWriter {
+    pub fn new(
+        directory: &str,
+        extension: &'static str,
+        max_bytes: Option<u64>,
+        max_lines: Option<u64>,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(directory)?;
+
+        Ok(RotatingWriter {
+            directory: PathBuf::from(directory),
+            extension,
+            max_bytes,
+            max_lines,
+            current: None,
+            bytes: 0,
+            lines: 0,
+            paths: vec![],
+            partial: vec![],
+        })
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        let line_size = line.len() as u64;
+        let full = self.max_bytes.is_some_and(|max| self.bytes > 0 && self.bytes + line_size > max)
+            || self.max_lines.is_some_and(|max| self.lines >= max);
+
+        if full || self.current.is_none() {
+            if let Some(mut current) = self.current.take() {
+                current.flush()?;
+            }
+
+            let path = self
+                .directory
+                .join(format!("part-{:04}.{}", self.paths.len() + 1, self.extension));
+            self.current = Some(BufWriter::new(File::create(&path)?));
+            self.paths.push(path);
+            self.bytes = 0;
+            self.lines = 0;
+        }
+
+        self.current.as_mut().expect("opened above").write_all(line)?;
+        self.bytes += line_size;
+        self.lines += 1;
+        Ok(())
+    }
+
+    /// Flushes the last file, and returns the paths of every written file.
+    pub fn finish(mut self) -> io::Result<Vec<String>> {
+        if !self.partial.is_empty() {
+            let line = std::mem::take(&mut self.partial);
+            self.write_line(&line)?;
+        }
+        self.flush()?;
+
+        Ok(self.paths.iter().map(|p| p.to_string_lossy().to_string()).collect())
+    }
+}
+
+impl Write for RotatingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+
+        while let Some(end) = rest.iter().position(|&b| b == b'\n') {
+            let (line, tail) = rest.split_at(end + 1);
+            if self.partial.is_empty() {
+                self.write_line(line)?;
+            } else {
+                let mut partial = std::mem::take(&mut self.partial);
+                partial.extend_from_slice(line);
+                self.write_line(&partial)?;
+            }
+            rest = tail;
+        }
+        self.partial.extend_from_slice(rest);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.current.as_mut() {
+            Some(current) => current.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The text encoding of CSV exports.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextEncoding {
+    Utf8,
+    /// UTF-8 with a byte order mark, which Excel needs to detect UTF-8.
+    Utf8Sig,
+    /// UTF-16 (little endian) with a byte order mark.
+    Utf16Le,
+}
+
+impl TextEncoding {
+    pub fn from_name(name: &str) -> PyResult<Self> {
+        match name.to_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(TextEncoding::Utf8),
+            "utf-8-sig" | "utf8-sig" => Ok(TextEncoding::Utf8Sig),
+            "utf-16le" | "utf-16-le" => Ok(TextEncoding::Utf16Le),
+            _ => Err(PyValueError::new_err(format!(
+                "encoding must be one of `utf-8`, `utf-8-sig`, `utf-16le`, got `{}`",
+                name
+            ))),
+        }
+    }
+}
+
+/// Re-encodes the UTF-8 text written to it, and starts with a byte order mark if the encoding has one.
+pub struct EncodingWriter<W: Write> {
+    inner: W,
+    encoding: TextEncoding,
+    bom_written: bool,
+    /// An incomplete UTF-8 sequence at the end of the last write.
+    partial: Vec<u8>,
+}
+
+impl<W: Write> EncodingWriter<W> {
+    pub fn new(inner: W, encoding: TextEncoding) -> Self {
+        EncodingWriter {
+            inner,
+            encoding,
+            bom_written: false,
+            partial: vec![],
+        }
+    }
+
+    fn write_utf16(&mut self, text: &str) -> io::Result<()> {
+        let encoded: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        self.inner.write_all(&encoded)
+    }
+}
+
+impl<W: Write> Write for EncodingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.bom_written {
+            self.bom_written = true;
+            match self.encoding {
+                TextEncoding::Utf8 => {}
+                TextEncoding::Utf8Sig => self.inner.write_all(&[0xef, 0xbb, 0xbf])?,
+                TextEncoding::Utf16Le => self.inner.write_all(&[0xff, 0xfe])?,
+            }
+        }
+
+        if self.encoding != TextEncoding::Utf16Le {
+            self.inner.write_all(buf)?;
+            return Ok(buf.len());
+        }
+
+        self.partial.extend_from_slice(buf);
+        let pending = std::mem::take(&mut self.partial);
+
+        let valid_up_to = match std::str::from_utf8(&pending) {
+            Ok(_) => pending.len(),
+            // The sequence may be completed by the next write.
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        };
+
+        let (text, rest) = pending.split_at(valid_up_to);
+        self.write_utf16(std::str::from_utf8(text).expect("validated above"))?;
+        self.partial = rest.to_vec();
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,103 @@
+//! Selection of the fields of serialized entries.
+use pyo3::exceptions::PyValueError;
+use pyo3::PyResult;
+use serde_json::{Map, Value};
+
+/// Fields to keep or drop from JSON output, as dotted paths (e.g. `header.sequence`).
+/// Arrays are traversed, so `attributes.header` refers to the header of every attribute.
+#[derive(Debug, Clone, Default)]
+pub struct FieldSelection {
+    /// If given, only these fields (and everything below them) are kept.
+    include: Option<Vec<Vec<String>>>,
+    /// These fields are dropped, after `include` is applied.
+    exclude: Vec<Vec<String>>,
+}
+
+fn parse_paths(paths: Vec<String>) -> PyResult<Vec<Vec<String>>> {
+    paths
+        .into_iter()
+        .map(|path| {
+            let components: Vec<String> = path.split('.').map(str::to_string).collect();
+            if components.iter().any(String::is_empty) {
+                return Err(PyValueError::new_err(format!("invalid field path `{}`", path)));
+            }
+            Ok(components)
+        })
+        .collect()
+}
+
+fn join_paths(paths: &[Vec<String>]) -> Vec<String> {
+    paths.iter().map(|path| path.join(".")).collect()
+}
+
+impl FieldSelection {
+    pub fn new(include: Option<Vec<String>>, exclude: Option<Vec<String>>) -> PyResult<Self> {
+        Ok(FieldSelection {
+            include: include.map(parse_paths).transpose()?,
+            exclude: parse_paths(exclude.unwrap_or_default())?,
+        })
+    }
+
+    /// The `include` paths, as given to `new`.
+    pub fn include(&self) -> Option<Vec<String>> {
+        self.include.as_ref().map(|paths| join_paths(paths))
+    }
+
+    /// The `exclude` paths, as given to `new`.
+    pub fn exclude(&self) -> Vec<String> {
+        join_paths(&self.exclude)
+    }
+
+    pub fn apply(&self, mut value: Value) -> Value {
+        if let Some(include) = &self.include {
+            let paths: Vec<&[String]> = include.iter().map(Vec::as_slice).collect();
+            value = keep(value, &paths);
+        }
+
+        for path in &self.exclude {
+            remove(&mut value, path);
+        }
+
+        value
+    }
+}
+
+fn keep(value: Value, paths: &[&[String]]) -> Value {
+    if paths.iter().any(|path| path.is_empty()) {
+        return value;
+    }
+
+    match value {
+        Value::Object(object) => {
+            let mut kept = Map::new();
+            for (key, value) in object {
+                let tails: Vec<&[String]> = paths
+                    .iter()
+                    .filter(|path| path[0] == key)
+                    .map(|path| &path[1..])
+                    .collect();
+
+                if !tails.is_empty() {
+                    kept.insert(key, keep(value, &tails));
+                }
+            }
+            Value::Object(kept)
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(|v| keep(v, paths)).collect()),
+        value => value,
+    }
+}
+
+fn remove(value: &mut Value, path: &[String]) {
+    match value {
+        Value::Object(object) => {
+            if path.len() == 1 {
+                object.remove(&path[0]);
+            } else if let Some(child) = object.get_mut(&path[0]) {
+                remove(child, &path[1..]);
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(|v| remove(v, path)),
+        _ => {}
+    }
+}
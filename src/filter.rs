@@ -0,0 +1,199 @@
+//! Filters which are evaluated while iterating, so that only matching entries cross into python.
+use crate::entry::{data_sizes, file_names, EntryContext};
+use crate::flags::parse_file_attribute_names;
+use crate::parser::MftParser;
+use crate::ReadSeek;
+
+use mft::attribute::{FileAttributeFlags, MftAttributeType};
+use mft::entry::{EntryFlags, BAAD_HEADER};
+use mft::MftEntry;
+use pyo3::exceptions::PyValueError;
+use pyo3::PyResult;
+use regex::RegexSet;
+
+pub type Parser = MftParser<Box<dyn ReadSeek + Send>>;
+
+pub trait EntryFilter: Send {
+    fn matches(&self, entry: &MftEntry, parser: &mut Parser, context: &mut EntryContext) -> bool;
+}
+
+/// Matches entries which have a $FILE_NAME containing a substring.
+pub struct NameContains {
+    needle: String,
+    case_sensitive: bool,
+}
+
+impl NameContains {
+    pub fn new(needle: &str, case_sensitive: bool) -> Self {
+        NameContains {
+            needle: if case_sensitive {
+                needle.to_string()
+            } else {
+                needle.to_lowercase()
+            },
+            case_sensitive,
+        }
+    }
+}
+
+impl EntryFilter for NameContains {
+    fn matches(&self, entry: &MftEntry, _parser: &mut Parser, _context: &mut EntryContext) -> bool {
+        file_names(entry).any(|name| {
+            if self.case_sensitive {
+                name.name.contains(&self.needle)
+            } else {
+                name.name.to_lowercase().contains(&self.needle)
+            }
+        })
+    }
+}
+
+/// Matches records with the `BAAD` signature, which chkdsk writes over records that failed the fixup check.
+pub struct BadSignature;
+
+impl EntryFilter for BadSignature {
+    fn matches(&self, entry: &MftEntry, _parser: &mut Parser, _context: &mut EntryContext) -> bool {
+        &entry.header.signature == BAAD_HEADER
+    }
+}
+
+/// What a `Search` pattern is matched against.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchField {
+    /// Every $FILE_NAME of the entry.
+    Name,
+    /// The resolved full path of the entry.
+    Path,
+}
+
+impl SearchField {
+    pub fn from_name(name: &str) -> PyResult<Self> {
+        match name.to_lowercase().as_str() {
+            "name" => Ok(SearchField::Name),
+            "path" => Ok(SearchField::Path),
+            _ => Err(PyValueError::new_err(format!(
+                "field must be one of `name`, `path`, got `{}`",
+                name
+            ))),
+        }
+    }
+}
+
+/// Matches entries for which any of a set of regular expressions matches.
+pub struct Search {
+    patterns: RegexSet,
+    field: SearchField,
+}
+
+impl Search {
+    pub fn new(patterns: &[String], field: SearchField) -> PyResult<Self> {
+        let patterns = RegexSet::new(patterns)
+            .map_err(|e| PyValueError::new_err(format!("invalid regular expression: {}", e)))?;
+
+        Ok(Search { patterns, field })
+    }
+}
+
+impl EntryFilter for Search {
+    fn matches(&self, entry: &MftEntry, parser: &mut Parser, context: &mut EntryContext) -> bool {
+        match self.field {
+            SearchField::Name => file_names(entry).any(|name| self.patterns.is_match(&name.name)),
+            SearchField::Path => match context.paths.full_path(entry, parser) {
+                Some(path) => self.patterns.is_match(&path.to_string_lossy()),
+                None => false,
+            },
+        }
+    }
+}
+
+/// Matches entries by the logical size of their unnamed $DATA stream (inclusive bounds).
+pub struct SizeRange {
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+}
+
+impl EntryFilter for SizeRange {
+    fn matches(&self, entry: &MftEntry, _parser: &mut Parser, _context: &mut EntryContext) -> bool {
+        let (file_size, _) = data_sizes(entry);
+
+        self.min_size.is_none_or(|min_size| file_size >= min_size)
+            && self.max_size.is_none_or(|max_size| file_size <= max_size)
+    }
+}
+
+/// The filters for the `min_size` and `max_size` arguments of the iterators.
+pub fn size_filters(min_size: Option<u64>, max_size: Option<u64>) -> Vec<Box<dyn EntryFilter>> {
+    if min_size.is_none() && max_size.is_none() {
+        return vec![];
+    }
+
+    vec![Box::new(SizeRange { min_size, max_size })]
+}
+
+/// Matches entries by the file attribute flags of their $STANDARD_INFORMATION: at least one of `any` must be set
+/// (unless it is empty), and none of `none`.
+/// `DIRECTORY` is never set in $STANDARD_INFORMATION, it is taken from the record header instead.
+pub struct AttributeFlags {
+    any: FileAttributeFlags,
+    none: FileAttributeFlags,
+}
+
+impl AttributeFlags {
+    fn flags(entry: &MftEntry) -> FileAttributeFlags {
+        let mut flags = entry
+            .iter_attributes_matching(Some(vec![MftAttributeType::StandardInformation]))
+            .filter_map(Result::ok)
+            .find_map(|a| a.data.into_standard_info())
+            .map_or(FileAttributeFlags::empty(), |info| info.file_flags);
+
+        if entry.header.flags.contains(EntryFlags::INDEX_PRESENT) {
+            flags |= FileAttributeFlags::FILE_ATTRIBUTE_DIRECTORY;
+        }
+        flags
+    }
+}
+
+impl EntryFilter for AttributeFlags {
+    fn matches(&self, entry: &MftEntry, _parser: &mut Parser, _context: &mut EntryContext) -> bool {
+        let flags = Self::flags(entry);
+
+        (self.any.is_empty() || flags.intersects(self.any)) && !flags.intersects(self.none)
+    }
+}
+
+/// The filters for the `flags_any` and `flags_none` arguments of the iterators.
+pub fn flag_filters(
+    flags_any: Option<Vec<String>>,
+    flags_none: Option<Vec<String>>,
+) -> PyResult<Vec<Box<dyn EntryFilter>>> {
+    if flags_any.is_none() && flags_none.is_none() {
+        return Ok(vec![]);
+    }
+
+    let any = parse_file_attribute_names(&flags_any.unwrap_or_default())?;
+    let none = parse_file_attribute_names(&flags_none.unwrap_or_default())?;
+    Ok(vec![Box::new(AttributeFlags { any, none })])
+}
+
+/// Matches base records of directories or of files, by the flags of their header only,
+/// so that records of the other kind are skipped without parsing their attributes.
+pub struct RecordKind {
+    directories: bool,
+}
+
+impl RecordKind {
+    pub fn directories() -> Self {
+        RecordKind { directories: true }
+    }
+
+    pub fn files() -> Self {
+        RecordKind { directories: false }
+    }
+}
+
+impl EntryFilter for RecordKind {
+    fn matches(&self, entry: &MftEntry, _parser: &mut Parser, _context: &mut EntryContext) -> bool {
+        entry.header.base_reference.entry == 0
+            && entry.header.flags.contains(EntryFlags::INDEX_PRESENT) == self.directories
+    }
+}
@@ -0,0 +1,102 @@
+//! Symbolic names of flag values.
+use mft::attribute::FileAttributeFlags;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// Names of the file attribute flags, without their `FILE_ATTRIBUTE_` prefix.
+pub const FILE_ATTRIBUTE_NAMES: [(FileAttributeFlags, &str); 19] = [
+    (FileAttributeFlags::FILE_ATTRIBUTE_READONLY, "READONLY"),
+    (FileAttributeFlags::FILE_ATTRIBUTE_HIDDEN, "HIDDEN"),
+    (FileAttributeFlags::FILE_ATTRIBUTE_SYSTEM, "SYSTEM"),
+    (FileAttributeFlags::FILE_ATTRIBUTE_DIRECTORY, "DIRECTORY"),
+    (FileAttributeFlags::FILE_ATTRIBUTE_ARCHIVE, "ARCHIVE"),
+    (FileAttributeFlags::FILE_ATTRIBUTE_DEVICE, "DEVICE"),
+    (FileAttributeFlags::FILE_ATTRIBUTE_NORMAL, "NORMAL"),
+    (FileAttributeFlags::FILE_ATTRIBUTE_TEMPORARY, "TEMPORARY"),
+    (FileAttributeFlags::FILE_ATTRIBUTE_SPARSE_FILE, "SPARSE_FILE"),
+    (FileAttributeFlags::FILE_ATTRIBUTE_REPARSE_POINT, "REPARSE_POINT"),
+    (FileAttributeFlags::FILE_ATTRIBUTE_COMPRESSED, "COMPRESSED"),
+    (FileAttributeFlags::FILE_ATTRIBUTE_OFFLINE, "OFFLINE"),
+    (FileAttributeFlags::FILE_ATTRIBUTE_NOT_CONTENT_INDEXED, "NOT_CONTENT_INDEXED"),
+    (FileAttributeFlags::FILE_ATTRIBUTE_ENCRYPTED, "ENCRYPTED"),
+    (FileAttributeFlags::FILE_ATTRIBUTE_INTEGRITY_STREAM, "INTEGRITY_STREAM"),
+    (FileAttributeFlags::FILE_ATTRIBUTE_NO_SCRUB_DATA, "NO_SCRUB_DATA"),
+    (FileAttributeFlags::FILE_ATTRIBUTE_HAS_EA, "HAS_EA"),
+    (FileAttributeFlags::FILE_ATTRIBUTE_IS_DIRECTORY, "IS_DIRECTORY"),
+    (FileAttributeFlags::FILE_ATTRIBUTE_INDEX_VIEW, "INDEX_VIEW"),
+];
+
+/// Separates the names of flags in flat output.
+pub const FLAG_NAMES_SEPARATOR: &str = "|";
+
+/// Names of the flags which are set, in order of their values.
+pub fn file_attribute_names(flags: FileAttributeFlags) -> Vec<&'static str> {
+    FILE_ATTRIBUTE_NAMES
+        .iter()
+        .filter(|(flag, _)| flags.contains(*flag))
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+/// Names of the flags of $VOLUME_INFORMATION, without their `VOLUME_` prefix.
+pub const VOLUME_FLAG_NAMES: [(u16, &str); 8] = [
+    (0x0001, "IS_DIRTY"),
+    (0x0002, "RESIZE_LOG_FILE"),
+    (0x0004, "UPGRADE_ON_MOUNT"),
+    (0x0008, "MOUNTED_ON_NT4"),
+    (0x0010, "DELETE_USN_UNDERWAY"),
+    (0x0020, "REPAIR_OBJECT_ID"),
+    (0x4000, "CHKDSK_UNDERWAY"),
+    (0x8000, "MODIFIED_BY_CHKDSK"),
+];
+
+/// Names of the volume flags which are set, in order of their values.
+pub fn volume_flag_names(flags: u16) -> Vec<&'static str> {
+    VOLUME_FLAG_NAMES
+        .iter()
+        .filter(|(flag, _)| flags & flag != 0)
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+/// Creates the `mft.FileAttributes` `enum.IntFlag`, with a member for every file attribute flag.
+pub fn file_attributes_enum(py: Python) -> PyResult<PyObject> {
+    let members: Vec<(&str, u32)> = FILE_ATTRIBUTE_NAMES
+        .iter()
+        .map(|(flag, name)| (*name, flag.bits()))
+        .collect();
+
+    let kwargs = PyDict::new(py);
+    kwargs.set_item("module", "mft")?;
+
+    let int_flag = py.import("enum")?.getattr("IntFlag")?;
+    Ok(int_flag.call(("FileAttributes", members), Some(kwargs))?.to_object(py))
+}
+
+/// Serializes the flags as their value, and the names of the flags which are set.
+pub fn file_attributes_json(flags: FileAttributeFlags) -> serde_json::Value {
+    serde_json::json!({
+        "value": flags.bits(),
+        "names": file_attribute_names(flags),
+    })
+}
+
+/// Parses names of file attribute flags (e.g. `HIDDEN`, case insensitive, with or without their `FILE_ATTRIBUTE_`
+/// prefix), into the union of these flags.
+pub fn parse_file_attribute_names(names: &[String]) -> PyResult<FileAttributeFlags> {
+    let mut flags = FileAttributeFlags::empty();
+
+    for name in names {
+        let upper = name.to_uppercase();
+        let short = upper.strip_prefix("FILE_ATTRIBUTE_").unwrap_or(&upper);
+        let flag = FILE_ATTRIBUTE_NAMES
+            .iter()
+            .find(|(_, flag_name)| *flag_name == short)
+            .map(|(flag, _)| *flag)
+            .ok_or_else(|| PyValueError::new_err(format!("unknown file attribute flag `{}`", name)))?;
+        flags |= flag;
+    }
+
+    Ok(flags)
+}
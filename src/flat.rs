@@ -0,0 +1,493 @@
+use crate::arrow::{Field, FLAT_SCHEMA};
+use crate::detect::timestamp_anomalies;
+use crate::digest::Algorithm;
+use crate::entry::{data_sizes, resident_data, valid_data_length, EntryContext, StorageFlags};
+use crate::flags::{file_attribute_names, FLAG_NAMES_SEPARATOR};
+use crate::parser::MftParser;
+use crate::reparse::{link_target, reparse_data};
+use crate::runlist::{fragment_count, stream_runs};
+use crate::secure::{owner_and_group, SecurityDescriptors};
+use crate::timestamp::{Timestamp, TimestampFormat};
+use crate::ReadSeek;
+
+use mft::attribute::{FileAttributeFlags, MftAttributeType};
+use mft::entry::EntryFlags;
+use mft::{MftAttribute, MftEntry};
+use pyo3::exceptions::PyValueError;
+use pyo3::PyResult;
+use serde::Serialize;
+use serde_json::Value;
+
+use std::io;
+use std::path::PathBuf;
+
+/// A projection of the columns of `FlatMftEntry`, in the requested order.
+/// The values of columns which are not selected are not computed, so paths are only resolved if `FullPath` is selected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlatColumns {
+    /// Indices in `FLAT_SCHEMA`.
+    indices: Vec<usize>,
+}
+
+impl FlatColumns {
+    pub fn new(names: &[String]) -> PyResult<Self> {
+        if names.is_empty() {
+            return Err(PyValueError::new_err("at least one column must be selected"));
+        }
+
+        let indices = names
+            .iter()
+            .map(|name| {
+                FLAT_SCHEMA.iter().position(|f| f.name == name).ok_or_else(|| {
+                    PyValueError::new_err(format!(
+                        "unknown column `{}`, expected one of {}",
+                        name,
+                        FLAT_SCHEMA.iter().map(|f| f.name).collect::<Vec<_>>().join(", ")
+                    ))
+                })
+            })
+            .collect::<PyResult<Vec<usize>>>()?;
+
+        Ok(FlatColumns { indices })
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.indices.iter().map(|&i| FLAT_SCHEMA[i].name).collect()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.indices.iter().any(|&i| FLAT_SCHEMA[i].name == name)
+    }
+
+    pub fn fields(&self) -> Vec<Field> {
+        self.indices.iter().map(|&i| FLAT_SCHEMA[i]).collect()
+    }
+
+    /// Keeps the selected values of a row in the order of `FLAT_SCHEMA`.
+    pub fn project<T: Clone>(&self, row: &[T]) -> Vec<T> {
+        self.indices.iter().map(|&i| row[i].clone()).collect()
+    }
+}
+
+/// The names of the selected columns, or of every column.
+pub fn column_names(columns: Option<&FlatColumns>) -> Vec<&'static str> {
+    columns.map_or_else(|| FLAT_SCHEMA.iter().map(|f| f.name).collect(), FlatColumns::names)
+}
+
+/// Used for CSV output.
+/// Has the columns of `mft::csv::FlatMftEntryWithName`, followed by the columns resolved by pymft.
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FlatMftEntry {
+    pub signature: String,
+
+    pub entry_id: u64,
+    pub sequence: u16,
+
+    pub base_entry_id: u64,
+    pub base_entry_sequence: u16,
+
+    pub hard_link_count: u16,
+    pub flags: EntryFlags,
+
+    pub used_entry_size: u32,
+    pub total_entry_size: u32,
+
+    pub file_size: u64,
+    pub allocated_size: u64,
+    pub valid_data_length: u64,
+    /// Number of fragments of the unnamed $DATA stream (from the run list in this record),
+    /// empty if the stream is resident or missing.
+    pub data_fragments: Option<u64>,
+
+    pub is_a_directory: bool,
+    pub is_deleted: bool,
+    pub is_compressed: bool,
+    pub is_encrypted: bool,
+    pub is_sparse: bool,
+
+    pub has_alternate_data_streams: bool,
+
+    pub standard_info_flags: Option<FileAttributeFlags>,
+    pub standard_info_flags_value: Option<u32>,
+    pub standard_info_flag_names: Option<String>,
+    pub standard_info_last_modified: Option<Timestamp>,
+    pub standard_info_last_access: Option<Timestamp>,
+    pub standard_info_created: Option<Timestamp>,
+    pub file_name_flags: Option<FileAttributeFlags>,
+    pub file_name_flags_value: Option<u32>,
+    pub file_name_flag_names: Option<String>,
+    pub file_name_last_modified: Option<Timestamp>,
+    pub file_name_last_access: Option<Timestamp>,
+    pub file_name_created: Option<Timestamp>,
+    /// Whether a $STANDARD_INFORMATION timestamp has zero fractional seconds (see `PyMftEntry.findings`).
+    pub standard_info_zero_fraction: bool,
+    /// Whether a timestamp is the FILETIME, Unix or DOS epoch.
+    pub epoch_timestamp: bool,
+
+    pub full_path: PathBuf,
+    /// Target of a symbolic link, mount point, or WSL symbolic link
+    pub link_target: Option<String>,
+
+    /// SHA256 of the resident $DATA content, only when enabled with `hash_resident_data`.
+    pub resident_data_sha256: Option<String>,
+
+    /// Present when the security descriptor of the entry can be resolved.
+    pub owner_sid: Option<String>,
+    pub group_sid: Option<String>,
+}
+
+impl FlatMftEntry {
+    pub fn from_entry(
+        entry: &MftEntry,
+        parser: &mut MftParser<impl ReadSeek>,
+        context: &mut EntryContext,
+    ) -> FlatMftEntry {
+        let full_path = if context.columns.as_ref().is_none_or(|columns| columns.contains("FullPath")) {
+            context
+                .paths
+                .full_path(entry, parser)
+                .map(|full_path| context.path_format.format(&full_path))
+                .unwrap_or_default()
+        } else {
+            PathBuf::new()
+        };
+
+        FlatMftEntry::with_path(
+            entry,
+            full_path,
+            context.security.as_ref(),
+            context.hash_resident_data,
+            context.timestamps,
+            context.columns.as_ref(),
+        )
+    }
+
+    /// Like `from_entry`, for an entry whose path is already resolved.
+    pub fn with_path(
+        entry: &MftEntry,
+        full_path: PathBuf,
+        security: Option<&SecurityDescriptors>,
+        hash_resident_data: bool,
+        timestamps: TimestampFormat,
+        columns: Option<&FlatColumns>,
+    ) -> FlatMftEntry {
+        let selected = columns.map(FlatColumns::names);
+        let wants = |prefixes: &[&str]| {
+            selected
+                .as_ref()
+                .is_none_or(|names| names.iter().any(|name| prefixes.iter().any(|p| name.starts_with(p))))
+        };
+        let wants_sids = wants(&["OwnerSid", "GroupSid"]);
+        let wants_link_target = wants(&["LinkTarget"]);
+        let wants_hash = wants(&["ResidentDataSha256"]);
+
+        let attribute_types: Vec<MftAttributeType> = [
+            ("FileName", MftAttributeType::FileName),
+            ("StandardInfo", MftAttributeType::StandardInformation),
+            ("HasAlternateDataStreams", MftAttributeType::DATA),
+        ]
+        .iter()
+        .filter(|(prefix, _)| wants(&[prefix]))
+        .map(|(_, attribute_type)| attribute_type.clone())
+        .collect();
+
+        let entry_attributes: Vec<MftAttribute> = if attribute_types.is_empty() {
+            vec![]
+        } else {
+            entry
+                .iter_attributes_matching(Some(attribute_types))
+                .filter_map(Result::ok)
+                .collect()
+        };
+
+        let file_name = entry_attributes
+            .iter()
+            .find(|a| a.header.type_code == MftAttributeType::FileName)
+            .and_then(|a| a.data.clone().into_file_name());
+
+        let standard_info = entry_attributes
+            .iter()
+            .find(|a| a.header.type_code == MftAttributeType::StandardInformation)
+            .and_then(|a| a.data.clone().into_standard_info());
+
+        let (file_size, allocated_size) = if wants(&["FileSize", "AllocatedSize"]) {
+            data_sizes(entry)
+        } else {
+            (0, 0)
+        };
+
+        let has_ads = entry_attributes
+            .iter()
+            .any(|a| a.header.type_code == MftAttributeType::DATA && !a.header.name.is_empty());
+
+        let (owner_sid, group_sid) = if wants_sids {
+            owner_and_group(entry, security)
+        } else {
+            (None, None)
+        };
+        let storage = if wants(&["IsADirectory", "IsCompressed", "IsEncrypted", "IsSparse"]) {
+            StorageFlags::from_entry(entry)
+        } else {
+            StorageFlags::default()
+        };
+
+        let (standard_info_zero_fraction, epoch_timestamp) =
+            if wants(&["StandardInfoZeroFraction", "EpochTimestamp"]) {
+                timestamp_anomalies(entry)
+            } else {
+                (false, false)
+            };
+
+        let resident_data_sha256 = if hash_resident_data && wants_hash {
+            resident_data(entry).map(|data| Algorithm::Sha256.hexdigest(&data))
+        } else {
+            None
+        };
+
+        FlatMftEntry {
+            entry_id: entry.header.record_number,
+            signature: String::from_utf8_lossy(&entry.header.signature.to_ascii_uppercase()).to_string(),
+            sequence: entry.header.sequence,
+            hard_link_count: entry.header.hard_link_count,
+            flags: entry.header.flags,
+            used_entry_size: entry.header.used_entry_size,
+            total_entry_size: entry.header.total_entry_size,
+            base_entry_id: entry.header.base_reference.entry,
+            base_entry_sequence: entry.header.base_reference.sequence,
+            is_a_directory: storage.is_directory,
+            is_deleted: !entry.header.flags.contains(EntryFlags::ALLOCATED),
+            is_compressed: storage.is_compressed,
+            is_encrypted: storage.is_encrypted,
+            is_sparse: storage.is_sparse,
+            has_alternate_data_streams: has_ads,
+            standard_info_flags: standard_info.as_ref().map(|i| i.file_flags),
+            standard_info_flags_value: standard_info.as_ref().map(|i| i.file_flags.bits()),
+            standard_info_flag_names: standard_info.as_ref().map(|i| flag_names(i.file_flags)),
+            standard_info_last_modified: standard_info.as_ref().map(|i| timestamps.format(i.modified)),
+            standard_info_last_access: standard_info.as_ref().map(|i| timestamps.format(i.accessed)),
+            standard_info_created: standard_info.as_ref().map(|i| timestamps.format(i.created)),
+            file_name_flags: file_name.as_ref().map(|i| i.flags),
+            file_name_flags_value: file_name.as_ref().map(|i| i.flags.bits()),
+            file_name_flag_names: file_name.as_ref().map(|i| flag_names(i.flags)),
+            file_name_last_modified: file_name.as_ref().map(|i| timestamps.format(i.modified)),
+            file_name_last_access: file_name.as_ref().map(|i| timestamps.format(i.accessed)),
+            file_name_created: file_name.as_ref().map(|i| timestamps.format(i.created)),
+            standard_info_zero_fraction,
+            epoch_timestamp,
+            file_size,
+            allocated_size,
+            valid_data_length: if wants(&["ValidDataLength"]) {
+                valid_data_length(entry)
+            } else {
+                0
+            },
+            data_fragments: if wants(&["DataFragments"]) {
+                stream_runs(std::iter::once(entry), "").map(|runs| fragment_count(&runs))
+            } else {
+                None
+            },
+            full_path,
+            link_target: if wants_link_target {
+                reparse_data(entry).and_then(|data| link_target(&data))
+            } else {
+                None
+            },
+            resident_data_sha256,
+            owner_sid,
+            group_sid,
+        }
+    }
+
+    /// The serialized value of a column.
+    fn column_value(&self, name: &str) -> serde_json::Result<Value> {
+        macro_rules! column_value {
+            ($($column:literal => $field:ident),* $(,)?) => {
+                match name {
+                    $($column => serde_json::to_value(&self.$field),)*
+                    _ => Ok(Value::Null),
+                }
+            };
+        }
+
+        column_value!(
+            "Signature" => signature,
+            "EntryId" => entry_id,
+            "Sequence" => sequence,
+            "BaseEntryId" => base_entry_id,
+            "BaseEntrySequence" => base_entry_sequence,
+            "HardLinkCount" => hard_link_count,
+            "Flags" => flags,
+            "UsedEntrySize" => used_entry_size,
+            "TotalEntrySize" => total_entry_size,
+            "FileSize" => file_size,
+            "AllocatedSize" => allocated_size,
+            "ValidDataLength" => valid_data_length,
+            "DataFragments" => data_fragments,
+            "IsADirectory" => is_a_directory,
+            "IsDeleted" => is_deleted,
+            "IsCompressed" => is_compressed,
+            "IsEncrypted" => is_encrypted,
+            "IsSparse" => is_sparse,
+            "HasAlternateDataStreams" => has_alternate_data_streams,
+            "StandardInfoFlags" => standard_info_flags,
+            "StandardInfoFlagsValue" => standard_info_flags_value,
+            "StandardInfoFlagNames" => standard_info_flag_names,
+            "StandardInfoLastModified" => standard_info_last_modified,
+            "StandardInfoLastAccess" => standard_info_last_access,
+            "StandardInfoCreated" => standard_info_created,
+            "FileNameFlags" => file_name_flags,
+            "FileNameFlagsValue" => file_name_flags_value,
+            "FileNameFlagNames" => file_name_flag_names,
+            "FileNameLastModified" => file_name_last_modified,
+            "FileNameLastAccess" => file_name_last_access,
+            "FileNameCreated" => file_name_created,
+            "StandardInfoZeroFraction" => standard_info_zero_fraction,
+            "EpochTimestamp" => epoch_timestamp,
+            "FullPath" => full_path,
+            "LinkTarget" => link_target,
+            "ResidentDataSha256" => resident_data_sha256,
+            "OwnerSid" => owner_sid,
+            "GroupSid" => group_sid,
+        )
+    }
+
+    /// The values of the selected columns (or of every column), as serialized.
+    pub fn column_values(&self, columns: Option<&FlatColumns>) -> serde_json::Result<Vec<(&'static str, Value)>> {
+        column_names(columns)
+            .into_iter()
+            .map(|name| Ok((name, self.column_value(name)?)))
+            .collect()
+    }
+
+    /// Writes the entry as a CSV record, with the selected columns (or every column).
+    /// When every column is written, the header is written by `writer` (if it has headers),
+    /// otherwise it is written first if `header` is set.
+    /// Control characters of the values read from the volume are escaped.
+    pub fn write_csv<W: io::Write>(
+        self,
+        writer: &mut csv::Writer<W>,
+        columns: Option<&FlatColumns>,
+        header: bool,
+    ) -> csv::Result<()> {
+        let entry = self.with_escaped_control_characters();
+        match columns {
+            None => writer.serialize(entry),
+            Some(columns) => {
+                if header {
+                    writer.write_record(columns.names())?;
+                }
+                writer.write_record(entry.csv_record(Some(columns)).map_err(io::Error::from)?)
+            }
+        }
+    }
+
+    /// Replaces control characters in the text columns with their Unicode control pictures.
+    fn with_escaped_control_characters(mut self) -> Self {
+        self.signature = escape_control_characters(&self.signature);
+        self.full_path = PathBuf::from(escape_control_characters(&self.full_path.to_string_lossy()));
+        self.link_target = self.link_target.as_deref().map(escape_control_characters);
+        self
+    }
+
+    /// Like `write_csv`, but with a first `Source` column, for the rows of several MFTs.
+    pub fn write_csv_with_source<W: io::Write>(
+        self,
+        writer: &mut csv::Writer<W>,
+        columns: Option<&FlatColumns>,
+        header: bool,
+        source: &str,
+    ) -> csv::Result<()> {
+        let entry = self.with_escaped_control_characters();
+        if header {
+            writer.write_record(std::iter::once("Source").chain(column_names(columns)))?;
+        }
+        let record = entry.csv_record(columns).map_err(io::Error::from)?;
+        writer.write_record(std::iter::once(escape_control_characters(source)).chain(record))
+    }
+
+    /// The values of the selected columns (or of every column), formatted like in CSV output.
+    pub fn csv_record(&self, columns: Option<&FlatColumns>) -> serde_json::Result<Vec<String>> {
+        Ok(self
+            .column_values(columns)?
+            .into_iter()
+            .map(|(_, value)| match value {
+                Value::Null => String::new(),
+                Value::String(s) => s,
+                value => value.to_string(),
+            })
+            .collect())
+    }
+}
+
+/// Replaces control characters (which spreadsheets mishandle, even in quoted fields)
+/// with their Unicode control pictures, e.g. U+0001 with `␁`.
+fn escape_control_characters(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '\u{0}'..='\u{1f}' => char::from_u32(0x2400 + c as u32).unwrap_or(c),
+            '\u{7f}' => '\u{2421}',
+            c => c,
+        })
+        .collect()
+}
+
+fn flag_names(flags: FileAttributeFlags) -> String {
+    file_attribute_names(flags).join(FLAG_NAMES_SEPARATOR)
+}
+
+/// Used for triage CSV output, a quick file listing.
+/// Only the $STANDARD_INFORMATION and $FILE_NAME attributes are parsed, and paths are not resolved.
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TriageMftEntry {
+    pub entry_id: u64,
+    pub sequence: u16,
+    pub flags: EntryFlags,
+    pub is_a_directory: bool,
+    pub is_deleted: bool,
+
+    /// The preferred name of the entry, and its parent directory.
+    pub name: Option<String>,
+    pub parent_entry_id: Option<u64>,
+    pub parent_entry_sequence: Option<u16>,
+
+    pub standard_info_created: Option<Timestamp>,
+    pub standard_info_last_modified: Option<Timestamp>,
+    pub standard_info_mft_modified: Option<Timestamp>,
+    pub standard_info_last_access: Option<Timestamp>,
+    pub file_name_created: Option<Timestamp>,
+    pub file_name_last_modified: Option<Timestamp>,
+    pub file_name_mft_modified: Option<Timestamp>,
+    pub file_name_last_access: Option<Timestamp>,
+}
+
+impl TriageMftEntry {
+    pub fn from_entry(entry: &MftEntry, context: &EntryContext) -> TriageMftEntry {
+        let standard_info = entry
+            .iter_attributes_matching(Some(vec![MftAttributeType::StandardInformation]))
+            .filter_map(Result::ok)
+            .find_map(|a| a.data.into_standard_info());
+        let file_name = context.paths.pick(entry);
+        let timestamps = context.timestamps;
+
+        TriageMftEntry {
+            entry_id: entry.header.record_number,
+            sequence: entry.header.sequence,
+            flags: entry.header.flags,
+            is_a_directory: entry.is_dir(),
+            is_deleted: !entry.header.flags.contains(EntryFlags::ALLOCATED),
+            standard_info_created: standard_info.as_ref().map(|i| timestamps.format(i.created)),
+            standard_info_last_modified: standard_info.as_ref().map(|i| timestamps.format(i.modified)),
+            standard_info_mft_modified: standard_info.as_ref().map(|i| timestamps.format(i.mft_modified)),
+            standard_info_last_access: standard_info.as_ref().map(|i| timestamps.format(i.accessed)),
+            file_name_created: file_name.as_ref().map(|i| timestamps.format(i.created)),
+            file_name_last_modified: file_name.as_ref().map(|i| timestamps.format(i.modified)),
+            file_name_mft_modified: file_name.as_ref().map(|i| timestamps.format(i.mft_modified)),
+            file_name_last_access: file_name.as_ref().map(|i| timestamps.format(i.accessed)),
+            parent_entry_id: file_name.as_ref().map(|i| i.parent.entry),
+            parent_entry_sequence: file_name.as_ref().map(|i| i.parent.sequence),
+            name: file_name.map(|i| i.name),
+        }
+    }
+}
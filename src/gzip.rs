@@ -0,0 +1,493 @@
+//! A gzip (https://www.rfc-editor.org/rfc/rfc1952) encoder of exports, so that compression runs without the GIL.
+//! Data is compressed with DEFLATE (https://www.rfc-editor.org/rfc/rfc1951): matches are found with hash chains
+//! over a 32 KiB window, and every block is written with its own (dynamic) Huffman codes.
+use std::io::{self, Write};
+
+/// How much data is compressed at once, as a block.
+const BLOCK_SIZE: usize = 256 * 1024;
+
+/// How far back matches are looked for.
+const WINDOW_SIZE: usize = 32 * 1024;
+
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+
+/// How many earlier positions with the same hash are compared, at most, to find a match.
+const MAX_CHAIN: usize = 64;
+
+const HASH_BITS: u32 = 15;
+
+const END_OF_BLOCK: usize = 256;
+
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+
+/// The order in which the lengths of the code length codes are written.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Writes gzip compressed data to `inner`.
+/// `finish` must be called once everything is written, to write the end of the stream.
+pub struct GzEncoder<W: Write> {
+    inner: W,
+    /// The last `WINDOW_SIZE` bytes which were compressed, followed by the data which was not compressed yet.
+    window: Vec<u8>,
+    /// Where the data which was not compressed yet starts in `window`.
+    pending: usize,
+    bits: BitWriter,
+    crc: u32,
+    size: u32,
+}
+
+impl<W: Write> GzEncoder<W> {
+    pub fn new(mut inner: W) -> io::Result<Self> {
+        // No flags or modification time, and an unknown operating system.
+        inner.write_all(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff])?;
+
+        Ok(GzEncoder {
+            inner,
+            window: Vec::with_capacity(WINDOW_SIZE + BLOCK_SIZE),
+            pending: 0,
+            bits: BitWriter::default(),
+            crc: 0,
+            size: 0,
+        })
+    }
+
+    /// Compresses the pending data as a block, and writes the complete bytes of the stream to `inner`.
+    fn write_block(&mut self, last: bool) -> io::Result<()> {
+        let tokens = find_matches(&self.window, self.pending);
+        write_block(&mut self.bits, &tokens, last);
+
+        let history = self.window.len().saturating_sub(WINDOW_SIZE);
+        self.window.drain(..history);
+        self.pending = self.window.len();
+
+        self.inner.write_all(&self.bits.take_bytes())
+    }
+
+    /// Writes the last block and the trailer of the stream, and returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_block(true)?;
+        self.bits.align();
+        self.bits.bytes.extend_from_slice(&self.crc.to_le_bytes());
+        self.bits.bytes.extend_from_slice(&self.size.to_le_bytes());
+        self.inner.write_all(&self.bits.take_bytes())?;
+
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for GzEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.pending + BLOCK_SIZE - self.window.len());
+        let buf = &buf[..len];
+
+        self.crc = crc32(self.crc, buf);
+        self.size = self.size.wrapping_add(len as u32);
+        self.window.extend_from_slice(buf);
+
+        if self.window.len() - self.pending >= BLOCK_SIZE {
+            self.write_block(false)?;
+        }
+
+        Ok(len)
+    }
+
+    /// Compresses the pending data, and ends it with an empty stored block (as zlib's `Z_SYNC_FLUSH` does),
+    /// so that everything written so far can be decompressed from the inner writer.
+    fn flush(&mut self) -> io::Result<()> {
+        if self.window.len() > self.pending {
+            self.write_block(false)?;
+
+            self.bits.write(0, 3);
+            self.bits.align();
+            self.bits.bytes.extend_from_slice(&[0, 0, 0xff, 0xff]);
+            self.inner.write_all(&self.bits.take_bytes())?;
+        }
+
+        self.inner.flush()
+    }
+}
+
+/// Writes bits least significant first, as DEFLATE does.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bit_buffer: u64,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        self.bit_buffer |= u64::from(value) << self.bit_count;
+        self.bit_count += count;
+
+        while self.bit_count >= 8 {
+            self.bytes.push(self.bit_buffer as u8);
+            self.bit_buffer >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    /// Pads the last byte with zeros.
+    fn align(&mut self) {
+        if self.bit_count > 0 {
+            self.write(0, 8 - self.bit_count);
+        }
+    }
+
+    /// Takes the complete bytes, an incomplete last byte is kept.
+    fn take_bytes(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.bytes)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Token {
+    Literal(u8),
+    Match { length: u16, distance: u16 },
+}
+
+fn hash(data: &[u8], at: usize) -> usize {
+    let value = u32::from(data[at]) << 16 | u32::from(data[at + 1]) << 8 | u32::from(data[at + 2]);
+    (value.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Chains of earlier positions with the same hash.
+/// Positions are stored plus one, 0 being the end of a chain.
+struct HashChains {
+    head: Vec<usize>,
+    previous: Vec<usize>,
+}
+
+impl HashChains {
+    fn insert(&mut self, data: &[u8], at: usize) {
+        if at + MIN_MATCH <= data.len() {
+            let h = hash(data, at);
+            self.previous[at] = self.head[h];
+            self.head[h] = at + 1;
+        }
+    }
+}
+
+/// Finds the literals and matches of `data[start..]`, with matches which may refer to `data[..start]`.
+fn find_matches(data: &[u8], start: usize) -> Vec<Token> {
+    let mut chains = HashChains {
+        head: vec![0; 1 << HASH_BITS],
+        previous: vec![0; data.len()],
+    };
+    for at in 0..start {
+        chains.insert(data, at);
+    }
+
+    let mut tokens = Vec::with_capacity(data.len() - start);
+    let mut at = start;
+
+    while at < data.len() {
+        let max_length = (data.len() - at).min(MAX_MATCH);
+        let mut best = (0, 0);
+
+        if max_length >= MIN_MATCH {
+            let mut candidate = chains.head[hash(data, at)];
+            let mut chain = 0;
+
+            while candidate > 0 && chain < MAX_CHAIN {
+                let position = candidate - 1;
+                let distance = at - position;
+                if distance > WINDOW_SIZE {
+                    break;
+                }
+
+                let length = data[position..position + max_length]
+                    .iter()
+                    .zip(&data[at..at + max_length])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length > best.0 {
+                    best = (length, distance);
+                    if length == max_length {
+                        break;
+                    }
+                }
+
+                candidate = chains.previous[position];
+                chain += 1;
+            }
+        }
+
+        let (length, distance) = best;
+        if length >= MIN_MATCH {
+            tokens.push(Token::Match {
+                length: length as u16,
+                distance: distance as u16,
+            });
+            for position in at..at + length {
+                chains.insert(data, position);
+            }
+            at += length;
+        } else {
+            tokens.push(Token::Literal(data[at]));
+            chains.insert(data, at);
+            at += 1;
+        }
+    }
+
+    tokens
+}
+
+/// The index of the code of `value`, in a table of bases (the smallest value of every code).
+fn code_index(bases: &[u16], value: u16) -> usize {
+    bases.partition_point(|&base| base <= value) - 1
+}
+
+/// Computes Huffman code lengths of at most `max_bits` bits for the symbols with a non-zero frequency.
+/// Code lengths which exceed the limit are shortened as miniz does, by moving leaves up the tree.
+fn code_lengths(frequencies: &[u32], max_bits: usize) -> Vec<u8> {
+    let mut lengths = vec![0_u8; frequencies.len()];
+    let mut symbols: Vec<usize> = (0..frequencies.len()).filter(|&s| frequencies[s] > 0).collect();
+    symbols.sort_by_key(|&s| (frequencies[s], s));
+
+    let leaves = symbols.len();
+    if leaves == 1 {
+        lengths[symbols[0]] = 1;
+    }
+    if leaves <= 1 {
+        return lengths;
+    }
+
+    // Leaves are sorted, and the internal nodes are created in order of weight, so the two lightest nodes
+    // are always at the front of one of the two queues.
+    let mut weights: Vec<u64> = symbols.iter().map(|&s| u64::from(frequencies[s])).collect();
+    let mut parents = vec![0; 2 * leaves - 1];
+    let (mut next_leaf, mut next_internal) = (0, leaves);
+
+    for node in leaves..2 * leaves - 1 {
+        let mut children = [0; 2];
+        for child in &mut children {
+            if next_leaf < leaves && (next_internal >= node || weights[next_leaf] <= weights[next_internal]) {
+                *child = next_leaf;
+                next_leaf += 1;
+            } else {
+                *child = next_internal;
+                next_internal += 1;
+            }
+        }
+
+        weights.push(weights[children[0]] + weights[children[1]]);
+        parents[children[0]] = node;
+        parents[children[1]] = node;
+    }
+
+    let mut depths = vec![0_usize; 2 * leaves - 1];
+    for node in (0..2 * leaves - 2).rev() {
+        depths[node] = depths[parents[node]] + 1;
+    }
+
+    let mut counts = vec![0_usize; max_bits + 1];
+    for &depth in &depths[..leaves] {
+        counts[depth.min(max_bits)] += 1;
+    }
+
+    // The Kraft sum of the lengths, in units of 2^-max_bits, must be exactly 1.
+    let mut total: usize = (1..=max_bits).map(|bits| counts[bits] << (max_bits - bits)).sum();
+    while total > 1 << max_bits {
+        counts[max_bits] -= 1;
+        if let Some(bits) = (1..max_bits).rev().find(|&bits| counts[bits] > 0) {
+            counts[bits] -= 1;
+            counts[bits + 1] += 2;
+        }
+        total -= 1;
+    }
+
+    // The least frequent symbols get the longest codes.
+    let mut symbols = symbols.into_iter();
+    for bits in (1..=max_bits).rev() {
+        for symbol in symbols.by_ref().take(counts[bits]) {
+            lengths[symbol] = bits as u8;
+        }
+    }
+
+    lengths
+}
+
+/// The canonical Huffman codes of the code lengths, with their bits reversed to be written least significant first.
+fn canonical_codes(lengths: &[u8]) -> Vec<u16> {
+    let mut counts = [0_u16; 16];
+    for &length in lengths {
+        counts[usize::from(length)] += 1;
+    }
+    counts[0] = 0;
+
+    let mut next_code = [0_u16; 16];
+    for bits in 1..16 {
+        next_code[bits] = (next_code[bits - 1] + counts[bits - 1]) << 1;
+    }
+
+    lengths
+        .iter()
+        .map(|&length| {
+            if length == 0 {
+                return 0;
+            }
+            let code = next_code[usize::from(length)];
+            next_code[usize::from(length)] += 1;
+            code.reverse_bits() >> (16 - length)
+        })
+        .collect()
+}
+
+/// Run-length encodes code lengths with the symbols 16 (repeat the previous length), 17 and 18 (repeat zero),
+/// as `(symbol, extra bits value)`.
+fn run_length_encode(lengths: &[u8]) -> Vec<(usize, u32)> {
+    let mut symbols = vec![];
+    let mut at = 0;
+
+    while at < lengths.len() {
+        let length = lengths[at];
+        let run = lengths[at..].iter().take_while(|&&l| l == length).count();
+
+        if length == 0 && run >= 3 {
+            let run = run.min(138);
+            symbols.push(if run <= 10 { (17, run as u32 - 3) } else { (18, run as u32 - 11) });
+            at += run;
+        } else if length != 0 && run >= 4 {
+            symbols.push((usize::from(length), 0));
+            let run = (run - 1).min(6);
+            symbols.push((16, run as u32 - 3));
+            at += run + 1;
+        } else {
+            symbols.push((usize::from(length), 0));
+            at += 1;
+        }
+    }
+
+    symbols
+}
+
+/// Writes `tokens` as a block with dynamic Huffman codes.
+fn write_block(bits: &mut BitWriter, tokens: &[Token], last: bool) {
+    let mut literal_frequencies = [0_u32; 286];
+    let mut distance_frequencies = [0_u32; 30];
+
+    for token in tokens {
+        match *token {
+            Token::Literal(byte) => literal_frequencies[usize::from(byte)] += 1,
+            Token::Match { length, distance } => {
+                literal_frequencies[257 + code_index(&LENGTH_BASES, length)] += 1;
+                distance_frequencies[code_index(&DISTANCE_BASES, distance)] += 1;
+            }
+        }
+    }
+    literal_frequencies[END_OF_BLOCK] += 1;
+
+    // Decoders expect complete codes, which needs at least two symbols.
+    for frequencies in [&mut literal_frequencies[..], &mut distance_frequencies[..]] {
+        for symbol in 0..2 {
+            if frequencies.iter().filter(|&&f| f > 0).count() < 2 && frequencies[symbol] == 0 {
+                frequencies[symbol] = 1;
+            }
+        }
+    }
+
+    let literal_lengths = code_lengths(&literal_frequencies, 15);
+    let distance_lengths = code_lengths(&distance_frequencies, 15);
+    let literal_count = 257.max(literal_lengths.iter().rposition(|&l| l > 0).map_or(0, |i| i + 1));
+    let distance_count = 1.max(distance_lengths.iter().rposition(|&l| l > 0).map_or(0, |i| i + 1));
+
+    let all_lengths: Vec<u8> = literal_lengths[..literal_count]
+        .iter()
+        .chain(&distance_lengths[..distance_count])
+        .copied()
+        .collect();
+    let length_symbols = run_length_encode(&all_lengths);
+
+    let mut length_frequencies = [0_u32; 19];
+    for &(symbol, _) in &length_symbols {
+        length_frequencies[symbol] += 1;
+    }
+    let length_lengths = code_lengths(&length_frequencies, 7);
+    let length_codes = canonical_codes(&length_lengths);
+    let length_count = 4.max(
+        CODE_LENGTH_ORDER
+            .iter()
+            .rposition(|&symbol| length_lengths[symbol] > 0)
+            .map_or(0, |i| i + 1),
+    );
+
+    bits.write(u32::from(last), 1);
+    bits.write(2, 2);
+    bits.write(literal_count as u32 - 257, 5);
+    bits.write(distance_count as u32 - 1, 5);
+    bits.write(length_count as u32 - 4, 4);
+    for &symbol in &CODE_LENGTH_ORDER[..length_count] {
+        bits.write(u32::from(length_lengths[symbol]), 3);
+    }
+    for &(symbol, extra) in &length_symbols {
+        bits.write(u32::from(length_codes[symbol]), u32::from(length_lengths[symbol]));
+        match symbol {
+            16 => bits.write(extra, 2),
+            17 => bits.write(extra, 3),
+            18 => bits.write(extra, 7),
+            _ => {}
+        }
+    }
+
+    let literal_codes = canonical_codes(&literal_lengths);
+    let distance_codes = canonical_codes(&distance_lengths);
+    let write_symbol = |bits: &mut BitWriter, codes: &[u16], lengths: &[u8], symbol: usize| {
+        bits.write(u32::from(codes[symbol]), u32::from(lengths[symbol]))
+    };
+
+    for token in tokens {
+        match *token {
+            Token::Literal(byte) => write_symbol(bits, &literal_codes, &literal_lengths, usize::from(byte)),
+            Token::Match { length, distance } => {
+                let code = code_index(&LENGTH_BASES, length);
+                write_symbol(bits, &literal_codes, &literal_lengths, 257 + code);
+                bits.write(u32::from(length - LENGTH_BASES[code]), u32::from(LENGTH_EXTRA_BITS[code]));
+
+                let code = code_index(&DISTANCE_BASES, distance);
+                write_symbol(bits, &distance_codes, &distance_lengths, code);
+                bits.write(u32::from(distance - DISTANCE_BASES[code]), u32::from(DISTANCE_EXTRA_BITS[code]));
+            }
+        }
+    }
+    write_symbol(bits, &literal_codes, &literal_lengths, END_OF_BLOCK);
+}
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut crc = n as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+            bit += 1;
+        }
+        table[n] = crc;
+        n += 1;
+    }
+    table
+}
+
+/// Updates a CRC-32 (as used by gzip) with `data`.
+fn crc32(crc: u32, data: &[u8]) -> u32 {
+    !data
+        .iter()
+        .fold(!crc, |crc, &byte| CRC_TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8))
+}
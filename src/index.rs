@@ -0,0 +1,170 @@
+//! Parsing of the entries of resident $I30 $INDEX_ROOT attributes (small directory indexes).
+use crate::attribute::PyMftAttributeX30;
+use crate::runlist::attribute_record;
+use crate::utils::{u16_at, u32_at, u64_at};
+
+use mft::attribute::MftAttributeType;
+use mft::{FileNameAttr, MftEntry};
+use pyo3::prelude::*;
+
+use std::io::Cursor;
+
+/// Size of the $INDEX_ROOT header, which is followed by the index node header.
+const INDEX_ROOT_HEADER_SIZE: usize = 16;
+const INDEX_ENTRY_HEADER_SIZE: usize = 16;
+/// Size of a $FILE_NAME attribute without its name.
+const FILE_NAME_MIN_SIZE: usize = 0x42;
+
+const INDEX_ENTRY_LAST: u32 = 0x02;
+
+#[derive(Debug, Clone)]
+pub struct IndexEntry {
+    pub entry_id: u64,
+    pub sequence: u16,
+    pub flags: u32,
+    pub file_name: Option<FileNameAttr>,
+    /// The raw $FILE_NAME key
+    pub key: Vec<u8>,
+    /// Found in the slack space of the index, after the last live entry
+    pub is_stale: bool,
+}
+
+fn parse_file_name(key: &[u8]) -> Option<FileNameAttr> {
+    if key.len() < FILE_NAME_MIN_SIZE || FILE_NAME_MIN_SIZE + key[0x40] as usize * 2 > key.len() {
+        return None;
+    }
+
+    FileNameAttr::from_stream(&mut Cursor::new(key)).ok()
+}
+
+/// Parses the index entry at the start of `buf`, returns it with its length.
+fn parse_entry(buf: &[u8], is_stale: bool) -> Option<(IndexEntry, usize)> {
+    if buf.len() < INDEX_ENTRY_HEADER_SIZE {
+        return None;
+    }
+
+    let reference = u64_at(buf, 0);
+    let length = u16_at(buf, 8) as usize;
+    let key_length = u16_at(buf, 10) as usize;
+    let flags = u32_at(buf, 12);
+
+    if length < INDEX_ENTRY_HEADER_SIZE || INDEX_ENTRY_HEADER_SIZE + key_length > length.min(buf.len()) {
+        return None;
+    }
+
+    let key = &buf[INDEX_ENTRY_HEADER_SIZE..INDEX_ENTRY_HEADER_SIZE + key_length];
+    let file_name = parse_file_name(key);
+
+    Some((
+        IndexEntry {
+            entry_id: reference & 0x0000_FFFF_FFFF_FFFF,
+            sequence: (reference >> 48) as u16,
+            flags,
+            file_name,
+            key: key.to_vec(),
+            is_stale,
+        },
+        length,
+    ))
+}
+
+/// Parses the content of an $INDEX_ROOT attribute.
+/// After the live entries, the slack space of the node is scanned for remnants of older entries.
+pub fn parse_index_root(content: &[u8]) -> Vec<IndexEntry> {
+    let mut entries = vec![];
+
+    let node = match content.get(INDEX_ROOT_HEADER_SIZE..) {
+        Some(node) if node.len() >= 16 => node,
+        _ => return entries,
+    };
+
+    let first_entry = u32_at(node, 0) as usize;
+    let live_end = (u32_at(node, 4) as usize).min(node.len());
+    let allocated_end = (u32_at(node, 8) as usize).min(node.len());
+
+    let mut offset = first_entry;
+    while offset < live_end {
+        let (entry, length) = match parse_entry(&node[offset..live_end], false) {
+            Some(parsed) => parsed,
+            None => break,
+        };
+
+        // The last entry has no key, it only points to the sub-node of the larger names.
+        if entry.flags & INDEX_ENTRY_LAST != 0 {
+            break;
+        }
+
+        entries.push(entry);
+        offset += length;
+    }
+
+    // Entries are 8 bytes aligned.
+    let mut offset = (live_end + 7) & !7;
+    while offset + INDEX_ENTRY_HEADER_SIZE + FILE_NAME_MIN_SIZE <= allocated_end {
+        match parse_entry(&node[offset..allocated_end], true) {
+            Some((entry, length)) if entry.file_name.is_some() && length % 8 == 0 => {
+                entries.push(entry);
+                offset += length;
+            }
+            _ => offset += 8,
+        }
+    }
+
+    entries
+}
+
+/// Returns the entries of the $I30 $INDEX_ROOT attribute of a directory.
+pub fn index_root_entries(entry: &MftEntry) -> Option<Vec<IndexEntry>> {
+    let instance = entry
+        .iter_attributes_matching(Some(vec![MftAttributeType::IndexRoot]))
+        .filter_map(Result::ok)
+        .find(|a| a.header.name == "$I30")?
+        .header
+        .instance;
+
+    let record = attribute_record(entry, instance)?;
+    let content_length = u32_at(record, 0x10) as usize;
+    let content_offset = u16_at(record, 0x14) as usize;
+
+    record
+        .get(content_offset..content_offset + content_length)
+        .map(parse_index_root)
+}
+
+#[pyclass]
+pub struct PyIndexEntry {
+    /// Entry number of the indexed file
+    #[pyo3(get)]
+    pub entry_id: u64,
+    #[pyo3(get)]
+    pub sequence: u16,
+    #[pyo3(get)]
+    pub flags: u32,
+    /// The $FILE_NAME of the indexed file (`PyMftAttributeX30`), with its timestamps and sizes
+    #[pyo3(get)]
+    pub file_name: Option<Py<PyMftAttributeX30>>,
+    /// Whether the entry was recovered from the slack space of the index (for example, a deleted file)
+    #[pyo3(get)]
+    pub is_stale: bool,
+}
+
+impl PyIndexEntry {
+    pub fn from_index_entry(py: Python, entry: IndexEntry) -> PyResult<Py<Self>> {
+        let key = entry.key;
+        let file_name = entry
+            .file_name
+            .map(|name| PyMftAttributeX30::from_x30(py, name, &key, false))
+            .transpose()?;
+
+        Py::new(
+            py,
+            PyIndexEntry {
+                entry_id: entry.entry_id,
+                sequence: entry.sequence,
+                flags: entry.flags,
+                file_name,
+                is_stale: entry.is_stale,
+            },
+        )
+    }
+}
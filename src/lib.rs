@@ -1,64 +1,232 @@
 #![deny(unused_must_use)]
 #![cfg_attr(not(debug_assertions), deny(clippy::dbg_macro))]
 
+mod aio;
+mod arrow;
 mod attribute;
+mod batch;
+mod bitmap;
+mod carve;
+mod checkpoint;
+mod cli;
+mod detect;
+mod diff;
+mod digest;
+mod ea;
 mod entry;
+mod export;
+mod fields;
+mod filter;
+mod flags;
+mod flat;
+mod gzip;
+mod index;
+mod logfile;
+mod msgpack;
+mod parquet;
+mod parser;
+mod path;
+mod reparse;
+mod runlist;
+mod secure;
+mod sort;
+mod stats;
+mod testing;
+mod timeline;
+mod timestamp;
+mod usn;
 mod utils;
+mod validate;
+mod volume;
+mod vss;
+mod xml;
 
 pub(crate) mod err;
 pub use entry::PyMftEntry;
 
-use mft::{MftEntry, MftParser};
+use mft::MftEntry;
 
+use std::collections::{HashMap, VecDeque};
 use std::fs::File;
 use std::io;
-use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::io::{BufReader, Read, Seek, Write};
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+use std::thread;
+use std::time::Instant;
 
 use pyo3::prelude::*;
 use pyo3::PyIterProtocol;
+use pyo3::buffer::PyBuffer;
 use pyo3::exceptions;
 
+use crate::aio::PyMftAsyncEntriesIterator;
 use crate::attribute::{
-    PyMftAttribute, PyMftAttributeOther, PyMftAttributeX10, PyMftAttributeX20, PyMftAttributeX30,
-    PyMftAttributeX40, PyMftAttributeX80, PyMftAttributeX90,
+    PyExtendedAttribute, PyMftAttribute, PyMftAttributeOther, PyMftAttributeX10, PyMftAttributeX20,
+    PyMftAttributeX30, PyMftAttributeX40, PyMftAttributeX80, PyMftAttributeX90, PyMftAttributeXD0,
+    PyMftAttributeXE0,
+};
+use crate::batch::PyMftBatch;
+use crate::bitmap::{PyClusterAllocation, PyVolumeBitmap};
+use crate::checkpoint::Checkpoint;
+use crate::cli::__pyo3_get_function_main;
+use crate::carve::{__pyo3_get_function_carve_entries, PyCarvedEntriesIterator};
+use crate::detect::{findings, PyMftFinding};
+use crate::diff::{__pyo3_get_function_diff, PyMftDiff, PyMftDiffIterator};
+use crate::ea::PyWslMetadata;
+use crate::entry::{
+    data_sizes, entry_to_json_value, link_count, EntryContext, PyMftAttributesIter, PyMftWalkIterator,
 };
-use crate::entry::PyMftAttributesIter;
 use crate::err::PyMftError;
-use crate::utils::{init_logging, FileOrFileLike};
+use crate::export::{
+    export, EncodingWriter, ExportFormat, ExportInput, ExportOptions, ExportOutput, ExportSource, ExportStats,
+    PyMftExportStats, RotatingWriter, TextEncoding,
+};
+use crate::fields::FieldSelection;
+use crate::filter::{
+    flag_filters, size_filters, BadSignature, EntryFilter, NameContains, RecordKind, Search, SearchField,
+};
+use crate::flags::{file_attributes_enum, volume_flag_names};
+use crate::flat::{FlatColumns, FlatMftEntry, TriageMftEntry};
+use crate::index::PyIndexEntry;
+use crate::logfile::{
+    PyLogFileParser, PyLogFileRecord, PyLogFileRecordsIterator, PyLogFileRestartArea,
+};
+use crate::msgpack::to_msgpack;
+use crate::parser::{MftParser, ENTRY_SIZES};
+use crate::path::{subtree_records, NamePreference, PathFormat, PathResolver, SharedParser, ORPHAN_PREFIX};
+use crate::runlist::{fragment_count, stream_runs};
+use crate::secure::{PyAce, PySecureParser, PySecurityDescriptor, SecurityDescriptors};
+use crate::sort::{largest_records, sorted_records, SortBy};
+use crate::stats::{attribute_type_stats, duplicate_paths, extension_stats};
+use crate::timeline::{l2t_rows, timeline_events, PyMftTimelineEvent};
+use crate::testing::PyMftBuilder;
+use crate::timestamp::TimestampFormat;
+use crate::usn::{
+    PyUsnEvent, PyUsnEventsIterator, PyUsnParser, PyUsnRecord, PyUsnRecordsIterator,
+};
+use crate::utils::{__pyo3_get_function_set_log_level, init_logging, BufferReader, FileOrFileLike};
+use crate::validate::{validate, PyMftIssue, PyMftValidationReport};
+use crate::volume::{SharedReader, VolumeInformation};
+use crate::vss::{PyShadowCopy, PyVssVolume};
+use crate::xml::flat_entry_to_xml;
 use csv::WriterBuilder;
-use mft::csv::FlatMftEntryWithName;
-use mft::entry::ZERO_HEADER;
-use pyo3::types::{PyBytes, PyString};
+use mft::entry::{EntryFlags, ZERO_HEADER};
+use pyo3::types::{PyBytes, PyDict, PyString};
 
 pub trait ReadSeek: Read + Seek {
     fn tell(&mut self) -> io::Result<u64> {
-        self.seek(SeekFrom::Current(0))
+        self.stream_position()
     }
 }
 
 impl<T: Read + Seek> ReadSeek for T {}
 
+#[derive(Clone)]
 pub enum Output {
     Python,
     CSV,
-    JSON,
+    /// JSON documents, with only the selected fields, pretty-printed if an indent is given.
+    JSON {
+        fields: FieldSelection,
+        indent: Option<usize>,
+    },
+    /// MessagePack documents, with only the selected fields.
+    MsgPack { fields: FieldSelection },
+    /// `PyMftTimelineEvent`s, several per entry.
+    Timeline,
+    /// log2timeline CSV lines, several per entry.
+    L2tCsv,
+    /// `PyMftFinding`s of the detections, for the entries which have any.
+    Findings,
+    /// CSV lines with only the header, name and timestamps of the entries.
+    TriageCsv,
+    /// `Entry` XML elements.
+    Xml,
 }
 
 #[pyclass]
-/// PyMftParser(self, path_or_file_like, /)
+/// PyMftParser(self, path_or_file_like, security=None, name_preference='win32', strict=False, lenient=False,
+///             entry_size=None, hash_resident_data=False, lossy_names=False, cache_size=0, verbose_warnings=None,
+///             orphan_prefix='[Orphan]', /)
 /// --
 ///
 /// Returns an instance of the parser.
-/// Works on both a path (string), or a file-like object.
+/// Works on a path (string), a file-like object, or any object supporting the buffer protocol
+/// (`bytes`, `memoryview`, `mmap`, ...), which is then read in place.
+/// The record size (1024 or 4096) is detected from the first valid record, unless `entry_size` is given.
+/// If `security` (a `PySecureParser`) is given, entries are annotated with their owner and group SIDs.
+/// `name_preference` selects which $FILE_NAME is used to build paths, one of `win32`, `dos`, `posix`,
+/// or `all` (every path of the entry, separated by `|`).
+/// In `strict` mode, entries whose fixup values do not match are yielded as errors.
+/// In `lenient` mode, attributes which fail to parse are returned as raw attributes (with a `parse_error`),
+/// instead of error objects, and do not hide the rest of the entry.
+/// With `hash_resident_data`, CSV output has the SHA256 of resident $DATA content.
+/// With `lossy_names`, names with invalid UTF-16 (e.g. unpaired surrogates) are decoded with U+FFFD
+/// replacement characters, instead of silently dropping the invalid code units.
+/// If `cache_size` is not 0, up to that many records are kept in memory (least recently used are evicted),
+/// so that the records looked up repeatedly to resolve paths and parents or to walk directories are parsed once.
+/// Warnings about the records (e.g. fixup mismatches) are logged to `logging.getLogger("mft")`
+/// if they pass the level set with `mft.set_log_level`, unless `verbose_warnings` is True (always logged)
+/// or False (never logged).
+/// The path of an entry whose parent directory cannot be resolved (missing, not a directory, or reused since,
+/// as its sequence number does not match) starts with `orphan_prefix`, e.g. `[Orphan]/name`.
+/// Several iterators can be created from the same parser, each with its own position,
+/// and they can be used from different threads.
 pub struct PyMftParser {
-    inner: Option<MftParser<Box<dyn ReadSeek + Send>>>,
+    inner: MftParser<Box<dyn ReadSeek + Send>>,
+    /// Another handle to the data of `inner`, for entries which resolve their path after iteration.
+    reader: SharedReader,
+    /// The input, if it is a buffer, which exports read in place.
+    buffer: Option<BufferReader>,
+    size: u64,
+    entry_size: u32,
+    pub(crate) security: Option<SecurityDescriptors>,
+    name_preference: NamePreference,
+    strict: bool,
+    lenient: bool,
+    hash_resident_data: bool,
+    lossy_names: bool,
+    cache_size: usize,
+    verbose_warnings: Option<bool>,
+    pub(crate) orphan_prefix: String,
 }
 
 #[pymethods]
 impl PyMftParser {
     #[new]
-    fn new(path_or_file_like: PyObject) -> PyResult<Self> {
+    #[allow(clippy::too_many_arguments)]
+    #[args(
+        security = "None",
+        name_preference = "\"win32\"",
+        strict = "false",
+        lenient = "false",
+        entry_size = "None",
+        hash_resident_data = "false",
+        lossy_names = "false",
+        cache_size = "0",
+        verbose_warnings = "None",
+        orphan_prefix = "\"[Orphan]\""
+    )]
+    fn new(
+        path_or_file_like: PyObject,
+        security: Option<PyRef<PySecureParser>>,
+        name_preference: &str,
+        strict: bool,
+        lenient: bool,
+        entry_size: Option<u32>,
+        hash_resident_data: bool,
+        lossy_names: bool,
+        cache_size: usize,
+        verbose_warnings: Option<bool>,
+        orphan_prefix: &str,
+    ) -> PyResult<Self> {
+        check_entry_size(entry_size)?;
+
         let file_or_file_like = FileOrFileLike::from_pyobject(path_or_file_like)?;
+        let buffer = match &file_or_file_like {
+            FileOrFileLike::Buffer(b) => Some(b.clone()),
+            _ => None,
+        };
 
         let (boxed_read_seek, size) = match file_or_file_like {
             FileOrFileLike::File(s) => {
@@ -70,13 +238,83 @@ impl PyMftParser {
                 (Box::new(reader) as Box<dyn ReadSeek + Send>, Some(size))
             }
             FileOrFileLike::FileLike(f) => (Box::new(f) as Box<dyn ReadSeek + Send>, None),
+            FileOrFileLike::Buffer(b) => {
+                let size = b.size();
+                (Box::new(b) as Box<dyn ReadSeek + Send>, Some(size))
+            }
         };
 
-        let parser = MftParser::from_read_seek(boxed_read_seek, size).map_err(PyMftError)?;
+        let mut parser = PyMftParser::from_read_seek(boxed_read_seek, size, entry_size)?;
+        parser.buffer = buffer;
 
-        Ok(PyMftParser {
-            inner: Some(parser),
-        })
+        parser.with_options(
+            security,
+            name_preference,
+            strict,
+            lenient,
+            hash_resident_data,
+            lossy_names,
+            cache_size,
+            verbose_warnings,
+            orphan_prefix,
+        )
+    }
+
+    /// from_buffer(bytes_like, security=None, name_preference='win32', strict=False, lenient=False,
+    ///             entry_size=None, hash_resident_data=False, lossy_names=False, cache_size=0, verbose_warnings=None,
+    ///             orphan_prefix='[Orphan]', /)
+    /// --
+    ///
+    /// Returns an instance of the parser over an MFT which is already in memory
+    /// (`bytes`, `bytearray`, `memoryview`, or any object supporting the buffer protocol).
+    /// The buffer is read in place, without calling back into python.
+    /// The options are the same as for `PyMftParser`.
+    #[staticmethod]
+    #[allow(clippy::too_many_arguments)]
+    #[args(
+        security = "None",
+        name_preference = "\"win32\"",
+        strict = "false",
+        lenient = "false",
+        entry_size = "None",
+        hash_resident_data = "false",
+        lossy_names = "false",
+        cache_size = "0",
+        verbose_warnings = "None",
+        orphan_prefix = "\"[Orphan]\""
+    )]
+    fn from_buffer(
+        bytes_like: &PyAny,
+        security: Option<PyRef<PySecureParser>>,
+        name_preference: &str,
+        strict: bool,
+        lenient: bool,
+        entry_size: Option<u32>,
+        hash_resident_data: bool,
+        lossy_names: bool,
+        cache_size: usize,
+        verbose_warnings: Option<bool>,
+        orphan_prefix: &str,
+    ) -> PyResult<Self> {
+        check_entry_size(entry_size)?;
+
+        let reader = BufferReader::new(PyBuffer::<u8>::get(bytes_like)?)?;
+        let size = reader.size();
+
+        let mut parser = PyMftParser::from_read_seek(Box::new(reader.clone()), Some(size), entry_size)?;
+        parser.buffer = Some(reader);
+
+        parser.with_options(
+            security,
+            name_preference,
+            strict,
+            lenient,
+            hash_resident_data,
+            lossy_names,
+            cache_size,
+            verbose_warnings,
+            orphan_prefix,
+        )
     }
 
     /// number_of_entries(self, /)
@@ -84,78 +322,1164 @@ impl PyMftParser {
     ///
     /// Returns the total number of entries in the MFT.
     fn number_of_entries(&self) -> PyResult<u64> {
-        match self.inner {
-            Some(ref inner) => Ok(inner.get_entry_count()),
-            None => Err(PyErr::new::<exceptions::PyRuntimeError, _>(
-                "Cannot call this method before object is initialized",
-            )),
+        Ok(self.inner.get_entry_count())
+    }
+
+    /// number_of_valid_entries(self, /)
+    /// --
+    ///
+    /// Returns the number of records with a valid `FILE` signature.
+    /// Unlike `number_of_entries`, zeroed, BAAD and unknown records are not counted (see `record_counts`).
+    fn number_of_valid_entries(&mut self) -> PyResult<u64> {
+        let counts = self.inner_mut().count_signatures().map_err(PyMftError)?;
+
+        Ok(counts.file)
+    }
+
+    /// record_counts(self, /)
+    /// --
+    ///
+    /// Returns a dict of the number of records by signature: `file`, `baad`, `zeroed` (empty slots),
+    /// and `unknown` (any other signature). Only the record headers are read.
+    fn record_counts(&mut self, py: Python) -> PyResult<PyObject> {
+        let counts = self.inner_mut().count_signatures().map_err(PyMftError)?;
+
+        let dict = PyDict::new(py);
+        dict.set_item("file", counts.file)?;
+        dict.set_item("baad", counts.baad)?;
+        dict.set_item("zeroed", counts.zeroed)?;
+        dict.set_item("unknown", counts.unknown)?;
+
+        Ok(dict.to_object(py))
+    }
+
+    /// hard_link_mismatches(self, /)
+    /// --
+    ///
+    /// Returns a list of `(entry_id, hard_link_count, number_of_names)` tuples, for every allocated
+    /// entry whose header hard link count disagrees with the number of its $FILE_NAME attributes.
+    /// Names stored in extension entries are counted towards their base entry.
+    fn hard_link_mismatches(&mut self) -> PyResult<Vec<(u64, u16, usize)>> {
+        let inner = self.inner_mut();
+
+        let mut link_counts: HashMap<u64, u16> = HashMap::new();
+        let mut names: HashMap<u64, usize> = HashMap::new();
+
+        for i in 0..inner.get_entry_count() {
+            let entry = match inner.read_entry(i) {
+                Ok(entry) if &entry.header.signature != ZERO_HEADER => entry,
+                _ => continue,
+            };
+
+            if !entry.header.flags.contains(EntryFlags::ALLOCATED) {
+                continue;
+            }
+
+            let base = if entry.header.base_reference.entry == 0 {
+                link_counts.insert(entry.header.record_number, entry.header.hard_link_count);
+                entry.header.record_number
+            } else {
+                entry.header.base_reference.entry
+            };
+
+            *names.entry(base).or_insert(0) += link_count(&entry);
+        }
+
+        let mut mismatches: Vec<(u64, u16, usize)> = link_counts
+            .into_iter()
+            .map(|(id, count)| (id, count, names.get(&id).copied().unwrap_or(0)))
+            .filter(|&(_, count, n)| usize::from(count) != n)
+            .collect();
+        mismatches.sort_unstable();
+
+        Ok(mismatches)
+    }
+
+    /// duplicate_paths(self, case_sensitive=False, /)
+    /// --
+    ///
+    /// Returns a list of `(full_path, entry_ids)` tuples, ordered by path, for every full path which distinct records
+    /// (allocated or deleted) resolve to. This typically happens when a file was deleted and created again,
+    /// or when a record was reused. Paths are compared ignoring case, like Windows does, unless `case_sensitive` is set.
+    #[args(case_sensitive = "false")]
+    fn duplicate_paths(&mut self, case_sensitive: bool) -> Vec<(String, Vec<u64>)> {
+        let mut context = self.entry_context();
+
+        duplicate_paths(self.inner_mut(), &mut context.paths, case_sensitive)
+    }
+
+    /// validate(self, /)
+    /// --
+    ///
+    /// Checks every entry of the MFT in one pass, and returns a `PyMftValidationReport` of the structural
+    /// problems found (bad signatures, fixup failures, impossible sizes, attributes out of bounds,
+    /// and parent references to records beyond the end of the MFT).
+    fn validate(&mut self) -> PyResult<PyMftValidationReport> {
+        let (number_of_entries, issues) = validate(self.inner_mut());
+
+        Ok(PyMftValidationReport::new(number_of_entries, issues))
+    }
+
+    /// extension_stats(self, split_deleted=False, /)
+    /// --
+    ///
+    /// Returns a dict of the number of files and of their total size (`count`, `total_size`), by lowercase extension.
+    /// Files without an extension are counted under an empty string.
+    /// If `split_deleted` is set, the counts are split into `allocated` and `deleted` files.
+    #[args(split_deleted = "false")]
+    fn extension_stats(&mut self, py: Python, split_deleted: bool) -> PyResult<PyObject> {
+        let name_preference = self.name_preference;
+        let stats = extension_stats(self.inner_mut(), name_preference);
+
+        let dict = PyDict::new(py);
+        for (extension, extension_stats) in stats {
+            dict.set_item(extension, extension_stats.to_dict(py, split_deleted)?)?;
         }
+
+        Ok(dict.to_object(py))
+    }
+
+    /// attribute_type_stats(self, /)
+    /// --
+    ///
+    /// Returns a dict by attribute type code (e.g. `0x80`) of the number of `resident` and `non_resident` attributes
+    /// of that type (`count`), and of the sum of their content sizes (`total_size`), with the `name` of the type.
+    /// Every record is counted, including deleted and extension records. Unexpected types or counts
+    /// (e.g. many $EA attributes) may be a sign of tampering.
+    fn attribute_type_stats(&mut self, py: Python) -> PyResult<PyObject> {
+        let stats = attribute_type_stats(self.inner_mut());
+
+        let dict = PyDict::new(py);
+        for (type_code, type_stats) in stats {
+            dict.set_item(type_code, type_stats.to_dict(py, type_code)?)?;
+        }
+
+        Ok(dict.to_object(py))
     }
 
-    /// entries(self, /)
+    /// volume_info(self, /)
+    /// --
+    ///
+    /// Returns a dict of the `label` of the volume, its NTFS `version` (e.g. `"3.1"`, also as `major_version`
+    /// and `minor_version`), and its `flags` (as an integer, and their names in `flags_list`, e.g. `IS_DIRTY`),
+    /// read from the $Volume entry (record 3). Values which are missing from the entry are None.
+    fn volume_info(&mut self, py: Python) -> PyResult<PyObject> {
+        let info = VolumeInformation::from_parser(self.inner_mut()).map_err(PyMftError)?;
+
+        let dict = PyDict::new(py);
+        dict.set_item("label", info.label)?;
+        dict.set_item("version", info.version.map(|(major, minor)| format!("{}.{}", major, minor)))?;
+        dict.set_item("major_version", info.version.map(|(major, _)| major))?;
+        dict.set_item("minor_version", info.version.map(|(_, minor)| minor))?;
+        dict.set_item("flags", info.flags)?;
+        dict.set_item("flags_list", info.flags.map(volume_flag_names))?;
+
+        Ok(dict.to_object(py))
+    }
+
+    /// mft_layout(self, /)
+    /// --
+    ///
+    /// Returns a dict describing the $MFT file itself, from its own record (record 0, and its extension records):
+    /// the `runs` of its $DATA stream as `(vcn, lcn, length)` tuples (in clusters, `lcn` is None for sparse runs),
+    /// its `size` and `allocated_size` in bytes, and the number of `fragments` (non-contiguous runs on the volume).
+    /// `runs` is None if the $DATA stream of record 0 is resident or missing.
+    fn mft_layout(&mut self, py: Python) -> PyResult<PyObject> {
+        let entry = self.inner_mut().read_entry(0).map_err(PyMftError)?;
+        let extensions = self.entry_context().shared_parser.extension_records(&entry);
+        let records = || std::iter::once(&entry).chain(extensions.iter());
+
+        let (size, allocated_size) = records()
+            .map(data_sizes)
+            .find(|sizes| *sizes != (0, 0))
+            .unwrap_or((0, 0));
+        let runs = stream_runs(records(), "");
+
+        let dict = PyDict::new(py);
+        dict.set_item(
+            "runs",
+            runs.as_ref()
+                .map(|runs| runs.iter().map(|run| (run.vcn, run.lcn, run.length)).collect::<Vec<_>>()),
+        )?;
+        dict.set_item("size", size)?;
+        dict.set_item("allocated_size", allocated_size)?;
+        dict.set_item("fragments", runs.as_deref().map_or(0, fragment_count))?;
+
+        Ok(dict.to_object(py))
+    }
+
+    /// export_csv(self, path_or_file_like, threads=None, compress=None, timestamp_format='iso', columns=None,
+    ///            encoding='utf-8', path_separator='/', path_root='', path_include_name=True, stats=False, /)
+    /// --
+    ///
+    /// Writes every entry of the MFT as CSV to a path or a (binary) file-like object,
+    /// and returns the number of written rows.
+    /// Entries are read and parsed on `threads` worker threads (by default, one per core), 1024 records at a time.
+    /// Entries which fail to parse are skipped with a warning.
+    /// The output is gzip compressed if `compress` is `gzip`, or if the path ends with `.gz`.
+    /// `timestamp_format` is one of `iso` (RFC 3339 strings), `epoch` (seconds since the Unix epoch, as a float),
+    /// or `epoch_ms` (milliseconds since the Unix epoch, as an integer).
+    /// If `columns` is given (a list of column names, e.g. `["EntryId", "FileSize"]`), only these columns are written,
+    /// in that order. The values of the other columns are not computed: paths are only resolved if `FullPath` is selected,
+    /// which makes exports of a few columns much faster.
+    /// `encoding` is one of `utf-8`, `utf-8-sig` (with a byte order mark, so that Excel detects UTF-8),
+    /// or `utf-16le` (with a byte order mark).
+    /// Control characters in paths, link targets and signatures are written as their Unicode control pictures
+    /// (e.g. U+0001 as `␁`), since spreadsheets mishandle them.
+    /// Paths are written with `path_separator` (`/` or `\`) between their components, starting with `path_root`
+    /// (e.g. `C:`) if it is given, and without the name of the entry (the path of its parent directory)
+    /// unless `path_include_name` is set.
+    /// If `stats` is set, a `PyMftExportStats` is returned instead of the number of rows, with the throughput
+    /// of the export and the time spent in each of its stages (timing them slows the export down slightly).
+    #[args(
+        threads = "None",
+        compress = "None",
+        timestamp_format = "\"iso\"",
+        columns = "None",
+        encoding = "\"utf-8\"",
+        path_separator = "\"/\"",
+        path_root = "\"\"",
+        path_include_name = "true",
+        stats = "false"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn export_csv(
+        &mut self,
+        py: Python,
+        path_or_file_like: PyObject,
+        threads: Option<usize>,
+        compress: Option<String>,
+        timestamp_format: &str,
+        columns: Option<Vec<String>>,
+        encoding: &str,
+        path_separator: &str,
+        path_root: &str,
+        path_include_name: bool,
+        stats: bool,
+    ) -> PyResult<PyObject> {
+        let encoding = TextEncoding::from_name(encoding)?;
+        let path_format = PathFormat::new(path_separator, path_root, path_include_name)?;
+        let context = self.flat_context(timestamp_format, columns, path_format)?;
+
+        let mut output = ExportOutput::open(path_or_file_like, compress.as_deref())?;
+        let result = {
+            let mut writer = EncodingWriter::new(&mut output.writer, encoding);
+            self.export_to(py, ExportFormat::Csv, &mut writer, threads, context, stats)?
+        };
+        output.close()?;
+
+        Ok(result)
+    }
+
+    /// export_json(self, path_or_file_like, threads=None, compress=None, timestamp_format='iso', stats=False, /)
+    /// --
+    ///
+    /// Like `export_csv`, but writes the entries as JSON lines (one JSON document per entry).
+    #[args(threads = "None", compress = "None", timestamp_format = "\"iso\"", stats = "false")]
+    fn export_json(
+        &mut self,
+        py: Python,
+        path_or_file_like: PyObject,
+        threads: Option<usize>,
+        compress: Option<String>,
+        timestamp_format: &str,
+        stats: bool,
+    ) -> PyResult<PyObject> {
+        let context = self.flat_context(timestamp_format, None, PathFormat::default())?;
+        self.export(py, ExportFormat::Json, path_or_file_like, threads, compress.as_deref(), context, stats)
+    }
+
+    /// export_jsonl(self, directory, max_bytes=None, max_entries=None, threads=None, timestamp_format='iso', /)
+    /// --
+    ///
+    /// Like `export_json`, but writes the entries to numbered files in `directory` (`part-0001.jsonl`,
+    /// `part-0002.jsonl`, ...), which is created if needed. A new file is started whenever the current one would exceed
+    /// `max_bytes` or `max_entries` (a single entry larger than `max_bytes` still gets a file of its own).
+    /// Returns the paths of the written files.
+    #[args(max_bytes = "None", max_entries = "None", threads = "None", timestamp_format = "\"iso\"")]
+    fn export_jsonl(
+        &mut self,
+        py: Python,
+        directory: &str,
+        max_bytes: Option<u64>,
+        max_entries: Option<u64>,
+        threads: Option<usize>,
+        timestamp_format: &str,
+    ) -> PyResult<Vec<String>> {
+        let context = self.flat_context(timestamp_format, None, PathFormat::default())?;
+        if max_bytes == Some(0) || max_entries == Some(0) {
+            return Err(PyErr::new::<exceptions::PyValueError, _>(
+                "max_bytes and max_entries must be greater than 0",
+            ));
+        }
+
+        let mut writer = RotatingWriter::new(directory, "jsonl", max_bytes, max_entries)?;
+        self.export_to(py, ExportFormat::Json, &mut writer, threads, context, false)?;
+
+        Ok(writer.finish()?)
+    }
+
+    /// export_xml(self, path_or_file_like, threads=None, compress=None, timestamp_format='iso', columns=None,
+    ///            path_separator='/', path_root='', path_include_name=True, stats=False, /)
+    /// --
+    ///
+    /// Like `export_csv`, but writes the entries as an XML document (UTF-8):
+    /// an `Mft` root element, with an `Entry` element per line.
+    /// The children of an `Entry` are the columns of `export_csv` (e.g. `<EntryId>5</EntryId>`), in the same order.
+    /// Columns without a value are omitted, and characters which are not allowed in XML are replaced with U+FFFD.
+    /// `columns` selects the children of `Entry`, paths are formatted and `stats` are returned, as in `export_csv`.
+    #[args(
+        threads = "None",
+        compress = "None",
+        timestamp_format = "\"iso\"",
+        columns = "None",
+        path_separator = "\"/\"",
+        path_root = "\"\"",
+        path_include_name = "true",
+        stats = "false"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn export_xml(
+        &mut self,
+        py: Python,
+        path_or_file_like: PyObject,
+        threads: Option<usize>,
+        compress: Option<String>,
+        timestamp_format: &str,
+        columns: Option<Vec<String>>,
+        path_separator: &str,
+        path_root: &str,
+        path_include_name: bool,
+        stats: bool,
+    ) -> PyResult<PyObject> {
+        let path_format = PathFormat::new(path_separator, path_root, path_include_name)?;
+        let context = self.flat_context(timestamp_format, columns, path_format)?;
+        self.export(py, ExportFormat::Xml, path_or_file_like, threads, compress.as_deref(), context, stats)
+    }
+
+    /// export_arrow_ipc(self, path_or_file_like, threads=None, columns=None, path_separator='/', path_root='',
+    ///                  path_include_name=True, stats=False, /)
+    /// --
+    ///
+    /// Like `export_csv`, but writes the entries as an Arrow IPC stream (the columns of `export_csv`,
+    /// with integer, boolean, string, and timestamp types), and returns the number of written rows.
+    /// A record batch is written and flushed every 1024 records, so that readers (e.g. `pyarrow.ipc.open_stream`)
+    /// can consume the stream while the export is still running.
+    /// Timestamps are in microseconds since the Unix epoch, in UTC.
+    /// `columns` selects the columns of the stream, paths are formatted and `stats` are returned, as in `export_csv`.
+    #[args(
+        threads = "None",
+        columns = "None",
+        path_separator = "\"/\"",
+        path_root = "\"\"",
+        path_include_name = "true",
+        stats = "false"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn export_arrow_ipc(
+        &mut self,
+        py: Python,
+        path_or_file_like: PyObject,
+        threads: Option<usize>,
+        columns: Option<Vec<String>>,
+        path_separator: &str,
+        path_root: &str,
+        path_include_name: bool,
+        stats: bool,
+    ) -> PyResult<PyObject> {
+        let path_format = PathFormat::new(path_separator, path_root, path_include_name)?;
+        let context = self.flat_context("iso", columns, path_format)?;
+        self.export(py, ExportFormat::ArrowIpc, path_or_file_like, threads, None, context, stats)
+    }
+
+    /// entries(self, start_from=None, path_prefix=None, min_size=None, max_size=None, skip_empty=True, flags_any=None,
+    ///         flags_none=None, /)
     /// --
     ///
     /// Returns an iterator that yields the mft entries as python objects.
-    fn entries(&mut self) -> PyResult<Py<PyMftEntriesIterator>> {
-        self.records_iterator(Output::Python)
+    /// Iteration starts at record number `start_from` (as returned by `PyMftEntriesIterator.tell()`), if given.
+    /// If `path_prefix` is given (e.g. `Users/bob/`), only the entries below that directory are yielded,
+    /// including deleted ones. A ValueError is raised if no directory matches the prefix.
+    /// If `min_size` or `max_size` are given, only the entries whose logical size (of the unnamed $DATA stream)
+    /// is within these bounds (inclusive) are yielded.
+    /// Zeroed records (empty slots) are skipped, unless `skip_empty` is False: a placeholder `PyMftEntry`
+    /// (with an empty `signature`) is then yielded for each of them, so that every record number is seen.
+    /// If `flags_any` or `flags_none` are given (lists of names of `mft.FileAttributes`, e.g. `['HIDDEN', 'SYSTEM']`),
+    /// only the entries whose $STANDARD_INFORMATION has at least one of the flags of `flags_any`
+    /// and none of the flags of `flags_none` set are yielded. `DIRECTORY` is set for directories.
+    /// Placeholders have no attributes to filter on, so a ValueError is raised if `skip_empty` is False
+    /// and any of `min_size`, `max_size`, `flags_any` or `flags_none` is given.
+    #[args(
+        start_from = "None",
+        path_prefix = "None",
+        min_size = "None",
+        max_size = "None",
+        skip_empty = "true",
+        flags_any = "None",
+        flags_none = "None"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn entries(
+        &mut self,
+        py: Python,
+        start_from: Option<u64>,
+        path_prefix: Option<String>,
+        min_size: Option<u64>,
+        max_size: Option<u64>,
+        skip_empty: bool,
+        flags_any: Option<Vec<String>>,
+        flags_none: Option<Vec<String>>,
+    ) -> PyResult<Py<PyMftEntriesIterator>> {
+        let mut filters = size_filters(min_size, max_size);
+        filters.extend(flag_filters(flags_any, flags_none)?);
+        if !skip_empty && !filters.is_empty() {
+            return Err(PyErr::new::<exceptions::PyValueError, _>(
+                "skip_empty=False cannot be combined with min_size, max_size, flags_any or flags_none",
+            ));
+        }
+
+        let iterator = self.filtered_entries(start_from, path_prefix, filters)?;
+        iterator.borrow(py).lock(py).skip_empty = skip_empty;
+
+        Ok(iterator)
     }
 
-    /// entries_json(self, /)
+    /// aentries(self, start_from=None, batch_size=256, /)
+    /// --
+    ///
+    /// Returns an asynchronous iterator that yields the mft entries as python objects, for use with `async for`.
+    /// Records are read on a background thread (with the GIL released), in batches of `batch_size`,
+    /// so that the event loop is not blocked.
+    #[args(start_from = "None", batch_size = "256")]
+    fn aentries(&mut self, start_from: Option<u64>, batch_size: usize) -> PyResult<PyMftAsyncEntriesIterator> {
+        let entries = self.records_iterator(Output::Python, start_from.unwrap_or(0), vec![])?;
+
+        Ok(PyMftAsyncEntriesIterator::new(entries, batch_size))
+    }
+
+    /// find(self, name_contains, case_sensitive=False, /)
+    /// --
+    ///
+    /// Returns an iterator that yields the entries (including deleted ones) which have a $FILE_NAME
+    /// containing `name_contains`.
+    #[args(case_sensitive = "false")]
+    fn find(&mut self, name_contains: &str, case_sensitive: bool) -> PyResult<Py<PyMftEntriesIterator>> {
+        let filter = NameContains::new(name_contains, case_sensitive);
+        self.records_iterator(Output::Python, 0, vec![Box::new(filter)])
+    }
+
+    /// directories(self, start_from=None, /)
+    /// --
+    ///
+    /// Returns an iterator that yields the entries (including deleted ones) of directories.
+    /// Records are told apart by the `INDEX_PRESENT` flag of their header, so that the records of files
+    /// are skipped without parsing their attributes. Extension records are skipped.
+    #[args(start_from = "None")]
+    fn directories(&mut self, start_from: Option<u64>) -> PyResult<Py<PyMftEntriesIterator>> {
+        self.records_iterator(Output::Python, start_from.unwrap_or(0), vec![Box::new(RecordKind::directories())])
+    }
+
+    /// files(self, start_from=None, /)
+    /// --
+    ///
+    /// Returns an iterator that yields the entries (including deleted ones) which are not directories,
+    /// as in `directories`.
+    #[args(start_from = "None")]
+    fn files(&mut self, start_from: Option<u64>) -> PyResult<Py<PyMftEntriesIterator>> {
+        self.records_iterator(Output::Python, start_from.unwrap_or(0), vec![Box::new(RecordKind::files())])
+    }
+
+    /// bad_entries(self, /)
+    /// --
+    ///
+    /// Returns an iterator that yields the entries whose record has the `BAAD` signature,
+    /// written by chkdsk over records which failed the fixup check. These are a sign of corruption or tampering.
+    fn bad_entries(&mut self) -> PyResult<Py<PyMftEntriesIterator>> {
+        self.records_iterator(Output::Python, 0, vec![Box::new(BadSignature)])
+    }
+
+    /// search(self, regex, field='name', /)
+    /// --
+    ///
+    /// Returns an iterator that yields the entries (including deleted ones) matching a regular expression,
+    /// or any of a list of regular expressions.
+    /// `field` is either `name` (any $FILE_NAME of the entry), or `path` (the full path of the entry).
+    #[args(field = "\"name\"")]
+    fn search(&mut self, regex: PyObject, field: &str) -> PyResult<Py<PyMftEntriesIterator>> {
+        let patterns: Vec<String> = Python::with_gil(|py| {
+            let regex = regex.as_ref(py);
+            match regex.extract::<String>() {
+                Ok(pattern) => Ok(vec![pattern]),
+                Err(_) => regex.extract(),
+            }
+        })?;
+
+        let search = Search::new(&patterns, SearchField::from_name(field)?)?;
+        self.records_iterator(Output::Python, 0, vec![Box::new(search)])
+    }
+
+    /// entries_sorted(self, by='path', descending=False, /)
+    /// --
+    ///
+    /// Returns an iterator that yields the mft entries as python objects, ordered by `by`,
+    /// one of `path`, `created`, `modified` ($STANDARD_INFORMATION timestamps), or `size`.
+    /// Keys are computed in a first pass over the MFT, and sorted externally (in runs which are written to temporary
+    /// files and merged), so that only the record numbers are kept in memory. Entries without a key are yielded last.
+    #[args(by = "\"path\"", descending = "false")]
+    fn entries_sorted(&mut self, by: &str, descending: bool) -> PyResult<Py<PyMftEntriesIterator>> {
+        let by = SortBy::from_name(by)?;
+
+        let mut inner = self.new_parser();
+        let mut context = self.entry_context();
+        let selection = sorted_records(&mut inner, &mut context, by, descending)?;
+
+        self.selection_iterator(inner, context, Output::Python, 0, Some(selection), vec![])
+    }
+
+    /// largest_files(self, n=100, /)
+    /// --
+    ///
+    /// Returns an iterator that yields the `n` largest files as python objects, largest first,
+    /// by logical size of their unnamed $DATA stream (`file_size`). Directories and extension records are skipped,
+    /// deleted files are included. The sizes are compared in a first pass over the MFT.
+    #[args(n = "100")]
+    fn largest_files(&mut self, n: usize) -> PyResult<Py<PyMftEntriesIterator>> {
+        let mut inner = self.new_parser();
+        let selection = largest_records(&mut inner, n);
+        let context = self.entry_context();
+
+        self.selection_iterator(inner, context, Output::Python, 0, Some(selection), vec![])
+    }
+
+    /// entries_timeline(self, start_from=None, /)
+    /// --
+    ///
+    /// Returns an iterator that yields a `PyMftTimelineEvent` for every timestamp of every entry:
+    /// the M/A/C/B timestamps of $STANDARD_INFORMATION, followed by those of every $FILE_NAME.
+    /// Events are in the order of the entries, and not sorted by time.
+    #[args(start_from = "None")]
+    fn entries_timeline(&mut self, start_from: Option<u64>) -> PyResult<Py<PyMftEntriesIterator>> {
+        self.records_iterator(Output::Timeline, start_from.unwrap_or(0), vec![])
+    }
+
+    /// findings(self, start_from=None, /)
+    /// --
+    ///
+    /// Returns an iterator that yields a `PyMftFinding` for every anomaly detected in the entries
+    /// (see `PyMftEntry.findings`), in the order of the entries.
+    #[args(start_from = "None")]
+    fn findings(&mut self, start_from: Option<u64>) -> PyResult<Py<PyMftEntriesIterator>> {
+        self.records_iterator(Output::Findings, start_from.unwrap_or(0), vec![])
+    }
+
+    /// entries_l2t_csv(self, start_from=None, /)
+    /// --
+    ///
+    /// Returns an iterator that yields CSV lines in the log2timeline (l2t_csv) format, starting with the header.
+    /// Timestamps of an attribute which are equal are merged into a single line (e.g. `M..B`).
+    #[args(start_from = "None")]
+    fn entries_l2t_csv(&mut self, start_from: Option<u64>) -> PyResult<Py<PyMftEntriesIterator>> {
+        self.records_iterator(Output::L2tCsv, start_from.unwrap_or(0), vec![])
+    }
+
+    /// resume(self, state, /)
+    /// --
+    ///
+    /// Returns an iterator which continues where the iterator whose `PyMftEntriesIterator.state()` was saved stopped,
+    /// with the same output format. The state must come from a parser over the same MFT.
+    fn resume(&mut self, py: Python, state: &PyDict) -> PyResult<Py<PyMftEntriesIterator>> {
+        let checkpoint = Checkpoint::from_dict(state)?;
+
+        let inner = self.new_parser();
+        if checkpoint.number_of_entries != inner.get_entry_count() {
+            return Err(PyErr::new::<exceptions::PyValueError, _>(format!(
+                "the state is for an MFT of {} entries, but this MFT has {} entries",
+                checkpoint.number_of_entries,
+                inner.get_entry_count()
+            )));
+        }
+
+        // Results of the last entry which were already yielded are produced again, and dropped.
+        let position = if checkpoint.pending > 0 {
+            checkpoint.position.saturating_sub(1)
+        } else {
+            checkpoint.position
+        };
+
+        let context = EntryContext {
+            timestamps: checkpoint.timestamps,
+            columns: checkpoint.columns,
+            path_format: checkpoint.path_format,
+            ..self.entry_context()
+        };
+        let iterator = self.selection_iterator(inner, context, checkpoint.output, position, None, vec![])?;
+
+        {
+            let iterator = iterator.borrow(py);
+            let mut state = iterator.lock(py);
+            state.csv_header_written = checkpoint.csv_header_written;
+            state.skip_empty = checkpoint.skip_empty;
+            state.keep_results = (checkpoint.pending > 0).then_some(checkpoint.pending);
+        }
+
+        Ok(iterator)
+    }
+
+    /// entries_triage_csv(self, start_from=None, timestamp_format='iso', /)
+    /// --
+    ///
+    /// Returns an iterator that yields CSV lines for a quick file listing, starting with the header:
+    /// the record number, flags, preferred name, parent reference and timestamps of every entry.
+    /// Other attributes are not parsed and paths are not resolved, which makes it faster than `entries_csv`.
+    /// `timestamp_format` is one of `iso`, `epoch` or `epoch_ms`, as in `export_csv`.
+    #[args(start_from = "None", timestamp_format = "\"iso\"")]
+    fn entries_triage_csv(
+        &mut self,
+        start_from: Option<u64>,
+        timestamp_format: &str,
+    ) -> PyResult<Py<PyMftEntriesIterator>> {
+        let timestamps = TimestampFormat::from_name(timestamp_format)?;
+
+        let inner = self.new_parser();
+        let context = EntryContext {
+            timestamps,
+            ..self.entry_context()
+        };
+
+        self.selection_iterator(inner, context, Output::TriageCsv, start_from.unwrap_or(0), None, vec![])
+    }
+
+    /// entries_json(self, start_from=None, min_size=None, max_size=None, include=None, exclude=None, indent=None,
+    ///              timestamp_format='iso', /)
     /// --
     ///
     /// Returns an iterator that yields mft entries as JSON.
-    fn entries_json(&mut self) -> PyResult<Py<PyMftEntriesIterator>> {
-        self.records_iterator(Output::JSON)
+    /// Iteration starts at record number `start_from` (as returned by `PyMftEntriesIterator.tell()`), if given.
+    /// `min_size` and `max_size` filter entries by logical size, as in `entries`.
+    /// `include` and `exclude` are lists of dotted field paths (e.g. `header.sequence`, `attributes.header`):
+    /// if `include` is given only these fields are kept, then the `exclude` fields are dropped.
+    /// Lists are traversed, so `attributes.data` refers to the content of every attribute.
+    /// If `indent` is given, documents are pretty-printed with that many spaces per level.
+    /// `timestamp_format` is one of `iso`, `epoch` or `epoch_ms`, as in `export_csv`.
+    #[args(
+        start_from = "None",
+        min_size = "None",
+        max_size = "None",
+        include = "None",
+        exclude = "None",
+        indent = "None",
+        timestamp_format = "\"iso\""
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn entries_json(
+        &mut self,
+        start_from: Option<u64>,
+        min_size: Option<u64>,
+        max_size: Option<u64>,
+        include: Option<Vec<String>>,
+        exclude: Option<Vec<String>>,
+        indent: Option<usize>,
+        timestamp_format: &str,
+    ) -> PyResult<Py<PyMftEntriesIterator>> {
+        let fields = FieldSelection::new(include, exclude)?;
+        let timestamps = TimestampFormat::from_name(timestamp_format)?;
+
+        let inner = self.new_parser();
+        let context = EntryContext {
+            timestamps,
+            ..self.entry_context()
+        };
+
+        self.selection_iterator(
+            inner,
+            context,
+            Output::JSON { fields, indent },
+            start_from.unwrap_or(0),
+            None,
+            size_filters(min_size, max_size),
+        )
+    }
+
+    /// entries_msgpack(self, start_from=None, min_size=None, max_size=None, include=None, exclude=None,
+    ///                 timestamp_format='iso', /)
+    /// --
+    ///
+    /// Returns an iterator that yields mft entries as MessagePack documents (`bytes`),
+    /// with the same content as `entries_json`, and the same options.
+    #[args(
+        start_from = "None",
+        min_size = "None",
+        max_size = "None",
+        include = "None",
+        exclude = "None",
+        timestamp_format = "\"iso\""
+    )]
+    fn entries_msgpack(
+        &mut self,
+        start_from: Option<u64>,
+        min_size: Option<u64>,
+        max_size: Option<u64>,
+        include: Option<Vec<String>>,
+        exclude: Option<Vec<String>>,
+        timestamp_format: &str,
+    ) -> PyResult<Py<PyMftEntriesIterator>> {
+        let fields = FieldSelection::new(include, exclude)?;
+        let timestamps = TimestampFormat::from_name(timestamp_format)?;
+
+        let inner = self.new_parser();
+        let context = EntryContext {
+            timestamps,
+            ..self.entry_context()
+        };
+
+        self.selection_iterator(
+            inner,
+            context,
+            Output::MsgPack { fields },
+            start_from.unwrap_or(0),
+            None,
+            size_filters(min_size, max_size),
+        )
     }
 
-    /// entries_csv(self, /)
+    /// entries_csv(self, start_from=None, min_size=None, max_size=None, timestamp_format='iso', columns=None,
+    ///             path_separator='/', path_root='', path_include_name=True, /)
     /// --
     ///
     /// Returns an iterator that yields mft entries CSV lines.
-    fn entries_csv(&mut self) -> PyResult<Py<PyMftEntriesIterator>> {
-        self.records_iterator(Output::CSV)
+    /// Iteration starts at record number `start_from` (as returned by `PyMftEntriesIterator.tell()`), if given.
+    /// `min_size` and `max_size` filter entries by logical size, as in `entries`.
+    /// `timestamp_format` is one of `iso`, `epoch` or `epoch_ms`, as in `export_csv`.
+    /// `columns` selects the columns, and paths are formatted, as in `export_csv`.
+    #[args(
+        start_from = "None",
+        min_size = "None",
+        max_size = "None",
+        timestamp_format = "\"iso\"",
+        columns = "None",
+        path_separator = "\"/\"",
+        path_root = "\"\"",
+        path_include_name = "true"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn entries_csv(
+        &mut self,
+        start_from: Option<u64>,
+        min_size: Option<u64>,
+        max_size: Option<u64>,
+        timestamp_format: &str,
+        columns: Option<Vec<String>>,
+        path_separator: &str,
+        path_root: &str,
+        path_include_name: bool,
+    ) -> PyResult<Py<PyMftEntriesIterator>> {
+        let path_format = PathFormat::new(path_separator, path_root, path_include_name)?;
+        let context = self.flat_context(timestamp_format, columns, path_format)?;
+        let inner = self.new_parser();
+
+        self.selection_iterator(
+            inner,
+            context,
+            Output::CSV,
+            start_from.unwrap_or(0),
+            None,
+            size_filters(min_size, max_size),
+        )
+    }
+
+    /// entries_xml(self, start_from=None, min_size=None, max_size=None, timestamp_format='iso', columns=None,
+    ///             path_separator='/', path_root='', path_include_name=True, /)
+    /// --
+    ///
+    /// Returns an iterator that yields mft entries as `Entry` XML elements (strings), as in `export_xml`.
+    /// The options are the same as in `entries_csv`.
+    #[args(
+        start_from = "None",
+        min_size = "None",
+        max_size = "None",
+        timestamp_format = "\"iso\"",
+        columns = "None",
+        path_separator = "\"/\"",
+        path_root = "\"\"",
+        path_include_name = "true"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn entries_xml(
+        &mut self,
+        start_from: Option<u64>,
+        min_size: Option<u64>,
+        max_size: Option<u64>,
+        timestamp_format: &str,
+        columns: Option<Vec<String>>,
+        path_separator: &str,
+        path_root: &str,
+        path_include_name: bool,
+    ) -> PyResult<Py<PyMftEntriesIterator>> {
+        let path_format = PathFormat::new(path_separator, path_root, path_include_name)?;
+        let context = self.flat_context(timestamp_format, columns, path_format)?;
+        let inner = self.new_parser();
+
+        self.selection_iterator(
+            inner,
+            context,
+            Output::Xml,
+            start_from.unwrap_or(0),
+            None,
+            size_filters(min_size, max_size),
+        )
     }
 }
 
 impl PyMftParser {
-    fn records_iterator(&mut self, output_format: Output) -> PyResult<Py<PyMftEntriesIterator>> {
-        let gil = Python::acquire_gil();
-        let py = gil.python();
+    pub(crate) fn from_read_seek(
+        read_seek: Box<dyn ReadSeek + Send>,
+        size: Option<u64>,
+        entry_size: Option<u32>,
+    ) -> PyResult<Self> {
+        let reader = SharedReader::new(read_seek)?;
+        let parser = MftParser::from_read_seek(Box::new(reader.clone()) as Box<dyn ReadSeek + Send>, size, entry_size)
+            .map_err(PyMftError)?;
+
+        Ok(PyMftParser {
+            reader,
+            buffer: None,
+            size: parser.get_entry_count() * u64::from(parser.entry_size()),
+            entry_size: parser.entry_size(),
+            inner: parser,
+            security: None,
+            name_preference: NamePreference::Win32,
+            strict: false,
+            lenient: false,
+            hash_resident_data: false,
+            lossy_names: false,
+            cache_size: 0,
+            verbose_warnings: None,
+            orphan_prefix: ORPHAN_PREFIX.to_string(),
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn with_options(
+        mut self,
+        security: Option<PyRef<PySecureParser>>,
+        name_preference: &str,
+        strict: bool,
+        lenient: bool,
+        hash_resident_data: bool,
+        lossy_names: bool,
+        cache_size: usize,
+        verbose_warnings: Option<bool>,
+        orphan_prefix: &str,
+    ) -> PyResult<Self> {
+        self.security = security.map(|s| s.descriptors.clone());
+        self.name_preference = NamePreference::from_name(name_preference)?;
+        self.strict = strict;
+        self.lenient = lenient;
+        self.hash_resident_data = hash_resident_data;
+        self.lossy_names = lossy_names;
+        self.cache_size = cache_size;
+        self.inner.set_cache_size(cache_size);
+        self.verbose_warnings = verbose_warnings;
+        self.inner.set_verbose_warnings(verbose_warnings);
+        self.orphan_prefix = orphan_prefix.to_string();
+
+        Ok(self)
+    }
+
+    pub(crate) fn inner_mut(&mut self) -> &mut MftParser<Box<dyn ReadSeek + Send>> {
+        &mut self.inner
+    }
 
-        let inner = match self.inner.take() {
-            Some(inner) => inner,
-            None => {
-                return Err(PyErr::new::<exceptions::PyRuntimeError, _>(
-                    "PyMftParser can only be used once",
-                ));
+    /// Creates another parser over the same data, with its own position, for an iterator.
+    pub(crate) fn new_parser(&self) -> MftParser<Box<dyn ReadSeek + Send>> {
+        let mut parser = MftParser::with_layout(
+            Box::new(self.reader.clone()) as Box<dyn ReadSeek + Send>,
+            self.size,
+            self.entry_size,
+        );
+        parser.set_cache_size(self.cache_size);
+        parser.set_verbose_warnings(self.verbose_warnings);
+        parser
+    }
+
+    pub(crate) fn path_resolver(&self) -> PathResolver {
+        PathResolver::new(self.name_preference)
+            .with_lossy_names(self.lossy_names)
+            .with_orphan_prefix(&self.orphan_prefix)
+    }
+
+    pub(crate) fn entry_context(&self) -> EntryContext {
+        let mut shared_parser = MftParser::with_layout(self.reader.clone(), self.size, self.entry_size);
+        shared_parser.set_cache_size(self.cache_size);
+        shared_parser.set_verbose_warnings(self.verbose_warnings);
+
+        EntryContext {
+            security: self.security.clone(),
+            paths: self.path_resolver(),
+            shared_parser: SharedParser::new(shared_parser, self.path_resolver()),
+            lenient: self.lenient,
+            lossy_names: self.lossy_names,
+            hash_resident_data: self.hash_resident_data,
+            timestamps: TimestampFormat::Iso,
+            columns: None,
+            path_format: PathFormat::default(),
+        }
+    }
+
+    /// The context of flat output, with the given timestamp format, columns and path format.
+    fn flat_context(
+        &self,
+        timestamp_format: &str,
+        columns: Option<Vec<String>>,
+        path_format: PathFormat,
+    ) -> PyResult<EntryContext> {
+        Ok(EntryContext {
+            timestamps: TimestampFormat::from_name(timestamp_format)?,
+            columns: columns.as_deref().map(FlatColumns::new).transpose()?,
+            path_format,
+            ..self.entry_context()
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn export(
+        &mut self,
+        py: Python,
+        format: ExportFormat,
+        path_or_file_like: PyObject,
+        threads: Option<usize>,
+        compress: Option<&str>,
+        context: EntryContext,
+        stats: bool,
+    ) -> PyResult<PyObject> {
+        let mut output = ExportOutput::open(path_or_file_like, compress)?;
+        let result = self.export_to(py, format, &mut output.writer, threads, context, stats)?;
+        output.close()?;
+
+        Ok(result)
+    }
+
+    /// Exports every entry to `writer`, with the GIL released.
+    /// Returns the number of written rows, or a `PyMftExportStats` if `stats` is set.
+    fn export_to(
+        &mut self,
+        py: Python,
+        format: ExportFormat,
+        writer: &mut (dyn Write + Send),
+        threads: Option<usize>,
+        context: EntryContext,
+        stats: bool,
+    ) -> PyResult<PyObject> {
+        let started = Instant::now();
+        let (source, mut options) = self.export_data(format, threads, context);
+        if stats {
+            options.stats = Some(ExportStats::default());
+        }
+
+        let input = Arc::new(ExportInput { source, options });
+        let rows = py.allow_threads(|| export(&input, writer))?;
+        let options = &input.options;
+
+        match &options.stats {
+            Some(stats) => {
+                let stats = PyMftExportStats::new(rows, options.entry_size, options.threads, started.elapsed(), stats);
+                Ok(Py::new(py, stats)?.to_object(py))
             }
+            None => Ok(rows.to_object(py)),
+        }
+    }
+
+    /// The input of the parser, for the workers of an export, and the options to export it with.
+    fn export_data(
+        &self,
+        format: ExportFormat,
+        threads: Option<usize>,
+        context: EntryContext,
+    ) -> (ExportSource, ExportOptions) {
+        let source = match &self.buffer {
+            Some(buffer) => ExportSource::Buffer(buffer.clone()),
+            None => ExportSource::Reader(self.reader.clone()),
+        };
+
+        let options = ExportOptions {
+            format,
+            entry_size: self.entry_size,
+            strict: self.strict,
+            verbose_warnings: self.verbose_warnings,
+            threads: threads
+                .or_else(|| thread::available_parallelism().ok().map(usize::from))
+                .unwrap_or(1)
+                .max(1),
+            context,
+            source: None,
+            stats: None,
         };
 
-        let n_records = inner.get_entry_count();
+        (source, options)
+    }
+
+    /// The entries below `path_prefix` (if given), which match every filter.
+    fn filtered_entries(
+        &mut self,
+        start_from: Option<u64>,
+        path_prefix: Option<String>,
+        filters: Vec<Box<dyn EntryFilter>>,
+    ) -> PyResult<Py<PyMftEntriesIterator>> {
+        let path_prefix = match path_prefix {
+            Some(path_prefix) => path_prefix,
+            None => return self.records_iterator(Output::Python, start_from.unwrap_or(0), filters),
+        };
+
+        let selection = subtree_records(self.inner_mut(), &path_prefix).ok_or_else(|| {
+            PyErr::new::<exceptions::PyValueError, _>(format!(
+                "no directory matches the path prefix `{}`",
+                path_prefix
+            ))
+        })?;
+
+        let inner = self.new_parser();
+        let context = self.entry_context();
+        self.selection_iterator(
+            inner,
+            context,
+            Output::Python,
+            start_from.unwrap_or(0),
+            Some(selection),
+            filters,
+        )
+    }
+
+    /// Creates an iterator over the entries which match every filter.
+    fn records_iterator(
+        &mut self,
+        output_format: Output,
+        start_from: u64,
+        filters: Vec<Box<dyn EntryFilter>>,
+    ) -> PyResult<Py<PyMftEntriesIterator>> {
+        let inner = self.new_parser();
+        let context = self.entry_context();
+
+        self.selection_iterator(inner, context, output_format, start_from, None, filters)
+    }
+
+    /// Creates an iterator over the given record numbers (in order), or over every record if `selection` is None.
+    /// Only the entries which match every filter are yielded.
+    fn selection_iterator(
+        &self,
+        inner: MftParser<Box<dyn ReadSeek + Send>>,
+        context: EntryContext,
+        output_format: Output,
+        start_from: u64,
+        selection: Option<Vec<u64>>,
+        filters: Vec<Box<dyn EntryFilter>>,
+    ) -> PyResult<Py<PyMftEntriesIterator>> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let n_records = match &selection {
+            Some(selection) => selection.len() as u64,
+            None => inner.get_entry_count(),
+        };
 
         Py::new(
             py,
-            PyMftEntriesIterator {
+            PyMftEntriesIterator::new(EntriesIteratorState {
                 inner,
                 total_number_of_records: n_records,
-                current_record: 0,
+                current_record: start_from.min(n_records),
+                selection,
+                filters,
+                release_gil: false,
+                pending: VecDeque::new(),
                 output_format,
                 csv_header_written: false,
-            },
+                skip_empty: true,
+                keep_results: None,
+                context,
+                strict: self.strict,
+            }),
         )
     }
 }
 
-#[pyclass]
-pub struct PyMftEntriesIterator {
+fn to_json_string(value: &serde_json::Value, indent: Option<usize>) -> serde_json::Result<String> {
+    let indent = match indent {
+        Some(indent) => " ".repeat(indent),
+        None => return serde_json::to_string(value),
+    };
+
+    let mut json = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    serde::Serialize::serialize(value, &mut serde_json::Serializer::with_formatter(&mut json, formatter))?;
+
+    // The serializer only writes valid UTF-8.
+    Ok(String::from_utf8(json).expect("JSON is valid UTF-8"))
+}
+
+fn check_entry_size(entry_size: Option<u32>) -> PyResult<()> {
+    match entry_size {
+        Some(entry_size) if !ENTRY_SIZES.contains(&entry_size) => Err(PyErr::new::<exceptions::PyValueError, _>(
+            format!("entry_size must be one of {:?}, got {}", ENTRY_SIZES, entry_size),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// The state of an iterator over entries.
+pub struct EntriesIteratorState {
     inner: MftParser<Box<dyn ReadSeek + Send>>,
     total_number_of_records: u64,
+    /// Position of the iterator, which is the record number unless there is a selection.
     current_record: u64,
+    /// Record numbers to iterate over, in order.
+    selection: Option<Vec<u64>>,
+    /// Only entries which match every filter are yielded.
+    filters: Vec<Box<dyn EntryFilter>>,
+    /// Whether records are read with the GIL released (for background threads).
+    release_gil: bool,
+    /// Results of the current record which were not yielded yet, for outputs with several results per entry.
+    pending: VecDeque<PyObject>,
     output_format: Output,
     csv_header_written: bool,
+    /// Whether zeroed records are skipped, instead of being yielded as placeholders.
+    skip_empty: bool,
+    /// When resuming in the middle of the results of an entry, how many of its last results are still to be yielded.
+    keep_results: Option<usize>,
+    context: EntryContext,
+    strict: bool,
+}
+
+/// An iterator over entries.
+/// Its state is locked while an entry is read, so that `__next__` can be called from several threads.
+#[pyclass]
+pub struct PyMftEntriesIterator {
+    state: Mutex<EntriesIteratorState>,
 }
 
 impl PyMftEntriesIterator {
+    fn new(state: EntriesIteratorState) -> Self {
+        PyMftEntriesIterator {
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Waits for the state, with the GIL released (the thread holding the lock may need the GIL to progress).
+    pub(crate) fn lock(&self, py: Python) -> MutexGuard<'_, EntriesIteratorState> {
+        loop {
+            match self.state.try_lock() {
+                Ok(state) => return state,
+                // A panic while reading an entry does not leave the position in an inconsistent state.
+                Err(TryLockError::Poisoned(poisoned)) => return poisoned.into_inner(),
+                Err(TryLockError::WouldBlock) => {
+                    let state = &self.state;
+                    py.allow_threads(move || drop(state.lock()));
+                }
+            }
+        }
+    }
+}
+
+impl EntriesIteratorState {
     fn entry_to_pyobject(
         &mut self,
         entry_result: Result<MftEntry, PyMftError>,
@@ -163,7 +1487,7 @@ impl PyMftEntriesIterator {
     ) -> PyObject {
         match entry_result {
             Ok(entry) => {
-                match PyMftEntry::from_mft_entry(py, entry, &mut self.inner)
+                match PyMftEntry::from_mft_entry(py, entry, &self.context)
                     .map(|entry| entry.to_object(py))
                 {
                     Ok(py_mft_entry) => py_mft_entry,
@@ -178,12 +1502,16 @@ impl PyMftEntriesIterator {
     }
 
     fn entry_to_json(
-        &mut self,
+        &self,
         entry_result: Result<MftEntry, PyMftError>,
+        fields: &FieldSelection,
+        indent: Option<usize>,
         py: Python,
     ) -> PyObject {
         match entry_result {
-            Ok(entry) => match serde_json::to_string(&entry) {
+            Ok(entry) => match entry_to_json_value(&entry, &self.context.entry_options(), self.context.timestamps)
+                .and_then(|v| to_json_string(&fields.apply(v), indent))
+            {
                 Ok(s) => PyString::new(py, &s).to_object(py),
                 Err(_e) => PyErr::new::<exceptions::PyRuntimeError, _>("JSON Serialization failed").to_object(py),
             },
@@ -191,18 +1519,35 @@ impl PyMftEntriesIterator {
         }
     }
 
+    fn entry_to_msgpack(&self, entry: &MftEntry, fields: &FieldSelection, py: Python) -> PyObject {
+        match entry_to_json_value(entry, &self.context.entry_options(), self.context.timestamps) {
+            Ok(value) => PyBytes::new(py, &to_msgpack(&fields.apply(value))).to_object(py),
+            Err(_e) => PyErr::new::<exceptions::PyRuntimeError, _>("MessagePack Serialization failed").to_object(py),
+        }
+    }
+
+    fn entry_to_xml(&mut self, entry: &MftEntry, py: Python) -> PyObject {
+        let flat = FlatMftEntry::from_entry(entry, &mut self.inner, &mut self.context);
+        match flat_entry_to_xml(&flat, self.context.columns.as_ref()) {
+            Ok(s) => PyString::new(py, &s).to_object(py),
+            Err(_e) => PyErr::new::<exceptions::PyRuntimeError, _>("XML Serialization failed").to_object(py),
+        }
+    }
+
     fn entry_to_csv(&mut self, entry_result: Result<MftEntry, PyMftError>, py: Python) -> PyObject {
         let mut writer = WriterBuilder::new()
             .has_headers(!self.csv_header_written)
             .from_writer(Vec::new());
 
+        let header = !self.csv_header_written;
         if !self.csv_header_written {
             self.csv_header_written = true
         }
 
         match entry_result {
             Ok(entry) => {
-                match writer.serialize(FlatMftEntryWithName::from_entry(&entry, &mut self.inner)) {
+                let flat = FlatMftEntry::from_entry(&entry, &mut self.inner, &mut self.context);
+                match flat.write_csv(&mut writer, self.context.columns.as_ref(), header) {
                     Ok(()) => {}
                     Err(_e) => {
                         return PyErr::new::<exceptions::PyRuntimeError, _>("CSV Serialization failed")
@@ -219,26 +1564,118 @@ impl PyMftEntriesIterator {
         }
     }
 
-    fn next(&mut self) -> PyResult<Option<PyObject>> {
-        let gil = Python::acquire_gil();
-        let py = gil.python();
+    /// Serializes a row, preceded by the header for the first row of the iterator.
+    fn row_to_csv(&mut self, row: &impl serde::Serialize, py: Python) -> PyResult<PyObject> {
+        let mut writer = WriterBuilder::new()
+            .has_headers(!self.csv_header_written)
+            .from_writer(Vec::new());
+
+        self.csv_header_written = true;
 
+        writer
+            .serialize(row)
+            .map_err(|_e| PyErr::new::<exceptions::PyRuntimeError, _>("CSV Serialization failed"))?;
+
+        match writer.into_inner() {
+            Ok(bytes) => Ok(PyBytes::new(py, &bytes).to_object(py)),
+            Err(e) => Err(PyErr::new::<exceptions::PyRuntimeError, _>(e.to_string())),
+        }
+    }
+
+    fn next_with_gil(&mut self, py: Python) -> PyResult<Option<PyObject>> {
         loop {
-            if self.current_record == self.total_number_of_records {
+            if let Some(obj) = self.pending.pop_front() {
+                return Ok(Some(obj));
+            }
+
+            if self.current_record >= self.total_number_of_records {
                 return Ok(None);
             }
 
-            let obj = match self.inner.get_entry(self.current_record) {
+            let record_number = match &self.selection {
+                Some(selection) => selection[self.current_record as usize],
+                None => self.current_record,
+            };
+
+            let entry_result = if self.release_gil {
+                let inner = &mut self.inner;
+                py.allow_threads(move || inner.read_entry(record_number))
+            } else {
+                self.inner.read_entry(record_number)
+            };
+
+            let obj = match entry_result {
                 Ok(entry) => {
                     if &entry.header.signature == ZERO_HEADER {
+                        self.current_record += 1;
+                        if self.skip_empty {
+                            continue;
+                        }
+
+                        // The header of a zeroed record does not hold its number.
+                        let mut entry = entry;
+                        entry.header.record_number = record_number;
+                        return Ok(Some(self.entry_to_pyobject(Ok(entry), py)));
+                    }
+
+                    let inner = &mut self.inner;
+                    let context = &mut self.context;
+                    if !self.filters.iter().all(|filter| filter.matches(&entry, inner, context)) {
                         self.current_record += 1;
                         continue;
                     }
 
-                    let ret = match self.output_format {
+                    if self.strict && entry.valid_fixup == Some(false) {
+                        self.current_record += 1;
+                        return Ok(Some(
+                            PyErr::new::<exceptions::PyRuntimeError, _>(format!(
+                                "Entry {} has mismatching fixup values",
+                                entry.header.record_number
+                            ))
+                            .to_object(py),
+                        ));
+                    }
+
+                    let ret = match &self.output_format {
                         Output::Python => self.entry_to_pyobject(Ok(entry), py),
-                        Output::JSON => self.entry_to_json(Ok(entry), py),
+                        Output::JSON { fields, indent } => self.entry_to_json(Ok(entry), fields, *indent, py),
+                        Output::MsgPack { fields } => self.entry_to_msgpack(&entry, fields, py),
                         Output::CSV => self.entry_to_csv(Ok(entry), py),
+                        Output::Xml => self.entry_to_xml(&entry, py),
+                        Output::Timeline => {
+                            for event in timeline_events(&entry, &mut self.inner, &mut self.context) {
+                                self.pending
+                                    .push_back(PyMftTimelineEvent::from_event(py, event)?.to_object(py));
+                            }
+                            self.drop_resumed_results();
+
+                            self.current_record += 1;
+                            continue;
+                        }
+                        Output::Findings => {
+                            for finding in findings(&entry, &mut self.inner, &mut self.context) {
+                                self.pending
+                                    .push_back(PyMftFinding::from_finding(py, finding)?.to_object(py));
+                            }
+                            self.drop_resumed_results();
+
+                            self.current_record += 1;
+                            continue;
+                        }
+                        Output::TriageCsv => {
+                            let row = TriageMftEntry::from_entry(&entry, &self.context);
+                            self.row_to_csv(&row, py).unwrap_or_else(|e| e.to_object(py))
+                        }
+                        Output::L2tCsv => {
+                            for row in l2t_rows(&entry, &mut self.inner, &mut self.context) {
+                                let line = self.row_to_csv(&row, py)?;
+                                self.pending.push_back(line);
+                            }
+                            self.drop_resumed_results();
+
+                            self.current_record += 1;
+                            continue;
+                        }
                     };
 
                     Ok(Some(ret))
@@ -250,12 +1687,65 @@ impl PyMftEntriesIterator {
             return obj;
         }
     }
+
+    /// Drops the results of the first entry after resuming which were yielded before the state was saved.
+    fn drop_resumed_results(&mut self) {
+        if let Some(keep) = self.keep_results.take() {
+            let yielded = self.pending.len().saturating_sub(keep);
+            self.pending.drain(..yielded);
+        }
+    }
+
+    fn next_batch(&mut self, py: Python, n: usize) -> PyResult<Vec<PyObject>> {
+        let mut batch = Vec::with_capacity(n.min(4096));
+
+        while batch.len() < n {
+            match self.next_with_gil(py)? {
+                Some(obj) => batch.push(obj),
+                None => break,
+            }
+        }
+
+        Ok(batch)
+    }
+}
+
+#[pymethods]
+impl PyMftEntriesIterator {
+    /// tell(self, /)
+    /// --
+    ///
+    /// Returns the record number the iterator will continue from
+    /// (or the position in the results, for iterators over a selection of records).
+    /// Can be saved, and later passed as `start_from` to resume iteration.
+    fn tell(&self, py: Python) -> u64 {
+        self.lock(py).current_record
+    }
+
+    /// state(self, /)
+    /// --
+    ///
+    /// Returns a dict of the position, output format and options of the iterator, which can be saved (as JSON)
+    /// and passed to `PyMftParser.resume` to continue the iteration later.
+    /// Iterators with filters (`min_size`, `path_prefix`, `find`, ...) or in sorted order cannot be saved.
+    fn state(&self, py: Python) -> PyResult<PyObject> {
+        Checkpoint::from_state(&self.lock(py))?.to_dict(py)
+    }
+
+    /// next_batch(self, n, /)
+    /// --
+    ///
+    /// Returns a list of up to `n` results (as would be yielded by the iterator).
+    /// The list is empty once the iterator is exhausted.
+    fn next_batch(&self, py: Python, n: usize) -> PyResult<Vec<PyObject>> {
+        self.lock(py).next_batch(py, n)
+    }
 }
 
 #[pyproto]
 impl PyIterProtocol for PyMftParser {
     fn __iter__(mut slf: PyRefMut<Self>) -> PyResult<Py<PyMftEntriesIterator>> {
-        slf.entries()
+        slf.records_iterator(Output::Python, 0, vec![])
     }
     fn __next__(_slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
         Err(PyErr::new::<exceptions::PyNotImplementedError, _>("Using `next()` over `PyMftParser` is not supported. Try iterating over `PyMftParser(...).entries()`"))
@@ -264,11 +1754,14 @@ impl PyIterProtocol for PyMftParser {
 
 #[pyproto]
 impl PyIterProtocol for PyMftEntriesIterator {
-    fn __iter__(slf: PyRefMut<Self>) -> PyResult<Py<PyMftEntriesIterator>> {
+    fn __iter__(slf: PyRef<Self>) -> PyResult<Py<PyMftEntriesIterator>> {
         Ok(slf.into())
     }
-    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
-        slf.next()
+    fn __next__(slf: PyRef<Self>) -> PyResult<Option<PyObject>> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        slf.lock(py).next_with_gil(py)
     }
 }
 
@@ -279,12 +1772,25 @@ fn mft(py: Python, m: &PyModule) -> PyResult<()> {
     init_logging(py).ok();
 
     m.add_class::<PyMftParser>()?;
+    m.add_class::<PyMftBatch>()?;
+    m.add_class::<PyMftExportStats>()?;
+    m.add_function(wrap_pyfunction!(carve_entries, m)?)?;
+    m.add_function(wrap_pyfunction!(set_log_level, m)?)?;
+    m.add_function(wrap_pyfunction!(diff, m)?)?;
+    m.add_function(wrap_pyfunction!(main, m)?)?;
 
     // Entry
     m.add_class::<PyMftEntriesIterator>()?;
+    m.add_class::<PyMftAsyncEntriesIterator>()?;
     m.add_class::<PyMftEntry>()?;
+    m.add_class::<PyMftWalkIterator>()?;
+    m.add_class::<PyWslMetadata>()?;
+    m.add_class::<PyIndexEntry>()?;
+    m.add_class::<PyMftTimelineEvent>()?;
+    m.add_class::<PyMftFinding>()?;
 
     // Attributes
+    m.add("FileAttributes", file_attributes_enum(py)?)?;
     m.add_class::<PyMftAttribute>()?;
     m.add_class::<PyMftAttributesIter>()?;
     m.add_class::<PyMftAttributeX10>()?;
@@ -293,7 +1799,53 @@ fn mft(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<PyMftAttributeX40>()?;
     m.add_class::<PyMftAttributeX80>()?;
     m.add_class::<PyMftAttributeX90>()?;
+    m.add_class::<PyMftAttributeXD0>()?;
+    m.add_class::<PyMftAttributeXE0>()?;
+    m.add_class::<PyExtendedAttribute>()?;
     m.add_class::<PyMftAttributeOther>()?;
 
+    // Validation
+    m.add_class::<PyMftValidationReport>()?;
+    m.add_class::<PyMftIssue>()?;
+
+    // Diff
+    m.add_class::<PyMftDiff>()?;
+    m.add_class::<PyMftDiffIterator>()?;
+
+    // Carving
+    m.add_class::<PyCarvedEntriesIterator>()?;
+
+    // Volume Shadow Copies
+    m.add_class::<PyVssVolume>()?;
+    m.add_class::<PyShadowCopy>()?;
+
+    // $Bitmap
+    m.add_class::<PyVolumeBitmap>()?;
+    m.add_class::<PyClusterAllocation>()?;
+
+    // USN Journal
+    m.add_class::<PyUsnParser>()?;
+    m.add_class::<PyUsnRecord>()?;
+    m.add_class::<PyUsnRecordsIterator>()?;
+    m.add_class::<PyUsnEvent>()?;
+    m.add_class::<PyUsnEventsIterator>()?;
+
+    // $LogFile
+    m.add_class::<PyLogFileParser>()?;
+    m.add_class::<PyLogFileRestartArea>()?;
+    m.add_class::<PyLogFileRecord>()?;
+    m.add_class::<PyLogFileRecordsIterator>()?;
+
+    // $Secure
+    m.add_class::<PySecureParser>()?;
+    m.add_class::<PySecurityDescriptor>()?;
+    m.add_class::<PyAce>()?;
+
+    // Synthetic MFTs, as `mft.testing` (also importable with `import mft.testing`)
+    let testing = PyModule::new(py, "testing")?;
+    testing.add_class::<PyMftBuilder>()?;
+    m.add_submodule(testing)?;
+    py.import("sys")?.getattr("modules")?.set_item("mft.testing", testing)?;
+
     Ok(())
 }
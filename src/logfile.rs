@@ -0,0 +1,548 @@
+//! $LogFile support.
+//! Format reference: https://github.com/libyal/assorted/blob/main/documentation/NTFS%20Log%20File%20format.asciidoc
+use crate::entry::PyMftEntry;
+use crate::utils::{invalid_data, u16_at, u32_at, u64_at, FileOrFileLike};
+use crate::{PyMftParser, ReadSeek};
+
+use mft::entry::ZERO_HEADER;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use pyo3::PyIterProtocol;
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader, SeekFrom};
+
+const RESTART_SIGNATURE: &[u8; 4] = b"RSTR";
+const RECORD_SIGNATURE: &[u8; 4] = b"RCRD";
+
+const LOG_RECORD_HEADER_SIZE: usize = 0x30;
+const CLIENT_HEADER_SIZE: usize = 0x20;
+const MAX_CLIENT_DATA_SIZE: usize = 0x1_0000;
+
+/// The record spans over the next page(s).
+const RECORD_FLAG_MULTI_PAGE: u16 = 0x0001;
+
+/// Applies the update sequence array of a multi-sector structure in place.
+/// Returns false if any sector did not end with the update sequence number.
+fn apply_fixups(page: &mut [u8]) -> bool {
+    let usa_offset = u16_at(page, 4) as usize;
+    let usa_count = u16_at(page, 6) as usize;
+
+    if usa_count == 0 || usa_offset + usa_count * 2 > page.len() {
+        return false;
+    }
+
+    let update_sequence = [page[usa_offset], page[usa_offset + 1]];
+    let mut valid = true;
+
+    for stride in 1..usa_count {
+        let end = stride * 512;
+        if end > page.len() {
+            return false;
+        }
+
+        if page[end - 2..end] != update_sequence {
+            valid = false;
+        }
+
+        let fixup = usa_offset + stride * 2;
+        page[end - 2] = page[fixup];
+        page[end - 1] = page[fixup + 1];
+    }
+
+    valid
+}
+
+/// Names of the NTFS log operation codes.
+pub fn operation_name(code: u16) -> &'static str {
+    match code {
+        0x00 => "Noop",
+        0x01 => "CompensationLogRecord",
+        0x02 => "InitializeFileRecordSegment",
+        0x03 => "DeallocateFileRecordSegment",
+        0x04 => "WriteEndOfFileRecordSegment",
+        0x05 => "CreateAttribute",
+        0x06 => "DeleteAttribute",
+        0x07 => "UpdateResidentValue",
+        0x08 => "UpdateNonresidentValue",
+        0x09 => "UpdateMappingPairs",
+        0x0A => "DeleteDirtyClusters",
+        0x0B => "SetNewAttributeSizes",
+        0x0C => "AddIndexEntryRoot",
+        0x0D => "DeleteIndexEntryRoot",
+        0x0E => "AddIndexEntryAllocation",
+        0x0F => "DeleteIndexEntryAllocation",
+        0x10 => "WriteEndOfIndexBuffer",
+        0x11 => "SetIndexEntryVcnRoot",
+        0x12 => "SetIndexEntryVcnAllocation",
+        0x13 => "UpdateFileNameRoot",
+        0x14 => "UpdateFileNameAllocation",
+        0x15 => "SetBitsInNonresidentBitMap",
+        0x16 => "ClearBitsInNonresidentBitMap",
+        0x17 => "HotFix",
+        0x18 => "EndTopLevelAction",
+        0x19 => "PrepareTransaction",
+        0x1A => "CommitTransaction",
+        0x1B => "ForgetTransaction",
+        0x1C => "OpenNonresidentAttribute",
+        0x1D => "OpenAttributeTableDump",
+        0x1E => "AttributeNamesDump",
+        0x1F => "DirtyPageTableDump",
+        0x20 => "TransactionTableDump",
+        0x21 => "UpdateRecordDataRoot",
+        0x22 => "UpdateRecordDataAllocation",
+        _ => "Unknown",
+    }
+}
+
+/// The restart area, as found in the first valid restart page.
+#[derive(Debug, Clone)]
+pub struct RestartArea {
+    pub chkdsk_lsn: u64,
+    pub system_page_size: u32,
+    pub log_page_size: u32,
+    pub major_version: i16,
+    pub minor_version: i16,
+    pub current_lsn: u64,
+    pub log_clients: u16,
+    pub flags: u16,
+    pub seq_number_bits: u32,
+    pub file_size: u64,
+    pub log_page_data_offset: u16,
+}
+
+impl RestartArea {
+    fn from_page(page: &[u8]) -> io::Result<RestartArea> {
+        if &page[..4] != RESTART_SIGNATURE {
+            return Err(invalid_data("invalid restart page signature"));
+        }
+
+        let area = u16_at(page, 24) as usize;
+        if area + 0x28 > page.len() {
+            return Err(invalid_data("restart area is out of bounds"));
+        }
+
+        Ok(RestartArea {
+            chkdsk_lsn: u64_at(page, 8),
+            system_page_size: u32_at(page, 16),
+            log_page_size: u32_at(page, 20),
+            minor_version: u16_at(page, 26) as i16,
+            major_version: u16_at(page, 28) as i16,
+            current_lsn: u64_at(page, area),
+            log_clients: u16_at(page, area + 8),
+            flags: u16_at(page, area + 14),
+            seq_number_bits: u32_at(page, area + 16),
+            file_size: u64_at(page, area + 24),
+            log_page_data_offset: u16_at(page, area + 38),
+        })
+    }
+}
+
+/// A single client (NTFS) log record.
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    pub lsn: u64,
+    pub previous_lsn: u64,
+    pub undo_next_lsn: u64,
+    pub record_type: u32,
+    pub transaction_id: u32,
+    pub flags: u16,
+    pub redo_operation: u16,
+    pub undo_operation: u16,
+    pub target_attribute: u16,
+    pub lcns_to_follow: u16,
+    pub record_offset: u16,
+    pub attribute_offset: u16,
+    pub cluster_block_offset: u16,
+    pub target_vcn: u64,
+    pub lcns: Vec<u64>,
+    pub redo_data: Vec<u8>,
+    pub undo_data: Vec<u8>,
+    /// Offset of the page in which the record starts.
+    pub page_offset: u64,
+}
+
+impl LogRecord {
+    fn from_buffer(buf: &[u8], page_offset: u64) -> Option<LogRecord> {
+        let client = buf.get(LOG_RECORD_HEADER_SIZE..)?;
+        if client.len() < CLIENT_HEADER_SIZE {
+            return None;
+        }
+
+        let redo_offset = u16_at(client, 4) as usize;
+        let redo_length = u16_at(client, 6) as usize;
+        let undo_offset = u16_at(client, 8) as usize;
+        let undo_length = u16_at(client, 10) as usize;
+        let lcns_to_follow = u16_at(client, 14);
+
+        let lcns = (0..lcns_to_follow as usize)
+            .map(|i| client.get(32 + i * 8..40 + i * 8).map(|b| u64_at(b, 0)))
+            .collect::<Option<Vec<u64>>>()?;
+
+        Some(LogRecord {
+            lsn: u64_at(buf, 0),
+            previous_lsn: u64_at(buf, 8),
+            undo_next_lsn: u64_at(buf, 16),
+            record_type: u32_at(buf, 32),
+            transaction_id: u32_at(buf, 36),
+            flags: u16_at(buf, 40),
+            redo_operation: u16_at(client, 0),
+            undo_operation: u16_at(client, 2),
+            target_attribute: u16_at(client, 12),
+            lcns_to_follow,
+            record_offset: u16_at(client, 16),
+            attribute_offset: u16_at(client, 18),
+            cluster_block_offset: u16_at(client, 20),
+            target_vcn: u64_at(client, 24),
+            lcns,
+            redo_data: client.get(redo_offset..redo_offset + redo_length)?.to_vec(),
+            undo_data: client.get(undo_offset..undo_offset + undo_length)?.to_vec(),
+            page_offset,
+        })
+    }
+}
+
+/// Reads the restart area and all the log records in the record pages of a $LogFile.
+pub struct LogFile {
+    pub restart_area: RestartArea,
+    pub records: Vec<LogRecord>,
+}
+
+impl LogFile {
+    pub fn from_reader<R: ReadSeek>(mut reader: R) -> io::Result<LogFile> {
+        let size = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+
+        let mut first_page = vec![0_u8; 4096];
+        reader.read_exact(&mut first_page)?;
+        apply_fixups(&mut first_page);
+
+        let restart_area = RestartArea::from_page(&first_page)?;
+        let page_size = u64::from(restart_area.log_page_size);
+        let system_page_size = u64::from(restart_area.system_page_size);
+
+        let data_offset = match restart_area.log_page_data_offset as usize {
+            0 => 0x40,
+            offset => offset,
+        };
+
+        if !(512..=0x1_0000).contains(&page_size) || system_page_size < 512 {
+            return Err(invalid_data("restart area has an invalid page size"));
+        }
+        if data_offset as u64 >= page_size {
+            return Err(invalid_data("restart area has a data offset beyond the page size"));
+        }
+
+        // Pages come after the two restart pages, data is read into one buffer per page.
+        let mut pages = vec![];
+        let mut offset = system_page_size * 2;
+
+        while offset + page_size <= size {
+            let mut page = vec![0_u8; page_size as usize];
+            reader.seek(SeekFrom::Start(offset))?;
+            reader.read_exact(&mut page)?;
+
+            if &page[..4] == RECORD_SIGNATURE && apply_fixups(&mut page) {
+                pages.push((offset, page));
+            }
+
+            offset += page_size;
+        }
+
+        let mut records = vec![];
+        let mut seen = HashSet::new();
+        // Index of the page where the last spanning record ended, and how many of its bytes that record took.
+        let mut resume = (0, 0);
+
+        for (index, (page_offset, page)) in pages.iter().enumerate() {
+            let mut position = match resume {
+                (resume_index, _) if index < resume_index => continue,
+                (resume_index, carried) if index == resume_index => data_offset + carried,
+                _ => data_offset,
+            };
+
+            while position + LOG_RECORD_HEADER_SIZE <= page.len() {
+                let header = &page[position..position + LOG_RECORD_HEADER_SIZE];
+                let lsn = u64_at(header, 0);
+                let client_data_length = u32_at(header, 24) as usize;
+                let record_type = u32_at(header, 32);
+
+                if lsn == 0
+                    || !(1..=2).contains(&record_type)
+                    || client_data_length > MAX_CLIENT_DATA_SIZE
+                {
+                    break;
+                }
+
+                let total = LOG_RECORD_HEADER_SIZE + client_data_length;
+                let mut buf = page[position..page.len().min(position + total)].to_vec();
+
+                // Collect the remainder of the record from the data area of the following pages.
+                let mut next = index + 1;
+                while buf.len() < total && u16_at(header, 40) & RECORD_FLAG_MULTI_PAGE != 0 {
+                    match pages.get(next) {
+                        Some((_, next_page)) => {
+                            let needed = total - buf.len();
+                            let available = &next_page[data_offset..];
+                            let take = needed.min(available.len());
+                            buf.extend_from_slice(&available[..take]);
+                            resume = (next, (take + 7) & !7);
+                        }
+                        None => break,
+                    }
+                    next += 1;
+                }
+
+                if buf.len() < total {
+                    break;
+                }
+
+                if seen.insert(lsn) {
+                    if let Some(record) = LogRecord::from_buffer(&buf, *page_offset) {
+                        records.push(record);
+                    }
+                }
+
+                if position + total > page.len() {
+                    break;
+                }
+
+                // Records are aligned to 8 bytes.
+                position += (total + 7) & !7;
+            }
+        }
+
+        records.sort_by_key(|r| r.lsn);
+
+        Ok(LogFile {
+            restart_area,
+            records,
+        })
+    }
+}
+
+#[pyclass]
+/// PyLogFileParser(self, path_or_file_like, /)
+/// --
+///
+/// Returns an instance of the parser for an extracted $LogFile.
+/// Works on both a path (string), or a file-like object.
+pub struct PyLogFileParser {
+    inner: LogFile,
+}
+
+#[pymethods]
+impl PyLogFileParser {
+    #[new]
+    fn new(path_or_file_like: PyObject) -> PyResult<Self> {
+        let inner = match FileOrFileLike::from_pyobject(path_or_file_like)? {
+            FileOrFileLike::File(s) => {
+                LogFile::from_reader(BufReader::with_capacity(1 << 16, File::open(s)?))?
+            }
+            FileOrFileLike::FileLike(f) => LogFile::from_reader(f)?,
+            FileOrFileLike::Buffer(b) => LogFile::from_reader(b)?,
+        };
+
+        Ok(PyLogFileParser { inner })
+    }
+
+    /// restart_area(self, /)
+    /// --
+    ///
+    /// Returns the restart area of the log.
+    fn restart_area(&self) -> PyResult<PyLogFileRestartArea> {
+        let area = &self.inner.restart_area;
+
+        Ok(PyLogFileRestartArea {
+            chkdsk_lsn: area.chkdsk_lsn,
+            system_page_size: area.system_page_size,
+            log_page_size: area.log_page_size,
+            major_version: area.major_version,
+            minor_version: area.minor_version,
+            current_lsn: area.current_lsn,
+            log_clients: area.log_clients,
+            flags: area.flags,
+            seq_number_bits: area.seq_number_bits,
+            file_size: area.file_size,
+        })
+    }
+
+    /// records(self, /)
+    /// --
+    ///
+    /// Returns an iterator that yields the log records ordered by LSN.
+    fn records(&self) -> PyResult<Py<PyLogFileRecordsIterator>> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        Py::new(
+            py,
+            PyLogFileRecordsIterator {
+                inner: Box::new(self.inner.records.clone().into_iter()),
+            },
+        )
+    }
+
+    /// correlate(self, parser, /)
+    /// --
+    ///
+    /// Returns a list of `(PyMftEntry, PyLogFileRecord)` tuples, for every MFT entry whose
+    /// $LogFile sequence number matches a record in the log.
+    fn correlate(&self, parser: &mut PyMftParser) -> PyResult<Vec<(PyObject, PyObject)>> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let by_lsn: HashMap<u64, &LogRecord> =
+            self.inner.records.iter().map(|r| (r.lsn, r)).collect();
+
+        let context = parser.entry_context();
+        let inner = parser.inner_mut();
+        let mut correlated = vec![];
+
+        for i in 0..inner.get_entry_count() {
+            let entry = match inner.read_entry(i) {
+                Ok(entry) if &entry.header.signature != ZERO_HEADER => entry,
+                _ => continue,
+            };
+
+            if let Some(record) = by_lsn.get(&entry.header.metadata_transaction_journal) {
+                let record = PyLogFileRecord::from_record(py, record)?.to_object(py);
+                let entry = PyMftEntry::from_mft_entry(py, entry, &context)?.to_object(py);
+                correlated.push((entry, record));
+            }
+        }
+
+        Ok(correlated)
+    }
+}
+
+#[pyclass]
+pub struct PyLogFileRestartArea {
+    #[pyo3(get)]
+    pub chkdsk_lsn: u64,
+    #[pyo3(get)]
+    pub system_page_size: u32,
+    #[pyo3(get)]
+    pub log_page_size: u32,
+    #[pyo3(get)]
+    pub major_version: i16,
+    #[pyo3(get)]
+    pub minor_version: i16,
+    /// The LSN of the last record written to the log
+    #[pyo3(get)]
+    pub current_lsn: u64,
+    #[pyo3(get)]
+    pub log_clients: u16,
+    #[pyo3(get)]
+    pub flags: u16,
+    #[pyo3(get)]
+    pub seq_number_bits: u32,
+    #[pyo3(get)]
+    pub file_size: u64,
+}
+
+#[pyclass]
+pub struct PyLogFileRecord {
+    inner: LogRecord,
+    #[pyo3(get)]
+    pub lsn: u64,
+    #[pyo3(get)]
+    pub previous_lsn: u64,
+    #[pyo3(get)]
+    pub undo_next_lsn: u64,
+    #[pyo3(get)]
+    pub record_type: u32,
+    #[pyo3(get)]
+    pub transaction_id: u32,
+    #[pyo3(get)]
+    pub flags: u16,
+    #[pyo3(get)]
+    pub redo_op_code: u16,
+    #[pyo3(get)]
+    pub undo_op_code: u16,
+    /// Name of the redo operation
+    #[pyo3(get)]
+    pub redo_operation: String,
+    /// Name of the undo operation
+    #[pyo3(get)]
+    pub undo_operation: String,
+    #[pyo3(get)]
+    pub target_attribute: u16,
+    #[pyo3(get)]
+    pub record_offset: u16,
+    #[pyo3(get)]
+    pub attribute_offset: u16,
+    #[pyo3(get)]
+    pub cluster_block_offset: u16,
+    #[pyo3(get)]
+    pub target_vcn: u64,
+    #[pyo3(get)]
+    pub lcns: Vec<u64>,
+    /// Offset of the page in which the record starts
+    #[pyo3(get)]
+    pub page_offset: u64,
+}
+
+impl PyLogFileRecord {
+    pub fn from_record(py: Python, record: &LogRecord) -> PyResult<Py<Self>> {
+        Py::new(
+            py,
+            PyLogFileRecord {
+                lsn: record.lsn,
+                previous_lsn: record.previous_lsn,
+                undo_next_lsn: record.undo_next_lsn,
+                record_type: record.record_type,
+                transaction_id: record.transaction_id,
+                flags: record.flags,
+                redo_op_code: record.redo_operation,
+                undo_op_code: record.undo_operation,
+                redo_operation: operation_name(record.redo_operation).to_string(),
+                undo_operation: operation_name(record.undo_operation).to_string(),
+                target_attribute: record.target_attribute,
+                record_offset: record.record_offset,
+                attribute_offset: record.attribute_offset,
+                cluster_block_offset: record.cluster_block_offset,
+                target_vcn: record.target_vcn,
+                lcns: record.lcns.clone(),
+                page_offset: record.page_offset,
+                inner: record.clone(),
+            },
+        )
+    }
+}
+
+#[pymethods]
+impl PyLogFileRecord {
+    #[getter]
+    pub fn redo_data(&self, py: Python) -> PyObject {
+        PyBytes::new(py, &self.inner.redo_data).to_object(py)
+    }
+
+    #[getter]
+    pub fn undo_data(&self, py: Python) -> PyObject {
+        PyBytes::new(py, &self.inner.undo_data).to_object(py)
+    }
+}
+
+#[pyclass]
+pub struct PyLogFileRecordsIterator {
+    inner: Box<dyn Iterator<Item = LogRecord> + Send>,
+}
+
+#[pyproto]
+impl PyIterProtocol for PyLogFileRecordsIterator {
+    fn __iter__(slf: PyRefMut<Self>) -> PyResult<Py<PyLogFileRecordsIterator>> {
+        Ok(slf.into())
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        match slf.inner.next() {
+            Some(record) => Ok(Some(PyLogFileRecord::from_record(py, &record)?.to_object(py))),
+            None => Ok(None),
+        }
+    }
+}
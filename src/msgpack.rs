@@ -0,0 +1,98 @@
+//! MessagePack encoding (https://github.com/msgpack/msgpack/blob/master/spec.md) of serialized entries.
+//! Values are encoded with their smallest representation, like most encoders do.
+use serde_json::Value;
+
+pub fn to_msgpack(value: &Value) -> Vec<u8> {
+    let mut buf = vec![];
+    write_value(&mut buf, value);
+    buf
+}
+
+fn write_length(buf: &mut Vec<u8>, length: usize, fix: u8, fix_max: usize, markers: [u8; 3]) {
+    if length <= fix_max {
+        buf.push(fix | length as u8);
+    } else if length <= u8::MAX as usize && markers[0] != 0 {
+        buf.push(markers[0]);
+        buf.push(length as u8);
+    } else if length <= u16::MAX as usize {
+        buf.push(markers[1]);
+        buf.extend_from_slice(&(length as u16).to_be_bytes());
+    } else {
+        buf.push(markers[2]);
+        buf.extend_from_slice(&(length as u32).to_be_bytes());
+    }
+}
+
+fn write_uint(buf: &mut Vec<u8>, v: u64) {
+    if v < 0x80 {
+        buf.push(v as u8);
+    } else if v <= u64::from(u8::MAX) {
+        buf.push(0xcc);
+        buf.push(v as u8);
+    } else if v <= u64::from(u16::MAX) {
+        buf.push(0xcd);
+        buf.extend_from_slice(&(v as u16).to_be_bytes());
+    } else if v <= u64::from(u32::MAX) {
+        buf.push(0xce);
+        buf.extend_from_slice(&(v as u32).to_be_bytes());
+    } else {
+        buf.push(0xcf);
+        buf.extend_from_slice(&v.to_be_bytes());
+    }
+}
+
+fn write_int(buf: &mut Vec<u8>, v: i64) {
+    if v >= 0 {
+        write_uint(buf, v as u64);
+    } else if v >= -32 {
+        buf.push(v as u8);
+    } else if v >= i64::from(i8::MIN) {
+        buf.push(0xd0);
+        buf.push(v as u8);
+    } else if v >= i64::from(i16::MIN) {
+        buf.push(0xd1);
+        buf.extend_from_slice(&(v as i16).to_be_bytes());
+    } else if v >= i64::from(i32::MIN) {
+        buf.push(0xd2);
+        buf.extend_from_slice(&(v as i32).to_be_bytes());
+    } else {
+        buf.push(0xd3);
+        buf.extend_from_slice(&v.to_be_bytes());
+    }
+}
+
+fn write_str(buf: &mut Vec<u8>, s: &str) {
+    write_length(buf, s.len(), 0xa0, 31, [0xd9, 0xda, 0xdb]);
+    buf.extend_from_slice(s.as_bytes());
+}
+
+fn write_value(buf: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => buf.push(0xc0),
+        Value::Bool(false) => buf.push(0xc2),
+        Value::Bool(true) => buf.push(0xc3),
+        Value::Number(n) => {
+            if let Some(v) = n.as_u64() {
+                write_uint(buf, v);
+            } else if let Some(v) = n.as_i64() {
+                write_int(buf, v);
+            } else {
+                buf.push(0xcb);
+                buf.extend_from_slice(&n.as_f64().unwrap_or(f64::NAN).to_be_bytes());
+            }
+        }
+        Value::String(s) => write_str(buf, s),
+        Value::Array(values) => {
+            // Arrays and maps have no 8 bit length.
+            write_length(buf, values.len(), 0x90, 15, [0, 0xdc, 0xdd]);
+            values.iter().for_each(|v| write_value(buf, v));
+        }
+        Value::Object(object) => {
+            write_length(buf, object.len(), 0x80, 15, [0, 0xde, 0xdf]);
+            for (key, value) in object {
+                write_str(buf, key);
+                write_value(buf, value);
+            }
+        }
+    }
+}
@@ -0,0 +1,359 @@
+//! Encoding of flat entries as a Parquet file (https://parquet.apache.org/docs/file-format/).
+//! A file is a magic number, followed by row groups of column chunks, and the metadata of the file in a footer.
+//! Every column chunk is a single uncompressed data page of PLAIN encoded values.
+//! Metadata is encoded with the Thrift compact protocol, which is written by hand,
+//! since only a handful of structs are needed.
+use crate::arrow::{bitmap, DataType, Field, Value};
+
+/// Starts and ends every file.
+pub const MAGIC: &[u8] = b"PAR1";
+
+// `Type` enum.
+const TYPE_BOOLEAN: i32 = 0;
+const TYPE_INT32: i32 = 1;
+const TYPE_INT64: i32 = 2;
+const TYPE_BYTE_ARRAY: i32 = 6;
+
+// `ConvertedType` enum, for readers which do not support logical types.
+const CONVERTED_UTF8: i32 = 0;
+const CONVERTED_TIMESTAMP_MICROS: i32 = 10;
+const CONVERTED_UINT_16: i32 = 12;
+const CONVERTED_UINT_32: i32 = 13;
+const CONVERTED_UINT_64: i32 = 14;
+
+// `LogicalType` union.
+const LOGICAL_STRING: i16 = 1;
+const LOGICAL_TIMESTAMP: i16 = 8;
+const LOGICAL_INTEGER: i16 = 10;
+
+/// `TimeUnit.MICROS`
+const TIME_UNIT_MICROS: i16 = 2;
+
+// `FieldRepetitionType` enum.
+const REQUIRED: i32 = 0;
+const OPTIONAL: i32 = 1;
+
+// `Encoding` enum.
+const ENCODING_PLAIN: i32 = 0;
+const ENCODING_RLE: i32 = 3;
+
+/// `PageType.DATA_PAGE`
+const DATA_PAGE: i32 = 0;
+
+/// `CompressionCodec.UNCOMPRESSED`
+const UNCOMPRESSED: i32 = 0;
+
+// Types of the compact protocol.
+const COMPACT_TRUE: u8 = 1;
+const COMPACT_FALSE: u8 = 2;
+const COMPACT_BYTE: u8 = 3;
+const COMPACT_I32: u8 = 5;
+const COMPACT_I64: u8 = 6;
+const COMPACT_BINARY: u8 = 8;
+const COMPACT_LIST: u8 = 9;
+const COMPACT_STRUCT: u8 = 12;
+
+/// A value of the Thrift compact protocol.
+enum Thrift {
+    Bool(bool),
+    I8(i8),
+    I32(i32),
+    I64(i64),
+    Binary(String),
+    /// Elements of the same type (given, since a list may be empty).
+    List(u8, Vec<Thrift>),
+    /// The fields of a struct, by increasing field id. Absent fields are not written.
+    Struct(Vec<(i16, Thrift)>),
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+fn zigzag(v: i64) -> u64 {
+    ((v << 1) ^ (v >> 63)) as u64
+}
+
+impl Thrift {
+    fn compact_type(&self) -> u8 {
+        match self {
+            Thrift::Bool(true) => COMPACT_TRUE,
+            Thrift::Bool(false) => COMPACT_FALSE,
+            Thrift::I8(_) => COMPACT_BYTE,
+            Thrift::I32(_) => COMPACT_I32,
+            Thrift::I64(_) => COMPACT_I64,
+            Thrift::Binary(_) => COMPACT_BINARY,
+            Thrift::List(..) => COMPACT_LIST,
+            Thrift::Struct(_) => COMPACT_STRUCT,
+        }
+    }
+
+    fn write(&self, buf: &mut Vec<u8>) {
+        match self {
+            // Booleans are only written on their own in lists, the header of a field holds its value.
+            Thrift::Bool(v) => buf.push(if *v { COMPACT_TRUE } else { COMPACT_FALSE }),
+            Thrift::I8(v) => buf.push(*v as u8),
+            Thrift::I32(v) => write_varint(buf, zigzag(i64::from(*v))),
+            Thrift::I64(v) => write_varint(buf, zigzag(*v)),
+            Thrift::Binary(s) => {
+                write_varint(buf, s.len() as u64);
+                buf.extend_from_slice(s.as_bytes());
+            }
+            Thrift::List(element_type, elements) => {
+                if elements.len() < 15 {
+                    buf.push((elements.len() as u8) << 4 | element_type);
+                } else {
+                    buf.push(0xf0 | element_type);
+                    write_varint(buf, elements.len() as u64);
+                }
+                for element in elements {
+                    element.write(buf);
+                }
+            }
+            Thrift::Struct(fields) => {
+                let mut last_id = 0;
+                for (id, value) in fields {
+                    // Field ids are written as a delta from the previous field when it fits in the header.
+                    match id - last_id {
+                        delta @ 1..=15 => buf.push((delta as u8) << 4 | value.compact_type()),
+                        _ => {
+                            buf.push(value.compact_type());
+                            write_varint(buf, zigzag(i64::from(*id)));
+                        }
+                    }
+                    if !matches!(value, Thrift::Bool(_)) {
+                        value.write(buf);
+                    }
+                    last_id = *id;
+                }
+                buf.push(0);
+            }
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        self.write(&mut buf);
+        buf
+    }
+}
+
+/// A column chunk of a row group, at an offset relative to the start of the row group.
+#[derive(Debug)]
+pub struct ColumnChunk {
+    offset: u64,
+    size: u64,
+    /// Including nulls.
+    values: u64,
+}
+
+/// The layout of an encoded row group, which is needed for the footer.
+#[derive(Debug)]
+pub struct RowGroup {
+    rows: u64,
+    size: u64,
+    columns: Vec<ColumnChunk>,
+}
+
+impl Field {
+    fn physical_type(&self) -> i32 {
+        match self.data_type {
+            DataType::Bool => TYPE_BOOLEAN,
+            DataType::UInt16 | DataType::UInt32 => TYPE_INT32,
+            DataType::UInt64 | DataType::Timestamp => TYPE_INT64,
+            DataType::Utf8 => TYPE_BYTE_ARRAY,
+        }
+    }
+
+    fn schema_element(&self) -> Thrift {
+        let integer = |bits: i8| {
+            Thrift::Struct(vec![(
+                LOGICAL_INTEGER,
+                Thrift::Struct(vec![(1, Thrift::I8(bits)), (2, Thrift::Bool(false))]),
+            )])
+        };
+        let types = match self.data_type {
+            DataType::Bool => None,
+            DataType::UInt16 => Some((CONVERTED_UINT_16, integer(16))),
+            DataType::UInt32 => Some((CONVERTED_UINT_32, integer(32))),
+            DataType::UInt64 => Some((CONVERTED_UINT_64, integer(64))),
+            DataType::Utf8 => Some((
+                CONVERTED_UTF8,
+                Thrift::Struct(vec![(LOGICAL_STRING, Thrift::Struct(vec![]))]),
+            )),
+            DataType::Timestamp => Some((
+                CONVERTED_TIMESTAMP_MICROS,
+                Thrift::Struct(vec![(
+                    LOGICAL_TIMESTAMP,
+                    Thrift::Struct(vec![
+                        // Adjusted to UTC.
+                        (1, Thrift::Bool(true)),
+                        (2, Thrift::Struct(vec![(TIME_UNIT_MICROS, Thrift::Struct(vec![]))])),
+                    ]),
+                )]),
+            )),
+        };
+
+        let mut fields = vec![
+            (1, Thrift::I32(self.physical_type())),
+            (3, Thrift::I32(if self.nullable { OPTIONAL } else { REQUIRED })),
+            (4, Thrift::Binary(self.name.to_string())),
+        ];
+        if let Some((converted_type, logical_type)) = types {
+            fields.push((6, Thrift::I32(converted_type)));
+            fields.push((10, logical_type));
+        }
+        Thrift::Struct(fields)
+    }
+}
+
+/// The definition levels of an optional column (1 for values, 0 for nulls),
+/// as runs of the RLE/bit-packing hybrid encoding, preceded by their length.
+fn definition_levels(defined: &[bool]) -> Vec<u8> {
+    let mut runs = vec![];
+    for run in defined.chunk_by(|a, b| a == b) {
+        write_varint(&mut runs, (run.len() as u64) << 1);
+        runs.push(u8::from(run[0]));
+    }
+
+    let mut levels = (runs.len() as u32).to_le_bytes().to_vec();
+    levels.extend_from_slice(&runs);
+    levels
+}
+
+/// The PLAIN encoding of the values of a column, without its nulls.
+fn plain_values(data_type: DataType, values: &[&Value]) -> Vec<u8> {
+    if data_type == DataType::Bool {
+        return bitmap(values.iter().map(|v| matches!(v, Value::Bool(true))));
+    }
+
+    let mut data = vec![];
+    for value in values {
+        match (data_type, value) {
+            (DataType::UInt16 | DataType::UInt32, Value::UInt(v)) => data.extend_from_slice(&(*v as u32).to_le_bytes()),
+            (_, Value::UInt(v)) => data.extend_from_slice(&v.to_le_bytes()),
+            (_, Value::Timestamp(v)) => data.extend_from_slice(&v.to_le_bytes()),
+            (_, Value::Utf8(s)) => {
+                data.extend_from_slice(&(s.len() as u32).to_le_bytes());
+                data.extend_from_slice(s.as_bytes());
+            }
+            (_, Value::Bool(_)) => {}
+        }
+    }
+    data
+}
+
+/// Encodes rows (whose values match the types of the schema) as a row group,
+/// and returns it along with its layout.
+pub fn row_group(schema: &[Field], rows: &[Vec<Option<Value>>]) -> (Vec<u8>, RowGroup) {
+    let mut data = vec![];
+    let mut columns = vec![];
+
+    for (column, field) in schema.iter().enumerate() {
+        let values: Vec<Option<&Value>> = rows.iter().map(|row| row[column].as_ref()).collect();
+
+        let mut page = vec![];
+        if field.nullable {
+            let defined: Vec<bool> = values.iter().map(Option::is_some).collect();
+            page.extend_from_slice(&definition_levels(&defined));
+        }
+        let present: Vec<&Value> = values.iter().flatten().copied().collect();
+        page.extend_from_slice(&plain_values(field.data_type, &present));
+
+        let header = Thrift::Struct(vec![
+            (1, Thrift::I32(DATA_PAGE)),
+            // Uncompressed and compressed sizes.
+            (2, Thrift::I32(page.len() as i32)),
+            (3, Thrift::I32(page.len() as i32)),
+            (
+                5,
+                Thrift::Struct(vec![
+                    (1, Thrift::I32(rows.len() as i32)),
+                    (2, Thrift::I32(ENCODING_PLAIN)),
+                    (3, Thrift::I32(ENCODING_RLE)),
+                    (4, Thrift::I32(ENCODING_RLE)),
+                ]),
+            ),
+        ]);
+
+        let offset = data.len() as u64;
+        data.extend_from_slice(&header.to_bytes());
+        data.extend_from_slice(&page);
+        columns.push(ColumnChunk {
+            offset,
+            size: data.len() as u64 - offset,
+            values: rows.len() as u64,
+        });
+    }
+
+    let row_group = RowGroup {
+        rows: rows.len() as u64,
+        size: data.len() as u64,
+        columns,
+    };
+    (data, row_group)
+}
+
+/// The end of a file: its metadata, the length of the metadata, and the magic number.
+/// `row_groups` are the row groups of the file, with the position at which they were written.
+pub fn footer(schema: &[Field], row_groups: &[(u64, RowGroup)]) -> Vec<u8> {
+    let root = Thrift::Struct(vec![
+        (4, Thrift::Binary("schema".to_string())),
+        (5, Thrift::I32(schema.len() as i32)),
+    ]);
+    let elements = std::iter::once(root).chain(schema.iter().map(Field::schema_element)).collect();
+    let rows: u64 = row_groups.iter().map(|(_, row_group)| row_group.rows).sum();
+
+    let row_groups: Vec<Thrift> = row_groups
+        .iter()
+        .map(|(position, row_group)| {
+            let columns = schema
+                .iter()
+                .zip(&row_group.columns)
+                .map(|(field, column)| {
+                    let offset = (position + column.offset) as i64;
+                    let metadata = Thrift::Struct(vec![
+                        (1, Thrift::I32(field.physical_type())),
+                        (
+                            2,
+                            Thrift::List(COMPACT_I32, vec![Thrift::I32(ENCODING_PLAIN), Thrift::I32(ENCODING_RLE)]),
+                        ),
+                        (3, Thrift::List(COMPACT_BINARY, vec![Thrift::Binary(field.name.to_string())])),
+                        (4, Thrift::I32(UNCOMPRESSED)),
+                        (5, Thrift::I64(column.values as i64)),
+                        // Uncompressed and compressed sizes.
+                        (6, Thrift::I64(column.size as i64)),
+                        (7, Thrift::I64(column.size as i64)),
+                        (9, Thrift::I64(offset)),
+                    ]);
+                    Thrift::Struct(vec![(2, Thrift::I64(offset)), (3, metadata)])
+                })
+                .collect();
+
+            Thrift::Struct(vec![
+                (1, Thrift::List(COMPACT_STRUCT, columns)),
+                (2, Thrift::I64(row_group.size as i64)),
+                (3, Thrift::I64(row_group.rows as i64)),
+            ])
+        })
+        .collect();
+
+    let metadata = Thrift::Struct(vec![
+        (1, Thrift::I32(1)),
+        (2, Thrift::List(COMPACT_STRUCT, elements)),
+        (3, Thrift::I64(rows as i64)),
+        (4, Thrift::List(COMPACT_STRUCT, row_groups)),
+        (6, Thrift::Binary(format!("pymft-rs version {}", env!("CARGO_PKG_VERSION")))),
+    ])
+    .to_bytes();
+
+    let mut footer = metadata;
+    let length = footer.len() as u32;
+    footer.extend_from_slice(&length.to_le_bytes());
+    footer.extend_from_slice(MAGIC);
+    footer
+}
@@ -0,0 +1,269 @@
+//! A parser over the records of an MFT.
+//! Unlike `mft::MftParser`, the record size can be given explicitly, or is detected from the first valid header
+//! (so that a zeroed first record does not prevent parsing).
+use crate::utils::{u16_at, u32_at, with_warnings};
+use crate::ReadSeek;
+
+use mft::entry::{BAAD_HEADER, FILE_HEADER, ZERO_HEADER};
+use mft::err::{Error, Result};
+use lru::LruCache;
+use mft::MftEntry;
+
+use std::io::{self, SeekFrom};
+
+/// Record sizes used by NTFS (512 byte and 4Kn sector disks).
+pub const ENTRY_SIZES: [u32; 2] = [1024, 4096];
+
+/// How many records are looked at to find a valid header.
+const MAX_DETECTION_RECORDS: u64 = 64;
+
+/// How much data is read at once when only the record signatures are needed.
+const SIGNATURES_CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// Number of records by signature.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SignatureCounts {
+    pub file: u64,
+    pub baad: u64,
+    pub zeroed: u64,
+    /// Records with any other signature.
+    pub unknown: u64,
+}
+
+pub struct MftParser<T: ReadSeek> {
+    data: T,
+    entry_size: u32,
+    size: u64,
+    /// The records most recently looked up by `get_entry`, if enabled.
+    cache: Option<LruCache<u64, MftEntry>>,
+    /// Whether warnings about the records (e.g. fixup mismatches) are logged regardless of the log level.
+    verbose_warnings: Option<bool>,
+}
+
+impl<T: ReadSeek> MftParser<T> {
+    pub fn from_read_seek(mut data: T, size: Option<u64>, entry_size: Option<u32>) -> Result<Self> {
+        let size = match size {
+            Some(sz) => sz,
+            None => data.seek(SeekFrom::End(0))?,
+        };
+
+        let entry_size = match entry_size {
+            Some(entry_size) if ENTRY_SIZES.contains(&entry_size) => entry_size,
+            Some(entry_size) => {
+                return Err(Error::from(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("entry size must be one of {:?}, got {}", ENTRY_SIZES, entry_size),
+                )))
+            }
+            None => detect_entry_size(&mut data, size)?,
+        };
+
+        data.seek(SeekFrom::Start(0))?;
+
+        Ok(MftParser {
+            data,
+            entry_size,
+            size,
+            cache: None,
+            verbose_warnings: None,
+        })
+    }
+
+    /// Creates a parser over data whose size and record size are already known, without reading it.
+    pub fn with_layout(data: T, size: u64, entry_size: u32) -> Self {
+        MftParser {
+            data,
+            entry_size,
+            size,
+            cache: None,
+            verbose_warnings: None,
+        }
+    }
+
+    /// Keeps up to `capacity` of the records read by `get_entry` in memory, 0 disables the cache.
+    pub fn set_cache_size(&mut self, capacity: usize) {
+        self.cache = match capacity {
+            0 => None,
+            capacity => Some(LruCache::new(capacity)),
+        };
+    }
+
+    pub fn entry_size(&self) -> u32 {
+        self.entry_size
+    }
+
+    pub fn get_entry_count(&self) -> u64 {
+        self.size / u64::from(self.entry_size)
+    }
+
+    /// Reads the raw (before fixups) bytes of a record.
+    pub fn get_raw_entry(&mut self, entry_number: u64) -> Result<Vec<u8>> {
+        self.data
+            .seek(SeekFrom::Start(entry_number * u64::from(self.entry_size)))?;
+
+        let mut entry_buffer = vec![0; self.entry_size as usize];
+        self.data.read_exact(&mut entry_buffer)?;
+
+        Ok(entry_buffer)
+    }
+
+    /// Counts the records of every signature, reading only the headers (in large chunks).
+    pub fn count_signatures(&mut self) -> Result<SignatureCounts> {
+        let entry_size = u64::from(self.entry_size);
+        let records_per_chunk = (SIGNATURES_CHUNK_SIZE / entry_size).max(1);
+        let mut counts = SignatureCounts::default();
+
+        self.data.seek(SeekFrom::Start(0))?;
+
+        let mut remaining = self.get_entry_count();
+        let mut chunk = vec![];
+
+        while remaining > 0 {
+            let records = remaining.min(records_per_chunk);
+            chunk.resize((records * entry_size) as usize, 0);
+            self.data.read_exact(&mut chunk)?;
+
+            for record in chunk.chunks(self.entry_size as usize) {
+                match &record[..4] {
+                    signature if signature == FILE_HEADER => counts.file += 1,
+                    signature if signature == BAAD_HEADER => counts.baad += 1,
+                    signature if signature == ZERO_HEADER => counts.zeroed += 1,
+                    _ => counts.unknown += 1,
+                }
+            }
+
+            remaining -= records;
+        }
+
+        Ok(counts)
+    }
+
+    /// Surfaces (`Some(true)`) or silences (`Some(false)`) the warnings logged while reading records.
+    pub fn set_verbose_warnings(&mut self, verbose_warnings: Option<bool>) {
+        self.verbose_warnings = verbose_warnings;
+    }
+
+    /// Reads an entry from the MFT by entry number, from the cache if it is enabled.
+    /// Used for lookups (parent directories, extension records, ...) which often read the same records.
+    pub fn get_entry(&mut self, entry_number: u64) -> Result<MftEntry> {
+        if let Some(entry) = self.cache.as_mut().and_then(|cache| cache.get(&entry_number)) {
+            return Ok(entry.clone());
+        }
+
+        let entry = self.read_entry(entry_number)?;
+        if let Some(cache) = self.cache.as_mut() {
+            cache.put(entry_number, entry.clone());
+        }
+
+        Ok(entry)
+    }
+
+    /// Reads an entry from the MFT by entry number, bypassing the cache.
+    /// Used for scans over every record, which would otherwise evict every cached record.
+    pub fn read_entry(&mut self, entry_number: u64) -> Result<MftEntry> {
+        let entry_buffer = self.get_raw_entry(entry_number)?;
+
+        with_warnings(self.verbose_warnings, || entry_from_buffer(entry_buffer, entry_number))
+    }
+}
+
+/// Guesses the record size, from the first record which has a valid header.
+fn detect_entry_size<T: ReadSeek>(data: &mut T, size: u64) -> Result<u32> {
+    let mut header = [0_u8; 0x20];
+
+    for i in 0..MAX_DETECTION_RECORDS {
+        // Records are at least 1024 bytes, and every record size is a multiple of it.
+        let offset = i * 1024;
+        if offset + header.len() as u64 > size {
+            break;
+        }
+
+        data.seek(SeekFrom::Start(offset))?;
+        data.read_exact(&mut header)?;
+
+        let signature = &header[..4];
+        let total_entry_size = u32_at(&header, 28);
+
+        if (signature == FILE_HEADER || signature == BAAD_HEADER)
+            && ENTRY_SIZES.contains(&total_entry_size)
+        {
+            return Ok(total_entry_size);
+        }
+    }
+
+    Err(Error::from(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "could not detect the MFT entry size, no valid FILE record was found",
+    )))
+}
+
+/// Parses a record with `MftEntry::from_buffer`, once the fields which it (and the iteration over attributes) uses
+/// as offsets are checked, so that malformed records (crafted, carved or damaged ones) are errors
+/// instead of panics or endless loops.
+pub fn entry_from_buffer(buffer: Vec<u8>, entry_number: u64) -> Result<MftEntry> {
+    let malformed = |detail: String| Error::Any {
+        detail: format!("[entry: {}] malformed record, {}", entry_number, detail),
+    };
+
+    check_record_header(&buffer).map_err(malformed)?;
+    let entry = MftEntry::from_buffer(buffer, entry_number)?;
+    check_attribute_lengths(&entry).map_err(malformed)?;
+
+    Ok(entry)
+}
+
+/// Checks the update sequence array and the offset of the first attribute of a FILE or BAAD record.
+pub fn check_record_header(record: &[u8]) -> std::result::Result<(), String> {
+    if record.len() < 4 || (&record[..4] != FILE_HEADER && &record[..4] != BAAD_HEADER) {
+        return Ok(());
+    }
+    if record.len() < 0x30 {
+        return Err(format!("{} bytes are too short for a header", record.len()));
+    }
+
+    let usa_offset = usize::from(u16_at(record, 4));
+    let usa_size = usize::from(u16_at(record, 6));
+    let first_attribute_offset = usize::from(u16_at(record, 0x14));
+    let used_size = u32_at(record, 0x18) as usize;
+
+    if usa_size != record.len() / 512 + 1 {
+        Err(format!(
+            "the update sequence array has {} items instead of {}",
+            usa_size,
+            record.len() / 512 + 1
+        ))
+    } else if usa_offset + usa_size * 2 > first_attribute_offset {
+        Err(format!(
+            "the update sequence array (at {:#x}) overlaps the attributes (at {:#x})",
+            usa_offset, first_attribute_offset
+        ))
+    } else if used_size > record.len() || first_attribute_offset >= used_size {
+        Err(format!(
+            "the attributes (at {:#x}) are not within the used size ({} bytes)",
+            first_attribute_offset, used_size
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that every attribute header has a length which leads to the next one within the record,
+/// as iterating over the attributes never advances past an attribute of length zero.
+fn check_attribute_lengths(entry: &MftEntry) -> std::result::Result<(), String> {
+    if &entry.header.signature == ZERO_HEADER {
+        return Ok(());
+    }
+
+    let data = &entry.data;
+    let mut offset = usize::from(entry.header.first_attribute_record_offset);
+
+    while offset + 4 <= data.len() && u32_at(data, offset) != 0xFFFF_FFFF {
+        let length = if offset + 8 <= data.len() { u32_at(data, offset + 4) as usize } else { 0 };
+        if length == 0 || offset + length > data.len() {
+            return Err(format!("the attribute at {:#x} has an invalid length of {} bytes", offset, length));
+        }
+        offset += length;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,531 @@
+use crate::entry::{decoded_file_names, extension_references, file_names};
+use crate::parser::MftParser;
+use crate::volume::SharedReader;
+use crate::ReadSeek;
+
+use mft::attribute::x30::FileNamespace;
+use mft::entry::{EntryFlags, ZERO_HEADER};
+use mft::{FileNameAttr, MftEntry};
+use winstructs::ntfs::mft_reference::MftReference;
+use pyo3::exceptions::PyValueError;
+use pyo3::PyResult;
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Separates the paths of the different names of an entry, when all names are requested.
+/// `|` cannot appear in NTFS file names, so the paths can always be split back.
+pub const PATHS_SEPARATOR: &str = "|";
+
+/// How paths are written in flat output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathFormat {
+    /// `/` or `\`.
+    pub separator: String,
+    /// Written before the path, followed by the separator (e.g. `C:`), unless empty.
+    pub root: String,
+    /// Whether the path ends with the name of the entry, or with its parent directory.
+    pub include_name: bool,
+}
+
+impl Default for PathFormat {
+    fn default() -> Self {
+        PathFormat {
+            separator: "/".to_string(),
+            root: String::new(),
+            include_name: true,
+        }
+    }
+}
+
+impl PathFormat {
+    pub fn new(separator: &str, root: &str, include_name: bool) -> PyResult<Self> {
+        if separator != "/" && separator != "\\" {
+            return Err(PyValueError::new_err(format!(
+                "path_separator must be one of `/`, `\\`, got `{}`",
+                separator
+            )));
+        }
+
+        Ok(PathFormat {
+            separator: separator.to_string(),
+            root: root.to_string(),
+            include_name,
+        })
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == PathFormat::default()
+    }
+
+    /// Formats a resolved path. Unresolved (empty) paths stay empty,
+    /// and every path is formatted when all names are resolved.
+    pub fn format(&self, full_path: &Path) -> PathBuf {
+        if self.is_default() {
+            return full_path.to_path_buf();
+        }
+
+        let full_path = full_path.to_string_lossy();
+        if full_path.is_empty() {
+            return PathBuf::new();
+        }
+
+        let paths: Vec<String> = full_path
+            .split(PATHS_SEPARATOR)
+            .map(|path| {
+                let mut components: Vec<String> = Path::new(path)
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy().to_string())
+                    .collect();
+                if !self.include_name {
+                    components.pop();
+                }
+
+                let path = components.join(&self.separator);
+                if self.root.is_empty() {
+                    path
+                } else {
+                    format!("{}{}{}", self.root, self.separator, path)
+                }
+            })
+            .collect();
+
+        PathBuf::from(paths.join(PATHS_SEPARATOR))
+    }
+}
+
+/// Guards against looping forever over corrupted parent references.
+const MAX_DEPTH: usize = 1024;
+
+/// Which $FILE_NAME attribute is used to build the path of an entry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NamePreference {
+    /// Long names, this is the default.
+    Win32,
+    /// Short (8.3) names.
+    Dos,
+    /// Case sensitive names.
+    Posix,
+    /// The paths of every name of the entry.
+    All,
+}
+
+impl NamePreference {
+    pub fn from_name(name: &str) -> PyResult<Self> {
+        match name.to_lowercase().as_str() {
+            "win32" => Ok(NamePreference::Win32),
+            "dos" => Ok(NamePreference::Dos),
+            "posix" => Ok(NamePreference::Posix),
+            "all" => Ok(NamePreference::All),
+            _ => Err(PyValueError::new_err(format!(
+                "name_preference must be one of `win32`, `dos`, `posix`, `all`, got `{}`",
+                name
+            ))),
+        }
+    }
+
+    fn matches(self, namespace: &FileNamespace) -> bool {
+        match self {
+            NamePreference::Win32 | NamePreference::All => {
+                matches!(namespace, FileNamespace::Win32 | FileNamespace::Win32AndDos)
+            }
+            NamePreference::Dos => matches!(namespace, FileNamespace::DOS | FileNamespace::Win32AndDos),
+            NamePreference::Posix => matches!(namespace, FileNamespace::POSIX),
+        }
+    }
+
+    /// Picks the preferred name of the entry.
+    /// Falls back to the Win32 name, and then to any name, if the entry has none in the preferred namespace.
+    pub fn pick(self, entry: &MftEntry) -> Option<FileNameAttr> {
+        self.pick_from(file_names(entry).collect())
+    }
+
+    pub fn pick_from(self, names: Vec<FileNameAttr>) -> Option<FileNameAttr> {
+        names
+            .iter()
+            .find(|name| self.matches(&name.namespace))
+            .or_else(|| {
+                names
+                    .iter()
+                    .find(|name| NamePreference::Win32.matches(&name.namespace))
+            })
+            .or_else(|| names.first())
+            .cloned()
+    }
+}
+
+/// The default prefix of the paths of orphaned entries.
+pub const ORPHAN_PREFIX: &str = "[Orphan]";
+
+/// Resolves entry paths according to a `NamePreference`.
+/// The paths of directories are cached.
+///
+/// The path of an entry whose parent cannot be resolved (the parent reference is zero or the entry itself,
+/// the parent record is unreadable, is not a directory, or was reused since as its sequence number does not match)
+/// starts with the orphan prefix instead of the path of the parent, e.g. `[Orphan]/name`.
+#[derive(Clone)]
+pub struct PathResolver {
+    preference: NamePreference,
+    /// Whether names with invalid UTF-16 are decoded with replacement characters.
+    lossy_names: bool,
+    orphan_prefix: String,
+    /// By record number, the sequence number and allocation status of the directory, and its path
+    /// (None if the record is not a readable directory).
+    directories: HashMap<u64, Option<(u16, bool, PathBuf)>>,
+}
+
+impl PathResolver {
+    pub fn new(preference: NamePreference) -> Self {
+        PathResolver {
+            preference,
+            lossy_names: false,
+            orphan_prefix: ORPHAN_PREFIX.to_string(),
+            directories: HashMap::new(),
+        }
+    }
+
+    pub fn with_lossy_names(mut self, lossy_names: bool) -> Self {
+        self.lossy_names = lossy_names;
+        self
+    }
+
+    pub fn with_orphan_prefix(mut self, orphan_prefix: &str) -> Self {
+        self.orphan_prefix = orphan_prefix.to_string();
+        self
+    }
+
+    /// The name of the entry used for its path.
+    pub fn pick(&self, entry: &MftEntry) -> Option<FileNameAttr> {
+        self.preference.pick_from(decoded_file_names(entry, self.lossy_names))
+    }
+
+    pub fn full_path(
+        &mut self,
+        entry: &MftEntry,
+        parser: &mut MftParser<impl ReadSeek>,
+    ) -> Option<PathBuf> {
+        match self.preference {
+            NamePreference::Win32 | NamePreference::Dos | NamePreference::Posix => self.path_for_name(
+                entry,
+                self.pick(entry),
+                parser,
+                0,
+            ),
+            NamePreference::All => {
+                let mut paths: Vec<String> = vec![];
+
+                for name in decoded_file_names(entry, self.lossy_names) {
+                    if let Some(path) = self.path_for_name(entry, Some(name), parser, 0) {
+                        let path = path.to_string_lossy().to_string();
+                        if !paths.contains(&path) {
+                            paths.push(path);
+                        }
+                    }
+                }
+
+                if paths.is_empty() {
+                    self.path_for_name(entry, None, parser, 0)
+                } else {
+                    Some(PathBuf::from(paths.join(PATHS_SEPARATOR)))
+                }
+            }
+        }
+    }
+
+    fn path_for_name(
+        &mut self,
+        entry: &MftEntry,
+        name: Option<FileNameAttr>,
+        parser: &mut MftParser<impl ReadSeek>,
+        depth: usize,
+    ) -> Option<PathBuf> {
+        let entry_id = entry.header.record_number;
+
+        match name {
+            Some(name) => {
+                let parent_entry_id = name.parent.entry;
+
+                // MFT entry 5 is the root path.
+                if parent_entry_id == 5 {
+                    return Some(PathBuf::from(name.name));
+                }
+
+                if parent_entry_id == entry_id || parent_entry_id == 0 {
+                    return Some(PathBuf::from(&self.orphan_prefix).join(name.name));
+                }
+
+                Some(self.directory_path(name.parent, parser, depth).join(name.name))
+            }
+            None => match entry.header.base_reference {
+                MftReference { entry: 0, .. } => None,
+                base_reference => Some(self.directory_path(base_reference, parser, depth)),
+            },
+        }
+    }
+
+    /// The path of `name` in the directory `parent` points to (e.g. the parent reference of a USN record),
+    /// under the orphan prefix if the directory cannot be resolved.
+    pub fn path_in_directory(
+        &mut self,
+        parent: MftReference,
+        name: &str,
+        parser: &mut MftParser<impl ReadSeek>,
+    ) -> PathBuf {
+        let directory = match parent.entry {
+            // MFT entry 5 is the root path.
+            5 => PathBuf::new(),
+            0 => PathBuf::from(&self.orphan_prefix),
+            _ => self.directory_path(parent, parser, 0),
+        };
+
+        directory.join(name)
+    }
+
+    /// The path of the directory a reference points to, or the orphan prefix if it cannot be resolved.
+    fn directory_path(
+        &mut self,
+        reference: MftReference,
+        parser: &mut MftParser<impl ReadSeek>,
+        depth: usize,
+    ) -> PathBuf {
+        match self.directory(reference.entry, parser, depth) {
+            Some((sequence, allocated, path)) if sequence_matches(reference.sequence, sequence, allocated) => path,
+            _ => PathBuf::from(&self.orphan_prefix),
+        }
+    }
+
+    fn directory(
+        &mut self,
+        entry_id: u64,
+        parser: &mut MftParser<impl ReadSeek>,
+        depth: usize,
+    ) -> Option<(u16, bool, PathBuf)> {
+        if let Some(directory) = self.directories.get(&entry_id) {
+            return directory.clone();
+        }
+
+        // Corrupted parent references may loop, these directories are not cached.
+        if depth >= MAX_DEPTH {
+            return None;
+        }
+
+        let directory = match parser.get_entry(entry_id) {
+            Ok(parent) if &parent.header.signature != ZERO_HEADER && parent.is_dir() => {
+                let name = self.pick(&parent);
+                let path = self
+                    .path_for_name(&parent, name, parser, depth + 1)
+                    // A parent without a filename attribute, default to root.
+                    .unwrap_or_default();
+
+                Some((
+                    parent.header.sequence,
+                    parent.header.flags.contains(EntryFlags::ALLOCATED),
+                    path,
+                ))
+            }
+            // Parent is maybe corrupted, incomplete or not a directory.
+            _ => None,
+        };
+
+        self.directories.insert(entry_id, directory.clone());
+        directory
+    }
+}
+
+/// Whether a record with `sequence` is still the one a reference with `reference_sequence` points to.
+/// The sequence number is incremented when a record is freed, so a deleted directory which was not reused since
+/// has the next sequence number. References with a sequence number of 0 are not checked.
+pub fn sequence_matches(reference_sequence: u16, sequence: u16, allocated: bool) -> bool {
+    reference_sequence == 0
+        || sequence == reference_sequence
+        || (!allocated && sequence == reference_sequence.wrapping_add(1))
+}
+
+/// A parser and a `PathResolver` shared by the entries of a `PyMftParser`,
+/// so that entries can resolve their path, parents and children on demand after the iteration which produced them.
+#[derive(Clone)]
+pub struct SharedParser {
+    inner: Arc<Mutex<SharedParserState>>,
+}
+
+struct SharedParserState {
+    parser: MftParser<SharedReader>,
+    paths: PathResolver,
+    /// Built on first use.
+    children: Option<ChildrenIndex>,
+}
+
+impl SharedParser {
+    pub fn new(parser: MftParser<SharedReader>, paths: PathResolver) -> Self {
+        SharedParser {
+            inner: Arc::new(Mutex::new(SharedParserState {
+                parser,
+                paths,
+                children: None,
+            })),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SharedParserState> {
+        // A panic while resolving a path does not leave the caches inconsistent.
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn get_entry(&self, entry_id: u64) -> mft::err::Result<MftEntry> {
+        self.lock().parser.get_entry(entry_id)
+    }
+
+    pub fn full_path(&self, entry: &MftEntry) -> Option<PathBuf> {
+        let mut inner = self.lock();
+        let SharedParserState { parser, paths, .. } = &mut *inner;
+
+        paths.full_path(entry, parser)
+    }
+
+    /// Reads the parent directory of the preferred name of the entry.
+    /// Returns None for entries without a name, for the root directory,
+    /// or if the parent record was reused since (see `sequence_matches`), like the paths of `PathResolver`.
+    pub fn parent(&self, entry: &MftEntry) -> Option<MftEntry> {
+        let mut inner = self.lock();
+        let SharedParserState { parser, paths, .. } = &mut *inner;
+
+        let reference = paths.pick(entry)?.parent;
+        if reference.entry == entry.header.record_number {
+            return None;
+        }
+
+        parser
+            .get_entry(reference.entry)
+            .ok()
+            .filter(|parent| {
+                &parent.header.signature != ZERO_HEADER
+                    && sequence_matches(reference.sequence, parent.header.sequence, parent.is_allocated())
+            })
+    }
+
+    /// Reads the extension records listed in the $ATTRIBUTE_LIST of an entry.
+    /// Records which were reused (sequence number mismatch) or which belong to another entry are skipped.
+    pub fn extension_records(&self, entry: &MftEntry) -> Vec<MftEntry> {
+        let mut inner = self.lock();
+
+        extension_references(entry)
+            .into_iter()
+            .filter_map(|reference| {
+                inner.parser.get_entry(reference.entry).ok().filter(|extension| {
+                    extension.header.sequence == reference.sequence
+                        && extension.header.base_reference.entry == entry.header.record_number
+                })
+            })
+            .collect()
+    }
+
+    /// Record numbers of every descendant of a directory, depth first.
+    pub fn descendants(&self, entry_id: u64) -> Vec<u64> {
+        let mut inner = self.lock();
+        let SharedParserState { parser, children, .. } = &mut *inner;
+
+        children
+            .get_or_insert_with(|| ChildrenIndex::build(parser))
+            .descendants(&[entry_id])
+    }
+}
+
+/// The records which reference each directory as parent in any of their $FILE_NAME attributes,
+/// including deleted records.
+pub struct ChildrenIndex {
+    /// Lowercase names and record numbers of the children, ordered by name.
+    children: HashMap<u64, Vec<(String, u64)>>,
+    /// Record numbers of the directories (records with an index).
+    directories: HashSet<u64>,
+}
+
+impl ChildrenIndex {
+    pub fn build(parser: &mut MftParser<impl ReadSeek>) -> Self {
+        let mut children: HashMap<u64, Vec<(String, u64)>> = HashMap::new();
+        let mut directories = HashSet::new();
+
+        for i in 0..parser.get_entry_count() {
+            let entry = match parser.read_entry(i) {
+                Ok(entry) if &entry.header.signature != ZERO_HEADER => entry,
+                _ => continue,
+            };
+
+            if entry.is_dir() {
+                directories.insert(i);
+            }
+
+            for name in file_names(&entry) {
+                // The root directory is its own parent.
+                if name.parent.entry != i {
+                    children
+                        .entry(name.parent.entry)
+                        .or_default()
+                        .push((name.name.to_lowercase(), i));
+                }
+            }
+        }
+
+        for names in children.values_mut() {
+            names.sort();
+        }
+
+        ChildrenIndex { children, directories }
+    }
+
+    fn children(&self, entry_id: u64) -> impl Iterator<Item = &(String, u64)> {
+        self.children.get(&entry_id).into_iter().flatten()
+    }
+
+    /// Record numbers of every descendant of the given directories, depth first,
+    /// with the children of a directory ordered by name. Every record is listed once.
+    pub fn descendants(&self, directories: &[u64]) -> Vec<u64> {
+        let mut visited: HashSet<u64> = directories.iter().copied().collect();
+        let mut pending: Vec<u64> = directories.iter().rev().copied().collect();
+        let mut descendants = vec![];
+
+        while let Some(directory) = pending.pop() {
+            if !directories.contains(&directory) {
+                descendants.push(directory);
+            }
+
+            let children: Vec<u64> = self
+                .children(directory)
+                .map(|(_, entry_id)| *entry_id)
+                .filter(|entry_id| visited.insert(*entry_id))
+                .collect();
+            pending.extend(children.into_iter().rev());
+        }
+
+        descendants
+    }
+}
+
+/// Finds the records below the directory at `prefix` (relative to the root, `/` or `\` separated,
+/// case insensitive), using an index of the parents of every $FILE_NAME (including those of deleted records).
+/// Returns None if no directory matches the prefix.
+pub fn subtree_records(parser: &mut MftParser<impl ReadSeek>, prefix: &str) -> Option<Vec<u64>> {
+    let index = ChildrenIndex::build(parser);
+
+    // MFT entry 5 is the root path.
+    let mut directories: BTreeSet<u64> = [5].iter().copied().collect();
+    for component in prefix.split(['/', '\\']).filter(|c| !c.is_empty()) {
+        let component = component.to_lowercase();
+
+        directories = directories
+            .iter()
+            .flat_map(|directory| index.children(*directory))
+            .filter(|(name, entry_id)| *name == component && index.directories.contains(entry_id))
+            .map(|(_, entry_id)| *entry_id)
+            .collect();
+
+        if directories.is_empty() {
+            return None;
+        }
+    }
+
+    let directories: Vec<u64> = directories.into_iter().collect();
+    let descendants: BTreeSet<u64> = index.descendants(&directories).into_iter().collect();
+
+    Some(descendants.into_iter().collect())
+}
@@ -0,0 +1,70 @@
+//! $REPARSE_POINT support.
+//! https://docs.microsoft.com/en-us/openspecs/windows_protocols/ms-fscc/b41f1cbf-10df-4a47-98d4-1c52a833d913
+use crate::utils::{u16_at, u32_at, utf16_to_string};
+
+use mft::attribute::{MftAttributeContent, MftAttributeType};
+use mft::MftEntry;
+
+pub const IO_REPARSE_TAG_MOUNT_POINT: u32 = 0xA000_0003;
+pub const IO_REPARSE_TAG_SYMLINK: u32 = 0xA000_000C;
+pub const IO_REPARSE_TAG_LX_SYMLINK: u32 = 0xA000_001D;
+
+const REPARSE_HEADER_SIZE: usize = 8;
+
+/// Returns the content of the (resident) $REPARSE_POINT attribute of an entry.
+pub fn reparse_data(entry: &MftEntry) -> Option<Vec<u8>> {
+    entry
+        .iter_attributes_matching(Some(vec![MftAttributeType::ReparsePoint]))
+        .filter_map(Result::ok)
+        .find_map(|a| match a.data {
+            MftAttributeContent::Raw(raw) => Some(raw.data),
+            _ => None,
+        })
+}
+
+/// Decodes the target of a symbolic link, a mount point (junction), or a WSL symbolic link.
+/// Returns `None` for other reparse tags, or malformed data.
+pub fn link_target(data: &[u8]) -> Option<String> {
+    if data.len() < REPARSE_HEADER_SIZE {
+        return None;
+    }
+
+    let tag = u32_at(data, 0);
+    let data_length = u16_at(data, 4) as usize;
+    let buffer = data.get(REPARSE_HEADER_SIZE..REPARSE_HEADER_SIZE + data_length)?;
+
+    match tag {
+        IO_REPARSE_TAG_SYMLINK => name_from_path_buffer(buffer, 12),
+        IO_REPARSE_TAG_MOUNT_POINT => name_from_path_buffer(buffer, 8),
+        // A version number, followed by the UTF-8 target.
+        IO_REPARSE_TAG_LX_SYMLINK => buffer
+            .get(4..)
+            .map(|target| String::from_utf8_lossy(target).to_string()),
+        _ => None,
+    }
+}
+
+/// Reads the print name (or the substitute name, if there is no print name) of a path buffer,
+/// which starts at `path_buffer_offset` in the reparse buffer.
+fn name_from_path_buffer(buffer: &[u8], path_buffer_offset: usize) -> Option<String> {
+    if buffer.len() < path_buffer_offset {
+        return None;
+    }
+
+    let name = |offset_field: usize| {
+        let offset = path_buffer_offset + u16_at(buffer, offset_field) as usize;
+        let length = u16_at(buffer, offset_field + 2) as usize;
+        buffer.get(offset..offset + length).map(utf16_to_string)
+    };
+
+    match name(4) {
+        Some(print_name) if !print_name.is_empty() => Some(print_name),
+        // Substitute names are NT paths (`\??\C:\...`).
+        _ => name(0).map(|substitute_name| {
+            substitute_name
+                .strip_prefix(r"\??\")
+                .map(str::to_string)
+                .unwrap_or(substitute_name)
+        }),
+    }
+}
@@ -0,0 +1,218 @@
+use mft::attribute::MftAttributeType;
+use mft::MftEntry;
+use num_traits::cast::ToPrimitive;
+
+use std::convert::TryInto;
+
+/// A single mapping pair from a non-resident attribute's run list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataRun {
+    /// First virtual cluster number covered by this run.
+    pub vcn: u64,
+    /// Logical cluster number on the volume, `None` if the run is sparse.
+    pub lcn: Option<u64>,
+    /// Length of the run, in clusters.
+    pub length: u64,
+}
+
+/// Decodes a mapping pairs array.
+/// Decoding stops at the terminating zero byte, or at the first malformed pair.
+pub fn decode_data_runs(bytes: &[u8]) -> Vec<DataRun> {
+    let mut runs = vec![];
+    let mut offset = 0;
+    let mut vcn = 0_u64;
+    let mut lcn = 0_i64;
+
+    while offset < bytes.len() {
+        let header = bytes[offset];
+        if header == 0 {
+            break;
+        }
+
+        let length_size = (header & 0x0F) as usize;
+        let offset_size = (header >> 4) as usize;
+        offset += 1;
+
+        if length_size == 0 || length_size > 8 || offset_size > 8 {
+            break;
+        }
+
+        if offset + length_size + offset_size > bytes.len() {
+            break;
+        }
+
+        let length = read_unsigned(&bytes[offset..offset + length_size]);
+        offset += length_size;
+
+        let run_lcn = if offset_size == 0 {
+            None
+        } else {
+            // Runs before the start of the volume, or past the last cluster number, are malformed.
+            match lcn.checked_add(read_signed(&bytes[offset..offset + offset_size])) {
+                Some(next) if next >= 0 => lcn = next,
+                _ => break,
+            }
+            offset += offset_size;
+            Some(lcn as u64)
+        };
+
+        let next_vcn = match vcn.checked_add(length) {
+            Some(next_vcn) => next_vcn,
+            None => break,
+        };
+
+        runs.push(DataRun {
+            vcn,
+            lcn: run_lcn,
+            length,
+        });
+
+        vcn = next_vcn;
+    }
+
+    runs
+}
+
+/// Returns the sparse runs (holes, which have no clusters allocated) of a run list, as `(vcn, length)` pairs.
+/// Adjacent sparse runs are merged.
+pub fn sparse_ranges(runs: &[DataRun]) -> Vec<(u64, u64)> {
+    let mut holes: Vec<(u64, u64)> = vec![];
+
+    for run in runs.iter().filter(|run| run.lcn.is_none()) {
+        match holes.last_mut() {
+            Some((vcn, length)) if *vcn + *length == run.vcn => *length += run.length,
+            _ => holes.push((run.vcn, run.length)),
+        }
+    }
+
+    holes
+}
+
+/// Counts the fragments of a run list: the allocated runs which do not continue the previous allocated run on the volume.
+/// Sparse runs do not have clusters, and do not split fragments.
+pub fn fragment_count(runs: &[DataRun]) -> u64 {
+    let mut fragments = 0;
+    let mut next_lcn = None;
+
+    for run in runs {
+        if let Some(lcn) = run.lcn {
+            if next_lcn != Some(lcn) {
+                fragments += 1;
+            }
+            next_lcn = Some(lcn + run.length);
+        }
+    }
+
+    fragments
+}
+
+/// Reads the run lists of every segment of a non-resident $DATA stream, across the records of a file
+/// (its base record and extension records), ordered by VCN.
+/// Returns `None` if none of the records has a non-resident segment of the stream.
+pub fn stream_runs<'a>(records: impl IntoIterator<Item = &'a MftEntry>, stream_name: &str) -> Option<Vec<DataRun>> {
+    let mut runs: Option<Vec<DataRun>> = None;
+
+    for record in records {
+        for attribute in record
+            .iter_attributes_matching(Some(vec![MftAttributeType::DATA]))
+            .filter_map(Result::ok)
+            .filter(|a| a.header.form_code == 1 && a.header.name == stream_name)
+        {
+            if let Some(segment) = data_runs_for_instance(record, attribute.header.instance) {
+                runs.get_or_insert_with(Vec::new).extend(segment);
+            }
+        }
+    }
+
+    if let Some(runs) = &mut runs {
+        runs.sort_by_key(|run| run.vcn);
+    }
+    runs
+}
+
+/// Reads the run list of the first non-resident attribute of type `type_code` with the given name.
+/// Returns `None` if no such attribute exists in the entry.
+pub fn data_runs_for_attribute(
+    entry: &MftEntry,
+    type_code: u32,
+    name: &str,
+) -> Option<Vec<DataRun>> {
+    let instance = entry
+        .iter_attributes()
+        .filter_map(Result::ok)
+        .find(|a| {
+            a.header.form_code == 1
+                && a.header.type_code.to_u32() == Some(type_code)
+                && a.header.name == name
+        })?
+        .header
+        .instance;
+
+    data_runs_for_instance(entry, instance)
+}
+
+/// Reads the run list of the non-resident attribute with the given instance id.
+pub fn data_runs_for_instance(entry: &MftEntry, instance: u16) -> Option<Vec<DataRun>> {
+    let record = attribute_record(entry, instance)?;
+
+    // Non-resident form.
+    if record.get(8) != Some(&1) {
+        return None;
+    }
+
+    let lowest_vcn = u64::from_le_bytes(record.get(0x10..0x18)?.try_into().ok()?);
+    let runs_offset = u16::from_le_bytes(record.get(0x20..0x22)?.try_into().ok()?) as usize;
+
+    // The runs of a segment other than the first start at its lowest VCN.
+    let mut runs = decode_data_runs(record.get(runs_offset..)?);
+    for run in &mut runs {
+        run.vcn += lowest_vcn;
+    }
+    Some(runs)
+}
+
+/// Returns the raw bytes of the attribute record with the given instance id.
+pub fn attribute_record(entry: &MftEntry, instance: u16) -> Option<&[u8]> {
+    let data = &entry.data;
+    let end = (entry.header.used_entry_size as usize).min(data.len());
+    let mut offset = entry.header.first_attribute_record_offset as usize;
+
+    while offset + 16 <= end {
+        let type_code = u32::from_le_bytes(data[offset..offset + 4].try_into().ok()?);
+        if type_code == 0xFFFF_FFFF {
+            return None;
+        }
+
+        let length = u32::from_le_bytes(data[offset + 4..offset + 8].try_into().ok()?) as usize;
+        if length == 0 || offset + length > end {
+            return None;
+        }
+
+        let record_instance = u16::from_le_bytes(data[offset + 14..offset + 16].try_into().ok()?);
+        if record_instance == instance {
+            return Some(&data[offset..offset + length]);
+        }
+
+        offset += length;
+    }
+
+    None
+}
+
+fn read_unsigned(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0_u64, |acc, &b| (acc << 8) | u64::from(b))
+}
+
+fn read_signed(bytes: &[u8]) -> i64 {
+    let unsigned = read_unsigned(bytes);
+    let bits = bytes.len() * 8;
+
+    if bits < 64 && unsigned & (1 << (bits - 1)) != 0 {
+        (unsigned | (!0_u64 << bits)) as i64
+    } else {
+        unsigned as i64
+    }
+}
@@ -0,0 +1,389 @@
+//! $Secure:$SDS support.
+//! Security descriptors are stored once per volume, and referenced by `security_id` from $STANDARD_INFORMATION.
+use crate::utils::{u16_at, u32_at, u64_at, FileOrFileLike};
+use crate::volume::{open_file_stream, SharedReader};
+use crate::ReadSeek;
+
+use mft::attribute::{MftAttributeContent, MftAttributeType};
+use mft::MftEntry;
+use pyo3::prelude::*;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::sync::Arc;
+
+/// MFT entry number of the $Secure metadata file.
+const SECURE_ENTRY: u64 = 9;
+
+const SDS_HEADER_SIZE: usize = 20;
+/// The $SDS stream is written in 256KiB blocks, each followed by a mirror copy.
+const SDS_BLOCK_SIZE: usize = 0x4_0000;
+
+/// Formats a binary SID in its string form (`S-1-5-21-...`).
+pub fn sid_to_string(buf: &[u8]) -> Option<String> {
+    let sub_authority_count = *buf.get(1)? as usize;
+    let authority = buf
+        .get(2..8)?
+        .iter()
+        .fold(0_u64, |acc, &b| (acc << 8) | u64::from(b));
+
+    let mut sid = format!("S-{}-{}", buf[0], authority);
+
+    for i in 0..sub_authority_count {
+        let offset = 8 + i * 4;
+        buf.get(offset..offset + 4)?;
+        sid.push_str(&format!("-{}", u32_at(buf, offset)));
+    }
+
+    Some(sid)
+}
+
+fn sid_at(descriptor: &[u8], offset: usize) -> Option<String> {
+    if offset == 0 {
+        return None;
+    }
+
+    descriptor.get(offset..).and_then(sid_to_string)
+}
+
+/// Names of the ACE types.
+fn ace_type_name(ace_type: u8) -> &'static str {
+    match ace_type {
+        0x00 => "ACCESS_ALLOWED",
+        0x01 => "ACCESS_DENIED",
+        0x02 => "SYSTEM_AUDIT",
+        0x03 => "SYSTEM_ALARM",
+        0x05 => "ACCESS_ALLOWED_OBJECT",
+        0x06 => "ACCESS_DENIED_OBJECT",
+        0x07 => "SYSTEM_AUDIT_OBJECT",
+        0x08 => "SYSTEM_ALARM_OBJECT",
+        0x09 => "ACCESS_ALLOWED_CALLBACK",
+        0x0A => "ACCESS_DENIED_CALLBACK",
+        0x11 => "SYSTEM_MANDATORY_LABEL",
+        _ => "UNKNOWN",
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Ace {
+    pub ace_type: u8,
+    pub flags: u8,
+    pub mask: u32,
+    pub sid: Option<String>,
+}
+
+fn parse_acl(descriptor: &[u8], offset: usize) -> Option<Vec<Ace>> {
+    if offset == 0 {
+        return None;
+    }
+
+    let acl = descriptor.get(offset..)?;
+    if acl.len() < 8 {
+        return None;
+    }
+
+    let ace_count = u16_at(acl, 4) as usize;
+    let mut aces = Vec::with_capacity(ace_count);
+    let mut position = 8;
+
+    for _ in 0..ace_count {
+        let header = acl.get(position..position + 8)?;
+        let ace_type = header[0];
+        let size = u16_at(header, 2) as usize;
+        if size < 8 {
+            break;
+        }
+
+        let mask = u32_at(header, 4);
+
+        // Object ACEs carry optional GUIDs before the SID.
+        let sid_offset = if (0x05..=0x08).contains(&ace_type) {
+            let object_flags = u32_at(acl.get(position + 8..position + 12)?, 0);
+            let mut offset = 12;
+            if object_flags & 0x1 != 0 {
+                offset += 16;
+            }
+            if object_flags & 0x2 != 0 {
+                offset += 16;
+            }
+            offset
+        } else {
+            8
+        };
+
+        aces.push(Ace {
+            ace_type,
+            flags: header[1],
+            mask,
+            sid: acl
+                .get(position + sid_offset..position + size)
+                .and_then(sid_to_string),
+        });
+
+        position += size;
+    }
+
+    Some(aces)
+}
+
+/// A parsed self-relative security descriptor.
+#[derive(Debug, Clone)]
+pub struct SecurityDescriptor {
+    pub security_id: u32,
+    pub hash: u32,
+    pub revision: u8,
+    pub control: u16,
+    pub owner_sid: Option<String>,
+    pub group_sid: Option<String>,
+    pub dacl: Option<Vec<Ace>>,
+    pub sacl: Option<Vec<Ace>>,
+    pub raw: Vec<u8>,
+}
+
+/// Security descriptors of a volume, keyed by security id.
+pub type SecurityDescriptors = Arc<HashMap<u32, SecurityDescriptor>>;
+
+impl SecurityDescriptor {
+    pub(crate) fn from_buffer(security_id: u32, hash: u32, descriptor: &[u8]) -> Option<SecurityDescriptor> {
+        if descriptor.len() < 20 {
+            return None;
+        }
+
+        Some(SecurityDescriptor {
+            security_id,
+            hash,
+            revision: descriptor[0],
+            control: u16_at(descriptor, 2),
+            owner_sid: sid_at(descriptor, u32_at(descriptor, 4) as usize),
+            group_sid: sid_at(descriptor, u32_at(descriptor, 8) as usize),
+            sacl: parse_acl(descriptor, u32_at(descriptor, 12) as usize),
+            dacl: parse_acl(descriptor, u32_at(descriptor, 16) as usize),
+            raw: descriptor.to_vec(),
+        })
+    }
+}
+
+/// Resolves the owner and group SIDs of an entry.
+/// NTFS 3.0+ volumes reference $Secure through the `security_id` of $STANDARD_INFORMATION,
+/// while older volumes store a $SECURITY_DESCRIPTOR attribute in the entry itself.
+pub fn owner_and_group(
+    entry: &MftEntry,
+    descriptors: Option<&SecurityDescriptors>,
+) -> (Option<String>, Option<String>) {
+    let matching = Some(vec![
+        MftAttributeType::StandardInformation,
+        MftAttributeType::SecurityDescriptor,
+    ]);
+
+    for attribute in entry.iter_attributes_matching(matching).filter_map(Result::ok) {
+        let descriptor = match attribute.data {
+            MftAttributeContent::AttrX10(info) => descriptors
+                .and_then(|d| d.get(&info.security_id))
+                .cloned(),
+            MftAttributeContent::Raw(raw) => SecurityDescriptor::from_buffer(0, 0, &raw.data),
+            _ => None,
+        };
+
+        if let Some(descriptor) = descriptor {
+            return (descriptor.owner_sid, descriptor.group_sid);
+        }
+    }
+
+    (None, None)
+}
+
+/// Parses all the security descriptors of a $SDS stream.
+pub fn read_sds<R: Read>(mut reader: R) -> io::Result<HashMap<u32, SecurityDescriptor>> {
+    let mut sds = vec![];
+    reader.read_to_end(&mut sds)?;
+
+    let mut descriptors = HashMap::new();
+    let mut position = 0;
+
+    while position + SDS_HEADER_SIZE <= sds.len() {
+        // Skip over the mirror copy of every block.
+        if (position / SDS_BLOCK_SIZE) % 2 == 1 {
+            position = (position / SDS_BLOCK_SIZE + 1) * SDS_BLOCK_SIZE;
+            continue;
+        }
+
+        let hash = u32_at(&sds, position);
+        let security_id = u32_at(&sds, position + 4);
+        let offset = u64_at(&sds, position + 8) as usize;
+        let length = u32_at(&sds, position + 16) as usize;
+
+        // Unused space at the end of a block, continue with the next block.
+        if length < SDS_HEADER_SIZE || offset != position || position + length > sds.len() {
+            position = (position / SDS_BLOCK_SIZE + 1) * SDS_BLOCK_SIZE;
+            continue;
+        }
+
+        if let Some(descriptor) = SecurityDescriptor::from_buffer(
+            security_id,
+            hash,
+            &sds[position + SDS_HEADER_SIZE..position + length],
+        ) {
+            descriptors.insert(security_id, descriptor);
+        }
+
+        // Entries are aligned to 16 bytes.
+        position += (length + 15) & !15;
+    }
+
+    Ok(descriptors)
+}
+
+#[pyclass]
+/// PySecureParser(self, path_or_file_like, /)
+/// --
+///
+/// Parses an extracted $Secure:$SDS stream, and resolves security ids to security descriptors.
+/// Works on both a path (string), or a file-like object.
+pub struct PySecureParser {
+    pub(crate) descriptors: SecurityDescriptors,
+}
+
+#[pymethods]
+impl PySecureParser {
+    #[new]
+    fn new(path_or_file_like: PyObject) -> PyResult<Self> {
+        let descriptors = match FileOrFileLike::from_pyobject(path_or_file_like)? {
+            FileOrFileLike::File(s) => read_sds(BufReader::new(File::open(s)?))?,
+            FileOrFileLike::FileLike(f) => read_sds(f)?,
+            FileOrFileLike::Buffer(b) => read_sds(b)?,
+        };
+
+        Ok(PySecureParser {
+            descriptors: Arc::new(descriptors),
+        })
+    }
+
+    /// from_volume(path_or_file_like, /)
+    /// --
+    ///
+    /// Reads the $Secure:$SDS stream from a raw NTFS volume image.
+    #[staticmethod]
+    fn from_volume(path_or_file_like: PyObject) -> PyResult<Self> {
+        let boxed_read_seek = match FileOrFileLike::from_pyobject(path_or_file_like)? {
+            FileOrFileLike::File(s) => {
+                Box::new(BufReader::with_capacity(4096, File::open(s)?)) as Box<dyn ReadSeek + Send>
+            }
+            FileOrFileLike::FileLike(f) => Box::new(f) as Box<dyn ReadSeek + Send>,
+            FileOrFileLike::Buffer(b) => Box::new(b) as Box<dyn ReadSeek + Send>,
+        };
+
+        let (sds, _size) = open_file_stream(SharedReader::new(boxed_read_seek)?, SECURE_ENTRY, "$SDS")?;
+
+        Ok(PySecureParser {
+            descriptors: Arc::new(read_sds(sds)?),
+        })
+    }
+
+    /// security_ids(self, /)
+    /// --
+    ///
+    /// Returns a sorted list of all the security ids in the stream.
+    fn security_ids(&self) -> Vec<u32> {
+        let mut ids: Vec<u32> = self.descriptors.keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// get(self, security_id, /)
+    /// --
+    ///
+    /// Returns the `PySecurityDescriptor` for the given security id (`PyMftAttributeX10.security_id`),
+    /// or None if it is not present in the stream.
+    fn get(&self, security_id: u32) -> Option<PySecurityDescriptor> {
+        self.descriptors
+            .get(&security_id)
+            .map(PySecurityDescriptor::from_descriptor)
+    }
+}
+
+#[pyclass]
+pub struct PySecurityDescriptor {
+    inner: SecurityDescriptor,
+    #[pyo3(get)]
+    pub security_id: u32,
+    #[pyo3(get)]
+    pub hash: u32,
+    #[pyo3(get)]
+    pub revision: u8,
+    /// Raw value of the control flags
+    #[pyo3(get)]
+    pub control: u16,
+    /// Owner SID, in its string form
+    #[pyo3(get)]
+    pub owner_sid: Option<String>,
+    /// Group SID, in its string form
+    #[pyo3(get)]
+    pub group_sid: Option<String>,
+}
+
+impl PySecurityDescriptor {
+    pub fn from_descriptor(descriptor: &SecurityDescriptor) -> Self {
+        PySecurityDescriptor {
+            security_id: descriptor.security_id,
+            hash: descriptor.hash,
+            revision: descriptor.revision,
+            control: descriptor.control,
+            owner_sid: descriptor.owner_sid.clone(),
+            group_sid: descriptor.group_sid.clone(),
+            inner: descriptor.clone(),
+        }
+    }
+}
+
+#[pymethods]
+impl PySecurityDescriptor {
+    /// List of `PyAce`, or None if the descriptor has no DACL.
+    #[getter]
+    pub fn dacl(&self) -> Option<Vec<PyAce>> {
+        self.inner
+            .dacl
+            .as_ref()
+            .map(|aces| aces.iter().map(PyAce::from_ace).collect())
+    }
+
+    /// List of `PyAce`, or None if the descriptor has no SACL.
+    #[getter]
+    pub fn sacl(&self) -> Option<Vec<PyAce>> {
+        self.inner
+            .sacl
+            .as_ref()
+            .map(|aces| aces.iter().map(PyAce::from_ace).collect())
+    }
+
+    /// The self-relative security descriptor bytes.
+    #[getter]
+    pub fn data(&self) -> &[u8] {
+        &self.inner.raw
+    }
+}
+
+#[pyclass]
+pub struct PyAce {
+    /// Name of the ACE type
+    #[pyo3(get)]
+    pub ace_type: String,
+    #[pyo3(get)]
+    pub flags: u8,
+    /// Access mask
+    #[pyo3(get)]
+    pub mask: u32,
+    #[pyo3(get)]
+    pub sid: Option<String>,
+}
+
+impl PyAce {
+    pub fn from_ace(ace: &Ace) -> Self {
+        PyAce {
+            ace_type: ace_type_name(ace.ace_type).to_string(),
+            flags: ace.flags,
+            mask: ace.mask,
+            sid: ace.sid.clone(),
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! Ordering of entries by a key, computed in a first pass over the MFT.
+//! Keys are sorted externally: in runs of `RUN_SIZE` keys, which are written to temporary files
+//! and merged, so that only the record numbers of the MFT are kept in memory.
+use crate::entry::{data_sizes, EntryContext};
+use crate::parser::MftParser;
+use crate::utils::invalid_data;
+use crate::ReadSeek;
+
+use chrono::{DateTime, TimeZone, Utc};
+use mft::attribute::{MftAttributeContent, MftAttributeType};
+use mft::entry::{EntryFlags, ZERO_HEADER};
+use mft::MftEntry;
+use pyo3::exceptions::PyValueError;
+use pyo3::PyResult;
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+
+/// Number of keys which are sorted in memory, before being written to a temporary file.
+const RUN_SIZE: usize = 1 << 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortBy {
+    Path,
+    /// $STANDARD_INFORMATION creation time
+    Created,
+    /// $STANDARD_INFORMATION modification time
+    Modified,
+    /// Logical size of the unnamed $DATA stream
+    Size,
+}
+
+impl SortBy {
+    pub fn from_name(name: &str) -> PyResult<Self> {
+        match name {
+            "path" => Ok(SortBy::Path),
+            "created" => Ok(SortBy::Created),
+            "modified" => Ok(SortBy::Modified),
+            "size" => Ok(SortBy::Size),
+            _ => Err(PyValueError::new_err(format!(
+                "by must be one of `path`, `created`, `modified`, `size`, got `{}`",
+                name
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum SortKey {
+    Path(String),
+    Time(DateTime<Utc>),
+    Size(u64),
+}
+
+// Tags of the keys in temporary files.
+const TAG_NONE: u8 = 0;
+const TAG_PATH: u8 = 1;
+const TAG_TIME: u8 = 2;
+const TAG_SIZE: u8 = 3;
+
+/// The key of a record, ordered like the records are yielded:
+/// by key (records without a key last), and then by record number.
+#[derive(Debug, PartialEq, Eq)]
+struct SortedRecord {
+    key: Option<SortKey>,
+    entry_id: u64,
+    descending: bool,
+}
+
+impl Ord for SortedRecord {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let by_key = match (&self.key, &other.key) {
+            (Some(a), Some(b)) if self.descending => b.cmp(a),
+            (Some(a), Some(b)) => a.cmp(b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+
+        by_key.then(self.entry_id.cmp(&other.entry_id))
+    }
+}
+
+impl PartialOrd for SortedRecord {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl SortedRecord {
+    fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        match &self.key {
+            None => writer.write_all(&[TAG_NONE])?,
+            Some(SortKey::Path(path)) => {
+                writer.write_all(&[TAG_PATH])?;
+                writer.write_all(&(path.len() as u32).to_le_bytes())?;
+                writer.write_all(path.as_bytes())?;
+            }
+            Some(SortKey::Time(time)) => {
+                writer.write_all(&[TAG_TIME])?;
+                writer.write_all(&time.timestamp().to_le_bytes())?;
+                writer.write_all(&time.timestamp_subsec_nanos().to_le_bytes())?;
+            }
+            Some(SortKey::Size(size)) => {
+                writer.write_all(&[TAG_SIZE])?;
+                writer.write_all(&size.to_le_bytes())?;
+            }
+        }
+
+        writer.write_all(&self.entry_id.to_le_bytes())
+    }
+
+    fn read(reader: &mut impl Read, descending: bool) -> io::Result<Self> {
+        fn bytes<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+            let mut buf = [0; N];
+            reader.read_exact(&mut buf)?;
+            Ok(buf)
+        }
+
+        let key = match bytes::<1>(reader)?[0] {
+            TAG_NONE => None,
+            TAG_PATH => {
+                let mut path = vec![0; u32::from_le_bytes(bytes(reader)?) as usize];
+                reader.read_exact(&mut path)?;
+                let path = String::from_utf8(path).map_err(|_| invalid_data("invalid path in sorted run"))?;
+                Some(SortKey::Path(path))
+            }
+            TAG_TIME => {
+                let seconds = i64::from_le_bytes(bytes(reader)?);
+                let nanoseconds = u32::from_le_bytes(bytes(reader)?);
+                let time = Utc
+                    .timestamp_opt(seconds, nanoseconds)
+                    .single()
+                    .ok_or_else(|| invalid_data("invalid timestamp in sorted run"))?;
+                Some(SortKey::Time(time))
+            }
+            TAG_SIZE => Some(SortKey::Size(u64::from_le_bytes(bytes(reader)?))),
+            _ => return Err(invalid_data("invalid key in sorted run")),
+        };
+
+        Ok(SortedRecord {
+            key,
+            entry_id: u64::from_le_bytes(bytes(reader)?),
+            descending,
+        })
+    }
+}
+
+/// A sorted run of keys, in a temporary file.
+struct Run {
+    reader: BufReader<File>,
+    remaining: usize,
+}
+
+impl Run {
+    /// Sorts `records`, and writes them to a temporary file (which is deleted once closed).
+    fn write(records: &mut Vec<SortedRecord>) -> io::Result<Self> {
+        records.sort();
+
+        let mut writer = BufWriter::new(tempfile::tempfile()?);
+        for record in records.iter() {
+            record.write(&mut writer)?;
+        }
+
+        let mut file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.seek(SeekFrom::Start(0))?;
+
+        let remaining = records.len();
+        records.clear();
+
+        Ok(Run {
+            reader: BufReader::new(file),
+            remaining,
+        })
+    }
+
+    fn next(&mut self, descending: bool) -> io::Result<Option<SortedRecord>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+
+        self.remaining -= 1;
+        SortedRecord::read(&mut self.reader, descending).map(Some)
+    }
+}
+
+fn sort_key(
+    entry: &MftEntry,
+    by: SortBy,
+    parser: &mut MftParser<impl ReadSeek>,
+    context: &mut EntryContext,
+) -> Option<SortKey> {
+    let standard_info = || {
+        entry
+            .iter_attributes_matching(Some(vec![MftAttributeType::StandardInformation]))
+            .find_map(|a| match a.ok()?.data {
+                MftAttributeContent::AttrX10(info) => Some(info),
+                _ => None,
+            })
+    };
+
+    match by {
+        SortBy::Path => context
+            .paths
+            .full_path(entry, parser)
+            .map(|path| SortKey::Path(path.to_string_lossy().to_string())),
+        SortBy::Created => standard_info().map(|info| SortKey::Time(info.created)),
+        SortBy::Modified => standard_info().map(|info| SortKey::Time(info.modified)),
+        SortBy::Size => Some(SortKey::Size(data_sizes(entry).0)),
+    }
+}
+
+/// Returns the record numbers of the MFT (except zeroed records), ordered by `by`.
+/// Records without a key (unreadable, or missing the attribute) are always last.
+/// Records with equal keys stay in record number order.
+pub fn sorted_records(
+    parser: &mut MftParser<impl ReadSeek>,
+    context: &mut EntryContext,
+    by: SortBy,
+    descending: bool,
+) -> io::Result<Vec<u64>> {
+    let mut records = Vec::with_capacity(RUN_SIZE.min(parser.get_entry_count() as usize));
+    let mut runs = vec![];
+
+    for i in 0..parser.get_entry_count() {
+        let key = match parser.read_entry(i) {
+            Ok(entry) if &entry.header.signature == ZERO_HEADER => continue,
+            Ok(entry) => sort_key(&entry, by, parser, context),
+            Err(_) => None,
+        };
+
+        records.push(SortedRecord {
+            key,
+            entry_id: i,
+            descending,
+        });
+        if records.len() == RUN_SIZE {
+            runs.push(Run::write(&mut records)?);
+        }
+    }
+
+    // Small MFTs are sorted in memory.
+    if runs.is_empty() {
+        records.sort();
+        return Ok(records.into_iter().map(|record| record.entry_id).collect());
+    }
+    if !records.is_empty() {
+        runs.push(Run::write(&mut records)?);
+    }
+
+    // Merges the runs, with a min-heap of the next record of every run.
+    let mut next = BinaryHeap::with_capacity(runs.len());
+    for (i, run) in runs.iter_mut().enumerate() {
+        if let Some(record) = run.next(descending)? {
+            next.push(Reverse((record, i)));
+        }
+    }
+
+    let mut sorted = vec![];
+    while let Some(Reverse((record, i))) = next.pop() {
+        sorted.push(record.entry_id);
+        if let Some(record) = runs[i].next(descending)? {
+            next.push(Reverse((record, i)));
+        }
+    }
+
+    Ok(sorted)
+}
+
+/// Returns the record numbers of the `n` largest files (by logical size of the unnamed $DATA stream), largest first.
+/// Directories and extension records are skipped, deleted files are included.
+/// Files with equal sizes stay in record number order.
+pub fn largest_records(parser: &mut MftParser<impl ReadSeek>, n: usize) -> Vec<u64> {
+    // A min-heap of the largest files seen so far, whose top is the smallest of them.
+    let mut largest: BinaryHeap<Reverse<(u64, Reverse<u64>)>> = BinaryHeap::with_capacity(n + 1);
+
+    for i in 0..parser.get_entry_count() {
+        let entry = match parser.read_entry(i) {
+            Ok(entry) if &entry.header.signature != ZERO_HEADER => entry,
+            _ => continue,
+        };
+
+        if entry.header.base_reference.entry != 0 || entry.header.flags.contains(EntryFlags::INDEX_PRESENT) {
+            continue;
+        }
+
+        largest.push(Reverse((data_sizes(&entry).0, Reverse(i))));
+        if largest.len() > n {
+            largest.pop();
+        }
+    }
+
+    largest
+        .into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((_, Reverse(i)))| i)
+        .collect()
+}
@@ -0,0 +1,223 @@
+//! Summaries of the MFT, computed in a single pass.
+use crate::entry::data_sizes;
+use crate::parser::MftParser;
+use crate::path::{NamePreference, PathResolver, PATHS_SEPARATOR};
+use crate::ReadSeek;
+
+use mft::attribute::MftAttributeType;
+use mft::entry::{EntryFlags, ZERO_HEADER};
+use mft::MftEntry;
+use num_traits::FromPrimitive;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Totals {
+    pub count: u64,
+    pub total_size: u64,
+}
+
+impl Totals {
+    fn add(&mut self, size: u64) {
+        self.count += 1;
+        self.total_size += size;
+    }
+
+    fn to_dict(self, py: Python) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("count", self.count)?;
+        dict.set_item("total_size", self.total_size)?;
+
+        Ok(dict.to_object(py))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExtensionStats {
+    pub allocated: Totals,
+    pub deleted: Totals,
+}
+
+impl ExtensionStats {
+    /// Converts to a dict of `count` and `total_size`,
+    /// or to a dict of those for `allocated` and `deleted` files if `split_deleted` is set.
+    pub fn to_dict(self, py: Python, split_deleted: bool) -> PyResult<PyObject> {
+        if !split_deleted {
+            return Totals {
+                count: self.allocated.count + self.deleted.count,
+                total_size: self.allocated.total_size + self.deleted.total_size,
+            }
+            .to_dict(py);
+        }
+
+        let dict = PyDict::new(py);
+        dict.set_item("allocated", self.allocated.to_dict(py)?)?;
+        dict.set_item("deleted", self.deleted.to_dict(py)?)?;
+
+        Ok(dict.to_object(py))
+    }
+}
+
+/// The lowercase extension of a file name, or an empty string if it has none.
+fn extension(name: &str) -> String {
+    match name.rfind('.') {
+        Some(position) if position > 0 => name[position + 1..].to_lowercase(),
+        _ => String::new(),
+    }
+}
+
+/// Counts the files (not directories) of the MFT, and sums the sizes of their unnamed $DATA streams,
+/// by extension of their preferred name.
+pub fn extension_stats(
+    parser: &mut MftParser<impl ReadSeek>,
+    preference: NamePreference,
+) -> BTreeMap<String, ExtensionStats> {
+    let mut stats: BTreeMap<String, ExtensionStats> = BTreeMap::new();
+
+    for i in 0..parser.get_entry_count() {
+        let entry = match parser.read_entry(i) {
+            Ok(entry) if &entry.header.signature != ZERO_HEADER => entry,
+            _ => continue,
+        };
+
+        if entry.is_dir() {
+            continue;
+        }
+
+        // Extension records do not have a name of their own.
+        let name = match preference.pick(&entry) {
+            Some(name) => name,
+            None => continue,
+        };
+
+        let extension_stats = stats.entry(extension(&name.name)).or_default();
+        let (file_size, _) = data_sizes(&entry);
+
+        if entry.header.flags.contains(EntryFlags::ALLOCATED) {
+            extension_stats.allocated.add(file_size);
+        } else {
+            extension_stats.deleted.add(file_size);
+        }
+    }
+
+    stats
+}
+
+/// Groups the records (allocated or deleted) which resolve to the same full path, ordered by path.
+/// Every path of an entry is considered when all names are resolved. Extension records are skipped,
+/// since they resolve to the path of their base record.
+pub fn duplicate_paths(
+    parser: &mut MftParser<impl ReadSeek>,
+    paths: &mut PathResolver,
+    case_sensitive: bool,
+) -> Vec<(String, Vec<u64>)> {
+    // By path (lowercase if not case sensitive): the first path seen, and the records.
+    let mut records: BTreeMap<String, (String, Vec<u64>)> = BTreeMap::new();
+
+    for i in 0..parser.get_entry_count() {
+        let entry = match parser.read_entry(i) {
+            Ok(entry) if &entry.header.signature != ZERO_HEADER => entry,
+            _ => continue,
+        };
+
+        if entry.header.base_reference.entry != 0 {
+            continue;
+        }
+
+        let full_path = match paths.full_path(&entry, parser) {
+            Some(full_path) => full_path.to_string_lossy().to_string(),
+            None => continue,
+        };
+
+        for path in full_path.split(PATHS_SEPARATOR) {
+            let key = if case_sensitive { path.to_string() } else { path.to_lowercase() };
+            let (_, ids) = records.entry(key).or_insert_with(|| (path.to_string(), vec![]));
+
+            if !ids.contains(&i) {
+                ids.push(i);
+            }
+        }
+    }
+
+    records
+        .into_values()
+        .filter(|(_, ids)| ids.len() > 1)
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AttributeTypeStats {
+    pub resident: Totals,
+    pub non_resident: Totals,
+}
+
+impl AttributeTypeStats {
+    /// Converts to a dict of `name` (e.g. `DATA`, or None for unknown type codes),
+    /// and of `count` and `total_size` for `resident` and `non_resident` instances.
+    pub fn to_dict(self, py: Python, type_code: u32) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("name", MftAttributeType::from_u32(type_code).map(|t| format!("{:?}", t)))?;
+        dict.set_item("resident", self.resident.to_dict(py)?)?;
+        dict.set_item("non_resident", self.non_resident.to_dict(py)?)?;
+
+        Ok(dict.to_object(py))
+    }
+}
+
+/// The type code, residence and content size of every attribute record of an entry.
+/// Records are read from their headers, so that attributes whose content fails to parse are counted too.
+fn attribute_records(entry: &MftEntry) -> Vec<(u32, bool, u64)> {
+    let data = &entry.data;
+    let end = (entry.header.used_entry_size as usize).min(data.len());
+    let mut offset = entry.header.first_attribute_record_offset as usize;
+    let mut records = vec![];
+
+    while offset + 16 <= end {
+        let u32_at = |o: usize| u32::from_le_bytes(data[o..o + 4].try_into().unwrap());
+        let type_code = u32_at(offset);
+        let length = u32_at(offset + 4) as usize;
+        if type_code == 0xFFFF_FFFF || length == 0 || offset + length > end {
+            break;
+        }
+
+        // The content size is read from the resident or non-resident header, which must fit in the attribute.
+        let resident = data[offset + 8] == 0;
+        let (size_offset, size_length) = if resident { (0x10, 4) } else { (0x30, 8) };
+        if size_offset + size_length <= length {
+            let mut size = [0; 8];
+            size[..size_length].copy_from_slice(&data[offset + size_offset..offset + size_offset + size_length]);
+            records.push((type_code, resident, u64::from_le_bytes(size)));
+        }
+
+        offset += length;
+    }
+
+    records
+}
+
+/// Counts the resident and non-resident attributes of every record (including deleted and extension records),
+/// and sums their content sizes (the real size of non-resident attributes), by attribute type code.
+pub fn attribute_type_stats(parser: &mut MftParser<impl ReadSeek>) -> BTreeMap<u32, AttributeTypeStats> {
+    let mut stats: BTreeMap<u32, AttributeTypeStats> = BTreeMap::new();
+
+    for i in 0..parser.get_entry_count() {
+        let entry = match parser.read_entry(i) {
+            Ok(entry) if &entry.header.signature != ZERO_HEADER => entry,
+            _ => continue,
+        };
+
+        for (type_code, resident, size) in attribute_records(&entry) {
+            let type_stats = stats.entry(type_code).or_default();
+            if resident {
+                type_stats.resident.add(size);
+            } else {
+                type_stats.non_resident.add(size);
+            }
+        }
+    }
+
+    stats
+}
@@ -0,0 +1,499 @@
+//! `mft.testing`, a builder of small synthetic MFTs, for fixtures which can be shipped with tests
+//! (unlike MFTs of real volumes).
+use crate::export::ExportOutput;
+
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{IntoPyDict, PyBytes, PyDateTime};
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// The entry number of the root directory.
+const ROOT: u64 = 5;
+/// The first entry number which is not reserved for the metadata files.
+const FIRST_USER_ENTRY: u64 = 16;
+/// The size of the clusters of non-resident attributes.
+const CLUSTER_SIZE: u64 = 4096;
+/// 2020-01-01T00:00:00Z, the default of every timestamp.
+const DEFAULT_TIMESTAMP: u64 = 132_223_104_000_000_000;
+
+const SECTOR_SIZE: usize = 512;
+const UPDATE_SEQUENCE_NUMBER: u16 = 1;
+
+const STANDARD_INFORMATION: u32 = 0x10;
+const FILE_NAME: u32 = 0x30;
+const DATA: u32 = 0x80;
+const INDEX_ROOT: u32 = 0x90;
+const END: u32 = 0xFFFF_FFFF;
+
+const ALLOCATED: u16 = 0x01;
+const INDEX_PRESENT: u16 = 0x02;
+/// The flag of directories in $FILE_NAME.
+const FILE_NAME_INDEX_PRESENT: u32 = 0x1000_0000;
+const HIDDEN_SYSTEM: u32 = 0x06;
+const WIN32_NAMESPACE: u8 = 1;
+
+#[derive(Clone)]
+struct Record {
+    sequence: u16,
+    name: String,
+    parent: u64,
+    directory: bool,
+    deleted: bool,
+    /// Resident data, or the size of non-resident (sparse) data.
+    data: Option<Vec<u8>>,
+    size: Option<u64>,
+    /// Created, modified, MFT modified and accessed, as FILETIMEs.
+    timestamps: [u64; 4],
+    file_attributes: u32,
+}
+
+#[pyclass]
+pub struct PyMftBuilder {
+    entry_size: usize,
+    timestamp: u64,
+    records: BTreeMap<u64, Record>,
+}
+
+/// Converts a `datetime` (naive ones are UTC) or an integer FILETIME to a FILETIME.
+fn filetime(py: Python, value: &PyAny) -> PyResult<u64> {
+    if let Ok(filetime) = value.extract::<u64>() {
+        return Ok(filetime);
+    }
+
+    let datetime = py.import("datetime")?;
+    if !value.is_instance::<PyDateTime>()? {
+        return Err(PyTypeError::new_err(format!(
+            "timestamps must be datetimes or FILETIME integers, got `{}`",
+            value.repr()?
+        )));
+    }
+
+    let utc = datetime.getattr("timezone")?.getattr("utc")?;
+    let value = if value.getattr("tzinfo")?.is_none() {
+        value.call_method("replace", (), Some([("tzinfo", utc)].into_py_dict(py)))?
+    } else {
+        value
+    };
+    let epoch = datetime
+        .getattr("datetime")?
+        .call((1601, 1, 1), Some([("tzinfo", utc)].into_py_dict(py)))?;
+    let delta = value.call_method1("__sub__", (epoch,))?;
+
+    let days: i64 = delta.getattr("days")?.extract()?;
+    let seconds: i64 = delta.getattr("seconds")?.extract()?;
+    let microseconds: i64 = delta.getattr("microseconds")?.extract()?;
+    if days < 0 {
+        return Err(PyValueError::new_err("timestamps cannot be before 1601-01-01"));
+    }
+
+    Ok(((days * 86_400 + seconds) * 10_000_000 + microseconds * 10) as u64)
+}
+
+/// The header of a resident attribute, followed by its name and its content.
+fn resident_attribute(type_code: u32, name: &str, content: &[u8], indexed: bool, instance: u16) -> Vec<u8> {
+    let name: Vec<u8> = name.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let content_offset = align(0x18 + name.len());
+    let length = align(content_offset + content.len());
+
+    let mut attribute = vec![0; length];
+    attribute[0..4].copy_from_slice(&type_code.to_le_bytes());
+    attribute[4..8].copy_from_slice(&(length as u32).to_le_bytes());
+    attribute[9] = (name.len() / 2) as u8;
+    attribute[0x0A..0x0C].copy_from_slice(&0x18u16.to_le_bytes());
+    attribute[0x0E..0x10].copy_from_slice(&instance.to_le_bytes());
+    attribute[0x10..0x14].copy_from_slice(&(content.len() as u32).to_le_bytes());
+    attribute[0x14..0x16].copy_from_slice(&(content_offset as u16).to_le_bytes());
+    attribute[0x16] = indexed as u8;
+    attribute[0x18..0x18 + name.len()].copy_from_slice(&name);
+    attribute[content_offset..content_offset + content.len()].copy_from_slice(content);
+    attribute
+}
+
+/// The header of an unnamed non-resident attribute of `size` bytes, stored as a single sparse run.
+fn sparse_attribute(type_code: u32, size: u64, instance: u16) -> Vec<u8> {
+    let clusters = size.div_ceil(CLUSTER_SIZE);
+    // The length of the run is signed, so it keeps a byte for its sign, and a run without an offset is sparse.
+    let length_size = (64 - clusters.leading_zeros() as usize) / 8 + 1;
+    let mut runlist = vec![length_size as u8];
+    runlist.extend(&clusters.to_le_bytes()[..length_size]);
+    runlist.push(0);
+
+    let length = align(0x40 + runlist.len());
+    let mut attribute = vec![0; length];
+    attribute[0..4].copy_from_slice(&type_code.to_le_bytes());
+    attribute[4..8].copy_from_slice(&(length as u32).to_le_bytes());
+    attribute[8] = 1;
+    attribute[0x0A..0x0C].copy_from_slice(&0x40u16.to_le_bytes());
+    attribute[0x0E..0x10].copy_from_slice(&instance.to_le_bytes());
+    attribute[0x18..0x20].copy_from_slice(&(clusters - 1).to_le_bytes());
+    attribute[0x20..0x22].copy_from_slice(&0x40u16.to_le_bytes());
+    attribute[0x28..0x30].copy_from_slice(&(clusters * CLUSTER_SIZE).to_le_bytes());
+    attribute[0x30..0x38].copy_from_slice(&size.to_le_bytes());
+    attribute[0x38..0x40].copy_from_slice(&size.to_le_bytes());
+    attribute[0x40..0x40 + runlist.len()].copy_from_slice(&runlist);
+    attribute
+}
+
+fn align(offset: usize) -> usize {
+    (offset + 7) & !7
+}
+
+impl Record {
+    fn logical_size(&self) -> u64 {
+        match (&self.data, self.size) {
+            (Some(data), _) => data.len() as u64,
+            (None, Some(size)) => size,
+            (None, None) => 0,
+        }
+    }
+
+    fn standard_information(&self) -> Vec<u8> {
+        let mut content = vec![0; 72];
+        for (i, timestamp) in self.timestamps.iter().enumerate() {
+            content[i * 8..i * 8 + 8].copy_from_slice(&timestamp.to_le_bytes());
+        }
+        content[0x20..0x24].copy_from_slice(&self.file_attributes.to_le_bytes());
+        content
+    }
+
+    fn file_name(&self, parent_sequence: u16) -> Vec<u8> {
+        let name: Vec<u8> = self.name.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let size = self.logical_size();
+        let flags = if self.directory {
+            self.file_attributes | FILE_NAME_INDEX_PRESENT
+        } else {
+            self.file_attributes
+        };
+
+        let mut content = vec![0; 0x42 + name.len()];
+        content[0..8].copy_from_slice(&(self.parent | u64::from(parent_sequence) << 48).to_le_bytes());
+        for (i, timestamp) in self.timestamps.iter().enumerate() {
+            content[8 + i * 8..16 + i * 8].copy_from_slice(&timestamp.to_le_bytes());
+        }
+        let allocated = size.div_ceil(CLUSTER_SIZE) * CLUSTER_SIZE;
+        content[0x28..0x30].copy_from_slice(&allocated.to_le_bytes());
+        content[0x30..0x38].copy_from_slice(&size.to_le_bytes());
+        content[0x38..0x3C].copy_from_slice(&flags.to_le_bytes());
+        content[0x40] = (name.len() / 2) as u8;
+        content[0x41] = WIN32_NAMESPACE;
+        content[0x42..].copy_from_slice(&name);
+        content
+    }
+
+    /// An empty $I30 index of file names.
+    fn index_root() -> Vec<u8> {
+        let mut content = vec![0; 0x30];
+        content[0..4].copy_from_slice(&FILE_NAME.to_le_bytes());
+        // COLLATION_FILENAME
+        content[4..8].copy_from_slice(&1u32.to_le_bytes());
+        content[8..0x0C].copy_from_slice(&(CLUSTER_SIZE as u32).to_le_bytes());
+        content[0x0C] = 1;
+        // The node header: the offset, size and allocated size of the entries.
+        content[0x10..0x14].copy_from_slice(&0x10u32.to_le_bytes());
+        content[0x14..0x18].copy_from_slice(&0x20u32.to_le_bytes());
+        content[0x18..0x1C].copy_from_slice(&0x20u32.to_le_bytes());
+        // The last entry, which has no key.
+        content[0x28..0x2A].copy_from_slice(&0x10u16.to_le_bytes());
+        content[0x2C] = 2;
+        content
+    }
+
+    fn to_bytes(&self, entry_id: u64, parent_sequence: u16, entry_size: usize) -> PyResult<Vec<u8>> {
+        let usa_size = entry_size / SECTOR_SIZE + 1;
+        let first_attribute_offset = align(0x30 + usa_size * 2);
+
+        let mut attributes = vec![
+            resident_attribute(STANDARD_INFORMATION, "", &self.standard_information(), false, 0),
+            resident_attribute(FILE_NAME, "", &self.file_name(parent_sequence), true, 1),
+        ];
+        if self.directory {
+            attributes.push(resident_attribute(INDEX_ROOT, "$I30", &Record::index_root(), false, 2));
+        } else {
+            attributes.push(match (&self.data, self.size) {
+                (None, Some(size)) if size > 0 => sparse_attribute(DATA, size, 2),
+                (data, _) => resident_attribute(DATA, "", data.as_deref().unwrap_or_default(), false, 2),
+            });
+        }
+
+        let used_size = first_attribute_offset + attributes.iter().map(Vec::len).sum::<usize>() + 8;
+        if used_size > entry_size {
+            return Err(PyValueError::new_err(format!(
+                "the attributes of entry {} ({} bytes) do not fit in a record of {} bytes",
+                entry_id, used_size, entry_size
+            )));
+        }
+
+        let flags = match (self.deleted, self.directory) {
+            (false, false) => ALLOCATED,
+            (false, true) => ALLOCATED | INDEX_PRESENT,
+            (true, false) => 0,
+            (true, true) => INDEX_PRESENT,
+        };
+
+        let mut record = vec![0; entry_size];
+        record[0..4].copy_from_slice(b"FILE");
+        record[4..6].copy_from_slice(&0x30u16.to_le_bytes());
+        record[6..8].copy_from_slice(&(usa_size as u16).to_le_bytes());
+        record[0x10..0x12].copy_from_slice(&self.sequence.to_le_bytes());
+        record[0x12..0x14].copy_from_slice(&1u16.to_le_bytes());
+        record[0x14..0x16].copy_from_slice(&(first_attribute_offset as u16).to_le_bytes());
+        record[0x16..0x18].copy_from_slice(&flags.to_le_bytes());
+        record[0x18..0x1C].copy_from_slice(&(used_size as u32).to_le_bytes());
+        record[0x1C..0x20].copy_from_slice(&(entry_size as u32).to_le_bytes());
+        record[0x28..0x2A].copy_from_slice(&(attributes.len() as u16).to_le_bytes());
+        record[0x2C..0x30].copy_from_slice(&(entry_id as u32).to_le_bytes());
+
+        let mut offset = first_attribute_offset;
+        for attribute in attributes {
+            record[offset..offset + attribute.len()].copy_from_slice(&attribute);
+            offset += attribute.len();
+        }
+        record[offset..offset + 4].copy_from_slice(&END.to_le_bytes());
+
+        // The update sequence array keeps the last two bytes of every sector, which are replaced by its number.
+        record[0x30..0x32].copy_from_slice(&UPDATE_SEQUENCE_NUMBER.to_le_bytes());
+        for sector in 0..entry_size / SECTOR_SIZE {
+            let end = (sector + 1) * SECTOR_SIZE - 2;
+            let usa_offset = 0x32 + sector * 2;
+            record.copy_within(end..end + 2, usa_offset);
+            record[end..end + 2].copy_from_slice(&UPDATE_SEQUENCE_NUMBER.to_le_bytes());
+        }
+
+        Ok(record)
+    }
+}
+
+#[pymethods]
+impl PyMftBuilder {
+    /// PyMftBuilder(entry_size=1024, timestamp=None, /)
+    /// --
+    ///
+    /// A builder of synthetic MFTs, with valid FILE records which `PyMftParser` (and other tools) can parse.
+    /// Every MFT has the records of `$MFT` (entry 0) and of the root directory `.` (entry 5),
+    /// the other entries up to the last added one are empty (zeroed) records.
+    /// `timestamp` (a `datetime`, UTC if naive, or a FILETIME integer) is the default of every timestamp,
+    /// 2020-01-01T00:00:00Z if not given, so that the built MFTs are reproducible.
+    #[new]
+    #[args(entry_size = "1024", timestamp = "None")]
+    fn new(py: Python, entry_size: usize, timestamp: Option<PyObject>) -> PyResult<Self> {
+        if entry_size != 1024 && entry_size != 4096 {
+            return Err(PyValueError::new_err(format!(
+                "entry_size must be 1024 or 4096, got {}",
+                entry_size
+            )));
+        }
+        let timestamp = timestamp.map(|t| filetime(py, t.as_ref(py))).transpose()?.unwrap_or(DEFAULT_TIMESTAMP);
+
+        let mut builder = PyMftBuilder {
+            entry_size,
+            timestamp,
+            records: BTreeMap::new(),
+        };
+        let metadata = |name: &str, sequence, directory| Record {
+            sequence,
+            name: name.to_string(),
+            parent: ROOT,
+            directory,
+            deleted: false,
+            data: None,
+            size: None,
+            timestamps: [timestamp; 4],
+            file_attributes: HIDDEN_SYSTEM,
+        };
+        builder.records.insert(0, metadata("$MFT", 1, false));
+        builder.records.insert(ROOT, metadata(".", ROOT as u16, true));
+
+        Ok(builder)
+    }
+
+    /// add_file(self, name, parent=5, data=None, size=None, created=None, modified=None, mft_modified=None,
+    ///          accessed=None, deleted=False, file_attributes=0, entry_id=None, sequence=1, /)
+    /// --
+    ///
+    /// Adds a file named `name` in the directory of the entry `parent` (the root by default), and returns its entry number:
+    /// `entry_id`, or the first free entry number from 16. `data` (bytes) is stored as resident $DATA;
+    /// otherwise `size` gives the size of non-resident (sparse) $DATA, whose content is not part of the MFT.
+    /// The four timestamps (`datetime`s or FILETIME integers) are written in both $STANDARD_INFORMATION and $FILE_NAME.
+    /// A `deleted` file has an unallocated record. `file_attributes` are the $STANDARD_INFORMATION flags
+    /// (such as `FileAttributes.ARCHIVE`), and `sequence` the sequence number of the record.
+    #[args(
+        parent = "ROOT",
+        data = "None",
+        size = "None",
+        created = "None",
+        modified = "None",
+        mft_modified = "None",
+        accessed = "None",
+        deleted = "false",
+        file_attributes = "0",
+        entry_id = "None",
+        sequence = "1"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn add_file(
+        &mut self,
+        py: Python,
+        name: String,
+        parent: u64,
+        data: Option<Vec<u8>>,
+        size: Option<u64>,
+        created: Option<PyObject>,
+        modified: Option<PyObject>,
+        mft_modified: Option<PyObject>,
+        accessed: Option<PyObject>,
+        deleted: bool,
+        file_attributes: u32,
+        entry_id: Option<u64>,
+        sequence: u16,
+    ) -> PyResult<u64> {
+        if data.is_some() && size.is_some() {
+            return Err(PyValueError::new_err("only one of data and size can be given"));
+        }
+        let timestamps = self.timestamps(py, [created, modified, mft_modified, accessed])?;
+
+        self.add(
+            entry_id,
+            Record {
+                sequence,
+                name,
+                parent,
+                directory: false,
+                deleted,
+                data,
+                size,
+                timestamps,
+                file_attributes,
+            },
+        )
+    }
+
+    /// add_directory(self, name, parent=5, created=None, modified=None, mft_modified=None, accessed=None,
+    ///               deleted=False, file_attributes=0, entry_id=None, sequence=1, /)
+    /// --
+    ///
+    /// Adds an (empty) directory named `name`, and returns its entry number. Its entries are added with the number
+    /// as their `parent`. The options are the same as in `add_file`.
+    #[args(
+        parent = "ROOT",
+        created = "None",
+        modified = "None",
+        mft_modified = "None",
+        accessed = "None",
+        deleted = "false",
+        file_attributes = "0",
+        entry_id = "None",
+        sequence = "1"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn add_directory(
+        &mut self,
+        py: Python,
+        name: String,
+        parent: u64,
+        created: Option<PyObject>,
+        modified: Option<PyObject>,
+        mft_modified: Option<PyObject>,
+        accessed: Option<PyObject>,
+        deleted: bool,
+        file_attributes: u32,
+        entry_id: Option<u64>,
+        sequence: u16,
+    ) -> PyResult<u64> {
+        let timestamps = self.timestamps(py, [created, modified, mft_modified, accessed])?;
+
+        self.add(
+            entry_id,
+            Record {
+                sequence,
+                name,
+                parent,
+                directory: true,
+                deleted,
+                data: None,
+                size: None,
+                timestamps,
+                file_attributes,
+            },
+        )
+    }
+
+    /// to_bytes(self, /)
+    /// --
+    ///
+    /// Returns the MFT, as stored on disk (with the update sequence numbers at the end of every sector).
+    fn to_bytes(&self, py: Python) -> PyResult<PyObject> {
+        Ok(PyBytes::new(py, &self.build()?).to_object(py))
+    }
+
+    /// write(self, path_or_file_like, /)
+    /// --
+    ///
+    /// Writes the MFT to a path or a file-like object.
+    fn write(&self, path_or_file_like: PyObject) -> PyResult<()> {
+        let mft = self.build()?;
+
+        let mut output = ExportOutput::open(path_or_file_like, None)?;
+        output.writer.write_all(&mft)?;
+        Ok(output.close()?)
+    }
+}
+
+impl PyMftBuilder {
+    fn timestamps(&self, py: Python, timestamps: [Option<PyObject>; 4]) -> PyResult<[u64; 4]> {
+        let mut filetimes = [self.timestamp; 4];
+        for (i, timestamp) in timestamps.iter().enumerate() {
+            if let Some(timestamp) = timestamp {
+                filetimes[i] = filetime(py, timestamp.as_ref(py))?;
+            }
+        }
+        Ok(filetimes)
+    }
+
+    fn add(&mut self, entry_id: Option<u64>, record: Record) -> PyResult<u64> {
+        if record.name.is_empty() || record.name.encode_utf16().count() > 255 {
+            return Err(PyValueError::new_err(format!(
+                "names must have 1 to 255 characters, got `{}`",
+                record.name
+            )));
+        }
+
+        let entry_id = match entry_id {
+            Some(entry_id) if self.records.contains_key(&entry_id) => {
+                return Err(PyValueError::new_err(format!("entry {} was already added", entry_id)))
+            }
+            Some(entry_id) if entry_id > u64::from(u32::MAX) => {
+                return Err(PyValueError::new_err(format!("entry {} is out of range", entry_id)))
+            }
+            Some(entry_id) => entry_id,
+            None => (FIRST_USER_ENTRY..)
+                .find(|entry_id| !self.records.contains_key(entry_id))
+                .unwrap_or_default(),
+        };
+        self.records.insert(entry_id, record);
+
+        Ok(entry_id)
+    }
+
+    fn build(&self) -> PyResult<Vec<u8>> {
+        let last = self.records.keys().next_back().copied().unwrap_or_default();
+        let mut mft = vec![0; (last as usize + 1) * self.entry_size];
+
+        for (entry_id, record) in &self.records {
+            let parent_sequence = self.records.get(&record.parent).map_or(0, |parent| parent.sequence);
+            let bytes = if *entry_id == 0 {
+                // The $DATA of $MFT covers the records of the whole MFT.
+                let size = Some(mft.len() as u64);
+                Record { size, ..record.clone() }.to_bytes(0, parent_sequence, self.entry_size)?
+            } else {
+                record.to_bytes(*entry_id, parent_sequence, self.entry_size)?
+            };
+
+            let start = *entry_id as usize * self.entry_size;
+            mft[start..start + self.entry_size].copy_from_slice(&bytes);
+        }
+
+        Ok(mft)
+    }
+}
@@ -0,0 +1,251 @@
+//! Expansion of entries into timeline events, one per timestamp.
+use crate::entry::{file_names, EntryContext};
+use crate::flags::{file_attribute_names, FLAG_NAMES_SEPARATOR};
+use crate::parser::MftParser;
+use crate::utils::date_to_pyobject;
+use crate::ReadSeek;
+
+use chrono::{DateTime, Utc};
+use mft::attribute::{FileAttributeFlags, MftAttributeType};
+use mft::entry::EntryFlags;
+use mft::MftEntry;
+use pyo3::prelude::*;
+use serde::Serialize;
+
+use std::path::PathBuf;
+
+#[derive(Debug, Clone)]
+pub struct TimelineEvent {
+    pub timestamp: DateTime<Utc>,
+    /// Either `$SI` or `$FN`
+    pub source: &'static str,
+    /// One of `M` (modified), `A` (accessed), `C` (entry changed) or `B` (born)
+    pub macb: &'static str,
+    pub entry_id: u64,
+    pub sequence: u16,
+    pub full_path: String,
+    pub file_flags: FileAttributeFlags,
+    pub is_deleted: bool,
+}
+
+pub const MACB: [&str; 4] = ["M", "A", "C", "B"];
+
+/// The timestamps of an attribute, in M/A/C/B order.
+pub struct AttributeTimestamps {
+    pub source: &'static str,
+    /// The name of a $FILE_NAME, empty for $STANDARD_INFORMATION.
+    pub name: String,
+    pub file_flags: FileAttributeFlags,
+    pub times: [DateTime<Utc>; 4],
+}
+
+/// Returns the timestamps of the $STANDARD_INFORMATION of the entry, followed by those of every $FILE_NAME.
+pub fn attribute_timestamps(entry: &MftEntry) -> Vec<AttributeTimestamps> {
+    let mut timestamps = vec![];
+
+    if let Some(info) = entry
+        .iter_attributes_matching(Some(vec![MftAttributeType::StandardInformation]))
+        .filter_map(Result::ok)
+        .find_map(|a| a.data.into_standard_info())
+    {
+        timestamps.push(AttributeTimestamps {
+            source: "$SI",
+            name: String::new(),
+            file_flags: info.file_flags,
+            times: [info.modified, info.accessed, info.mft_modified, info.created],
+        });
+    }
+
+    for name in file_names(entry) {
+        timestamps.push(AttributeTimestamps {
+            source: "$FN",
+            name: name.name.clone(),
+            file_flags: name.flags,
+            times: [name.modified, name.accessed, name.mft_modified, name.created],
+        });
+    }
+
+    timestamps
+}
+
+pub fn resolved_path(entry: &MftEntry, parser: &mut MftParser<impl ReadSeek>, context: &mut EntryContext) -> String {
+    context
+        .paths
+        .full_path(entry, parser)
+        .unwrap_or_else(|| PathBuf::from("[UNKNOWN]"))
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Returns the $STANDARD_INFORMATION events of the entry, followed by the events of every $FILE_NAME.
+pub fn timeline_events(
+    entry: &MftEntry,
+    parser: &mut MftParser<impl ReadSeek>,
+    context: &mut EntryContext,
+) -> Vec<TimelineEvent> {
+    let full_path = resolved_path(entry, parser, context);
+
+    let mut events = vec![];
+    for AttributeTimestamps {
+        source,
+        file_flags,
+        times,
+        ..
+    } in attribute_timestamps(entry)
+    {
+        for (macb, timestamp) in MACB.iter().zip(times.iter()) {
+            events.push(TimelineEvent {
+                timestamp: *timestamp,
+                source,
+                macb,
+                entry_id: entry.header.record_number,
+                sequence: entry.header.sequence,
+                full_path: full_path.clone(),
+                file_flags,
+                is_deleted: !entry.header.flags.contains(EntryFlags::ALLOCATED),
+            });
+        }
+    }
+
+    events
+}
+
+/// Descriptions of the M/A/C/B timestamps, as used by plaso.
+const L2T_TYPES: [&str; 4] = [
+    "Content Modification Time",
+    "Last Access Time",
+    "Metadata Modification Time",
+    "Creation Time",
+];
+
+/// A row of the log2timeline (l2t_csv) format.
+#[derive(Serialize)]
+pub struct L2tRow {
+    pub date: String,
+    pub time: String,
+    pub timezone: &'static str,
+    #[serde(rename = "MACB")]
+    pub macb: String,
+    pub source: &'static str,
+    pub sourcetype: &'static str,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub user: &'static str,
+    pub host: &'static str,
+    pub short: String,
+    pub desc: String,
+    pub version: u8,
+    pub filename: String,
+    pub inode: u64,
+    pub notes: &'static str,
+    pub format: &'static str,
+    pub extra: String,
+}
+
+/// Returns the l2t_csv rows of the entry.
+/// Timestamps of the same attribute which are equal are merged into a single row.
+pub fn l2t_rows(
+    entry: &MftEntry,
+    parser: &mut MftParser<impl ReadSeek>,
+    context: &mut EntryContext,
+) -> Vec<L2tRow> {
+    let full_path = resolved_path(entry, parser, context);
+    let is_deleted = !entry.header.flags.contains(EntryFlags::ALLOCATED);
+
+    let mut rows = vec![];
+    for attribute in attribute_timestamps(entry) {
+        let mut times: Vec<DateTime<Utc>> = attribute.times.to_vec();
+        times.sort();
+        times.dedup();
+
+        for timestamp in times {
+            let matching: Vec<usize> = (0..4).filter(|i| attribute.times[*i] == timestamp).collect();
+
+            let macb: String = (0..4)
+                .map(|i| if matching.contains(&i) { MACB[i] } else { "." })
+                .collect();
+            let kind = matching
+                .iter()
+                .map(|i| L2T_TYPES[*i])
+                .collect::<Vec<&str>>()
+                .join("; ");
+
+            rows.push(L2tRow {
+                date: timestamp.format("%m/%d/%Y").to_string(),
+                time: timestamp.format("%H:%M:%S").to_string(),
+                timezone: "UTC",
+                macb,
+                source: "FILE",
+                sourcetype: "NTFS $MFT",
+                kind,
+                user: "-",
+                host: "-",
+                short: full_path.clone(),
+                desc: format!(
+                    "{} File reference: {}-{} Attribute: {} Flags: {}",
+                    full_path,
+                    entry.header.record_number,
+                    entry.header.sequence,
+                    attribute.source,
+                    file_attribute_names(attribute.file_flags).join(FLAG_NAMES_SEPARATOR)
+                ),
+                version: 2,
+                filename: full_path.clone(),
+                inode: entry.header.record_number,
+                notes: if is_deleted { "Deleted" } else { "-" },
+                format: "pymft",
+                extra: "-".to_string(),
+            });
+        }
+    }
+
+    rows
+}
+
+#[pyclass]
+pub struct PyMftTimelineEvent {
+    /// Either `$SI` ($STANDARD_INFORMATION) or `$FN` ($FILE_NAME)
+    #[pyo3(get)]
+    pub source: String,
+    /// One of `M` (modified), `A` (accessed), `C` (entry changed) or `B` (born)
+    #[pyo3(get)]
+    pub macb: String,
+    #[pyo3(get)]
+    pub entry_id: u64,
+    #[pyo3(get)]
+    pub sequence: u16,
+    #[pyo3(get)]
+    pub full_path: String,
+    /// File attribute flags of the attribute holding the timestamp
+    #[pyo3(get)]
+    pub file_flags: u32,
+    #[pyo3(get)]
+    pub is_deleted: bool,
+    timestamp: DateTime<Utc>,
+}
+
+impl PyMftTimelineEvent {
+    pub fn from_event(py: Python, event: TimelineEvent) -> PyResult<Py<Self>> {
+        Py::new(
+            py,
+            PyMftTimelineEvent {
+                source: event.source.to_string(),
+                macb: event.macb.to_string(),
+                entry_id: event.entry_id,
+                sequence: event.sequence,
+                full_path: event.full_path,
+                file_flags: event.file_flags.bits(),
+                is_deleted: event.is_deleted,
+                timestamp: event.timestamp,
+            },
+        )
+    }
+}
+
+#[pymethods]
+impl PyMftTimelineEvent {
+    #[getter]
+    pub fn timestamp(&self) -> PyResult<PyObject> {
+        date_to_pyobject(&self.timestamp)
+    }
+}
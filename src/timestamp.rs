@@ -0,0 +1,105 @@
+//! Formatting of timestamps in serialized (JSON and CSV) output.
+use chrono::{DateTime, Utc};
+use pyo3::exceptions::PyValueError;
+use pyo3::PyResult;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+/// Names of the timestamp fields of $STANDARD_INFORMATION and $FILE_NAME attributes in JSON output.
+const TIMESTAMP_FIELDS: [&str; 4] = ["created", "modified", "mft_modified", "accessed"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TimestampFormat {
+    /// RFC 3339 strings, this is the default.
+    #[default]
+    Iso,
+    /// Seconds since the Unix epoch, as a float.
+    Epoch,
+    /// Milliseconds since the Unix epoch, as an integer.
+    EpochMillis,
+}
+
+impl TimestampFormat {
+    pub fn from_name(name: &str) -> PyResult<Self> {
+        match name.to_lowercase().as_str() {
+            "iso" => Ok(TimestampFormat::Iso),
+            "epoch" => Ok(TimestampFormat::Epoch),
+            "epoch_ms" => Ok(TimestampFormat::EpochMillis),
+            _ => Err(PyValueError::new_err(format!(
+                "timestamp_format must be one of `iso`, `epoch`, `epoch_ms`, got `{}`",
+                name
+            ))),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TimestampFormat::Iso => "iso",
+            TimestampFormat::Epoch => "epoch",
+            TimestampFormat::EpochMillis => "epoch_ms",
+        }
+    }
+
+    pub fn to_json(self, timestamp: &DateTime<Utc>) -> Value {
+        match self {
+            TimestampFormat::Iso => serde_json::json!(timestamp),
+            TimestampFormat::Epoch => serde_json::json!(
+                timestamp.timestamp() as f64 + f64::from(timestamp.timestamp_subsec_nanos()) / 1e9
+            ),
+            TimestampFormat::EpochMillis => serde_json::json!(timestamp.timestamp_millis()),
+        }
+    }
+
+    /// Rewrites the timestamps of the attributes of a serialized entry (which are RFC 3339 strings).
+    pub fn convert_json(self, value: &mut Value) {
+        if self == TimestampFormat::Iso {
+            return;
+        }
+
+        match value {
+            Value::Object(object) => {
+                for (key, value) in object.iter_mut() {
+                    let timestamp = match value {
+                        Value::String(s) if TIMESTAMP_FIELDS.contains(&key.as_str()) => {
+                            DateTime::parse_from_rfc3339(s).ok()
+                        }
+                        _ => None,
+                    };
+
+                    match timestamp {
+                        Some(timestamp) => *value = self.to_json(&timestamp.with_timezone(&Utc)),
+                        None => self.convert_json(value),
+                    }
+                }
+            }
+            Value::Array(values) => values.iter_mut().for_each(|v| self.convert_json(v)),
+            _ => {}
+        }
+    }
+
+    pub fn format(self, timestamp: DateTime<Utc>) -> Timestamp {
+        Timestamp {
+            timestamp,
+            format: self,
+        }
+    }
+}
+
+/// A timestamp which serializes according to its format.
+#[derive(Debug, Clone, Copy)]
+pub struct Timestamp {
+    timestamp: DateTime<Utc>,
+    format: TimestampFormat,
+}
+
+impl Timestamp {
+    pub fn datetime(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.format.to_json(&self.timestamp).serialize(serializer)
+    }
+}
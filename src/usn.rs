@@ -0,0 +1,424 @@
+use crate::path::{sequence_matches, PathResolver};
+use crate::utils::{date_to_pyobject, u16_at, u32_at, u64_at, utf16_to_string, FileOrFileLike};
+use crate::{PyMftParser, ReadSeek};
+
+use bitflags::bitflags;
+use chrono::{DateTime, Utc};
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3::PyIterProtocol;
+use winstructs::ntfs::mft_reference::MftReference;
+use winstructs::timestamp::WinTimestamp;
+
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+
+/// Records are never larger than a journal page.
+const MAX_RECORD_SIZE: usize = 0x1_0000;
+const RECORD_V2_MIN_SIZE: usize = 60;
+const RECORD_V3_MIN_SIZE: usize = 76;
+
+bitflags! {
+    /// https://docs.microsoft.com/en-us/windows/win32/api/winioctl/ns-winioctl-usn_record_v2
+    pub struct UsnReasonFlags: u32 {
+        const DATA_OVERWRITE         = 0x0000_0001;
+        const DATA_EXTEND            = 0x0000_0002;
+        const DATA_TRUNCATION        = 0x0000_0004;
+        const NAMED_DATA_OVERWRITE   = 0x0000_0010;
+        const NAMED_DATA_EXTEND      = 0x0000_0020;
+        const NAMED_DATA_TRUNCATION  = 0x0000_0040;
+        const FILE_CREATE            = 0x0000_0100;
+        const FILE_DELETE            = 0x0000_0200;
+        const EA_CHANGE              = 0x0000_0400;
+        const SECURITY_CHANGE        = 0x0000_0800;
+        const RENAME_OLD_NAME        = 0x0000_1000;
+        const RENAME_NEW_NAME        = 0x0000_2000;
+        const INDEXABLE_CHANGE       = 0x0000_4000;
+        const BASIC_INFO_CHANGE      = 0x0000_8000;
+        const HARD_LINK_CHANGE       = 0x0001_0000;
+        const COMPRESSION_CHANGE     = 0x0002_0000;
+        const ENCRYPTION_CHANGE      = 0x0004_0000;
+        const OBJECT_ID_CHANGE       = 0x0008_0000;
+        const REPARSE_POINT_CHANGE   = 0x0010_0000;
+        const STREAM_CHANGE          = 0x0020_0000;
+        const TRANSACTED_CHANGE      = 0x0040_0000;
+        const INTEGRITY_CHANGE       = 0x0080_0000;
+        const CLOSE                  = 0x8000_0000;
+    }
+}
+
+/// A single USN_RECORD_V2 or USN_RECORD_V3 from $Extend\$UsnJrnl:$J.
+#[derive(Debug, Clone)]
+pub struct UsnRecord {
+    pub major_version: u16,
+    pub entry_id: u64,
+    pub sequence: u16,
+    pub parent_entry_id: u64,
+    pub parent_entry_sequence: u16,
+    pub usn: i64,
+    pub timestamp: DateTime<Utc>,
+    pub reason: UsnReasonFlags,
+    pub source_info: u32,
+    pub security_id: u32,
+    pub file_attributes: u32,
+    pub file_name: String,
+}
+
+/// Splits a file reference into its entry number (lower 48 bits) and sequence (upper 16 bits).
+fn split_reference(reference: u64) -> (u64, u16) {
+    (reference & 0x0000_FFFF_FFFF_FFFF, (reference >> 48) as u16)
+}
+
+impl UsnRecord {
+    /// Parses a record from a buffer holding exactly one record.
+    /// Returns `None` if the buffer does not look like a supported USN record.
+    pub fn from_buffer(buf: &[u8]) -> Option<UsnRecord> {
+        let major_version = u16_at(buf, 4);
+
+        // Offsets of (file reference, parent reference, usn, name length) by version.
+        // V3 records use 128-bit file ids, of which NTFS only uses the lower 64 bits.
+        let (file_ref, parent_ref, usn, name_length) = match major_version {
+            2 if buf.len() >= RECORD_V2_MIN_SIZE => (8, 16, 24, 56),
+            3 if buf.len() >= RECORD_V3_MIN_SIZE => (8, 24, 40, 72),
+            _ => return None,
+        };
+
+        let (entry_id, sequence) = split_reference(u64_at(buf, file_ref));
+        let (parent_entry_id, parent_entry_sequence) = split_reference(u64_at(buf, parent_ref));
+
+        let name_size = u16_at(buf, name_length) as usize;
+        let name_offset = u16_at(buf, name_length + 2) as usize;
+        let name_bytes = buf.get(name_offset..name_offset + name_size)?;
+
+        let file_name = utf16_to_string(name_bytes);
+
+        Some(UsnRecord {
+            major_version,
+            entry_id,
+            sequence,
+            parent_entry_id,
+            parent_entry_sequence,
+            usn: u64_at(buf, usn) as i64,
+            timestamp: WinTimestamp::new(&buf[usn + 8..usn + 16]).ok()?.to_datetime(),
+            reason: UsnReasonFlags::from_bits_truncate(u32_at(buf, usn + 16)),
+            source_info: u32_at(buf, usn + 20),
+            security_id: u32_at(buf, usn + 24),
+            file_attributes: u32_at(buf, usn + 28),
+            file_name,
+        })
+    }
+}
+
+/// Reads USN records sequentially from a $J stream.
+/// The stream is usually sparse, so zeroed and unparsable regions are skipped 8 bytes at a time.
+pub struct UsnReader {
+    inner: Box<dyn ReadSeek + Send>,
+}
+
+impl UsnReader {
+    pub fn new(inner: Box<dyn ReadSeek + Send>) -> Self {
+        UsnReader { inner }
+    }
+
+    pub fn next_record(&mut self) -> io::Result<Option<UsnRecord>> {
+        let mut header = [0_u8; 8];
+
+        loop {
+            match self.inner.read_exact(&mut header) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(e),
+            }
+
+            let record_length = u32_at(&header, 0) as usize;
+            let major_version = u16_at(&header, 4);
+
+            if !(RECORD_V2_MIN_SIZE..=MAX_RECORD_SIZE).contains(&record_length)
+                || !record_length.is_multiple_of(8)
+                || !(2..=3).contains(&major_version)
+            {
+                continue;
+            }
+
+            let mut buf = vec![0_u8; record_length];
+            buf[..8].copy_from_slice(&header);
+            self.inner.read_exact(&mut buf[8..])?;
+
+            if let Some(record) = UsnRecord::from_buffer(&buf) {
+                return Ok(Some(record));
+            }
+        }
+    }
+}
+
+#[pyclass]
+/// PyUsnParser(self, path_or_file_like, /)
+/// --
+///
+/// Returns an instance of the parser for an extracted $Extend\$UsnJrnl:$J stream.
+/// Works on both a path (string), or a file-like object.
+pub struct PyUsnParser {
+    inner: Option<UsnReader>,
+}
+
+#[pymethods]
+impl PyUsnParser {
+    #[new]
+    fn new(path_or_file_like: PyObject) -> PyResult<Self> {
+        let boxed_read_seek = match FileOrFileLike::from_pyobject(path_or_file_like)? {
+            FileOrFileLike::File(s) => {
+                Box::new(BufReader::with_capacity(1 << 16, File::open(s)?)) as Box<dyn ReadSeek + Send>
+            }
+            FileOrFileLike::FileLike(f) => Box::new(f) as Box<dyn ReadSeek + Send>,
+            FileOrFileLike::Buffer(b) => Box::new(b) as Box<dyn ReadSeek + Send>,
+        };
+
+        Ok(PyUsnParser {
+            inner: Some(UsnReader::new(boxed_read_seek)),
+        })
+    }
+
+    /// records(self, /)
+    /// --
+    ///
+    /// Returns an iterator that yields the USN records as python objects.
+    fn records(&mut self) -> PyResult<Py<PyUsnRecordsIterator>> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        Py::new(
+            py,
+            PyUsnRecordsIterator {
+                inner: self.take_inner()?,
+            },
+        )
+    }
+
+    /// events(self, parser, /)
+    /// --
+    ///
+    /// Returns an iterator that yields the USN records joined with the entries of the given `PyMftParser`.
+    fn events(&mut self, parser: Py<PyMftParser>) -> PyResult<Py<PyUsnEventsIterator>> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let paths = parser.try_borrow(py)?.path_resolver();
+
+        Py::new(
+            py,
+            PyUsnEventsIterator {
+                inner: self.take_inner()?,
+                parser,
+                paths,
+            },
+        )
+    }
+
+    /// history(self, parser, /)
+    /// --
+    ///
+    /// Returns a dict mapping `(entry_id, sequence)` to the list of events of that file, in journal order.
+    /// This covers the create, rename and delete history of every file reference seen in the journal.
+    fn history(&mut self, parser: Py<PyMftParser>) -> PyResult<PyObject> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let mut inner = self.take_inner()?;
+        let mut paths = parser.try_borrow(py)?.path_resolver();
+        let history = PyDict::new(py);
+
+        while let Some(record) = inner.next_record()? {
+            let key = (record.entry_id, record.sequence);
+            let event = PyUsnEvent::from_record(py, record, &parser, &mut paths)?;
+
+            match history.get_item(key) {
+                Some(events) => {
+                    events.call_method1("append", (event,))?;
+                }
+                None => history.set_item(key, vec![event])?,
+            }
+        }
+
+        Ok(history.to_object(py))
+    }
+}
+
+impl PyUsnParser {
+    fn take_inner(&mut self) -> PyResult<UsnReader> {
+        self.inner.take().ok_or_else(|| {
+            PyErr::new::<exceptions::PyRuntimeError, _>("PyUsnParser can only be used once")
+        })
+    }
+}
+
+#[pyclass]
+pub struct PyUsnRecord {
+    #[pyo3(get)]
+    pub major_version: u16,
+    #[pyo3(get)]
+    pub usn: i64,
+    #[pyo3(get)]
+    pub entry_id: u64,
+    #[pyo3(get)]
+    pub sequence: u16,
+    #[pyo3(get)]
+    pub parent_entry_id: u64,
+    #[pyo3(get)]
+    pub parent_entry_sequence: u16,
+    /// Raw value of the reason flags
+    #[pyo3(get)]
+    pub reason_code: u32,
+    /// Names of the reason flags
+    #[pyo3(get)]
+    pub reason: String,
+    #[pyo3(get)]
+    pub source_info: u32,
+    #[pyo3(get)]
+    pub security_id: u32,
+    #[pyo3(get)]
+    pub file_attributes: u32,
+    #[pyo3(get)]
+    pub file_name: String,
+    timestamp: DateTime<Utc>,
+}
+
+impl PyUsnRecord {
+    pub fn from_record(py: Python, record: &UsnRecord) -> PyResult<Py<Self>> {
+        Py::new(
+            py,
+            PyUsnRecord {
+                major_version: record.major_version,
+                usn: record.usn,
+                entry_id: record.entry_id,
+                sequence: record.sequence,
+                parent_entry_id: record.parent_entry_id,
+                parent_entry_sequence: record.parent_entry_sequence,
+                reason_code: record.reason.bits(),
+                reason: format!("{:?}", record.reason),
+                source_info: record.source_info,
+                security_id: record.security_id,
+                file_attributes: record.file_attributes,
+                file_name: record.file_name.clone(),
+                timestamp: record.timestamp,
+            },
+        )
+    }
+}
+
+#[pymethods]
+impl PyUsnRecord {
+    #[getter]
+    pub fn timestamp(&self) -> PyResult<PyObject> {
+        date_to_pyobject(&self.timestamp)
+    }
+}
+
+#[pyclass]
+pub struct PyUsnEvent {
+    /// The underlying `PyUsnRecord`
+    #[pyo3(get)]
+    pub record: Py<PyUsnRecord>,
+    /// Path of the file at the time of the record, resolved through its parent reference.
+    /// Starts with the `orphan_prefix` of the parser if the parent record was reused since.
+    #[pyo3(get)]
+    pub full_path: String,
+    /// Full path of the MFT entry, if it still belongs to the same file (sequence numbers match,
+    /// or the entry was deleted since).
+    #[pyo3(get)]
+    pub current_path: Option<String>,
+    /// True if the MFT entry was since reallocated to a different file.
+    #[pyo3(get)]
+    pub entry_reused: bool,
+}
+
+impl PyUsnEvent {
+    /// Joins a record with the entries of `parser`, whose paths are resolved (and cached) by `paths`.
+    pub fn from_record(
+        py: Python,
+        record: UsnRecord,
+        parser: &Py<PyMftParser>,
+        paths: &mut PathResolver,
+    ) -> PyResult<Py<Self>> {
+        let mut parser = parser.try_borrow_mut(py)?;
+        let inner = parser.inner_mut();
+
+        let parent = MftReference::new(record.parent_entry_id, record.parent_entry_sequence);
+        let full_path = paths
+            .path_in_directory(parent, &record.file_name, inner)
+            .to_string_lossy()
+            .to_string();
+
+        let (current_path, entry_reused) = match inner.get_entry(record.entry_id) {
+            Ok(entry) if sequence_matches(record.sequence, entry.header.sequence, entry.is_allocated()) => (
+                paths
+                    .full_path(&entry, inner)
+                    .map(|p| p.to_string_lossy().to_string()),
+                false,
+            ),
+            Ok(_) => (None, true),
+            Err(_) => (None, false),
+        };
+
+        Py::new(
+            py,
+            PyUsnEvent {
+                record: PyUsnRecord::from_record(py, &record)?,
+                full_path,
+                current_path,
+                entry_reused,
+            },
+        )
+    }
+}
+
+#[pyclass]
+pub struct PyUsnRecordsIterator {
+    inner: UsnReader,
+}
+
+#[pyproto]
+impl PyIterProtocol for PyUsnRecordsIterator {
+    fn __iter__(slf: PyRefMut<Self>) -> PyResult<Py<PyUsnRecordsIterator>> {
+        Ok(slf.into())
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        match slf.inner.next_record() {
+            Ok(Some(record)) => Ok(Some(PyUsnRecord::from_record(py, &record)?.to_object(py))),
+            Ok(None) => Ok(None),
+            Err(e) => Ok(Some(PyErr::from(e).to_object(py))),
+        }
+    }
+}
+
+#[pyclass]
+pub struct PyUsnEventsIterator {
+    inner: UsnReader,
+    parser: Py<PyMftParser>,
+    paths: PathResolver,
+}
+
+#[pyproto]
+impl PyIterProtocol for PyUsnEventsIterator {
+    fn __iter__(slf: PyRefMut<Self>) -> PyResult<Py<PyUsnEventsIterator>> {
+        Ok(slf.into())
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let PyUsnEventsIterator { inner, parser, paths } = &mut *slf;
+
+        match inner.next_record() {
+            Ok(Some(record)) => match PyUsnEvent::from_record(py, record, parser, paths) {
+                Ok(event) => Ok(Some(event.to_object(py))),
+                Err(e) => Ok(Some(e.to_object(py))),
+            },
+            Ok(None) => Ok(None),
+            Err(e) => Ok(Some(PyErr::from(e).to_object(py))),
+        }
+    }
+}
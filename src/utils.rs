@@ -1,16 +1,32 @@
-use log::{Level, Log, Metadata, Record, SetLoggerError};
+use log::{Level, LevelFilter, Log, Metadata, Record};
 
 use chrono::{DateTime, Datelike, Timelike, Utc};
 use log::warn;
 use pyo3::types::{PyDateTime, PyString};
 use pyo3::ToPyObject;
+use pyo3::prelude::pyfunction;
 use pyo3::{PyObject, PyResult, Python};
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::{PyBufferError, PyValueError};
+use pyo3::{ffi, PyErr};
 use pyo3_file::PyFileLikeObject;
 
+use std::cell::Cell;
+use std::char::decode_utf16;
+use std::convert::TryInto;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::os::raw::{c_int, c_void};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::volume::resolve_seek;
+
 #[derive(Debug)]
 pub enum FileOrFileLike {
     File(String),
     FileLike(PyFileLikeObject),
+    /// An object supporting the buffer protocol (`bytes`, `bytearray`, `memoryview`, `mmap`, ...), read in place.
+    Buffer(BufferReader),
 }
 
 impl FileOrFileLike {
@@ -25,62 +41,242 @@ impl FileOrFileLike {
             ));
         }
 
+        if let Ok(buffer) = PyBuffer::<u8>::get(path_or_file_like.as_ref(py)) {
+            return BufferReader::new(buffer).map(FileOrFileLike::Buffer);
+        }
+
         // We only need read + seek
         match PyFileLikeObject::with_requirements(path_or_file_like, true, false, true) {
             Ok(f) => Ok(FileOrFileLike::FileLike(f)),
             Err(e) => Err(e),
         }
     }
+
+    /// Like `from_pyobject`, for output files (a file-like object only needs to be writable).
+    pub fn from_pyobject_for_writing(path_or_file_like: PyObject) -> PyResult<FileOrFileLike> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        if let Ok(string_ref) = path_or_file_like.cast_as::<PyString>(py) {
+            return Ok(FileOrFileLike::File(
+                string_ref.to_string_lossy().to_string(),
+            ));
+        }
+
+        PyFileLikeObject::with_requirements(path_or_file_like, false, true, false).map(FileOrFileLike::FileLike)
+    }
+}
+
+/// Reads the memory of a python buffer, without copying it.
+/// The buffer is held (and so can not be resized) for as long as the reader (or any of its clones) lives.
+/// Every clone keeps its own position.
+#[derive(Debug, Clone)]
+pub struct BufferReader {
+    buffer: Arc<PyBuffer<u8>>,
+    position: u64,
+}
+
+impl BufferReader {
+    pub fn new(buffer: PyBuffer<u8>) -> PyResult<Self> {
+        if !buffer.is_c_contiguous() {
+            return Err(PyValueError::new_err("buffer must be contiguous"));
+        }
+
+        Ok(BufferReader {
+            buffer: Arc::new(buffer),
+            position: 0,
+        })
+    }
+
+    pub fn size(&self) -> u64 {
+        self.buffer.len_bytes() as u64
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        if self.buffer.len_bytes() == 0 {
+            return &[];
+        }
+
+        // The memory is contiguous, and stays valid while the buffer is held.
+        unsafe { std::slice::from_raw_parts(self.buffer.buf_ptr() as *const u8, self.buffer.len_bytes()) }
+    }
+}
+
+impl Read for BufferReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let data = self.as_slice();
+        if self.position >= data.len() as u64 {
+            return Ok(0);
+        }
+
+        let remaining = &data[self.position as usize..];
+        let to_read = remaining.len().min(buf.len());
+        buf[..to_read].copy_from_slice(&remaining[..to_read]);
+        self.position += to_read as u64;
+
+        Ok(to_read)
+    }
 }
 
-/// A logger that prints all messages with a readable output format.
+impl Seek for BufferReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = resolve_seek(pos, self.position, self.size())?;
+        Ok(self.position)
+    }
+}
+
+/// A logger that delivers messages to the `mft` logger of the python `logging` module.
 struct PyLogger {
-    level: Level,
-    warnings_module: PyObject,
+    logger: PyObject,
+}
+
+/// The python `logging` level of a log level.
+fn python_level(level: Level) -> u32 {
+    match level {
+        Level::Error => 40,
+        Level::Warn => 30,
+        Level::Info => 20,
+        Level::Debug => 10,
+        Level::Trace => 5,
+    }
+}
+
+/// The most verbose log level of a python `logging` level.
+fn level_filter(python_level: u32) -> LevelFilter {
+    match python_level {
+        0..=5 => LevelFilter::Trace,
+        6..=10 => LevelFilter::Debug,
+        11..=20 => LevelFilter::Info,
+        21..=30 => LevelFilter::Warn,
+        31..=40 => LevelFilter::Error,
+        _ => LevelFilter::Off,
+    }
+}
+
+/// The level set with `set_log_level`, or the effective level of the `mft` logger on import.
+static LOG_LEVEL: AtomicUsize = AtomicUsize::new(LevelFilter::Warn as usize);
+/// Whether a parser was created with `verbose_warnings=True`, whose warnings are logged at any level.
+static VERBOSE_WARNINGS_USED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// The `verbose_warnings` option of the parser reading on this thread.
+    static VERBOSE_WARNINGS: Cell<Option<bool>> = const { Cell::new(None) };
+}
+
+/// Runs `f` with the warnings of this thread surfaced (`Some(true)`) or silenced (`Some(false)`),
+/// regardless of the log level, or with the log level deciding (`None`).
+pub fn with_warnings<T>(verbose: Option<bool>, f: impl FnOnce() -> T) -> T {
+    if verbose.is_none() {
+        return f();
+    }
+
+    if verbose == Some(true) && !VERBOSE_WARNINGS_USED.swap(true, Ordering::Relaxed) {
+        log::set_max_level(log::max_level().max(LevelFilter::Warn));
+    }
+
+    let previous = VERBOSE_WARNINGS.with(|v| v.replace(verbose));
+    let result = f();
+    VERBOSE_WARNINGS.with(|v| v.set(previous));
+    result
+}
+
+/// Sets the most verbose level of the messages which are logged.
+pub fn set_level(level: LevelFilter) {
+    LOG_LEVEL.store(level as usize, Ordering::Relaxed);
+
+    if VERBOSE_WARNINGS_USED.load(Ordering::Relaxed) {
+        log::set_max_level(level.max(LevelFilter::Warn));
+    } else {
+        log::set_max_level(level);
+    }
+}
+
+/// Whether a message is logged at the log level regardless of the `verbose_warnings` of the thread.
+fn enabled_by_level(level: Level) -> bool {
+    level as usize <= LOG_LEVEL.load(Ordering::Relaxed)
 }
 
 impl Log for PyLogger {
     fn enabled(&self, metadata: &Metadata) -> bool {
-        metadata.level() <= self.level
+        let level = metadata.level();
+
+        match VERBOSE_WARNINGS.with(Cell::get) {
+            Some(verbose) if level <= Level::Warn => verbose,
+            _ => enabled_by_level(level),
+        }
     }
 
     fn log(&self, record: &Record) {
-        if self.enabled(record.metadata()) {
-            if let Level::Warn = self.level {
-                let level_string = record.level().to_string();
-                let gil = Python::acquire_gil();
-                let py = gil.python();
-
-                let message = format!(
-                    "{:<5} [{}] {}",
-                    level_string,
-                    record.module_path().unwrap_or_default(),
-                    record.args()
-                );
-
-                self.warnings_module
-                    .call_method(py, "warn", (message,), None)
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let level = python_level(record.level());
+        let message = format!("[{}] {}", record.module_path().unwrap_or_default(), record.args());
+        Python::with_gil(|py| {
+            let logger = self.logger.as_ref(py);
+
+            if enabled_by_level(record.level()) {
+                logger.call_method1("log", (level, message)).ok();
+            } else {
+                // A verbose warning, which is handled even if the level of the python logger is higher.
+                logger
+                    .call_method1("makeRecord", ("mft", level, "", 0, message, (), py.None()))
+                    .and_then(|record| logger.call_method1("handle", (record,)))
                     .ok();
             }
-        }
+        });
     }
 
     fn flush(&self) {}
 }
 
-pub fn init_logging(py: Python) -> Result<(), SetLoggerError> {
-    let warnings = py
-        .import("warnings")
-        .expect("python to have warning module")
-        .to_object(py);
+/// Routes log messages to `logging.getLogger("mft")`.
+/// Messages are only formatted if they are at least as severe as the effective level of that logger on import
+/// (or as the level set with `set_log_level`).
+pub fn init_logging(py: Python) -> PyResult<()> {
+    let logger = py.import("logging")?.call_method1("getLogger", ("mft",))?;
+    let effective_level: u32 = logger.call_method0("getEffectiveLevel")?.extract()?;
 
     let logger = PyLogger {
-        level: Level::Warn,
-        warnings_module: warnings,
+        logger: logger.to_object(py),
     };
 
-    log::set_boxed_logger(Box::new(logger))?;
-    log::set_max_level(Level::Warn.to_level_filter());
+    log::set_boxed_logger(Box::new(logger)).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    set_level(level_filter(effective_level));
+
+    Ok(())
+}
+
+/// set_log_level(level, /)
+/// --
+///
+/// Sets the level of the messages which are logged to `logging.getLogger("mft")` (and the level of that logger),
+/// one of `trace`, `debug`, `info`, `warning`, `error`, or `off`.
+/// Messages below the level are not even formatted. Parsers created with `verbose_warnings` override it
+/// for their warnings.
+#[pyfunction]
+pub fn set_log_level(py: Python, level: &str) -> PyResult<()> {
+    let (filter, python_level) = match level.to_lowercase().as_str() {
+        "trace" => (LevelFilter::Trace, python_level(Level::Trace)),
+        "debug" => (LevelFilter::Debug, python_level(Level::Debug)),
+        "info" => (LevelFilter::Info, python_level(Level::Info)),
+        "warning" | "warn" => (LevelFilter::Warn, python_level(Level::Warn)),
+        "error" => (LevelFilter::Error, python_level(Level::Error)),
+        // Above CRITICAL.
+        "off" => (LevelFilter::Off, 51),
+        _ => {
+            return Err(PyValueError::new_err(format!(
+                "level must be one of `trace`, `debug`, `info`, `warning`, `error`, `off`, got `{}`",
+                level
+            )))
+        }
+    };
+
+    py.import("logging")?
+        .call_method1("getLogger", ("mft",))?
+        .call_method1("setLevel", (python_level,))?;
+    set_level(filter);
 
     Ok(())
 }
@@ -120,3 +316,66 @@ pub fn get_utc() -> PyResult<PyObject> {
 
     Ok(utc)
 }
+
+pub fn u16_at(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(buf[offset..offset + 2].try_into().expect("2 bytes"))
+}
+
+pub fn u32_at(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().expect("4 bytes"))
+}
+
+pub fn u64_at(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().expect("8 bytes"))
+}
+
+pub fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Decodes UTF-16LE bytes, invalid characters are replaced.
+pub fn utf16_to_string(bytes: &[u8]) -> String {
+    decode_utf16(bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])))
+        .map(|c| c.unwrap_or(std::char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// The python `repr` of a string, for the `__repr__` of python objects.
+pub fn py_repr(s: &str) -> String {
+    Python::with_gil(|py| {
+        PyString::new(py, s)
+            .repr()
+            .map(|r| r.to_string_lossy().to_string())
+            .unwrap_or_else(|_| format!("{:?}", s))
+    })
+}
+
+/// Exports `data`, which is owned by `owner` and never modified, as a read-only buffer of bytes,
+/// for the `bf_getbuffer` slot of the buffer protocol. `memoryview(owner)` then reads the data in place.
+pub fn fill_read_only_buffer(
+    owner: *mut ffi::PyObject,
+    data: &[u8],
+    view: *mut ffi::Py_buffer,
+    flags: c_int,
+) -> PyResult<()> {
+    if view.is_null() {
+        return Err(PyBufferError::new_err("view is null"));
+    }
+
+    // Sets `obj` to a new reference to the owner, which keeps the data alive while the view exists.
+    let result = unsafe {
+        ffi::PyBuffer_FillInfo(
+            view,
+            owner,
+            data.as_ptr() as *mut c_void,
+            data.len() as ffi::Py_ssize_t,
+            1,
+            flags,
+        )
+    };
+
+    if result == -1 {
+        return Err(Python::with_gil(PyErr::fetch));
+    }
+    Ok(())
+}
@@ -0,0 +1,216 @@
+//! Structural validation of an MFT.
+use crate::entry::file_names;
+use crate::parser::{check_record_header, MftParser};
+use crate::utils::u32_at;
+use crate::ReadSeek;
+
+use mft::entry::{BAAD_HEADER, ZERO_HEADER};
+use mft::MftEntry;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone)]
+pub struct Issue {
+    pub entry_id: u64,
+    pub kind: &'static str,
+    pub message: String,
+}
+
+fn check_entry(entry: &MftEntry, entry_size: usize, entry_count: u64, issues: &mut Vec<Issue>) {
+    let header = &entry.header;
+    let mut issue = |kind, message: String| {
+        issues.push(Issue {
+            entry_id: header.record_number,
+            kind,
+            message,
+        })
+    };
+
+    if &header.signature == BAAD_HEADER {
+        issue("bad_signature", "entry is marked as BAAD".to_string());
+    }
+
+    if entry.valid_fixup == Some(false) {
+        issue(
+            "fixup_mismatch",
+            "fixup values do not match the update sequence array".to_string(),
+        );
+    }
+
+    let used = header.used_entry_size as usize;
+    let total = header.total_entry_size as usize;
+    let first_attribute = header.first_attribute_record_offset as usize;
+
+    if total != entry_size || used > total || first_attribute >= used {
+        issue(
+            "impossible_size",
+            format!(
+                "used size {}, allocated size {}, first attribute at {} (records are {} bytes)",
+                used, total, first_attribute, entry_size
+            ),
+        );
+        // Attribute offsets cannot be trusted.
+        return;
+    }
+
+    // The names are only read once every attribute is known to have a usable length.
+    let mut offset = first_attribute;
+    loop {
+        if offset + 4 > used {
+            issue(
+                "attribute_out_of_bounds",
+                format!("attribute at {} exceeds the used size {}", offset, used),
+            );
+            return;
+        }
+
+        if u32_at(&entry.data, offset) == 0xFFFF_FFFF {
+            break;
+        }
+
+        let length = if offset + 8 <= used {
+            u32_at(&entry.data, offset + 4) as usize
+        } else {
+            0
+        };
+
+        if length == 0 || offset + length > used {
+            issue(
+                "attribute_out_of_bounds",
+                format!(
+                    "attribute at {} with length {} exceeds the used size {}",
+                    offset, length, used
+                ),
+            );
+            return;
+        }
+
+        offset += length;
+    }
+
+    for name in file_names(entry) {
+        if name.parent.entry >= entry_count {
+            issue(
+                "parent_out_of_range",
+                format!(
+                    "parent of `{}` is entry {}, but the MFT has {} entries",
+                    name.name, name.parent.entry, entry_count
+                ),
+            );
+        }
+    }
+}
+
+/// Checks every entry of the MFT for structural problems.
+pub fn validate(parser: &mut MftParser<impl ReadSeek>) -> (u64, Vec<Issue>) {
+    let entry_count = parser.get_entry_count();
+    let entry_size = parser.entry_size() as usize;
+
+    let mut issues = vec![];
+
+    for i in 0..entry_count {
+        let raw = match parser.get_raw_entry(i) {
+            Ok(raw) => raw,
+            Err(e) => {
+                issues.push(Issue {
+                    entry_id: i,
+                    kind: "unreadable",
+                    message: e.to_string(),
+                });
+                continue;
+            }
+        };
+
+        // `read_entry` fails on malformed headers, which are reported here instead.
+        if let Err(message) = check_record_header(&raw) {
+            issues.push(Issue {
+                entry_id: i,
+                kind: "impossible_size",
+                message,
+            });
+            continue;
+        }
+
+        match MftEntry::from_buffer(raw, i) {
+            Ok(entry) if &entry.header.signature == ZERO_HEADER => continue,
+            Ok(entry) => check_entry(&entry, entry_size, entry_count, &mut issues),
+            Err(mft::err::Error::InvalidEntrySignature { bad_sig }) => issues.push(Issue {
+                entry_id: i,
+                kind: "bad_signature",
+                message: format!("invalid signature {:?}", bad_sig),
+            }),
+            Err(e) => issues.push(Issue {
+                entry_id: i,
+                kind: "unreadable",
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    (entry_count, issues)
+}
+
+#[pyclass]
+pub struct PyMftValidationReport {
+    /// Number of entries which were checked
+    #[pyo3(get)]
+    pub number_of_entries: u64,
+    issues: Vec<Issue>,
+}
+
+impl PyMftValidationReport {
+    pub fn new(number_of_entries: u64, issues: Vec<Issue>) -> Self {
+        PyMftValidationReport {
+            number_of_entries,
+            issues,
+        }
+    }
+}
+
+#[pymethods]
+impl PyMftValidationReport {
+    /// List of `PyMftIssue`, ordered by entry.
+    #[getter]
+    pub fn issues(&self) -> Vec<PyMftIssue> {
+        self.issues
+            .iter()
+            .map(|issue| PyMftIssue {
+                entry_id: issue.entry_id,
+                kind: issue.kind.to_string(),
+                message: issue.message.clone(),
+            })
+            .collect()
+    }
+
+    /// summary(self, /)
+    /// --
+    ///
+    /// Returns a dict of the number of issues of every kind.
+    pub fn summary(&self, py: Python) -> PyResult<PyObject> {
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for issue in &self.issues {
+            *counts.entry(issue.kind).or_insert(0) += 1;
+        }
+
+        let dict = PyDict::new(py);
+        for (kind, count) in counts {
+            dict.set_item(kind, count)?;
+        }
+
+        Ok(dict.to_object(py))
+    }
+}
+
+#[pyclass]
+pub struct PyMftIssue {
+    #[pyo3(get)]
+    pub entry_id: u64,
+    /// One of `bad_signature`, `unreadable`, `fixup_mismatch`, `impossible_size`,
+    /// `attribute_out_of_bounds`, `parent_out_of_range`
+    #[pyo3(get)]
+    pub kind: String,
+    #[pyo3(get)]
+    pub message: String,
+}
@@ -0,0 +1,364 @@
+use crate::runlist::{data_runs_for_attribute, DataRun};
+use crate::utils::{invalid_data, utf16_to_string};
+use crate::ReadSeek;
+
+use mft::attribute::header::ResidentialHeader;
+use mft::attribute::{MftAttributeContent, MftAttributeType};
+use crate::parser::{entry_from_buffer, MftParser};
+use mft::MftEntry;
+use pyo3::{ffi, Python};
+
+use std::convert::TryInto;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+
+/// A cloneable handle to a single underlying reader.
+/// Every handle keeps its own position, so several views can read from the same source.
+#[derive(Clone)]
+pub struct SharedReader {
+    inner: Arc<Mutex<Box<dyn ReadSeek + Send>>>,
+    position: u64,
+    size: u64,
+}
+
+impl SharedReader {
+    pub fn new(mut inner: Box<dyn ReadSeek + Send>) -> io::Result<Self> {
+        let size = inner.seek(SeekFrom::End(0))?;
+        inner.seek(SeekFrom::Start(0))?;
+
+        Ok(SharedReader {
+            inner: Arc::new(Mutex::new(inner)),
+            position: 0,
+            size,
+        })
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Waits for the reader with the GIL released, since the handle holding it may need the GIL to read
+    /// (file-like objects), and handles can be used from several threads.
+    fn lock(&self) -> io::Result<MutexGuard<'_, Box<dyn ReadSeek + Send>>> {
+        loop {
+            match self.inner.try_lock() {
+                Ok(inner) => return Ok(inner),
+                Err(TryLockError::Poisoned(_)) => return Err(io::Error::other("Reader lock was poisoned")),
+                Err(TryLockError::WouldBlock) => {
+                    let inner = &self.inner;
+                    let wait = move || drop(inner.lock());
+
+                    if unsafe { ffi::PyGILState_Check() } == 1 {
+                        Python::with_gil(|py| py.allow_threads(wait));
+                    } else {
+                        wait();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reads exactly `buf.len()` bytes at `offset`, without moving this handle's position.
+    pub fn read_exact_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        let mut inner = self.lock()?;
+
+        inner.seek(SeekFrom::Start(offset))?;
+        inner.read_exact(buf)
+    }
+}
+
+impl Read for SharedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = {
+            let mut inner = self.lock()?;
+
+            inner.seek(SeekFrom::Start(self.position))?;
+            inner.read(buf)?
+        };
+        self.position += n as u64;
+
+        Ok(n)
+    }
+}
+
+impl Seek for SharedReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = resolve_seek(pos, self.position, self.size)?;
+        Ok(self.position)
+    }
+}
+
+/// Computes the absolute position of a seek over a stream of a known size.
+pub fn resolve_seek(pos: SeekFrom, current: u64, size: u64) -> io::Result<u64> {
+    let target = match pos {
+        SeekFrom::Start(offset) => Some(offset),
+        SeekFrom::Current(delta) => checked_add_signed(current, delta),
+        SeekFrom::End(delta) => checked_add_signed(size, delta),
+    };
+
+    target.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid seek to a negative or overflowing position",
+        )
+    })
+}
+
+fn checked_add_signed(base: u64, delta: i64) -> Option<u64> {
+    if delta >= 0 {
+        base.checked_add(delta as u64)
+    } else {
+        base.checked_sub(delta.unsigned_abs())
+    }
+}
+
+/// Presents the content of a non-resident attribute as a contiguous stream,
+/// by following its run list over the volume.
+pub struct RunlistReader<R: ReadSeek> {
+    volume: R,
+    runs: Vec<DataRun>,
+    cluster_size: u64,
+    size: u64,
+    position: u64,
+}
+
+impl<R: ReadSeek> RunlistReader<R> {
+    pub fn new(volume: R, runs: Vec<DataRun>, cluster_size: u64, size: u64) -> Self {
+        RunlistReader {
+            volume,
+            runs,
+            cluster_size,
+            size,
+            position: 0,
+        }
+    }
+}
+
+impl<R: ReadSeek> Read for RunlistReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let vcn = self.position / self.cluster_size;
+        let run = match self
+            .runs
+            .iter()
+            .find(|r| vcn >= r.vcn && vcn - r.vcn < r.length)
+        {
+            Some(run) => run,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "offset is not mapped by the run list",
+                ))
+            }
+        };
+
+        // The run list comes from the volume, so its clusters are not trusted to be addressable.
+        let out_of_range = || invalid_data("run list maps clusters beyond the addressable range");
+        let run_start = run.vcn.checked_mul(self.cluster_size).ok_or_else(out_of_range)?;
+        let run_end = run
+            .vcn
+            .checked_add(run.length)
+            .and_then(|end| end.checked_mul(self.cluster_size))
+            .map_or(self.size, |end| end.min(self.size));
+        let in_run = self.position - run_start;
+        let to_read = ((run_end - self.position) as usize).min(buf.len());
+
+        let n = match run.lcn {
+            Some(lcn) => {
+                let offset = lcn
+                    .checked_mul(self.cluster_size)
+                    .and_then(|offset| offset.checked_add(in_run))
+                    .ok_or_else(out_of_range)?;
+                self.volume.seek(SeekFrom::Start(offset))?;
+                self.volume.read(&mut buf[..to_read])?
+            }
+            // Sparse runs read as zeroes.
+            None => {
+                buf[..to_read].iter_mut().for_each(|b| *b = 0);
+                to_read
+            }
+        };
+
+        self.position += n as u64;
+
+        Ok(n)
+    }
+}
+
+impl<R: ReadSeek> Seek for RunlistReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = resolve_seek(pos, self.position, self.size)?;
+        Ok(self.position)
+    }
+}
+
+/// The fields of the NTFS boot sector needed to locate the MFT.
+#[derive(Debug, Clone)]
+pub struct BootSector {
+    pub cluster_size: u64,
+    /// The byte offset of the MFT in the volume
+    pub mft_offset: u64,
+    pub mft_record_size: u64,
+}
+
+/// NTFS clusters are 512 bytes to 2 MiB.
+const CLUSTER_SIZES: std::ops::RangeInclusive<u64> = 512..=0x20_0000;
+/// MFT records are 256 bytes to 64 KiB.
+const RECORD_SIZES: std::ops::RangeInclusive<u64> = 0x100..=0x1_0000;
+
+impl BootSector {
+    pub fn from_reader<R: Read>(reader: &mut R) -> io::Result<BootSector> {
+        let mut sector = [0_u8; 512];
+        reader.read_exact(&mut sector)?;
+
+        if &sector[3..11] != b"NTFS    " {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "volume does not have an NTFS boot sector",
+            ));
+        }
+
+        let invalid_geometry = || invalid_data("boot sector has an invalid geometry");
+
+        let bytes_per_sector = u16::from_le_bytes([sector[0x0B], sector[0x0C]]);
+        let sectors_per_cluster = match sector[0x0D] {
+            // Values above 0x80 are a negated power of two.
+            n if n > 0x80 => 1_u64.checked_shl(256 - u32::from(n)).ok_or_else(invalid_geometry)?,
+            n => u64::from(n),
+        };
+        let cluster_size = u64::from(bytes_per_sector)
+            .checked_mul(sectors_per_cluster)
+            .filter(|size| CLUSTER_SIZES.contains(size))
+            .ok_or_else(invalid_geometry)?;
+
+        let mft_lcn = u64::from_le_bytes(sector[0x30..0x38].try_into().expect("8 bytes"));
+        let mft_offset = mft_lcn.checked_mul(cluster_size).ok_or_else(invalid_geometry)?;
+
+        let mft_record_size = match sector[0x40] as i8 {
+            n if n < 0 => 1_u64.checked_shl(u32::from(n.unsigned_abs())),
+            n => (n as u64).checked_mul(cluster_size),
+        }
+        .filter(|size| RECORD_SIZES.contains(size))
+        .ok_or_else(invalid_geometry)?;
+
+        Ok(BootSector {
+            cluster_size,
+            mft_offset,
+            mft_record_size,
+        })
+    }
+}
+
+const VOLUME_ENTRY: u64 = 3;
+
+/// The label and version of the volume, from the $VOLUME_NAME and $VOLUME_INFORMATION attributes of $Volume.
+#[derive(Debug, Clone, Default)]
+pub struct VolumeInformation {
+    /// None if $Volume has no $VOLUME_NAME attribute
+    pub label: Option<String>,
+    /// The major and minor NTFS version, None (as are the flags) if $Volume has no $VOLUME_INFORMATION attribute
+    pub version: Option<(u8, u8)>,
+    pub flags: Option<u16>,
+}
+
+impl VolumeInformation {
+    pub fn from_entry(entry: &MftEntry) -> Self {
+        let mut info = VolumeInformation::default();
+
+        for attribute in entry
+            .iter_attributes_matching(Some(vec![MftAttributeType::VolumeName, MftAttributeType::VolumeInformation]))
+            .filter_map(Result::ok)
+        {
+            let data = match &attribute.data {
+                MftAttributeContent::Raw(raw) => &raw.data,
+                _ => continue,
+            };
+
+            match attribute.header.type_code {
+                MftAttributeType::VolumeName => info.label = Some(utf16_to_string(data)),
+                // 8 reserved bytes, followed by the major and minor version and the flags.
+                _ if data.len() >= 12 => {
+                    info.version = Some((data[8], data[9]));
+                    info.flags = Some(u16::from_le_bytes([data[10], data[11]]));
+                }
+                _ => {}
+            }
+        }
+
+        info
+    }
+
+    pub fn from_parser(parser: &mut MftParser<impl ReadSeek>) -> mft::err::Result<Self> {
+        Ok(VolumeInformation::from_entry(&parser.read_entry(VOLUME_ENTRY)?))
+    }
+}
+
+/// Locates the $MFT of an NTFS volume, and returns a stream over its content and its size.
+pub fn open_mft_stream<R: ReadSeek + Send + 'static>(
+    mut volume: R,
+) -> io::Result<(Box<dyn ReadSeek + Send>, u64)> {
+    volume.seek(SeekFrom::Start(0))?;
+    let boot = BootSector::from_reader(&mut volume)?;
+
+    volume.seek(SeekFrom::Start(boot.mft_offset))?;
+    let mut record = vec![0_u8; boot.mft_record_size as usize];
+    volume.read_exact(&mut record)?;
+
+    let entry = entry_from_buffer(record, 0)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+    data_stream(volume, &entry, "", boot.cluster_size)
+}
+
+/// Opens a named $DATA stream of the file at `record_number`, by looking it up through the volume's $MFT.
+pub fn open_file_stream<R: ReadSeek + Send + Clone + 'static>(
+    mut volume: R,
+    record_number: u64,
+    stream_name: &str,
+) -> io::Result<(Box<dyn ReadSeek + Send>, u64)> {
+    volume.seek(SeekFrom::Start(0))?;
+    let boot = BootSector::from_reader(&mut volume)?;
+
+    let (mft, size) = open_mft_stream(volume.clone())?;
+    let entry = MftParser::from_read_seek(mft, Some(size), Some(boot.mft_record_size as u32))
+        .and_then(|mut parser| parser.get_entry(record_number))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+    data_stream(volume, &entry, stream_name, boot.cluster_size)
+}
+
+/// Returns a stream over the content of a non-resident $DATA attribute, and its size.
+fn data_stream<R: ReadSeek + Send + 'static>(
+    volume: R,
+    entry: &MftEntry,
+    name: &str,
+    cluster_size: u64,
+) -> io::Result<(Box<dyn ReadSeek + Send>, u64)> {
+    let size = entry
+        .iter_attributes_matching(Some(vec![MftAttributeType::DATA]))
+        .filter_map(Result::ok)
+        .find(|a| a.header.name == name)
+        .and_then(|a| match a.header.residential_header {
+            ResidentialHeader::NonResident(nr) => Some(nr.file_size),
+            ResidentialHeader::Resident(_) => None,
+        });
+
+    let runs = data_runs_for_attribute(entry, 0x80, name);
+
+    match (runs, size) {
+        (Some(runs), Some(size)) => Ok((
+            Box::new(RunlistReader::new(volume, runs, cluster_size, size)),
+            size,
+        )),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "entry {} does not have a non-resident $DATA:{} attribute",
+                entry.header.record_number, name
+            ),
+        )),
+    }
+}
@@ -0,0 +1,359 @@
+//! Volume Shadow Copy (VSS) support.
+//! Format reference: https://github.com/libyal/libvshadow/blob/main/documentation/Volume%20Shadow%20Snapshot%20(VSS)%20format.asciidoc
+use crate::utils::{date_to_pyobject, invalid_data, u32_at, u64_at, FileOrFileLike};
+use crate::volume::{open_mft_stream, resolve_seek, SharedReader};
+use crate::{PyMftParser, ReadSeek};
+
+use chrono::{DateTime, Utc};
+use pyo3::prelude::*;
+use winstructs::guid::Guid;
+use winstructs::timestamp::WinTimestamp;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+const VSS_IDENTIFIER: [u8; 16] = [
+    0x6B, 0x87, 0x08, 0x38, 0x76, 0xC1, 0x48, 0x4E, 0xB7, 0xAE, 0x04, 0x04, 0x6E, 0x6C, 0xC7, 0x52,
+];
+
+const VOLUME_HEADER_OFFSET: u64 = 0x1E00;
+const BLOCK_SIZE: u64 = 0x4000;
+const BLOCK_HEADER_SIZE: usize = 128;
+const CATALOG_ENTRY_SIZE: usize = 128;
+const BLOCK_DESCRIPTOR_SIZE: usize = 32;
+
+const RECORD_TYPE_VOLUME_HEADER: u32 = 1;
+const RECORD_TYPE_CATALOG: u32 = 2;
+const RECORD_TYPE_BLOCK_LIST: u32 = 3;
+
+const FLAG_FORWARDER: u32 = 0x01;
+const FLAG_OVERLAY: u32 = 0x02;
+const FLAG_NOT_USED: u32 = 0x04;
+
+/// Guards against looping forever over corrupted block chains.
+const MAX_CHAIN_BLOCKS: usize = 1 << 20;
+
+#[derive(Debug, Clone)]
+struct BlockDescriptor {
+    original_offset: u64,
+    relative_offset: u64,
+    store_data_offset: u64,
+    flags: u32,
+    allocation_bitmap: u32,
+}
+
+#[derive(Debug, Clone)]
+struct Store {
+    identifier: Guid,
+    creation_time: DateTime<Utc>,
+    volume_size: u64,
+    descriptors: HashMap<u64, BlockDescriptor>,
+    overlays: HashMap<u64, BlockDescriptor>,
+}
+
+/// Reads a 16KiB VSS block, and validates its header.
+fn read_block(volume: &SharedReader, offset: u64, record_type: u32) -> io::Result<Vec<u8>> {
+    let mut block = vec![0_u8; BLOCK_SIZE as usize];
+    volume.read_exact_at(offset, &mut block)?;
+
+    if block[..16] != VSS_IDENTIFIER || u32_at(&block, 20) != record_type {
+        return Err(invalid_data("invalid VSS block header"));
+    }
+
+    Ok(block)
+}
+
+/// Reads all stores listed in the catalog, ordered from oldest to newest.
+fn read_stores(volume: &SharedReader) -> io::Result<Vec<Store>> {
+    let mut header = [0_u8; 128];
+    volume.read_exact_at(VOLUME_HEADER_OFFSET, &mut header)?;
+
+    // Not a VSS enabled volume.
+    if header[..16] != VSS_IDENTIFIER || u32_at(&header, 20) != RECORD_TYPE_VOLUME_HEADER {
+        return Ok(vec![]);
+    }
+
+    let mut catalog_offset = u64_at(&header, 48);
+    let mut pending: HashMap<[u8; 16], (DateTime<Utc>, u64)> = HashMap::new();
+    let mut stores = vec![];
+
+    for _ in 0..MAX_CHAIN_BLOCKS {
+        if catalog_offset == 0 {
+            break;
+        }
+
+        let block = read_block(volume, catalog_offset, RECORD_TYPE_CATALOG)?;
+
+        for entry in block[BLOCK_HEADER_SIZE..].chunks(CATALOG_ENTRY_SIZE) {
+            let mut identifier = [0_u8; 16];
+            identifier.copy_from_slice(&entry[16..32]);
+
+            match u64_at(entry, 0) {
+                // Store information.
+                0x02 => {
+                    let creation_time = WinTimestamp::new(&entry[48..56])
+                        .map_err(|_| invalid_data("invalid catalog creation time"))?
+                        .to_datetime();
+
+                    pending.insert(identifier, (creation_time, u64_at(entry, 8)));
+                }
+                // Store location.
+                0x03 => {
+                    let (creation_time, volume_size) = pending
+                        .remove(&identifier)
+                        .ok_or_else(|| invalid_data("catalog store entry without information"))?;
+
+                    let (descriptors, overlays) = read_block_list(volume, u64_at(entry, 8))?;
+
+                    stores.push(Store {
+                        identifier: Guid::from_buffer(&identifier)
+                            .map_err(|_| invalid_data("invalid store identifier"))?,
+                        creation_time,
+                        volume_size,
+                        descriptors,
+                        overlays,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        catalog_offset = u64_at(&block, 40);
+    }
+
+    stores.sort_by_key(|s| s.creation_time);
+
+    Ok(stores)
+}
+
+#[allow(clippy::type_complexity)]
+fn read_block_list(
+    volume: &SharedReader,
+    mut offset: u64,
+) -> io::Result<(HashMap<u64, BlockDescriptor>, HashMap<u64, BlockDescriptor>)> {
+    let mut descriptors = HashMap::new();
+    let mut overlays: HashMap<u64, BlockDescriptor> = HashMap::new();
+
+    for _ in 0..MAX_CHAIN_BLOCKS {
+        if offset == 0 {
+            break;
+        }
+
+        let block = read_block(volume, offset, RECORD_TYPE_BLOCK_LIST)?;
+
+        for raw in block[BLOCK_HEADER_SIZE..].chunks(BLOCK_DESCRIPTOR_SIZE) {
+            let descriptor = BlockDescriptor {
+                original_offset: u64_at(raw, 0),
+                relative_offset: u64_at(raw, 8),
+                store_data_offset: u64_at(raw, 16),
+                flags: u32_at(raw, 24),
+                allocation_bitmap: u32_at(raw, 28),
+            };
+
+            if descriptor.original_offset == 0
+                && descriptor.relative_offset == 0
+                && descriptor.store_data_offset == 0
+            {
+                continue;
+            }
+
+            if descriptor.flags & FLAG_NOT_USED != 0 {
+                continue;
+            }
+
+            if descriptor.flags & FLAG_OVERLAY != 0 {
+                match overlays.get_mut(&descriptor.original_offset) {
+                    // Later overlays of the same block extend the set of overridden sectors.
+                    Some(existing) if existing.store_data_offset == descriptor.store_data_offset => {
+                        existing.allocation_bitmap |= descriptor.allocation_bitmap
+                    }
+                    _ => {
+                        overlays.insert(descriptor.original_offset, descriptor);
+                    }
+                }
+            } else {
+                descriptors.insert(descriptor.original_offset, descriptor);
+            }
+        }
+
+        offset = u64_at(&block, 40);
+    }
+
+    Ok((descriptors, overlays))
+}
+
+/// A read-only view of the volume as it was when a given store was created.
+struct ShadowCopyReader {
+    volume: SharedReader,
+    stores: Arc<Vec<Store>>,
+    index: usize,
+    size: u64,
+    position: u64,
+}
+
+impl ShadowCopyReader {
+    /// Resolves the volume offset holding the data of the 512-byte sector at `offset`.
+    fn resolve(&self, offset: u64) -> io::Result<u64> {
+        let mut block_offset = offset - offset % BLOCK_SIZE;
+        let in_block = offset % BLOCK_SIZE;
+        let sector_bit = 1_u32 << (in_block / 512);
+        // The offsets come from the block descriptors, which are not trusted to be within the volume.
+        let in_store = |store_data_offset: u64| {
+            store_data_offset
+                .checked_add(in_block)
+                .ok_or_else(|| invalid_data("block descriptor has an out of range store offset"))
+        };
+
+        for store in &self.stores[self.index..] {
+            if let Some(overlay) = store.overlays.get(&block_offset) {
+                if overlay.allocation_bitmap & sector_bit != 0 {
+                    return in_store(overlay.store_data_offset);
+                }
+            }
+
+            if let Some(descriptor) = store.descriptors.get(&block_offset) {
+                if descriptor.flags & FLAG_FORWARDER != 0 {
+                    // The data is stored under another block offset of a newer store.
+                    block_offset = descriptor.relative_offset;
+                    continue;
+                }
+
+                return in_store(descriptor.store_data_offset);
+            }
+        }
+
+        // The block did not change since the snapshot was taken.
+        in_store(block_offset)
+    }
+}
+
+impl Read for ShadowCopyReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        // Never read across a sector, since every sector can be stored elsewhere.
+        let until_sector_end = 512 - (self.position % 512);
+        let to_read = (until_sector_end.min(self.size - self.position) as usize).min(buf.len());
+
+        self.volume
+            .read_exact_at(self.resolve(self.position)?, &mut buf[..to_read])?;
+        self.position += to_read as u64;
+
+        Ok(to_read)
+    }
+}
+
+impl Seek for ShadowCopyReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = resolve_seek(pos, self.position, self.size)?;
+        Ok(self.position)
+    }
+}
+
+#[pyclass]
+/// PyVssVolume(self, path_or_file_like, /)
+/// --
+///
+/// Opens a raw NTFS volume image, and enumerates its volume shadow copies.
+/// Works on both a path (string), or a file-like object.
+pub struct PyVssVolume {
+    volume: SharedReader,
+    stores: Arc<Vec<Store>>,
+}
+
+#[pymethods]
+impl PyVssVolume {
+    #[new]
+    fn new(path_or_file_like: PyObject) -> PyResult<Self> {
+        let boxed_read_seek = match FileOrFileLike::from_pyobject(path_or_file_like)? {
+            FileOrFileLike::File(s) => {
+                Box::new(BufReader::with_capacity(4096, File::open(s)?)) as Box<dyn ReadSeek + Send>
+            }
+            FileOrFileLike::FileLike(f) => Box::new(f) as Box<dyn ReadSeek + Send>,
+            FileOrFileLike::Buffer(b) => Box::new(b) as Box<dyn ReadSeek + Send>,
+        };
+
+        let volume = SharedReader::new(boxed_read_seek)?;
+        let stores = read_stores(&volume)?;
+
+        Ok(PyVssVolume {
+            volume,
+            stores: Arc::new(stores),
+        })
+    }
+
+    /// snapshots(self, /)
+    /// --
+    ///
+    /// Returns a list of the shadow copies of the volume, ordered from oldest to newest.
+    fn snapshots(&self) -> PyResult<Vec<PyShadowCopy>> {
+        Ok(self
+            .stores
+            .iter()
+            .enumerate()
+            .map(|(index, store)| PyShadowCopy {
+                index,
+                identifier: store.identifier.to_string(),
+                volume_size: store.volume_size,
+                creation_time: store.creation_time,
+            })
+            .collect())
+    }
+
+    /// open_mft(self, snapshot=None, /)
+    /// --
+    ///
+    /// Returns a `PyMftParser` over the MFT as it existed in the snapshot with the given index.
+    /// If `snapshot` is None, the MFT of the live volume is opened.
+    #[args(snapshot = "None")]
+    fn open_mft(&self, snapshot: Option<usize>) -> PyResult<PyMftParser> {
+        let (stream, size) = match snapshot {
+            None => open_mft_stream(self.volume.clone())?,
+            Some(index) => {
+                let store = self.stores.get(index).ok_or_else(|| {
+                    PyErr::new::<pyo3::exceptions::PyIndexError, _>(format!(
+                        "Volume has {} snapshots, got index {}",
+                        self.stores.len(),
+                        index
+                    ))
+                })?;
+
+                open_mft_stream(ShadowCopyReader {
+                    volume: self.volume.clone(),
+                    stores: self.stores.clone(),
+                    index,
+                    size: store.volume_size.min(self.volume.size()),
+                    position: 0,
+                })?
+            }
+        };
+
+        PyMftParser::from_read_seek(stream, Some(size), None)
+    }
+}
+
+#[pyclass]
+pub struct PyShadowCopy {
+    /// Index of the snapshot, to be used with `PyVssVolume.open_mft`
+    #[pyo3(get)]
+    pub index: usize,
+    /// The store identifier (GUID)
+    #[pyo3(get)]
+    pub identifier: String,
+    /// Size of the volume when the snapshot was taken
+    #[pyo3(get)]
+    pub volume_size: u64,
+    creation_time: DateTime<Utc>,
+}
+
+#[pymethods]
+impl PyShadowCopy {
+    #[getter]
+    pub fn creation_time(&self) -> PyResult<PyObject> {
+        date_to_pyobject(&self.creation_time)
+    }
+}
@@ -0,0 +1,60 @@
+//! XML serialization of flat entries.
+//!
+//! An export is a document with an `Mft` root element, and one `Entry` element per line:
+//!
+//! ```xml
+//! <?xml version="1.0" encoding="UTF-8"?>
+//! <Mft>
+//! <Entry><Signature>FILE</Signature><EntryId>0</EntryId>...<FullPath>$MFT</FullPath></Entry>
+//! </Mft>
+//! ```
+//!
+//! The children of `Entry` are the columns of CSV output, in the same order and with the same values.
+//! Columns without a value (e.g. `OwnerSid` when no security descriptors are given) are omitted.
+//! Booleans are `true` or `false`, timestamps are formatted according to the timestamp format.
+//! Characters which are not allowed in XML 1.0 (control characters) are replaced with U+FFFD.
+use crate::flat::{FlatColumns, FlatMftEntry};
+
+use serde_json::Value;
+
+pub const XML_PROLOGUE: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Mft>\n";
+pub const XML_EPILOGUE: &str = "</Mft>\n";
+
+fn push_escaped(xml: &mut String, s: &str) {
+    for c in s.chars() {
+        match c {
+            '&' => xml.push_str("&amp;"),
+            '<' => xml.push_str("&lt;"),
+            '>' => xml.push_str("&gt;"),
+            '"' => xml.push_str("&quot;"),
+            '\'' => xml.push_str("&apos;"),
+            '\t' | '\n' | '\r' => xml.push(c),
+            c if c < ' ' || c == '\u{fffe}' || c == '\u{ffff}' => xml.push('\u{fffd}'),
+            c => xml.push(c),
+        }
+    }
+}
+
+/// Serializes an entry as an `Entry` element, with the selected columns (or every column).
+pub fn flat_entry_to_xml(entry: &FlatMftEntry, columns: Option<&FlatColumns>) -> serde_json::Result<String> {
+    let mut xml = String::from("<Entry>");
+
+    for (name, value) in entry.column_values(columns)? {
+        let text = match value {
+            Value::Null => continue,
+            Value::String(s) => s,
+            value => value.to_string(),
+        };
+
+        xml.push('<');
+        xml.push_str(name);
+        xml.push('>');
+        push_escaped(&mut xml, &text);
+        xml.push_str("</");
+        xml.push_str(name);
+        xml.push('>');
+    }
+
+    xml.push_str("</Entry>");
+    Ok(xml)
+}
@@ -1,10 +1,30 @@
+import asyncio
+import collections
+import contextlib
+import csv
 import datetime
+import enum
+import hashlib
+import gzip
+import io
+import itertools
+import json
+import logging
+import mmap
+import struct
+import threading
+import uuid
+
+from xml.etree import ElementTree
 
 import pytest
 
 from pathlib import Path
 
-from mft import PyMftParser, PyMftEntry
+from mft import (
+    carve_entries, diff, FileAttributes, PyLogFileParser, PyMftBatch, PyMftParser, PyMftEntry, PySecureParser,
+    PyUsnParser, PyVolumeBitmap, PyVssVolume, PyWslMetadata, main, set_log_level,
+)
 
 
 @pytest.fixture
@@ -59,3 +79,2696 @@ def test_doesnt_yield_zeroed_entries(sample_mft):
             assert False, (e, entry.entry_id)
 
 
+
+
+def volume_image(mft_data):
+    """A volume with a boot sector, and the MFT stored contiguously at cluster 16."""
+    mft_data = bytearray(mft_data)
+
+    # Relocate the $MFT data runs.
+    first_attribute = struct.unpack_from("<H", mft_data, 0x14)[0]
+    offset = first_attribute
+    while struct.unpack_from("<I", mft_data, offset)[0] != 0x80:
+        offset += struct.unpack_from("<I", mft_data, offset + 4)[0]
+
+    runs_offset = offset + struct.unpack_from("<H", mft_data, offset + 0x20)[0]
+    clusters = (len(mft_data) + 4095) // 4096
+    mft_data[runs_offset:runs_offset + 8] = (
+        b"\x33" + clusters.to_bytes(3, "little") + (16).to_bytes(3, "little") + b"\x00"
+    )
+
+    boot_sector = bytearray(512)
+    boot_sector[3:11] = b"NTFS    "
+    struct.pack_into("<HB", boot_sector, 0x0B, 512, 8)
+    struct.pack_into("<Q", boot_sector, 0x30, 16)
+    boot_sector[0x40] = 0xF6
+
+    image = bytearray(16 * 4096) + mft_data
+    image[:512] = boot_sector
+    return image
+
+
+def test_vss_volume_opens_live_mft(sample_mft, tmp_path):
+    image = tmp_path / "volume.img"
+    image.write_bytes(volume_image(Path(sample_mft).read_bytes()))
+
+    volume = PyVssVolume(str(image))
+    assert volume.snapshots() == []
+
+    parser = volume.open_mft()
+    assert parser.number_of_entries() == PyMftParser(str(sample_mft)).number_of_entries()
+    assert next(parser.entries()).full_path == "$MFT"
+
+
+def vss_block(record_type, entries, next_offset=0):
+    identifier = bytes.fromhex("6b87083876c1484eb7ae04046e6cc752")
+    header = identifier + struct.pack("<II16xQ", 1, record_type, next_offset)
+    block = bytearray(0x4000)
+    block[:len(header)] = header
+    block[128:128 + len(entries)] = entries
+    return block
+
+
+def test_vss_volume_opens_snapshot_mft(sample_mft, tmp_path):
+    original = volume_image(Path(sample_mft).read_bytes())
+    # Since the snapshot, record 11 ($Extend) was zeroed on the live volume.
+    live = bytearray(original)
+    live[16 * 4096 + 11 * 1024:16 * 4096 + 12 * 1024] = bytes(1024)
+
+    catalog, block_list, store_data = 0x2000000, 0x2004000, 0x2008000
+    store = uuid.uuid4().bytes_le
+    created = 132223104000000000
+    catalog_entries = (
+        struct.pack("<QQ16s16xQ", 2, len(live), store, created).ljust(128, b"\0")
+        + struct.pack("<QQ16s", 3, block_list, store).ljust(128, b"\0")
+    )
+    # The block holding the first records of the MFT, as it was when the snapshot was taken.
+    block = 16 * 4096
+    descriptors = struct.pack("<QQQII", block, block, store_data, 0, 0)
+
+    volume_header = bytes.fromhex("6b87083876c1484eb7ae04046e6cc752") + struct.pack("<II24xQ", 1, 1, catalog)
+    live[0x1E00:0x1E00 + len(volume_header)] = volume_header
+    live += bytes(catalog - len(live))
+    live += vss_block(2, catalog_entries) + vss_block(3, descriptors) + original[block:block + 0x4000]
+
+    image = tmp_path / "volume.img"
+    image.write_bytes(live)
+
+    volume = PyVssVolume(str(image))
+    snapshot, = volume.snapshots()
+    assert snapshot.identifier.lower() == str(uuid.UUID(bytes_le=store))
+    assert snapshot.creation_time == datetime.datetime(2020, 1, 1, tzinfo=datetime.timezone.utc)
+
+    live_entries = {e.entry_id: e.full_path for e in volume.open_mft().entries()}
+    snapshot_entries = {e.entry_id: e.full_path for e in volume.open_mft(snapshot.index).entries()}
+    assert 11 not in live_entries
+    assert snapshot_entries[11] == "$Extend"
+
+    with pytest.raises(IndexError):
+        volume.open_mft(1)
+
+
+@pytest.mark.parametrize("offset, value", [
+    (0x0D, b"\x81"),  # 2^127 sectors per cluster
+    (0x40, b"\x80"),  # 2^128 byte records
+    (0x40, b"\x7F"),  # 127 clusters per record
+    (0x30, b"\xFF" * 8),  # MFT cluster beyond the addressable range
+])
+def test_vss_volume_invalid_boot_sector(sample_mft, tmp_path, offset, value):
+    image = volume_image(Path(sample_mft).read_bytes()[:16 * 1024])
+    image[offset:offset + len(value)] = value
+    path = tmp_path / "volume.img"
+    path.write_bytes(image)
+
+    with pytest.raises(OSError, match="invalid geometry"):
+        PyVssVolume(str(path)).open_mft()
+
+
+def usn_record_v2(entry_id, sequence, parent_id, parent_sequence, usn, reason, name):
+    encoded_name = name.encode("utf-16-le")
+    length = (60 + len(encoded_name) + 7) // 8 * 8
+    record = struct.pack(
+        "<IHHQQqQIIIIHH",
+        length, 2, 0,
+        (sequence << 48) | entry_id,
+        (parent_sequence << 48) | parent_id,
+        usn,
+        132223104000000000,
+        reason, 0, 0, 0x20,
+        len(encoded_name), 60,
+    )
+    return (record + encoded_name).ljust(length, b"\x00")
+
+
+def test_usn_events_are_joined_with_mft_entries(sample_mft, tmp_path):
+    journal = tmp_path / "J"
+    journal.write_bytes(
+        b"\x00" * 4096
+        + usn_record_v2(0, 1, 5, 5, 100, 0x80000100, "$MFT")
+        + usn_record_v2(40, 2, 5, 5, 200, 0x80000200, "old.txt")
+    )
+
+    records = list(PyUsnParser(str(journal)).records())
+    assert [r.usn for r in records] == [100, 200]
+    assert records[0].reason == "FILE_CREATE | CLOSE"
+
+    history = PyUsnParser(str(journal)).history(PyMftParser(str(sample_mft)))
+    created, = history[(0, 1)]
+    assert created.full_path == "$MFT"
+    assert created.current_path == "$MFT"
+    assert not created.entry_reused
+
+    deleted, = history[(40, 2)]
+    assert deleted.full_path == "old.txt"
+    assert deleted.entry_reused
+
+
+def test_usn_events_use_the_path_options_of_the_parser(sample_mft, tmp_path):
+    journal = tmp_path / "J"
+    journal.write_bytes(
+        usn_record_v2(62, 1, 61, 1, 100, 0x100, "Repository")
+        # The parent directory was reused since.
+        + usn_record_v2(62, 1, 61, 7, 200, 0x100, "Repository")
+    )
+
+    parser = PyMftParser(str(sample_mft), name_preference="dos", orphan_prefix="[Lost]")
+    current, reused_parent = PyUsnParser(str(journal)).events(parser)
+    assert current.full_path == "WINDOWS/system32/wbem/Repository"
+    assert current.current_path == "WINDOWS/system32/wbem/REPOSI~1"
+    assert reused_parent.full_path == "[Lost]/Repository"
+
+
+def with_fixups(page, usa_offset):
+    page = bytearray(page)
+    count = len(page) // 512 + 1
+    struct.pack_into("<HH", page, 4, usa_offset, count)
+    struct.pack_into("<H", page, usa_offset, 1)
+    for i in range(1, count):
+        end = i * 512
+        page[usa_offset + i * 2:usa_offset + i * 2 + 2] = page[end - 2:end]
+        page[end - 2:end] = b"\x01\x00"
+    return bytes(page)
+
+
+def without_fixups(record):
+    record = bytearray(record)
+    usa_offset, usa_size = struct.unpack_from("<HH", record, 4)
+    for i in range(1, usa_size):
+        record[i * 512 - 2:i * 512] = record[usa_offset + i * 2:usa_offset + i * 2 + 2]
+    return record
+
+
+def log_record(lsn, redo_operation, redo_data, flags=0):
+    client = struct.pack("<HHHHHHHHHHHHQ", redo_operation, 0, 0x20, len(redo_data), 0x20, 0, 0, 0, 0, 0, 0, 0, 0)
+    client += redo_data
+    header = struct.pack("<QQQIHHIIH6x", lsn, 0, 0, len(client), 0, 0, 1, 7, flags)
+    return header + client
+
+
+def test_logfile_records_and_lsn_correlation(sample_mft, tmp_path):
+    mft_lsn = struct.unpack_from("<Q", Path(sample_mft).read_bytes(), 8)[0]
+
+    restart = bytearray(4096)
+    restart[:4] = b"RSTR"
+    struct.pack_into("<QIIHhh", restart, 8, 0, 4096, 4096, 0x30, 1, 1)
+    struct.pack_into("<QHHHHIHHQIHH", restart, 0x30, mft_lsn, 1, 0, 0, 0, 44, 0x40, 0, 1 << 20, 0, 0x30, 0x40)
+    restart = with_fixups(restart, 0x1E)
+
+    small = log_record(mft_lsn, 0x02, b"\xAA" * 16)
+    spanning = log_record(mft_lsn + 1, 0x07, b"\xBB" * 4096, flags=1)
+
+    first = bytearray(4096)
+    first[:4] = b"RCRD"
+    first[0x40:0x40 + len(small)] = small
+    position = 0x40 + (len(small) + 7) // 8 * 8
+    first[position:] = spanning[:4096 - position]
+
+    second = bytearray(4096)
+    second[:4] = b"RCRD"
+    rest = spanning[4096 - position:]
+    second[0x40:0x40 + len(rest)] = rest
+
+    logfile = tmp_path / "LogFile"
+    logfile.write_bytes(restart * 2 + with_fixups(first, 0x28) + with_fixups(second, 0x28))
+
+    parser = PyLogFileParser(str(logfile))
+    assert parser.restart_area().current_lsn == mft_lsn
+
+    records = list(parser.records())
+    assert [r.lsn for r in records] == [mft_lsn, mft_lsn + 1]
+    assert records[0].redo_operation == "InitializeFileRecordSegment"
+    assert records[1].redo_data == b"\xBB" * 4096
+
+    (entry, record), = parser.correlate(PyMftParser(str(sample_mft)))
+    assert entry.entry_id == 0
+    assert record.lsn == mft_lsn
+
+    # A data offset of the record pages beyond their size is rejected.
+    crafted = bytearray(restart)
+    struct.pack_into("<H", crafted, 0x30 + 38, 0x2000)
+    logfile.write_bytes(bytes(crafted) * 2 + with_fixups(first, 0x28) + with_fixups(second, 0x28))
+    with pytest.raises(OSError, match="data offset"):
+        list(PyLogFileParser(str(logfile)).records())
+
+
+def sid(*sub_authorities, authority=5):
+    return struct.pack("<BB", 1, len(sub_authorities)) + authority.to_bytes(6, "big") + struct.pack(
+        "<%dI" % len(sub_authorities), *sub_authorities
+    )
+
+
+def security_descriptor(owner, group, ace_sid):
+    ace = struct.pack("<BBHI", 0, 0x03, 8 + len(ace_sid), 0x1F01FF) + ace_sid
+    acl = struct.pack("<BBHHH", 2, 0, 8 + len(ace), 1, 0) + ace
+    owner_offset = 20
+    group_offset = owner_offset + len(owner)
+    dacl_offset = group_offset + len(group)
+    return struct.pack("<BBHIIII", 1, 0, 0x8004, owner_offset, group_offset, 0, dacl_offset) + owner + group + acl
+
+
+def sds_entry(security_id, offset, descriptor):
+    return struct.pack("<IIQI", 0, security_id, offset, 20 + len(descriptor)) + descriptor
+
+
+def test_secure_sds_resolves_security_ids(tmp_path):
+    system = sid(18)
+    admins = sid(32, 544)
+
+    block = bytearray(0x40000)
+    first = sds_entry(0x100, 0, security_descriptor(admins, system, system))
+    block[:len(first)] = first
+    position = (len(first) + 15) // 16 * 16
+    second = sds_entry(0x101, position, security_descriptor(system, system, admins))
+    block[position:position + len(second)] = second
+
+    following = bytearray(0x40000)
+    third = sds_entry(0x102, 0x80000, security_descriptor(admins, admins, admins))
+    following[:len(third)] = third
+
+    sds = tmp_path / "SDS"
+    sds.write_bytes(block * 2 + following)
+
+    parser = PySecureParser(str(sds))
+    assert parser.security_ids() == [0x100, 0x101, 0x102]
+    assert parser.get(0x999) is None
+
+    descriptor = parser.get(0x100)
+    assert descriptor.owner_sid == "S-1-5-32-544"
+    assert descriptor.group_sid == "S-1-5-18"
+    assert descriptor.sacl is None
+
+    ace, = descriptor.dacl
+    assert ace.ace_type == "ACCESS_ALLOWED"
+    assert ace.mask == 0x1F01FF
+    assert ace.sid == "S-1-5-18"
+
+
+def test_entries_expose_owner_and_group_sids(sample_mft, tmp_path):
+    descriptor = security_descriptor(sid(32, 544), sid(18), sid(18))
+    sds = tmp_path / "SDS"
+    sds.write_bytes(sds_entry(256, 0, descriptor))
+
+    secure = PySecureParser(str(sds))
+
+    entry = next(iter(PyMftParser(str(sample_mft), security=secure)))
+    assert entry.entry_id == 0
+    assert entry.owner_sid == "S-1-5-32-544"
+    assert entry.group_sid == "S-1-5-18"
+
+    record = json.loads(next(PyMftParser(str(sample_mft), security=secure).entries_json()))
+    assert record["owner_sid"] == "S-1-5-32-544"
+
+    csv_lines = next(PyMftParser(str(sample_mft), security=secure).entries_csv()).decode().splitlines()
+    header, row = csv_lines
+    assert header.endswith("OwnerSid,GroupSid")
+    assert row.endswith("S-1-5-32-544,S-1-5-18")
+
+    # Without a security source, the SIDs are not resolved.
+    assert next(iter(PyMftParser(str(sample_mft)))).owner_sid is None
+
+
+def test_object_id_guids_are_exposed_as_strings_and_bytes(sample_mft):
+    parser = PyMftParser(str(sample_mft))
+
+    object_ids = [
+        attribute.attribute_content
+        for entry in parser.entries()
+        if isinstance(entry, PyMftEntry)
+        for attribute in entry.attributes()
+        if not isinstance(attribute, Exception) and attribute.type_code == 0x40
+    ]
+    assert object_ids
+
+    for object_id in object_ids:
+        assert str(uuid.UUID(bytes_le=object_id.object_id_bytes)).upper() == object_id.object_id
+
+        for name in ("birth_volume_id", "birth_object_id", "domain_id"):
+            raw = getattr(object_id, name + "_bytes")
+            if raw is None:
+                assert getattr(object_id, name) == ""
+            else:
+                assert str(uuid.UUID(bytes_le=raw)).upper() == getattr(object_id, name)
+
+
+def test_hard_links_and_link_count_mismatches(sample_mft, tmp_path):
+    raw = bytearray(Path(sample_mft).read_bytes()[:1024 * 128])
+
+    entry = next(e for e in PyMftParser(str(sample_mft)).entries() if e.entry_id == 64)
+    assert entry.hard_link_count == 2
+    assert sorted(entry.hard_links()) == [((28, 1), "CONNEC~1", "DOS"), ((28, 1), "Connection Wizard", "Win32")]
+
+    assert PyMftParser(str(sample_mft)).hard_link_mismatches() == []
+
+    struct.pack_into("<H", raw, 64 * 1024 + 0x12, 3)
+    mft = tmp_path / "MFT"
+    mft.write_bytes(raw)
+
+    assert PyMftParser(str(mft)).hard_link_mismatches() == [(64, 3, 2)]
+
+
+@pytest.mark.parametrize(
+    "name_preference, expected",
+    [
+        ("win32", "WINDOWS/Connection Wizard"),
+        ("dos", "WINDOWS/CONNEC~1"),
+        ("posix", "WINDOWS/Connection Wizard"),
+        ("all", "WINDOWS/CONNEC~1|WINDOWS/Connection Wizard"),
+    ],
+)
+def test_name_preference_selects_path_names(sample_mft, name_preference, expected):
+    parser = PyMftParser(str(sample_mft), name_preference=name_preference)
+    entry = next(e for e in parser.entries() if e.entry_id == 64)
+    assert entry.full_path == expected
+
+    namespaces = [a.attribute_content.namespace for a in entry.attributes() if a.type_code == 0x30]
+    assert sorted(namespaces) == ["DOS", "Win32"]
+
+
+def test_name_preference_is_validated(sample_mft):
+    with pytest.raises(ValueError):
+        PyMftParser(str(sample_mft), name_preference="long")
+
+
+def test_entry_exposes_all_header_fields(sample_mft):
+    raw = Path(sample_mft).read_bytes()
+
+    for entry in PyMftParser(str(sample_mft)).entries():
+        if entry.entry_id > 64:
+            break
+
+        offset = entry.entry_id * 1024
+        (
+            signature, usa_offset, usa_size, lsn, sequence, hard_link_count, first_attribute_offset, _flags,
+            used_entry_size, total_entry_size, base_reference, first_attribute_id,
+        ) = struct.unpack_from("<4sHHQHHHHIIQH", raw, offset)
+
+        assert entry.signature == signature.decode()
+        assert entry.usa_offset == usa_offset
+        assert entry.usa_size == usa_size
+        assert entry.log_sequence_number == lsn
+        assert entry.sequence == sequence
+        assert entry.hard_link_count == hard_link_count
+        assert entry.first_attribute_offset == first_attribute_offset
+        assert entry.used_entry_size == used_entry_size
+        assert entry.total_entry_size == total_entry_size
+        assert entry.base_entry_id == base_reference & 0xFFFFFFFFFFFF
+        assert entry.base_entry_sequence == base_reference >> 48
+        assert entry.first_attribute_id == first_attribute_id
+
+
+def test_fixup_mismatches_are_exposed_and_rejected_in_strict_mode(sample_mft, tmp_path):
+    raw = bytearray(Path(sample_mft).read_bytes()[:1024 * 16])
+    # Tear the second sector of entry 3.
+    raw[3 * 1024 + 1022] ^= 0xFF
+    mft = tmp_path / "MFT"
+    mft.write_bytes(raw)
+
+    entries = list(PyMftParser(str(mft)).entries())
+    assert [e.entry_id for e in entries if not e.fixup_ok] == [3]
+
+    entries = list(PyMftParser(str(mft), strict=True).entries())
+    assert len(entries) == 16
+    assert isinstance(entries[3], RuntimeError)
+    assert all(isinstance(e, PyMftEntry) for i, e in enumerate(entries) if i != 3)
+
+
+@contextlib.contextmanager
+def mft_log_records():
+    records = []
+    handler = logging.Handler()
+    handler.emit = records.append
+    logger = logging.getLogger("mft")
+    logger.addHandler(handler)
+    try:
+        yield records
+    finally:
+        logger.removeHandler(handler)
+
+
+def torn_mft(sample_mft, tmp_path):
+    raw = bytearray(Path(sample_mft).read_bytes()[:1024 * 16])
+    raw[3 * 1024 + 1022] ^= 0xFF
+    mft = tmp_path / "MFT"
+    mft.write_bytes(raw)
+    return mft
+
+
+def test_warnings_are_logged_to_the_mft_logger(sample_mft, tmp_path):
+    mft = torn_mft(sample_mft, tmp_path)
+
+    with mft_log_records() as records:
+        PyMftParser(str(mft), strict=True).export_csv(io.BytesIO())
+
+    assert records
+    assert all((r.name, r.levelno) == ("mft", logging.WARNING) for r in records)
+    messages = [r.getMessage() for r in records]
+    assert any("[mft::entry] [entry: 3] fixup bytes are not equal" in m for m in messages)
+    assert any("Skipping entry 3, it has mismatching fixup values" in m for m in messages)
+
+
+def test_log_level_and_verbose_warnings(sample_mft, tmp_path):
+    mft = torn_mft(sample_mft, tmp_path)
+
+    try:
+        set_log_level("error")
+        with mft_log_records() as records:
+            list(PyMftParser(str(mft)).entries())
+            PyMftParser(str(mft), strict=True).export_csv(io.BytesIO())
+        assert records == []
+
+        # Verbose warnings are logged regardless of the level.
+        with mft_log_records() as records:
+            list(PyMftParser(str(mft), verbose_warnings=True).entries())
+        assert records and all(r.levelno == logging.WARNING for r in records)
+        with mft_log_records() as records:
+            PyMftParser(str(mft), strict=True, verbose_warnings=True).export_csv(io.BytesIO())
+        assert any("Skipping entry 3" in r.getMessage() for r in records)
+
+        set_log_level("debug")
+        assert logging.getLogger("mft").level == logging.DEBUG
+        with mft_log_records() as records:
+            list(PyMftParser(str(mft), verbose_warnings=False).entries())
+        assert all(r.levelno < logging.WARNING for r in records)
+
+        with pytest.raises(ValueError):
+            set_log_level("loud")
+    finally:
+        set_log_level("warning")
+
+
+def test_entry_slack(sample_mft):
+    raw = Path(sample_mft).read_bytes()
+    entry = next(iter(PyMftParser(str(sample_mft))))
+
+    assert len(entry.slack) == entry.total_entry_size - entry.used_entry_size
+    # Outside of the fixup positions, the slack is the raw record content.
+    assert entry.used_entry_size < 510
+    assert entry.slack[:510 - entry.used_entry_size] == raw[entry.used_entry_size:510]
+    assert entry.slack[512 - entry.used_entry_size:-2] == raw[512:1022]
+
+
+def test_carve_entries_from_arbitrary_data(sample_mft, tmp_path):
+    raw = Path(sample_mft).read_bytes()
+    blob = b"\xCC" * 777 + raw[:1024 * 8] + b"FILE" + b"\x00" * 3000 + raw[1024 * 64:1024 * 65]
+
+    carved = list(carve_entries(blob))
+    assert [offset for offset, _ in carved] == [777 + 1024 * i for i in range(8)] + [777 + 1024 * 8 + 3004]
+    assert [entry.entry_id for _, entry in carved] == list(range(8)) + [64]
+    assert carved[0][1].full_path == "$MFT"
+    assert all(entry.fixup_ok for _, entry in carved)
+
+    dump = tmp_path / "dump"
+    dump.write_bytes(blob)
+    with open(dump, "rb") as f:
+        assert len(list(carve_entries(f))) == len(carved)
+
+
+def attribute_offsets(record):
+    offset = struct.unpack_from("<H", record, 0x14)[0]
+    offsets = {}
+    while struct.unpack_from("<I", record, offset)[0] != 0xFFFFFFFF:
+        type_code, length = struct.unpack_from("<II", record, offset)
+        offsets[type_code] = offset
+        offset += length
+    return offsets
+
+
+def test_lenient_mode_keeps_partially_broken_entries(sample_mft, tmp_path):
+    raw = bytearray(Path(sample_mft).read_bytes()[:1024 * 16])
+    offsets = attribute_offsets(raw[:1024])
+
+    # An unknown $FILE_NAME namespace fails the attribute content.
+    content_offset = offsets[0x30] + struct.unpack_from("<H", raw, offsets[0x30] + 0x14)[0]
+    raw[content_offset + 0x41] = 9
+    # An unknown attribute type hides every following attribute.
+    struct.pack_into("<I", raw, offsets[0x80], 0x1234)
+
+    mft = tmp_path / "MFT"
+    mft.write_bytes(raw)
+
+    strict = list(next(iter(PyMftParser(str(mft)))).attributes())
+    assert [a.type_code for a in strict if not isinstance(a, Exception)] == [0x10]
+    assert len(strict) == 3
+
+    lenient = list(next(iter(PyMftParser(str(mft), lenient=True))).attributes())
+    assert isinstance(lenient[2], Exception)
+    x10, x30, _, xb0 = lenient
+    assert x10.parse_error is None
+    assert x30.type_code == 0x30
+    assert x30.parse_error
+    assert x30.attribute_content.data[0x41] == 9
+    assert xb0.type_code == 0xB0
+
+    record = json.loads(next(PyMftParser(str(mft), lenient=True).entries_json()))
+    assert len(record["attributes"]) == 3
+
+
+def test_validate_reports_structural_problems(sample_mft, tmp_path):
+    assert PyMftParser(str(sample_mft)).validate().issues == []
+
+    raw = bytearray(Path(sample_mft).read_bytes()[:1024 * 16])
+    raw[2 * 1024:2 * 1024 + 4] = b"XXXX"
+    raw[3 * 1024 + 1022] ^= 0xFF
+    struct.pack_into("<I", raw, 4 * 1024 + 0x18, 2048)
+
+    record = raw[6 * 1024:7 * 1024]
+    offsets = attribute_offsets(record)
+    struct.pack_into("<I", raw, 6 * 1024 + offsets[0x10] + 4, 4000)
+
+    record = raw[7 * 1024:8 * 1024]
+    x30 = attribute_offsets(record)[0x30]
+    content = x30 + struct.unpack_from("<H", record, x30 + 0x14)[0]
+    struct.pack_into("<I", raw, 7 * 1024 + content, 99999)
+
+    mft = tmp_path / "MFT"
+    mft.write_bytes(raw)
+
+    report = PyMftParser(str(mft)).validate()
+    assert report.number_of_entries == 16
+    assert [(i.entry_id, i.kind) for i in report.issues] == [
+        (2, "bad_signature"),
+        (3, "fixup_mismatch"),
+        (4, "impossible_size"),
+        (6, "attribute_out_of_bounds"),
+        (7, "parent_out_of_range"),
+    ]
+    assert report.summary()["bad_signature"] == 1
+
+
+def test_bad_entries(sample_mft, tmp_path):
+    assert list(PyMftParser(str(sample_mft)).bad_entries()) == []
+
+    raw = bytearray(Path(sample_mft).read_bytes())
+    for entry_id in (3, 40):
+        raw[entry_id * 1024:entry_id * 1024 + 4] = b"BAAD"
+    mft = tmp_path / "MFT"
+    mft.write_bytes(raw)
+
+    parser = PyMftParser(str(mft))
+    bad = list(parser.bad_entries())
+    assert [(e.entry_id, e.signature) for e in bad] == [(3, "BAAD"), (40, "BAAD")]
+    assert parser.record_counts()["baad"] == 2
+    assert {e.entry_id: e.signature for e in parser.entries() if isinstance(e, PyMftEntry)}[0] == "FILE"
+
+
+def with_4096_byte_records(raw):
+    """Re-lays the 1024 bytes records of an MFT into 4096 bytes records (as on 4Kn sector disks)."""
+    out = bytearray()
+    for offset in range(0, len(raw), 1024):
+        record = bytearray(raw[offset:offset + 1024])
+        large = bytearray(4096)
+        if record[:4] == b"FILE":
+            record = without_fixups(record)
+
+            first_attribute = struct.unpack_from("<H", record, 0x14)[0]
+            used = struct.unpack_from("<I", record, 0x18)[0]
+            # The larger update sequence array does not fit before the first attribute anymore.
+            large[:0x30] = record[:0x30]
+            large[0x48:0x48 + used - first_attribute] = record[first_attribute:used]
+            struct.pack_into("<H", large, 0x14, 0x48)
+            struct.pack_into("<II", large, 0x18, used - first_attribute + 0x48, 4096)
+            large = bytearray(with_fixups(large, 0x30))
+        out += large
+    return bytes(out)
+
+
+def test_4096_byte_records_are_detected(sample_mft, tmp_path):
+    raw = Path(sample_mft).read_bytes()[:1024 * 64]
+    expected = [(e.entry_id, e.full_path) for e in PyMftParser(io.BytesIO(raw)).entries()]
+
+    large = bytearray(with_4096_byte_records(raw))
+    parser = PyMftParser(io.BytesIO(large))
+    assert parser.number_of_entries() == 64
+    entries = list(parser.entries())
+    assert [(e.entry_id, e.full_path) for e in entries] == expected
+    assert all(e.total_entry_size == 4096 and e.fixup_ok for e in entries)
+
+    # A zeroed first record does not prevent detection.
+    large[:4096] = bytes(4096)
+    assert PyMftParser(io.BytesIO(large)).number_of_entries() == 64
+    assert PyMftParser(io.BytesIO(large), entry_size=4096).number_of_entries() == 64
+
+    with pytest.raises(ValueError):
+        PyMftParser(io.BytesIO(large), entry_size=512)
+
+
+@pytest.mark.parametrize("method", ["entries", "entries_json", "entries_csv"])
+def test_next_batch_returns_the_same_results_as_iteration(sample_mft, method):
+    expected = list(getattr(PyMftParser(str(sample_mft)), method)())
+
+    iterator = getattr(PyMftParser(str(sample_mft)), method)()
+    first = next(iterator)
+    batches = [iterator.next_batch(1000)]
+    while batches[-1]:
+        assert len(batches[-1]) <= 1000
+        batches.append(iterator.next_batch(1000))
+
+    results = [first] + [result for batch in batches for result in batch]
+    assert len(results) == len(expected)
+    if method == "entries":
+        assert [e.entry_id for e in results] == [e.entry_id for e in expected]
+    else:
+        assert results == expected
+    assert iterator.next_batch(10) == []
+
+
+def test_iteration_can_be_resumed_from_a_position(sample_mft):
+    expected = [e.entry_id for e in PyMftParser(str(sample_mft)).entries()]
+
+    iterator = PyMftParser(str(sample_mft)).entries()
+    assert iterator.tell() == 0
+    first = [next(iterator).entry_id for _ in range(100)]
+    position = iterator.tell()
+    assert position == first[-1] + 1
+
+    rest = [e.entry_id for e in PyMftParser(str(sample_mft)).entries(start_from=position)]
+    assert first + rest == expected
+
+    json_rest = list(PyMftParser(str(sample_mft)).entries_json(start_from=position))
+    assert [json.loads(e)["header"]["record_number"] for e in json_rest] == rest
+
+    parser = PyMftParser(str(sample_mft))
+    assert list(parser.entries(start_from=parser.number_of_entries() + 10)) == []
+
+
+@pytest.mark.parametrize("threads", [1, 4])
+def test_export_csv_matches_entries_csv(sample_mft, tmp_path, threads):
+    expected = b"".join(PyMftParser(str(sample_mft)).entries_csv())
+
+    output = tmp_path / "mft.csv"
+    rows = PyMftParser(str(sample_mft)).export_csv(str(output), threads=threads)
+    assert output.read_bytes() == expected
+    assert rows == expected.count(b"\n") - 1
+
+    f = io.BytesIO()
+    PyMftParser(str(sample_mft)).export_csv(f, threads=threads)
+    assert f.getvalue() == expected
+
+
+@pytest.mark.parametrize("threads", [1, 4])
+def test_export_csv_from_buffer_and_file_like_inputs(sample_mft, threads):
+    expected = b"".join(PyMftParser(str(sample_mft)).entries_csv())
+    data = Path(sample_mft).read_bytes()
+
+    for parser in (
+        PyMftParser.from_buffer(memoryview(data)),
+        PyMftParser(bytearray(data)),
+        PyMftParser(io.BytesIO(data)),
+    ):
+        f = io.BytesIO()
+        parser.export_csv(f, threads=threads)
+        assert f.getvalue() == expected
+
+    with open(sample_mft, "rb") as raw, mmap.mmap(raw.fileno(), 0, access=mmap.ACCESS_READ) as mapped:
+        f = io.BytesIO()
+        PyMftParser(mapped).export_csv(f, threads=threads)
+        assert f.getvalue() == expected
+
+
+def test_column_projection(sample_mft, tmp_path):
+    columns = ["FileSize", "EntryId", "StandardInfoCreated", "IsDeleted"]
+    rows = list(csv.DictReader(io.StringIO(b"".join(PyMftParser(str(sample_mft)).entries_csv()).decode())))
+    expected = [[row[c] for c in columns] for row in rows]
+
+    lines = b"".join(PyMftParser(str(sample_mft)).entries_csv(columns=columns)).decode()
+    assert list(csv.reader(io.StringIO(lines))) == [columns] + expected
+
+    f = io.BytesIO()
+    assert PyMftParser(str(sample_mft)).export_csv(f, threads=2, columns=columns) == len(rows)
+    assert f.getvalue().decode() == lines
+
+    f = io.BytesIO()
+    PyMftParser(str(sample_mft)).export_arrow_ipc(f, columns=["EntryId", "FullPath"])
+    schema = read_arrow_ipc_messages(f.getvalue())[0][0]
+    assert b"FullPath" in schema and b"FileSize" not in schema
+
+    element = ElementTree.fromstring(next(PyMftParser(str(sample_mft)).entries_xml(columns=["FullPath", "EntryId"])))
+    assert [(child.tag, child.text) for child in element] == [("FullPath", "$MFT"), ("EntryId", "0")]
+
+    with pytest.raises(ValueError):
+        PyMftParser(str(sample_mft)).entries_csv(columns=["NoSuchColumn"])
+    with pytest.raises(ValueError):
+        PyMftParser(str(sample_mft)).export_csv(io.BytesIO(), columns=[])
+
+
+def test_export_json_and_gzip_compression(sample_mft, tmp_path):
+    expected_json = list(PyMftParser(str(sample_mft)).entries_json())
+    expected_csv = b"".join(PyMftParser(str(sample_mft)).entries_csv())
+
+    output = tmp_path / "mft.jsonl"
+    assert PyMftParser(str(sample_mft)).export_json(str(output)) == len(expected_json)
+    assert output.read_text().splitlines() == expected_json
+
+    output = tmp_path / "mft.csv.gz"
+    PyMftParser(str(sample_mft)).export_csv(str(output), threads=4)
+    assert gzip.decompress(output.read_bytes()) == expected_csv
+    assert output.stat().st_size < len(expected_csv) // 4
+
+    f = io.BytesIO()
+    PyMftParser(str(sample_mft)).export_json(f, compress="gzip")
+    assert not f.closed
+    assert gzip.decompress(f.getvalue()).decode().splitlines() == expected_json
+
+    with pytest.raises(ValueError):
+        PyMftParser(str(sample_mft)).export_csv(str(tmp_path / "mft.csv"), compress="zip")
+
+
+def test_export_jsonl_rotates_files(sample_mft, tmp_path):
+    expected = list(PyMftParser(str(sample_mft)).entries_json())
+
+    paths = PyMftParser(str(sample_mft)).export_jsonl(str(tmp_path / "by_count"), max_entries=5000)
+    assert [Path(p).name for p in paths] == ["part-0001.jsonl", "part-0002.jsonl", "part-0003.jsonl"]
+    lines = [Path(p).read_text().splitlines() for p in paths]
+    assert [len(part) for part in lines] == [5000, 5000, len(expected) - 10000]
+    assert sum(lines, []) == expected
+
+    max_bytes = 1024 * 1024
+    paths = PyMftParser(str(sample_mft)).export_jsonl(str(tmp_path / "by_size"), max_bytes=max_bytes, threads=2)
+    assert len(paths) > 1
+    assert all(Path(p).stat().st_size <= max_bytes for p in paths)
+    assert sum((Path(p).read_text().splitlines() for p in paths), []) == expected
+
+    with pytest.raises(ValueError):
+        PyMftParser(str(sample_mft)).export_jsonl(str(tmp_path), max_entries=0)
+
+
+def test_entries_xml_and_export_xml(sample_mft, tmp_path):
+    rows = list(csv.DictReader(io.StringIO(b"".join(PyMftParser(str(sample_mft)).entries_csv()).decode())))
+    elements = list(PyMftParser(str(sample_mft)).entries_xml())
+    assert len(elements) == len(rows)
+
+    for element, row in zip(elements[:200], rows):
+        entry = ElementTree.fromstring(element)
+        assert entry.tag == "Entry"
+        values = {child.tag: child.text or "" for child in entry}
+        assert {k: v for k, v in values.items() if v} == {k: v for k, v in row.items() if v}
+        assert list(values) == [k for k in row if k in values]
+
+    output = tmp_path / "mft.xml"
+    assert PyMftParser(str(sample_mft)).export_xml(str(output), threads=2) == len(rows)
+    root = ElementTree.parse(str(output)).getroot()
+    assert root.tag == "Mft" and len(root) == len(rows)
+    children = lambda entry: [(child.tag, child.text) for child in entry]
+    assert [children(e) for e in root[:50]] == [children(ElementTree.fromstring(e)) for e in elements[:50]]
+    assert root[1].find("FullPath").text == "$MFTMirr"
+
+
+def unpack_msgpack(data, position=0):
+    """Decodes the MessagePack value at `position`, returns it with the position of the next value."""
+    marker = data[position]
+    position += 1
+    if marker < 0x80:
+        return marker, position
+    if marker >= 0xE0:
+        return marker - 0x100, position
+    if 0xA0 <= marker <= 0xBF or marker in (0xD9, 0xDA, 0xDB):
+        if marker <= 0xBF:
+            length = marker & 0x1F
+        else:
+            size = {0xD9: 1, 0xDA: 2, 0xDB: 4}[marker]
+            length = int.from_bytes(data[position:position + size], "big")
+            position += size
+        return data[position:position + length].decode(), position + length
+    if 0x80 <= marker <= 0x9F or marker in (0xDC, 0xDD, 0xDE, 0xDF):
+        if marker <= 0x9F:
+            length, is_map = marker & 0x0F, marker < 0x90
+        else:
+            size = 2 if marker in (0xDC, 0xDE) else 4
+            length, is_map = int.from_bytes(data[position:position + size], "big"), marker >= 0xDE
+            position += size
+        values = []
+        for _ in range(length * 2 if is_map else length):
+            value, position = unpack_msgpack(data, position)
+            values.append(value)
+        return (dict(zip(values[::2], values[1::2])) if is_map else values), position
+    if marker in (0xC0, 0xC2, 0xC3):
+        return {0xC0: None, 0xC2: False, 0xC3: True}[marker], position
+    if marker == 0xCB:
+        return struct.unpack_from(">d", data, position)[0], position + 8
+    size = {0xCC: 1, 0xCD: 2, 0xCE: 4, 0xCF: 8, 0xD0: 1, 0xD1: 2, 0xD2: 4, 0xD3: 8}[marker]
+    value = int.from_bytes(data[position:position + size], "big", signed=marker >= 0xD0)
+    return value, position + size
+
+
+def test_entries_msgpack_matches_entries_json(sample_mft):
+    documents = list(PyMftParser(str(sample_mft)).entries_msgpack(timestamp_format="epoch"))
+    expected = [json.loads(d) for d in PyMftParser(str(sample_mft)).entries_json(timestamp_format="epoch")]
+    assert len(documents) == len(expected)
+
+    for document, expected_document in zip(documents, expected):
+        assert isinstance(document, bytes)
+        value, end = unpack_msgpack(document)
+        assert end == len(document)
+        assert value == expected_document
+
+    document = next(PyMftParser(str(sample_mft)).entries_msgpack(include=["header.record_number"]))
+    assert unpack_msgpack(document)[0] == {"header": {"record_number": 0}}
+    assert len(document) < len(next(PyMftParser(str(sample_mft)).entries_json()))
+
+
+def read_arrow_ipc_messages(data):
+    """Splits an Arrow IPC stream into (metadata, body) pairs, checking the framing and the end-of-stream marker."""
+    messages = []
+    position = 0
+    while True:
+        continuation, metadata_size = struct.unpack_from("<Ii", data, position)
+        assert continuation == 0xFFFFFFFF
+        position += 8
+        if metadata_size == 0:
+            assert position == len(data)
+            return messages
+
+        assert metadata_size % 8 == 0
+        metadata = data[position:position + metadata_size]
+        # `Message.bodyLength` is the last field of the message table.
+        root = struct.unpack_from("<I", metadata, 0)[0]
+        vtable = root - struct.unpack_from("<i", metadata, root)[0]
+        body_length = struct.unpack_from("<q", metadata, root + struct.unpack_from("<H", metadata, vtable + 10)[0])[0]
+        position += metadata_size
+        messages.append((metadata, data[position:position + body_length]))
+        position += body_length
+
+
+def test_export_arrow_ipc(sample_mft, tmp_path):
+    entry_count = sum(1 for _ in PyMftParser(str(sample_mft)).entries())
+
+    output = tmp_path / "mft.arrow"
+    assert PyMftParser(str(sample_mft)).export_arrow_ipc(str(output)) == entry_count
+    data = output.read_bytes()
+
+    f = io.BytesIO()
+    PyMftParser(str(sample_mft)).export_arrow_ipc(f, threads=1)
+    assert f.getvalue() == data
+
+    messages = read_arrow_ipc_messages(data)
+    schema, _ = messages[0]
+    assert b"EntryId" in schema and b"StandardInfoCreated" in schema and b"UTC" in schema
+
+    # One record batch per 1024 records.
+    assert len(messages) == 1 + 13
+    assert b"$MFTMirr" in messages[1][1]
+
+
+def test_export_arrow_ipc_reads_with_pyarrow(sample_mft, tmp_path):
+    ipc = pytest.importorskip("pyarrow.ipc")
+
+    output = tmp_path / "mft.arrow"
+    rows = PyMftParser(str(sample_mft)).export_arrow_ipc(str(output))
+
+    table = ipc.open_stream(str(output)).read_all()
+    assert table.num_rows == rows
+    assert table.column("FullPath")[1].as_py() == "$MFTMirr"
+    assert str(table.schema.field("StandardInfoCreated").type) == "timestamp[us, tz=UTC]"
+
+
+def test_diff_reports_added_removed_and_modified_records(sample_mft, tmp_path):
+    raw = Path(sample_mft).read_bytes()[:1024 * 64]
+    ids = [e.entry_id for e in PyMftParser(io.BytesIO(raw)).entries()]
+    assert list(diff(PyMftParser(io.BytesIO(raw)), PyMftParser(io.BytesIO(raw)))) == []
+
+    removed, reused, modified = ids[30], ids[31], ids[32]
+    changed = bytearray(raw)
+    changed[removed * 1024:(removed + 1) * 1024] = bytes(1024)
+    struct.pack_into("<H", changed, reused * 1024 + 0x10, struct.unpack_from("<H", raw, reused * 1024 + 0x10)[0] + 1)
+    struct.pack_into("<H", changed, modified * 1024 + 0x12, 7)
+    # Records beyond the first 48 are only in the second capture.
+    baseline = raw[:1024 * 48]
+
+    differences = [
+        (d.kind, d.entry_id, d.changed_fields, d.old is not None, d.new is not None)
+        for d in diff(PyMftParser(io.BytesIO(baseline)), PyMftParser(io.BytesIO(bytes(changed))))
+    ]
+    assert differences[:4] == [
+        ("removed", removed, [], True, False),
+        ("removed", reused, [], True, False),
+        ("added", reused, [], False, True),
+        ("modified", modified, ["hard_link_count"], True, True),
+    ]
+    assert differences[4:] == [("added", i, [], False, True) for i in ids if i >= 48]
+
+
+def test_resident_data_hashes(sample_mft):
+    hashes = {}
+    for entry in PyMftParser(str(sample_mft)).entries():
+        data = [a.attribute_content.data for a in entry.attributes()
+                if a.type_code == 0x80 and a.name == "" and a.is_resident]
+        if data:
+            assert entry.resident_data_hashes() == {
+                "md5": hashlib.md5(data[0]).hexdigest(),
+                "sha1": hashlib.sha1(data[0]).hexdigest(),
+                "sha256": hashlib.sha256(data[0]).hexdigest(),
+            }
+            hashes[entry.entry_id] = hashlib.sha256(data[0]).hexdigest()
+        else:
+            assert entry.resident_data_hashes() is None
+    assert hashes
+
+    rows = list(csv.DictReader(
+        io.StringIO(b"".join(PyMftParser(str(sample_mft), hash_resident_data=True).entries_csv()).decode())
+    ))
+    assert {int(r["EntryId"]): r["ResidentDataSha256"] for r in rows if r["ResidentDataSha256"]} == hashes
+
+    f = io.BytesIO()
+    PyMftParser(str(sample_mft), hash_resident_data=True).export_csv(f, threads=4)
+    rows = csv.DictReader(io.StringIO(f.getvalue().decode()))
+    assert {int(r["EntryId"]): r["ResidentDataSha256"] for r in rows if r["ResidentDataSha256"]} == hashes
+
+    rows = csv.DictReader(io.StringIO(b"".join(PyMftParser(str(sample_mft)).entries_csv()).decode()))
+    assert all(r["ResidentDataSha256"] == "" for r in rows)
+
+
+def test_file_size_and_allocated_size(sample_mft):
+    entries = {e.entry_id: e for e in PyMftParser(str(sample_mft)).entries()}
+
+    mft = entries[0]
+    assert mft.file_size > 0
+    assert mft.allocated_size >= mft.file_size
+    assert mft.allocated_size % 512 == 0
+
+    resident = [e for e in entries.values() if e.resident_data_hashes() is not None]
+    assert resident and all(e.allocated_size == 0 for e in resident)
+
+    rows = csv.DictReader(io.StringIO(b"".join(PyMftParser(str(sample_mft)).entries_csv()).decode()))
+    for row in rows:
+        entry = entries[int(row["EntryId"])]
+        assert (int(row["FileSize"]), int(row["AllocatedSize"])) == (entry.file_size, entry.allocated_size)
+
+
+def test_attribute_header_flags(sample_mft):
+    raw = bytearray(Path(sample_mft).read_bytes()[:1024 * 16])
+    x80 = attribute_offsets(raw[:1024])[0x80]
+    struct.pack_into("<H", raw, x80 + 0x0C, 0x8001)
+    x10 = attribute_offsets(raw[1024:2048])[0x10]
+    struct.pack_into("<H", raw, 1024 + x10 + 0x0C, 0x4000)
+
+    entries = PyMftParser(io.BytesIO(bytes(raw))).entries()
+    data = [a for a in next(entries).attributes() if a.type_code == 0x80][0]
+    assert (data.flags, data.is_compressed, data.is_encrypted, data.is_sparse) == (0x8001, True, False, True)
+
+    info = [a for a in next(entries).attributes() if a.type_code == 0x10][0]
+    assert (info.flags, info.is_compressed, info.is_encrypted, info.is_sparse) == (0x4000, False, True, False)
+
+    others = [a for a in next(entries).attributes()]
+    assert all(a.flags == 0 and not (a.is_compressed or a.is_encrypted or a.is_sparse) for a in others)
+
+
+def test_entry_storage_flags(sample_mft):
+    raw = bytearray(Path(sample_mft).read_bytes()[:1024 * 16])
+    x10 = attribute_offsets(raw[:1024])[0x10]
+    content = x10 + struct.unpack_from("<H", raw, x10 + 0x14)[0]
+    struct.pack_into("<I", raw, content + 0x20, 0x800 | 0x4000)
+    x80 = attribute_offsets(raw[1024:2048])[0x80]
+    struct.pack_into("<H", raw, 1024 + x80 + 0x0C, 0x8000)
+
+    def flags(e):
+        return e.is_directory, e.is_compressed, e.is_encrypted, e.is_sparse
+
+    entries = {e.entry_id: e for e in PyMftParser(io.BytesIO(bytes(raw))).entries()}
+    assert flags(entries[0]) == (False, True, True, False)
+    assert flags(entries[1]) == (False, False, False, True)
+    assert flags(entries[5]) == (True, False, False, False)
+
+    rows = {int(r["EntryId"]): r for r in csv.DictReader(io.StringIO(
+        b"".join(PyMftParser(io.BytesIO(bytes(raw))).entries_csv()).decode()
+    ))}
+    assert [rows[0][c] for c in ["IsADirectory", "IsCompressed", "IsEncrypted", "IsSparse"]] == [
+        "false", "true", "true", "false"
+    ]
+
+    documents = {}
+    for document in PyMftParser(io.BytesIO(bytes(raw))).entries_json():
+        document = json.loads(document)
+        documents[document["header"]["record_number"]] = document
+    assert [documents[1][k] for k in ["is_directory", "is_compressed", "is_encrypted", "is_sparse"]] == [
+        False, False, False, True
+    ]
+
+
+def test_sparse_ranges_from_run_list(sample_mft):
+    raw = bytearray(Path(sample_mft).read_bytes()[:1024 * 16])
+    x80 = attribute_offsets(raw[:1024])[0x80]
+    length = struct.unpack_from("<I", raw, x80 + 4)[0]
+    runs_offset = struct.unpack_from("<H", raw, x80 + 0x20)[0]
+    # 4 clusters at lcn 0x10, 8 + 2 sparse clusters, 3 clusters at lcn 0x15, 1 sparse cluster.
+    runs = bytes([0x11, 4, 0x10, 0x01, 8, 0x01, 2, 0x11, 3, 5, 0x01, 1, 0])
+    assert runs_offset + len(runs) <= length
+    raw[x80 + runs_offset:x80 + length] = runs.ljust(length - runs_offset, b"\x00")
+
+    assert next(PyMftParser(io.BytesIO(bytes(raw))).entries()).sparse_ranges() == []
+
+    struct.pack_into("<H", raw, x80 + 0x0C, 0x8000)
+    entry = next(PyMftParser(io.BytesIO(bytes(raw))).entries())
+    assert entry.sparse_ranges() == [(4, 10), (17, 1)]
+    assert entry.sparse_ranges("missing") is None
+
+
+def append_attribute(raw, record_offset, type_code, content, name=""):
+    """Appends a resident attribute to the record at `record_offset`."""
+    record = without_fixups(raw[record_offset:record_offset + 1024])
+    end = max(attribute_offsets(record).values(), default=0)
+    end += struct.unpack_from("<I", record, end + 4)[0]
+    # `end` is now the offset of the end marker.
+    encoded_name = name.encode("utf-16-le")
+    content_offset = (0x18 + len(encoded_name) + 7) & ~7
+    length = (content_offset + len(content) + 7) & ~7
+    instance = struct.unpack_from("<H", record, 0x28)[0]
+
+    attribute = bytearray(length)
+    struct.pack_into("<IIBBHHHIH", attribute, 0, type_code, length, 0, len(name), 0x18, 0, instance,
+                     len(content), content_offset)
+    attribute[0x18:0x18 + len(encoded_name)] = encoded_name
+    attribute[content_offset:content_offset + len(content)] = content
+    attribute += b"\xff\xff\xff\xff\x00\x00\x00\x00"
+
+    assert end + len(attribute) <= 1024
+    record[end:end + len(attribute)] = attribute
+    struct.pack_into("<H", record, 0x28, instance + 1)
+    struct.pack_into("<I", record, 0x18, end + len(attribute))
+    raw[record_offset:record_offset + 1024] = with_fixups(record, struct.unpack_from("<H", record, 4)[0])
+
+
+def reparse_point(tag, data):
+    return struct.pack("<IHH", tag, len(data), 0) + data
+
+
+def test_link_targets_of_reparse_points(sample_mft):
+    raw = bytearray(Path(sample_mft).read_bytes()[:1024 * 64])
+    ids = [e.entry_id for e in PyMftParser(io.BytesIO(bytes(raw))).entries()][30:33]
+
+    substitute, printed = r"\??\C:\Users\Public".encode("utf-16-le"), r"C:\Users\Public".encode("utf-16-le")
+    symlink = struct.pack("<HHHHI", 0, len(substitute), len(substitute), len(printed), 1) + substitute + printed
+    append_attribute(raw, ids[0] * 1024, 0xC0, reparse_point(0xA000000C, symlink))
+
+    # A junction without a print name.
+    junction = struct.pack("<HHHH", 0, len(substitute), len(substitute), 0) + substitute + b"\x00\x00"
+    append_attribute(raw, ids[1] * 1024, 0xC0, reparse_point(0xA0000003, junction))
+
+    append_attribute(raw, ids[2] * 1024, 0xC0, reparse_point(0xA000001D, struct.pack("<I", 2) + b"/mnt/c/target"))
+
+    entries = {e.entry_id: e for e in PyMftParser(io.BytesIO(bytes(raw))).entries()}
+    assert [entries[i].link_target() for i in ids] == [r"C:\Users\Public", r"C:\Users\Public", "/mnt/c/target"]
+    assert entries[0].link_target() is None
+
+    rows = {int(r["EntryId"]): r for r in csv.DictReader(io.StringIO(
+        b"".join(PyMftParser(io.BytesIO(bytes(raw))).entries_csv()).decode()
+    ))}
+    assert rows[ids[2]]["LinkTarget"] == "/mnt/c/target"
+    assert rows[0]["LinkTarget"] == ""
+
+
+def extended_attributes(*attributes):
+    data = b""
+    for i, (name, value) in enumerate(attributes):
+        ea = struct.pack("<IBBH", 0, 0, len(name), len(value)) + name + b"\x00" + value
+        ea += b"\x00" * (-len(ea) % 4)
+        if i != len(attributes) - 1:
+            ea = struct.pack("<I", len(ea)) + ea[4:]
+        data += ea
+    return data
+
+
+def test_wsl_metadata_from_extended_attributes(sample_mft):
+    raw = bytearray(Path(sample_mft).read_bytes()[:1024 * 64])
+    ids = [e.entry_id for e in PyMftParser(io.BytesIO(bytes(raw))).entries()][30:32]
+
+    lxattrb = struct.pack("<HHIIIIIIIQQQ", 0, 1, 0o100644, 1000, 1001, 0, 5, 6, 7, 1600000000, 1600000001, 1600000002)
+    append_attribute(raw, ids[0] * 1024, 0xE0, extended_attributes((b"LXATTRB", lxattrb)))
+    append_attribute(raw, ids[1] * 1024, 0xE0, extended_attributes(
+        (b"$LXUID", struct.pack("<I", 0)),
+        (b"$LXGID", struct.pack("<I", 5)),
+        (b"$LXMOD", struct.pack("<I", 0o20620)),
+        (b"$LXDEV", struct.pack("<II", 4, 1)),
+    ))
+
+    entries = {e.entry_id: e for e in PyMftParser(io.BytesIO(bytes(raw))).entries()}
+    wsl1 = entries[ids[0]].wsl_metadata()
+    assert isinstance(wsl1, PyWslMetadata)
+    assert (wsl1.mode, wsl1.uid, wsl1.gid, wsl1.device) == (0o100644, 1000, 1001, None)
+    assert wsl1.modification_time == datetime.datetime(2020, 9, 13, 12, 26, 41, tzinfo=datetime.timezone.utc)
+
+    drvfs = entries[ids[1]].wsl_metadata()
+    assert (drvfs.mode, drvfs.uid, drvfs.gid, drvfs.device) == (0o20620, 0, 5, (4, 1))
+    assert drvfs.access_time is None
+
+    assert entries[0].wsl_metadata() is None
+
+
+def test_ea_and_ea_information_attributes(sample_mft):
+    raw = bytearray(Path(sample_mft).read_bytes()[:1024 * 64])
+    entry_id = [e.entry_id for e in PyMftParser(io.BytesIO(bytes(raw))).entries()][30]
+
+    eas = extended_attributes((b"PAYLOAD", b"\x90" * 9), (b"$KERNEL.PURGE.ESBCACHE", b"\x01\x02"))
+    append_attribute(raw, entry_id * 1024, 0xD0, struct.pack("<HHI", 40, 1, len(eas)))
+    append_attribute(raw, entry_id * 1024, 0xE0, eas)
+
+    entry = [e for e in PyMftParser(io.BytesIO(bytes(raw))).entries() if e.entry_id == entry_id][0]
+    attributes = {a.type_code: a.attribute_content for a in entry.attributes()}
+
+    info = attributes[0xD0]
+    assert (info.packed_ea_size, info.need_ea_count, info.unpacked_ea_size) == (40, 1, len(eas))
+
+    assert [(ea.name, ea.flags, ea.value) for ea in attributes[0xE0].extended_attributes] == [
+        ("PAYLOAD", 0, b"\x90" * 9),
+        ("$KERNEL.PURGE.ESBCACHE", 0, b"\x01\x02"),
+    ]
+    assert bytes(memoryview(attributes[0xE0].extended_attributes[0])) == b"\x90" * 9
+
+
+def test_attribute_content_buffer_protocol(sample_mft):
+    contents = [
+        a.attribute_content
+        for e in PyMftParser(str(sample_mft)).entries() if isinstance(e, PyMftEntry)
+        for a in e.attributes() if a.is_resident and a.type_code in (0x80, 0x50)
+    ]
+    data = next(c for c in contents if type(c).__name__ == "PyMftAttributeX80" and c.file_size > 0)
+    other = next(c for c in contents if type(c).__name__ == "PyMftAttributeOther")
+
+    for content in (data, other):
+        view = memoryview(content)
+        assert view.readonly
+        assert view.format == "B"
+        assert view.tobytes() == content.data
+        assert hashlib.sha256(content).hexdigest() == hashlib.sha256(content.data).hexdigest()
+        with pytest.raises(TypeError):
+            view[0] = 0
+
+    # The view keeps the attribute alive.
+    view = memoryview(data)
+    expected = data.data
+    del data, contents
+    assert view.tobytes() == expected
+
+    # Contents are the resident bytes of their attribute, non-resident $DATA has none.
+    for e in itertools.islice(PyMftParser(str(sample_mft)).entries(), 200):
+        for a in e.attributes():
+            if a.type_code in (0x80, 0x50) and a.is_resident:
+                assert memoryview(a.attribute_content).tobytes() == a.raw_content
+            elif a.type_code == 0x80:
+                assert a.attribute_content.data == b"" and len(memoryview(a.attribute_content)) == 0
+
+
+def test_index_root_entries_with_stale_entries(sample_mft):
+    entries = {e.entry_id: e for e in PyMftParser(str(sample_mft)).entries()}
+    extend = entries[11].index_entries()
+    assert [(i.entry_id, i.file_name.name, i.is_stale) for i in extend] == [
+        (25, "$ObjId", False), (24, "$Quota", False), (26, "$Reparse", False)
+    ]
+    assert all(i.file_name.parent_entry_id == 11 for i in extend)
+    assert entries[0].index_entries() is None
+
+    # Make the first entry the last one, which leaves the others in the slack space of the index.
+    raw = bytearray(Path(sample_mft).read_bytes()[:1024 * 16])
+    record = without_fixups(raw[11 * 1024:12 * 1024])
+    x90 = attribute_offsets(record)[0x90]
+    node = x90 + struct.unpack_from("<H", record, x90 + 0x14)[0] + 16
+    first_entry = node + struct.unpack_from("<I", record, node)[0]
+    record[first_entry:first_entry + 16] = struct.pack("<QHHI", 0, 16, 0, 2)
+    struct.pack_into("<I", record, node + 4, first_entry - node + 16)
+    raw[11 * 1024:12 * 1024] = with_fixups(record, struct.unpack_from("<H", record, 4)[0])
+
+    entry = [e for e in PyMftParser(io.BytesIO(bytes(raw))).entries() if e.entry_id == 11][0]
+    assert [(i.entry_id, i.file_name.name, i.is_stale) for i in entry.index_entries()] == [
+        (24, "$Quota", True), (26, "$Reparse", True)
+    ]
+
+
+def test_entries_sorted(sample_mft):
+    entries = list(PyMftParser(str(sample_mft)).entries())
+
+    by_path = [e.entry_id for e in PyMftParser(str(sample_mft)).entries_sorted()]
+    # Entries without a path are last.
+    with_path = sorted((e for e in entries if e.full_path != "[UNKNOWN]"), key=lambda e: e.full_path)
+    without_path = [e for e in entries if e.full_path == "[UNKNOWN]"]
+    assert by_path == [e.entry_id for e in with_path + without_path]
+
+    by_size = [e.file_size for e in PyMftParser(str(sample_mft)).entries_sorted(by="size", descending=True)]
+    assert by_size == sorted((e.file_size for e in entries), reverse=True)
+
+    created = [
+        [a.attribute_content.created for a in e.attributes() if a.type_code == 0x10][0]
+        for e in PyMftParser(str(sample_mft)).entries_sorted(by="created")
+        if any(a.type_code == 0x10 for a in e.attributes())
+    ]
+    assert created == sorted(created)
+
+    with pytest.raises(ValueError):
+        PyMftParser(str(sample_mft)).entries_sorted(by="name")
+
+
+def test_entries_sorted_merges_runs(sample_mft):
+    # More records than are sorted in memory at once (65536), so that sorted runs are merged.
+    raw = Path(sample_mft).read_bytes() * 6
+    entries = list(PyMftParser(io.BytesIO(raw)).entries())
+    assert len(entries) > 65536
+
+    by_size = [e.entry_id for e in PyMftParser(io.BytesIO(raw)).entries_sorted(by="size", descending=True)]
+    assert by_size == [e.entry_id for e in sorted(entries, key=lambda e: -e.file_size)]
+
+    by_path = [e.entry_id for e in PyMftParser(io.BytesIO(raw)).entries_sorted(by="path")]
+    with_path = sorted((e for e in entries if e.full_path != "[UNKNOWN]"), key=lambda e: e.full_path)
+    without_path = [e for e in entries if e.full_path == "[UNKNOWN]"]
+    assert by_path == [e.entry_id for e in with_path + without_path]
+
+
+def test_find_by_name(sample_mft):
+    entries = list(PyMftParser(str(sample_mft)).entries())
+    expected = [
+        e.entry_id
+        for e in entries
+        if any("mft" in a.attribute_content.name.lower() for a in e.attributes() if a.type_code == 0x30)
+    ]
+    assert expected
+
+    found = list(PyMftParser(str(sample_mft)).find(name_contains="MFT"))
+    assert [e.entry_id for e in found] == expected
+    assert found[0].full_path == "$MFT"
+
+    sensitive = list(PyMftParser(str(sample_mft)).find(name_contains="MFT", case_sensitive=True))
+    assert sensitive
+    assert all(
+        any("MFT" in a.attribute_content.name for a in e.attributes() if a.type_code == 0x30) for e in sensitive
+    )
+    assert not list(PyMftParser(str(sample_mft)).find(name_contains="no such file name"))
+
+
+def test_search(sample_mft):
+    by_name = list(PyMftParser(str(sample_mft)).search(r"^\$(MFT|LogFile)$"))
+    assert [e.entry_id for e in by_name] == [0, 2]
+
+    patterns = [r"^\$MFT$", r"\.txt$"]
+    by_names = list(PyMftParser(str(sample_mft)).search(patterns))
+    assert by_names[0].entry_id == 0
+    assert len(by_names) > 1
+
+    entries = list(PyMftParser(str(sample_mft)).entries())
+    by_path = list(PyMftParser(str(sample_mft)).search(r"^\$Extend/", field="path"))
+    assert by_path
+    assert [e.entry_id for e in by_path] == [e.entry_id for e in entries if e.full_path.startswith("$Extend/")]
+
+    with pytest.raises(ValueError):
+        PyMftParser(str(sample_mft)).search("(")
+
+    with pytest.raises(ValueError):
+        PyMftParser(str(sample_mft)).search("a", field="content")
+
+
+def test_extension_stats(sample_mft):
+    stats = PyMftParser(str(sample_mft)).extension_stats()
+
+    files = [e for e in PyMftParser(str(sample_mft)).entries() if not e.is_directory and e.full_path != "[UNKNOWN]"]
+    exe = [e for e in files if e.full_path.lower().endswith(".exe")]
+    assert stats["exe"] == {"count": len(exe), "total_size": sum(e.file_size for e in exe)}
+    assert sum(s["count"] for s in stats.values()) <= len(files)
+
+    split = PyMftParser(str(sample_mft)).extension_stats(split_deleted=True)
+    assert split.keys() == stats.keys()
+    for extension, totals in stats.items():
+        assert split[extension]["allocated"]["count"] + split[extension]["deleted"]["count"] == totals["count"]
+        assert (
+            split[extension]["allocated"]["total_size"] + split[extension]["deleted"]["total_size"]
+            == totals["total_size"]
+        )
+
+
+def test_attribute_type_stats(sample_mft):
+    stats = PyMftParser(str(sample_mft)).attribute_type_stats()
+
+    counts = collections.Counter(
+        (a.type_code, a.is_resident)
+        for e in PyMftParser(str(sample_mft)).entries() if isinstance(e, PyMftEntry)
+        for a in e.attributes()
+    )
+    assert stats.keys() == {type_code for type_code, _ in counts}
+    for type_code, type_stats in stats.items():
+        assert type_stats["resident"]["count"] == counts[(type_code, True)]
+        assert type_stats["non_resident"]["count"] == counts[(type_code, False)]
+
+    assert stats[0x80]["name"] == "DATA"
+    assert stats[0xA0]["resident"] == {"count": 0, "total_size": 0}
+    assert stats[0x10]["resident"]["total_size"] >= 48 * stats[0x10]["resident"]["count"]
+
+    # A resident attribute too short for its header, at the end of the record.
+    raw = bytearray(Path(sample_mft).read_bytes()[:2 * 1024])
+    struct.pack_into("<HHI", raw, 1024 + 0x14, 1008, 0, 1024)
+    struct.pack_into("<IIB", raw, 1024 + 1008, 0x80, 16, 0)
+    stats = PyMftParser.from_buffer(bytes(raw)).attribute_type_stats()
+    assert stats[0x80]["resident"]["count"] + stats[0x80]["non_resident"]["count"] == 1
+
+
+def test_entries_path_prefix(sample_mft):
+    entries = list(PyMftParser(str(sample_mft)).entries())
+
+    below = list(PyMftParser(str(sample_mft)).entries(path_prefix="WINDOWS/system32/"))
+    assert below
+    assert [e.entry_id for e in below] == [
+        e.entry_id for e in entries if e.full_path.lower().startswith("windows/system32/")
+    ]
+
+    assert [e.entry_id for e in PyMftParser(str(sample_mft)).entries(path_prefix="windows\\System32")] == [
+        e.entry_id for e in below
+    ]
+
+    with pytest.raises(ValueError):
+        PyMftParser(str(sample_mft)).entries(path_prefix="no/such/directory")
+    # Files are not directories, even though their path matches.
+    with pytest.raises(ValueError, match="no directory matches"):
+        PyMftParser(str(sample_mft)).entries(path_prefix="WINDOWS/system.ini")
+
+
+def test_entry_raw(sample_mft):
+    data = sample_mft.read_bytes()
+
+    for entry in list(PyMftParser(str(sample_mft)).entries())[:50]:
+        on_disk = data[entry.entry_id * 1024:(entry.entry_id + 1) * 1024]
+
+        assert entry.raw_pre_fixup == on_disk
+        assert entry.raw == bytes(without_fixups(on_disk))
+        assert entry.raw[entry.used_entry_size:] == entry.slack
+
+
+def test_attribute_raw_bytes(sample_mft):
+    entry = next(PyMftParser(str(sample_mft)).entries())
+
+    for attribute in entry.attributes():
+        header = attribute.raw_header
+        assert struct.unpack_from("<I", header, 0)[0] == attribute.type_code
+        assert header + attribute.raw_content in entry.raw
+
+        if attribute.is_resident:
+            assert len(attribute.raw_content) == struct.unpack_from("<I", header, 0x10)[0]
+        else:
+            assert len(header) == struct.unpack_from("<H", header, 0x20)[0]
+
+    file_name = [a for a in entry.attributes() if a.type_code == 0x30][0]
+    assert file_name.raw_content[0x42:0x42 + 8].decode("utf-16-le") == "$MFT"
+
+
+def test_attribute_header_metadata(sample_mft):
+    entry = next(PyMftParser(str(sample_mft)).entries())
+    attributes = list(entry.attributes())
+
+    instances = [a.instance for a in attributes]
+    assert len(set(instances)) == len(instances)
+
+    for attribute in attributes:
+        assert struct.unpack_from("<H", attribute.raw_header, 0x0E)[0] == attribute.instance
+
+        if attribute.is_resident:
+            assert attribute.content_offset == len(attribute.raw_header)
+            assert attribute.content_length == len(attribute.raw_content)
+        else:
+            assert attribute.content_offset is None
+            assert attribute.content_length is None
+
+    data = [a for a in attributes if a.type_code == 0x80][0]
+    assert not data.is_resident
+    assert data.name == ""
+
+
+def test_data_stream_names(sample_mft):
+    raw = bytearray(Path(sample_mft).read_bytes()[:1024 * 64])
+    pagefile = [e for e in PyMftParser(io.BytesIO(bytes(raw))).entries() if e.full_path == "pagefile.sys"][0]
+    target = pagefile.entry_id
+    assert pagefile.data_stream_names() == [""]
+
+    append_attribute(raw, target * 1024, 0x80, b"[ZoneTransfer]\r\nZoneId=3\r\n", name="Zone.Identifier")
+
+    entry = [e for e in PyMftParser(io.BytesIO(bytes(raw))).entries() if e.entry_id == target][0]
+    assert entry.data_stream_names() == ["", "Zone.Identifier"]
+
+    directory = next(e for e in PyMftParser(io.BytesIO(bytes(raw))).entries() if e.is_directory)
+    assert directory.data_stream_names() == []
+
+
+def test_standard_info_file_flags(sample_mft):
+    entry = next(PyMftParser(str(sample_mft)).entries())
+    info = [a.attribute_content for a in entry.attributes() if a.type_code == 0x10][0]
+
+    assert info.file_flags == 0x06
+    assert info.flags_list() == ["HIDDEN", "SYSTEM"]
+
+    raw = struct.unpack_from("<I", [a for a in entry.attributes() if a.type_code == 0x10][0].raw_content, 0x20)[0]
+    assert info.file_flags == raw
+
+
+def test_standard_info_fields(sample_mft):
+    entry = next(PyMftParser(str(sample_mft)).entries())
+    attribute = [a for a in entry.attributes() if a.type_code == 0x10][0]
+    info = attribute.attribute_content
+
+    fields = struct.unpack_from("<IIIIIQQ", attribute.raw_content, 0x24)
+    assert (info.max_versions, info.version_number, info.class_id, info.owner_id, info.security_id,
+            info.quota_charged, info.update_sequence_number) == fields
+    assert info.security_id == 256
+    assert (info.max_version, info.version, info.quota, info.usn) == (
+        info.max_versions, info.version_number, info.quota_charged, info.update_sequence_number
+    )
+
+    # An NTFS 1.2 $STANDARD_INFORMATION, without the owner id, security id, quota and USN.
+    raw = bytearray(sample_mft.read_bytes())
+    first_attribute = struct.unpack_from("<H", raw, 0x14)[0]
+    struct.pack_into("<I", raw, first_attribute + 0x10, 48)
+    entry = next(PyMftParser(io.BytesIO(bytes(raw))).entries())
+    info = [a for a in entry.attributes() if a.type_code == 0x10][0].attribute_content
+    assert (info.owner_id, info.security_id, info.quota, info.usn) == (0, 0, 0, 0)
+    assert info.flags_list() == ["HIDDEN", "SYSTEM"]
+
+
+def test_file_name_flags_and_parent(sample_mft):
+    entry = next(PyMftParser(str(sample_mft)).entries())
+    attribute = [a for a in entry.attributes() if a.type_code == 0x30][0]
+    file_name = attribute.attribute_content
+
+    assert file_name.flags_list() == ["HIDDEN", "SYSTEM"]
+    assert file_name.file_flags == struct.unpack_from("<I", attribute.raw_content, 0x38)[0]
+    assert file_name.parent == (5, 5)
+
+    packed = struct.unpack_from("<Q", attribute.raw_content, 0)[0]
+    assert file_name.parent == (packed & 0xFFFFFFFFFFFF, packed >> 48)
+    assert file_name.parent == (file_name.parent_entry_id, file_name.parent_entry_sequence)
+
+
+def test_file_attributes_flags_in_output(sample_mft):
+    assert issubclass(FileAttributes, enum.IntFlag)
+    assert FileAttributes.HIDDEN | FileAttributes.SYSTEM == 0x06
+
+    entry = next(PyMftParser(str(sample_mft)).entries())
+    info = [a.attribute_content for a in entry.attributes() if a.type_code == 0x10][0]
+    assert FileAttributes(info.file_flags) == FileAttributes.HIDDEN | FileAttributes.SYSTEM
+
+    row = next(csv.DictReader(io.StringIO(b"".join(PyMftParser(str(sample_mft)).entries_csv()).decode())))
+    assert int(row["StandardInfoFlagsValue"]) == info.file_flags
+    assert row["StandardInfoFlagNames"] == "HIDDEN|SYSTEM"
+    assert row["FileNameFlagNames"].split("|") == [
+        flag.name for flag in FileAttributes if flag & int(row["FileNameFlagsValue"])
+    ]
+
+    value = json.loads(next(PyMftParser(str(sample_mft)).entries_json()))
+    assert value["standard_info_flags"] == {"value": 6, "names": ["HIDDEN", "SYSTEM"]}
+    assert value["file_name_flags"]["value"] == int(row["FileNameFlagsValue"])
+
+
+def test_entries_size_filters(sample_mft):
+    sizes = {e.entry_id: e.file_size for e in PyMftParser(str(sample_mft)).entries()}
+
+    large = [e.entry_id for e in PyMftParser(str(sample_mft)).entries(min_size=1024 * 1024)]
+    assert large
+    assert large == [i for i, size in sizes.items() if size >= 1024 * 1024]
+
+    empty = [e.entry_id for e in PyMftParser(str(sample_mft)).entries(max_size=0)]
+    assert empty == [i for i, size in sizes.items() if size == 0]
+
+    between = [json.loads(e)["header"]["record_number"]
+               for e in PyMftParser(str(sample_mft)).entries_json(min_size=1, max_size=4096)]
+    assert between == [i for i, size in sizes.items() if 1 <= size <= 4096]
+
+    rows = list(csv.DictReader(io.StringIO(
+        b"".join(PyMftParser(str(sample_mft)).entries_csv(min_size=1024 * 1024)).decode()
+    )))
+    assert [int(row["EntryId"]) for row in rows] == large
+
+
+def test_parser_from_buffer(sample_mft):
+    data = Path(sample_mft).read_bytes()
+    expected = [e.full_path for e in PyMftParser(str(sample_mft)).entries()]
+
+    assert [e.full_path for e in PyMftParser.from_buffer(data).entries()] == expected
+    assert [e.full_path for e in PyMftParser.from_buffer(bytearray(data)).entries()] == expected
+    assert [e.full_path for e in PyMftParser.from_buffer(memoryview(data)).entries()] == expected
+
+    parser = PyMftParser.from_buffer(data, name_preference="dos", entry_size=1024)
+    assert parser.number_of_entries() == len(data) // 1024
+
+    with pytest.raises(ValueError):
+        PyMftParser.from_buffer(data, entry_size=2048)
+
+    with pytest.raises(TypeError):
+        PyMftParser.from_buffer("not a buffer")
+
+
+def test_parser_from_buffer_protocol_objects(sample_mft):
+    expected = [e.full_path for e in PyMftParser(str(sample_mft)).entries()]
+    data = Path(sample_mft).read_bytes()
+
+    assert [e.full_path for e in PyMftParser(data).entries()] == expected
+    assert [e.full_path for e in PyMftParser(memoryview(bytearray(data))).entries()] == expected
+
+    with open(sample_mft, "rb") as f, mmap.mmap(f.fileno(), 0, access=mmap.ACCESS_READ) as mapped:
+        assert [e.full_path for e in PyMftParser(mapped).entries()] == expected
+
+    with pytest.raises(ValueError):
+        PyMftParser(memoryview(data)[::2])
+
+    assert len(list(carve_entries(memoryview(data)))) == len(list(carve_entries(data)))
+
+
+def test_async_entries(sample_mft):
+    expected = [e.entry_id for e in PyMftParser(str(sample_mft)).entries()]
+
+    async def collect(**kwargs):
+        return [e.entry_id async for e in PyMftParser(str(sample_mft)).aentries(**kwargs)]
+
+    assert asyncio.run(collect()) == expected
+    assert asyncio.run(collect(batch_size=7)) == expected
+    assert asyncio.run(collect(start_from=expected[10])) == expected[10:]
+
+
+def test_entries_iterator_shared_between_threads(sample_mft):
+    expected = [e.entry_id for e in PyMftParser(str(sample_mft)).entries()]
+
+    # Reads from a file-like object call back into python, which lets other threads run mid-entry.
+    entries = PyMftParser(io.BytesIO(Path(sample_mft).read_bytes())).entries()
+    results = []
+
+    def consume():
+        results.extend(entries)
+
+    threads = [threading.Thread(target=consume) for _ in range(4)]
+    for thread in threads:
+        thread.start()
+    for thread in threads:
+        thread.join()
+
+    assert all(isinstance(e, PyMftEntry) for e in results)
+    assert sorted(e.entry_id for e in results) == sorted(expected)
+
+
+def test_entries_timeline(sample_mft):
+    entry = next(PyMftParser(str(sample_mft)).entries())
+    events = [e for e in PyMftParser(str(sample_mft)).entries_timeline() if e.entry_id == entry.entry_id]
+
+    attributes = [a.attribute_content for a in entry.attributes() if a.type_code in (0x10, 0x30)]
+    assert len(events) == 4 * len(attributes)
+    assert [(e.source, e.macb) for e in events[:4]] == [("$SI", "M"), ("$SI", "A"), ("$SI", "C"), ("$SI", "B")]
+    assert {e.source for e in events[4:]} == {"$FN"}
+
+    info = attributes[0]
+    assert [e.timestamp for e in events[:4]] == [info.modified, info.accessed, info.mft_modified, info.created]
+    assert all(e.full_path == "$MFT" and not e.is_deleted for e in events)
+    assert events[0].file_flags == info.file_flags
+
+    entries = list(PyMftParser(str(sample_mft)).entries())
+    assert len(list(PyMftParser(str(sample_mft)).entries_timeline())) == sum(
+        4 * len([a for a in e.attributes() if a.type_code in (0x10, 0x30)]) for e in entries
+    )
+
+
+def test_fn_after_si_findings(sample_mft):
+    findings = list(PyMftParser(str(sample_mft)).findings())
+    assert "fn_after_si" in {f.kind for f in findings}
+
+    bootvid = [f for f in findings if f.entry_id == 178 and f.kind == "fn_after_si"]
+    assert {f.full_path for f in bootvid} == {"WINDOWS/system32/bootvid.dll"}
+    assert [f.message[:6] for f in bootvid] == ["$FN M ", "$FN B "]
+
+    entries = {e.entry_id: e for e in PyMftParser(str(sample_mft)).entries() if isinstance(e, PyMftEntry)}
+    by_entry = collections.defaultdict(list)
+    for f in findings:
+        by_entry[f.entry_id].append((f.sequence, f.kind, f.message))
+    for entry_id, entry in entries.items():
+        assert [(f.sequence, f.kind, f.message) for f in entry.findings()] == by_entry.get(entry_id, [])
+
+    # Every finding is a $FILE_NAME timestamp later than the $STANDARD_INFORMATION one.
+    attributes = {a.type_code: a.attribute_content for a in entries[178].attributes()}
+    assert attributes[0x30].modified > attributes[0x10].modified
+    assert attributes[0x30].created > attributes[0x10].created
+    assert attributes[0x30].accessed <= attributes[0x10].accessed
+
+
+def test_zero_fraction_and_epoch_timestamp_findings(sample_mft):
+    findings = list(PyMftParser(str(sample_mft)).findings())
+    zero_fraction = {f.entry_id for f in findings if f.kind == "zero_fraction"}
+    epoch = {f.entry_id for f in findings if f.kind == "epoch_timestamp"}
+
+    assert [f.message for f in findings if f.entry_id == 178 and f.kind == "zero_fraction"][0] == (
+        "$SI M timestamp 2006-02-28T12:00:00+00:00 has no fractional seconds"
+    )
+    # The $FILE_NAME timestamps of $Secure are not set.
+    assert [f.message for f in findings if f.kind == "epoch_timestamp"] == [
+        "$FN {} timestamp is the epoch 1601-01-01T00:00:00+00:00".format(macb) for macb in "MACB"
+    ]
+    assert epoch == {9}
+
+    rows = list(csv.DictReader(io.StringIO(
+        b"".join(PyMftParser(str(sample_mft)).entries_csv()).decode("utf-8")
+    )))
+    assert {int(r["EntryId"]) for r in rows if r["StandardInfoZeroFraction"] == "true"} == zero_fraction
+    assert {int(r["EntryId"]) for r in rows if r["EpochTimestamp"] == "true"} == epoch
+
+    projected = b"".join(PyMftParser(str(sample_mft)).entries_csv(columns=["EntryId", "EpochTimestamp"]))
+    lines = projected.decode("utf-8").splitlines()
+    assert lines[0] == "EntryId,EpochTimestamp"
+    assert {int(line.split(",")[0]) for line in lines[1:] if line.endswith(",true")} == epoch
+
+
+def test_entries_l2t_csv(sample_mft):
+    lines = PyMftParser(str(sample_mft)).entries_l2t_csv()
+    rows = list(csv.DictReader(io.StringIO(b"".join(lines).decode("utf-8"))))
+
+    assert list(rows[0].keys()) == [
+        "date", "time", "timezone", "MACB", "source", "sourcetype", "type", "user", "host", "short", "desc",
+        "version", "filename", "inode", "notes", "format", "extra",
+    ]
+
+    mft_rows = [r for r in rows if r["inode"] == "0"]
+    assert mft_rows
+    assert all(r["filename"] == "$MFT" and r["source"] == "FILE" and r["notes"] == "-" for r in mft_rows)
+
+    info = [a.attribute_content for a in next(PyMftParser(str(sample_mft)).entries()).attributes()
+            if a.type_code == 0x10][0]
+    si_rows = [r for r in mft_rows if "$SI" in r["desc"]]
+    assert info.created.strftime("%m/%d/%Y") in {r["date"] for r in si_rows if r["MACB"].endswith("B")}
+    # Each of M, A, C and B appears exactly once among the rows of an attribute.
+    for i, letter in enumerate("MACB"):
+        assert [r["MACB"][i] for r in si_rows].count(letter) == 1
+
+
+def test_entries_json_field_selection(sample_mft):
+    full = json.loads(next(PyMftParser(str(sample_mft)).entries_json()))
+
+    selected = json.loads(next(PyMftParser(str(sample_mft)).entries_json(
+        include=["header.record_number", "attributes.header.type_code", "is_directory"],
+    )))
+    assert selected == {
+        "header": {"record_number": 0},
+        "attributes": [{"header": {"type_code": a["header"]["type_code"]}} for a in full["attributes"]],
+        "is_directory": False,
+    }
+
+    pruned = json.loads(next(PyMftParser(str(sample_mft)).entries_json(exclude=["attributes.data", "owner_sid"])))
+    assert "owner_sid" not in pruned
+    assert all(set(a) == {"header"} for a in pruned["attributes"])
+    assert pruned["header"] == full["header"]
+
+    with pytest.raises(ValueError):
+        PyMftParser(str(sample_mft)).entries_json(exclude=["header..flags"])
+
+
+def test_entry_to_json(sample_mft):
+    entries = [e for e in PyMftParser(str(sample_mft)).entries() if isinstance(e, PyMftEntry)]
+    documents = list(PyMftParser(str(sample_mft)).entries_json())
+    rows = list(csv.DictReader(io.StringIO(
+        b"".join(PyMftParser(str(sample_mft)).entries_csv()).decode("utf-8")
+    )))
+
+    for entry, document, row in list(zip(entries, documents, rows))[:200]:
+        assert json.loads(entry.to_json()) == json.loads(document)
+
+        flat = json.loads(entry.to_json(flat=True))
+        assert list(flat) == list(row)
+        for column, value in flat.items():
+            if value is None:
+                assert row[column] == ""
+            elif isinstance(value, bool):
+                assert row[column] == str(value).lower()
+            else:
+                assert row[column] == str(value)
+
+
+def test_entries_json_indent(sample_mft):
+    compact = next(PyMftParser(str(sample_mft)).entries_json())
+    pretty = next(PyMftParser(str(sample_mft)).entries_json(indent=2))
+
+    assert "\n" not in compact
+    assert pretty == json.dumps(json.loads(compact), indent=2, ensure_ascii=False)
+    assert next(PyMftParser(str(sample_mft)).entries_json(indent=4)).splitlines()[1].startswith("    \"")
+
+
+def test_epoch_timestamps(sample_mft):
+    info = [a.attribute_content for a in next(PyMftParser(str(sample_mft)).entries()).attributes()
+            if a.type_code == 0x10][0]
+
+    entry = json.loads(next(PyMftParser(str(sample_mft)).entries_json(timestamp_format="epoch")))
+    assert abs(entry["attributes"][0]["data"]["created"] - info.created.timestamp()) < 1e-6
+
+    entry = json.loads(next(PyMftParser(str(sample_mft)).entries_json(timestamp_format="epoch_ms")))
+    assert entry["attributes"][0]["data"]["created"] == int(info.created.timestamp() * 1000)
+
+    lines = PyMftParser(str(sample_mft)).entries_csv(timestamp_format="epoch_ms")
+    row = next(csv.DictReader(io.StringIO((next(lines) + next(lines)).decode("utf-8"))))
+    assert int(row["StandardInfoCreated"]) == int(info.created.timestamp() * 1000)
+
+    out = io.BytesIO()
+    PyMftParser(str(sample_mft)).export_json(out, timestamp_format="epoch")
+    exported = json.loads(out.getvalue().splitlines()[0])
+    assert abs(exported["attributes"][0]["data"]["created"] - info.created.timestamp()) < 1e-6
+
+    with pytest.raises(ValueError):
+        PyMftParser(str(sample_mft)).entries_csv(timestamp_format="unix")
+
+
+def test_full_path_resolved_on_access(sample_mft):
+    with open(sample_mft, "rb") as f:
+        data = f.read()
+
+    entries = list(PyMftParser(io.BytesIO(data)).entries())
+    rows = list(csv.DictReader(io.StringIO(
+        b"".join(PyMftParser(str(sample_mft)).entries_csv()).decode("utf-8")
+    )))
+    paths = {int(row["EntryId"]): row["FullPath"] for row in rows}
+
+    # Paths are resolved after iteration, in any order.
+    for entry in reversed(entries):
+        if not isinstance(entry, PyMftEntry):
+            continue
+        expected = paths[entry.entry_id] or "[UNKNOWN]"
+        assert entry.full_path == expected
+        # Cached
+        assert entry.full_path == expected
+
+
+def test_reprs(sample_mft):
+    entries = {e.entry_id: e for e in PyMftParser(str(sample_mft)).entries(skip_empty=False)
+               if isinstance(e, PyMftEntry)}
+
+    assert repr(entries[0]) == "<MftEntry 0 '$MFT' in_use file>"
+    assert str(entries[0]) == repr(entries[0])
+    directory = next(e for e in entries.values() if e.full_path == "WINDOWS")
+    assert repr(directory) == f"<MftEntry {directory.entry_id} 'WINDOWS' in_use directory>"
+    deleted = next(e for e in entries.values() if e.signature == "FILE" and "ALLOCATED" not in e.flags)
+    assert repr(deleted).endswith(" deleted file>") or repr(deleted).endswith(" deleted directory>")
+    empty = next(e for e in entries.values() if e.signature == "")
+    assert repr(empty) == f"<MftEntry {empty.entry_id} empty>"
+
+    attributes = list(entries[0].attributes())
+    assert repr(attributes[0]) == "<MftAttribute StandardInformation resident>"
+    assert repr(attributes[0].attribute_content) == (
+        "<MftAttributeX10 created=2007-06-30T12:50:52.252395Z modified=2007-06-30T12:50:52.252395Z"
+        " flags=HIDDEN|SYSTEM>"
+    )
+    assert repr(attributes[1].attribute_content) == "<MftAttributeX30 '$MFT' parent=5-5 Win32AndDos>"
+    assert repr(attributes[2]) == "<MftAttribute DATA non-resident>"
+    assert repr(attributes[2].attribute_content) == "<MftAttributeX80 file_size=13402112 allocated_size=13402112>"
+
+
+def test_entry_equality_and_hash(sample_mft):
+    parser = PyMftParser(str(sample_mft))
+    first = [e for e in parser.entries() if isinstance(e, PyMftEntry)]
+    second = [e for e in parser.entries() if isinstance(e, PyMftEntry)]
+
+    assert first[0] == second[0]
+    assert first[0] != second[1]
+    assert hash(first[0]) == hash(second[0])
+    assert first[0] != (0, 1)
+    assert len(set(first) | set(second)) == len(first)
+
+    by_entry = {e: e.full_path for e in first}
+    assert all(by_entry[e] == e.full_path for e in second)
+
+
+def test_entry_parent(sample_mft):
+    entries = {e.entry_id: e for e in PyMftParser(str(sample_mft)).entries() if isinstance(e, PyMftEntry)}
+
+    child = next(e for e in entries.values() if e.full_path.count("/") >= 2 and e.base_entry_id == 0)
+    parent = child.parent()
+    assert parent.is_directory
+    assert parent.full_path == child.full_path.rsplit("/", 1)[0]
+    assert parent.parent().full_path == parent.full_path.rsplit("/", 1)[0]
+
+    # Entries in the root directory have the root as parent, which has none.
+    root = entries[0].parent()
+    assert root.entry_id == 5
+    assert root.parent() is None
+
+
+def test_entry_parent_of_deleted_directory(sample_mft):
+    # Entry 61 (`WINDOWS/system32/wbem`) is deleted: its sequence number is incremented and it is no longer allocated.
+    raw = bytearray(Path(sample_mft).read_bytes())
+    struct.pack_into("<H", raw, 61 * 1024 + 0x10, 2)
+    raw[61 * 1024 + 0x16] &= ~1
+
+    child = next(e for e in PyMftParser(io.BytesIO(bytes(raw))).entries() if e.entry_id == 62)
+    assert child.full_path == "WINDOWS/system32/wbem/Repository"
+    assert child.parent().entry_id == 61
+
+
+def test_entry_walk(sample_mft):
+    entries = [e for e in PyMftParser(str(sample_mft)).entries() if isinstance(e, PyMftEntry)]
+    directory = next(e for e in entries if e.full_path == "WINDOWS")
+
+    walked = list(directory.walk())
+    expected = {e.entry_id for e in entries if e.full_path.startswith("WINDOWS/")}
+    assert {e.entry_id for e in walked} == expected
+    assert len(walked) == len(expected)
+
+    # Depth first: every entry comes after its parent directory.
+    positions = {e.full_path: i for i, e in enumerate(walked)}
+    for i, e in enumerate(walked):
+        parent_path = e.full_path.rsplit("/", 1)[0]
+        if parent_path != "WINDOWS":
+            assert positions[parent_path] < i
+
+    with pytest.raises(ValueError):
+        next(e for e in entries if not e.is_directory).walk()
+
+
+def test_number_of_valid_entries(sample_mft):
+    parser = PyMftParser(str(sample_mft))
+    counts = parser.record_counts()
+
+    assert sum(counts.values()) == parser.number_of_entries()
+    assert parser.number_of_valid_entries() == counts["file"]
+
+    results = list(parser.entries())
+    assert counts["file"] == len([e for e in results if isinstance(e, PyMftEntry) and e.signature == "FILE"])
+    assert counts["file"] + counts["baad"] + counts["unknown"] == len(results)
+    assert counts["zeroed"] > 0
+
+
+def test_entries_include_empty(sample_mft):
+    parser = PyMftParser(str(sample_mft))
+    counts = parser.record_counts()
+    number_of_entries = parser.number_of_entries()
+    results = list(parser.entries(skip_empty=False))
+
+    assert len(results) == number_of_entries
+    assert [e.entry_id for e in results] == list(range(number_of_entries))
+
+    empty = [e for e in results if isinstance(e, PyMftEntry) and e.signature == ""]
+    assert len(empty) == counts["zeroed"]
+    assert all(e.raw[:4] == bytes(4) for e in empty)
+
+
+@pytest.mark.parametrize("filters", [{"min_size": 1}, {"max_size": 100}, {"flags_any": ["HIDDEN"]}, {"flags_none": ["SYSTEM"]}])
+def test_entries_include_empty_rejects_filters(sample_mft, filters):
+    parser = PyMftParser(str(sample_mft))
+
+    with pytest.raises(ValueError, match="skip_empty"):
+        parser.entries(skip_empty=False, **filters)
+
+    assert all(e.signature != "" for e in parser.entries(**filters) if isinstance(e, PyMftEntry))
+
+
+def test_resolve_extensions(sample_mft):
+    entries = {e.entry_id: e for e in PyMftParser(str(sample_mft)).entries() if isinstance(e, PyMftEntry)}
+    entry = next(e for e in entries.values() if e.full_path == "WINDOWS/system32/config/system.LOG")
+
+    assert entry.file_size == 0
+    assert 0x80 not in [a.type_code for a in entry.attributes()]
+
+    resolved = entry.resolve_extensions()
+    type_codes = [a.type_code for a in resolved.attributes()]
+    assert 0x80 in type_codes
+    assert type_codes[:len(list(entry.attributes()))] == [a.type_code for a in entry.attributes()]
+    assert resolved.file_size > 0
+    assert resolved.full_path == entry.full_path
+
+    # Without an attribute list, nothing changes.
+    assert [a.type_code for a in entries[0].resolve_extensions().attributes()] == [
+        a.type_code for a in entries[0].attributes()
+    ]
+
+
+def test_attribute_list_entries(sample_mft):
+    entry = next(
+        e for e in PyMftParser(str(sample_mft)).entries()
+        if isinstance(e, PyMftEntry) and e.full_path == "WINDOWS/system32/config/system.LOG"
+    )
+    attribute_list = next(a for a in entry.attributes() if a.type_code == 0x20).attribute_content
+    items = list(attribute_list.entries())
+
+    assert [i.attribute_type for i in items] == [0x10, 0x30, 0x80]
+    assert all(i.lowest_vcn == 0 and i.name == "" for i in items)
+
+    base = [i for i in items if i.segment_entry_id == entry.entry_id]
+    assert {i.attribute_type for i in base} == {0x10, 0x30}
+    assert all(i.segment_entry_sequence == entry.sequence for i in base)
+    assert sorted(i.attribute_id for i in base) == sorted(
+        a.instance for a in entry.attributes() if a.type_code in (0x10, 0x30)
+    )
+
+    data = next(i for i in items if i.attribute_type == 0x80)
+    extension = next(a for a in entry.resolve_extensions().attributes() if a.type_code == 0x80)
+    assert data.segment_entry_id != entry.entry_id
+    assert data.attribute_id == extension.instance
+
+
+def test_lossy_names(sample_mft):
+    raw = bytearray(Path(sample_mft).read_bytes()[:1024 * 16])
+    record = without_fixups(raw[11 * 1024:12 * 1024])
+    x30 = attribute_offsets(record)[0x30]
+    name = x30 + struct.unpack_from("<H", record, x30 + 0x14)[0] + 0x42
+    # An unpaired surrogate in place of the `E` of `$Extend`.
+    struct.pack_into("<H", record, name + 2, 0xD800)
+    raw[11 * 1024:12 * 1024] = with_fixups(record, struct.unpack_from("<H", record, 4)[0])
+
+    def extend(**kwargs):
+        entry = [e for e in PyMftParser.from_buffer(raw, **kwargs).entries() if e.entry_id == 11][0]
+        return entry, next(a for a in entry.attributes() if a.type_code == 0x30).attribute_content
+
+    entry, file_name = extend()
+    assert file_name.name == "$xtend"
+    assert file_name.name_bytes == "$\ud800xtend".encode("utf-16-le", "surrogatepass")
+    assert entry.full_path == "$xtend"
+
+    entry, file_name = extend(lossy_names=True)
+    assert file_name.name == "$�xtend"
+    assert entry.full_path == "$�xtend"
+
+    # Valid names are not affected.
+    entry = next(PyMftParser(str(sample_mft), lossy_names=True).entries())
+    file_name = next(a for a in entry.attributes() if a.type_code == 0x30).attribute_content
+    assert file_name.name == "$MFT" and file_name.name_bytes == "$MFT".encode("utf-16-le")
+
+
+def test_export_csv_encoding_and_control_characters(sample_mft):
+    raw = bytearray(Path(sample_mft).read_bytes()[:1024 * 16])
+    record = without_fixups(raw[11 * 1024:12 * 1024])
+    x30 = attribute_offsets(record)[0x30]
+    name = x30 + struct.unpack_from("<H", record, x30 + 0x14)[0] + 0x42
+    # A control character in place of the `E` of `$Extend`.
+    struct.pack_into("<H", record, name + 2, 0x01)
+    raw[11 * 1024:12 * 1024] = with_fixups(record, struct.unpack_from("<H", record, 4)[0])
+
+    f = io.BytesIO()
+    PyMftParser.from_buffer(raw).export_csv(f)
+    expected = f.getvalue()
+    assert "$\u2401xtend".encode() in expected and b"\x01" not in expected
+
+    f = io.BytesIO()
+    PyMftParser.from_buffer(raw).export_csv(f, encoding="utf-8-sig")
+    assert f.getvalue() == b"\xef\xbb\xbf" + expected
+
+    f = io.BytesIO()
+    PyMftParser.from_buffer(raw).export_csv(f, encoding="utf-16le", threads=2)
+    assert f.getvalue()[:2] == b"\xff\xfe"
+    assert f.getvalue()[2:].decode("utf-16-le") == expected.decode()
+
+    with pytest.raises(ValueError):
+        PyMftParser.from_buffer(raw).export_csv(io.BytesIO(), encoding="latin-1")
+
+
+def test_valid_data_length(sample_mft):
+    entries = {e.entry_id: e for e in PyMftParser(str(sample_mft)).entries()}
+
+    sam = next(e for e in entries.values() if e.full_path == "WINDOWS/system32/config/SAM")
+    data = next(a for a in sam.attributes() if a.type_code == 0x80).attribute_content
+    assert (data.file_size, data.valid_data_length, data.allocated_size) == (262144, 24576, 262144)
+    assert data.data == b""
+
+    resident = next(
+        a.attribute_content for e in entries.values() for a in e.attributes()
+        if a.type_code == 0x80 and a.is_resident
+    )
+    assert resident.valid_data_length == resident.file_size == len(resident.data)
+    assert resident.allocated_size == 0
+
+    rows = {int(r["EntryId"]): r for r in csv.DictReader(
+        io.StringIO(b"".join(PyMftParser(str(sample_mft)).entries_csv()).decode())
+    )}
+    assert int(rows[sam.entry_id]["ValidDataLength"]) == 24576
+    assert int(rows[0]["ValidDataLength"]) == entries[0].file_size
+
+
+def test_volume_bitmap_cluster_allocation(sample_mft, tmp_path):
+    entries = {e.entry_id: e for e in PyMftParser(str(sample_mft)).entries()}
+    deleted = entries[11607]
+    assert "ALLOCATED" not in deleted.flags
+
+    bitmap = tmp_path / "$Bitmap"
+    bitmap.write_bytes(bytes(1 << 20))
+    free = PyVolumeBitmap(str(bitmap))
+    assert free.cluster_count == 8 << 20
+    assert free.is_allocated(0) is False and free.is_allocated(8 << 20) is None
+
+    status = free.check_entry(deleted)
+    assert status.clusters > 0 and status.clusters == status.unallocated_clusters
+    assert status.is_recoverable
+
+    # Another file reused the first cluster of the deleted entry.
+    lcn = status.runs[0][0]
+    data = bytearray(1 << 20)
+    data[lcn // 8] |= 1 << (lcn % 8)
+    reused = PyVolumeBitmap(io.BytesIO(bytes(data))).check_entry(deleted)
+    assert (reused.allocated_clusters, reused.unallocated_clusters) == (1, status.clusters - 1)
+    assert reused.runs[0][2] == 1
+    assert not reused.is_recoverable
+
+    # Runs past the end of the bitmap.
+    assert PyVolumeBitmap(io.BytesIO(b"")).check_entry(deleted).out_of_range_clusters == status.clusters
+
+    assert free.check_entry(deleted, "missing") is None
+
+
+def test_entries_triage_csv(sample_mft):
+    entries = {e.entry_id: e for e in PyMftParser(str(sample_mft)).entries()}
+    rows = list(csv.DictReader(io.StringIO(b"".join(PyMftParser(str(sample_mft)).entries_triage_csv()).decode())))
+
+    assert [int(r["EntryId"]) for r in rows] == sorted(entries)
+    assert list(rows[0]) == [
+        "EntryId", "Sequence", "Flags", "IsADirectory", "IsDeleted", "Name", "ParentEntryId", "ParentEntrySequence",
+        "StandardInfoCreated", "StandardInfoLastModified", "StandardInfoMftModified", "StandardInfoLastAccess",
+        "FileNameCreated", "FileNameLastModified", "FileNameMftModified", "FileNameLastAccess",
+    ]
+
+    for row in rows:
+        entry = entries[int(row["EntryId"])]
+        if entry.full_path and "/" not in entry.full_path and not entry.full_path.startswith("["):
+            assert row["Name"] == entry.full_path
+        assert row["IsADirectory"] == str(entry.is_directory).lower()
+
+    mft = rows[0]
+    assert (mft["Name"], mft["ParentEntryId"]) == ("$MFT", "5")
+    si = next(a for a in entries[0].attributes() if a.type_code == 0x10).attribute_content
+    assert datetime.datetime.fromisoformat(mft["StandardInfoMftModified"].replace("Z", "+00:00")) == si.mft_modified
+
+    epoch = next(csv.DictReader(io.StringIO(
+        b"".join(PyMftParser(str(sample_mft)).entries_triage_csv(timestamp_format="epoch_ms")).decode()
+    )))
+    assert int(epoch["StandardInfoCreated"]) == int(si.created.timestamp() * 1000)
+
+
+def test_entry_properties_are_computed_on_access(sample_mft):
+    entries = list(PyMftParser(str(sample_mft)).entries())
+    rows = csv.DictReader(io.StringIO(b"".join(PyMftParser(str(sample_mft)).entries_csv()).decode()))
+
+    for entry, row in zip(entries, rows):
+        for _ in range(2):
+            assert (entry.file_size, entry.allocated_size) == (int(row["FileSize"]), int(row["AllocatedSize"]))
+            assert [str(getattr(entry, name)).lower() for name in (
+                "is_directory", "is_compressed", "is_encrypted", "is_sparse"
+            )] == [row[column] for column in ("IsADirectory", "IsCompressed", "IsEncrypted", "IsSparse")]
+
+
+def test_entry_cache(sample_mft):
+    def resolve(**kwargs):
+        entries = [e for e in PyMftParser(str(sample_mft), **kwargs).entries() if isinstance(e, PyMftEntry)]
+        windows = next(e for e in entries if e.full_path == "WINDOWS")
+        parents = [e.parent() for e in entries]
+        return (
+            [e.full_path for e in entries],
+            [p.entry_id if p is not None else None for p in parents],
+            [e.entry_id for e in windows.walk()],
+        )
+
+    assert resolve(cache_size=16) == resolve()
+
+
+def test_concurrent_iterators_from_one_parser(sample_mft):
+    parser = PyMftParser(str(sample_mft))
+    expected = [e.entry_id for e in PyMftParser(str(sample_mft)).entries()]
+
+    # Interleaved iterators keep their own position.
+    first, second = parser.entries(), parser.entries()
+    interleaved = [(a.entry_id, b.entry_id) for a, b in zip(first, second)]
+    assert interleaved == [(i, i) for i in expected]
+
+    results = {}
+
+    def consume(name, iterator):
+        results[name] = [e.entry_id for e in iterator]
+
+    with open(sample_mft, "rb") as f:
+        file_like = PyMftParser(f)
+        threads = [
+            threading.Thread(target=consume, args=(name, iterator))
+            for name, iterator in [
+                ("path", parser.entries()),
+                ("generator", (e for e in parser.entries())),
+                ("file_like_a", file_like.entries()),
+                ("file_like_b", file_like.entries()),
+            ]
+        ]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join()
+
+    assert all(r == expected for r in results.values()) and len(results) == 4
+    assert parser.number_of_entries() == PyMftParser(str(sample_mft)).number_of_entries()
+
+
+@pytest.mark.parametrize("method, kwargs", [
+    ("entries", {}),
+    ("entries_csv", {"timestamp_format": "epoch"}),
+    ("entries_json", {"include": ["header.record_number"], "indent": 2}),
+    ("entries_msgpack", {"exclude": ["attributes"]}),
+    ("entries_xml", {}),
+    ("entries_csv", {"columns": ["EntryId", "FullPath"]}),
+    ("entries_csv", {"path_separator": "\\", "path_root": "C:", "path_include_name": False}),
+    ("entries_timeline", {}),
+    ("entries_l2t_csv", {}),
+    ("findings", {}),
+])
+def test_iterator_state_and_resume(sample_mft, method, kwargs):
+    def key(result):
+        if isinstance(result, PyMftEntry):
+            return result.entry_id
+        if hasattr(result, "timestamp"):
+            return (result.entry_id, result.source, result.macb, result.timestamp)
+        if hasattr(result, "kind"):
+            return (result.entry_id, result.kind, result.message)
+        return result
+
+    expected = [key(r) for r in getattr(PyMftParser(str(sample_mft)), method)(**kwargs)]
+
+    iterator = getattr(PyMftParser(str(sample_mft)), method)(**kwargs)
+    # Stop in the middle of the results of an entry, for outputs with several results per entry.
+    first = [key(r) for r in iterator.next_batch(1001)]
+    state = json.loads(json.dumps(iterator.state()))
+    assert state["output"] and state["position"] == iterator.tell()
+
+    resumed = PyMftParser(str(sample_mft)).resume(state)
+    assert first + [key(r) for r in resumed] == expected
+
+
+def test_iterator_state_is_validated(sample_mft):
+    parser = PyMftParser(str(sample_mft))
+    with pytest.raises(ValueError):
+        parser.entries(min_size=1).state()
+
+    state = parser.entries().state()
+    with pytest.raises(ValueError):
+        parser.resume({**state, "number_of_entries": 1})
+    with pytest.raises(ValueError):
+        parser.resume({**state, "output": "yaml"})
+    with pytest.raises(ValueError):
+        parser.resume({k: v for k, v in state.items() if k != "position"})
+
+
+def test_attribute_to_dict(sample_mft):
+    seen = set()
+    for entry in itertools.islice(PyMftParser(str(sample_mft)).entries(), 200):
+        for attribute in entry.attributes():
+            d = attribute.to_dict()
+            content = attribute.attribute_content
+            assert (d["attribute_content"] is None) == (content is None)
+            for key, value in d.items():
+                if key != "attribute_content":
+                    assert value == getattr(attribute, key)
+            if content is None:
+                continue
+
+            seen.add(type(content).__name__)
+            assert d["attribute_content"] == content.to_dict()
+            for key, value in d["attribute_content"].items():
+                if key == "flags_list":
+                    assert value == content.flags_list()
+                elif key == "entries":
+                    assert value == [e.to_dict() for e in content.entries()]
+                elif key in ("data", "value") or key.endswith("_bytes"):
+                    assert value is None or type(value) is bytes
+                    assert value == getattr(content, key)
+                elif type(content).__name__ == "PyMftAttributeOther":
+                    assert value == attribute.type_code
+                else:
+                    assert value == getattr(content, key)
+
+    assert {"PyMftAttributeX10", "PyMftAttributeX30", "PyMftAttributeX80", "PyMftAttributeX90"} <= seen
+
+
+def test_volume_info(sample_mft):
+    info = PyMftParser(str(sample_mft)).volume_info()
+    assert info == {
+        "label": "",
+        "version": "3.1",
+        "major_version": 3,
+        "minor_version": 1,
+        "flags": 0,
+        "flags_list": [],
+    }
+
+    raw = bytearray(sample_mft.read_bytes())
+    record = 3 * 1024
+    volume_information = raw.index(bytes.fromhex("000000000000000003010000"), record, record + 1024)
+    struct.pack_into("<H", raw, volume_information + 10, 0x8001)
+    info = PyMftParser(io.BytesIO(bytes(raw))).volume_info()
+    assert info["flags"] == 0x8001
+    assert info["flags_list"] == ["IS_DIRTY", "MODIFIED_BY_CHKDSK"]
+
+
+def test_mft_layout(sample_mft):
+    layout = PyMftParser(str(sample_mft)).mft_layout()
+    assert layout["runs"] == [(0, 205374, 4536), (4536, 261023, 1288), (5824, 230736, 336), (6160, 399772, 384)]
+    assert layout["size"] == layout["allocated_size"] == 13402112
+    assert layout["fragments"] == 4
+
+    # A size of 2048 bytes per cluster.
+    assert sum(length for _, _, length in layout["runs"]) * 2048 == layout["allocated_size"]
+
+
+def test_fragment_count(sample_mft):
+    parser = PyMftParser(str(sample_mft))
+    entries = {e.entry_id: e for e in parser.entries() if isinstance(e, PyMftEntry)}
+
+    assert entries[0].data_runs() == parser.mft_layout()["runs"]
+    assert entries[0].fragment_count() == 4
+
+    pagefile = entries[27]
+    assert pagefile.full_path == "pagefile.sys"
+    runs = pagefile.data_runs()
+    assert pagefile.fragment_count() == 26
+    assert sum(length for _, _, length in runs) * 2048 == pagefile.allocated_size
+    contiguous = sum(1 for previous, run in zip(runs, runs[1:]) if previous[1] + previous[2] == run[1])
+    assert len(runs) - contiguous == 26
+
+    resident = next(e for e in entries.values() if e.data_stream_names() == [""] and e.allocated_size == 0
+                    and not e.is_directory and e.file_size > 0)
+    assert resident.data_runs() is None
+    assert resident.fragment_count() is None
+
+    rows = {int(r["EntryId"]): r for r in csv.DictReader(io.StringIO(b"".join(parser.entries_csv()).decode()))}
+    assert rows[27]["DataFragments"] == "26"
+    assert rows[resident.entry_id]["DataFragments"] == ""
+
+    projected = b"".join(parser.entries_csv(columns=["EntryId", "DataFragments"])).decode().splitlines()
+    assert projected[0] == "EntryId,DataFragments"
+    assert "27,26" in projected
+
+
+def test_largest_files(sample_mft):
+    parser = PyMftParser(str(sample_mft))
+    files = [e for e in parser.entries() if isinstance(e, PyMftEntry) and not e.is_directory and e.base_entry_id == 0]
+    expected = sorted(files, key=lambda e: (-e.file_size, e.entry_id))
+
+    largest = list(parser.largest_files(10))
+    assert [e.entry_id for e in largest] == [e.entry_id for e in expected[:10]]
+    assert largest[0].full_path == "pagefile.sys"
+    assert len(list(parser.largest_files())) == 100
+    assert list(parser.largest_files(0)) == []
+
+
+def test_duplicate_paths(sample_mft):
+    parser = PyMftParser(str(sample_mft))
+
+    def expected(key):
+        groups = collections.defaultdict(list)
+        for e in parser.entries():
+            if isinstance(e, PyMftEntry) and e.base_entry_id == 0 and e.full_path != "[UNKNOWN]":
+                groups[key(e.full_path)].append(e.entry_id)
+        return sorted(ids for ids in groups.values() if len(ids) > 1)
+
+    duplicates = parser.duplicate_paths()
+    assert ("Documents and Settings/Donald Blake/Cookies/donald blake@aol[2].txt", [12888, 13069]) in duplicates
+    assert sorted(ids for _, ids in duplicates) == expected(str.lower)
+    assert [path for path, _ in duplicates] == sorted(path for path, _ in duplicates)
+    assert sorted(ids for _, ids in parser.duplicate_paths(case_sensitive=True)) == expected(str)
+
+
+def test_orphan_prefix(sample_mft):
+    def paths(parser):
+        return {e.entry_id: e.full_path for e in parser.entries() if isinstance(e, PyMftEntry)}
+
+    def csv_paths(parser):
+        rows = csv.DictReader(io.StringIO(b"".join(parser.entries_csv()).decode()))
+        return {int(r["EntryId"]): r["FullPath"] for r in rows}
+
+    default = paths(PyMftParser(str(sample_mft)))
+    orphans = {i for i, path in default.items() if path.startswith("[Orphan]/")}
+    assert orphans
+
+    parser = PyMftParser(str(sample_mft), orphan_prefix="ORPHANS")
+    custom = paths(parser)
+    assert {i for i, path in custom.items() if path.startswith("ORPHANS/")} == orphans
+    assert {i: path for i, path in csv_paths(parser).items() if i in orphans} == {i: custom[i] for i in orphans}
+
+    # Once the WINDOWS directory is reused (its sequence number changes), its children are orphans.
+    windows = next(i for i, path in default.items() if path == "WINDOWS")
+    raw = bytearray(sample_mft.read_bytes())
+    sequence = struct.unpack_from("<H", raw, windows * 1024 + 0x10)[0]
+    struct.pack_into("<H", raw, windows * 1024 + 0x10, sequence + 2)
+    parser = PyMftParser(io.BytesIO(bytes(raw)))
+    reused = paths(parser)
+    assert reused[windows] == "WINDOWS"
+    system32 = next(i for i, path in default.items() if path == "WINDOWS/system32")
+    assert reused[system32] == "[Orphan]/system32"
+    assert csv_paths(parser)[system32] == "[Orphan]/system32"
+    assert reused[0] == "$MFT"
+
+    # A deleted directory has the next sequence number, its children still resolve through it.
+    struct.pack_into("<H", raw, windows * 1024 + 0x10, sequence + 1)
+    flags = struct.unpack_from("<H", raw, windows * 1024 + 0x16)[0]
+    struct.pack_into("<H", raw, windows * 1024 + 0x16, flags & ~0x01)
+    assert paths(PyMftParser(io.BytesIO(bytes(raw))))[system32] == "WINDOWS/system32"
+
+
+def test_path_format(sample_mft):
+    def csv_paths(**kwargs):
+        rows = csv.DictReader(io.StringIO(b"".join(PyMftParser(str(sample_mft)).entries_csv(**kwargs)).decode()))
+        return {int(r["EntryId"]): r["FullPath"] for r in rows}
+
+    default = csv_paths()
+    system32 = next(i for i, path in default.items() if path == "WINDOWS/system32")
+
+    windows = csv_paths(path_separator="\\", path_root="C:")
+    assert windows[system32] == "C:\\WINDOWS\\system32"
+    assert windows[0] == "C:\\$MFT"
+    assert csv_paths(path_root="/mnt/c")[system32] == "/mnt/c/WINDOWS/system32"
+
+    parents = csv_paths(path_include_name=False)
+    assert parents[system32] == "WINDOWS"
+    assert parents[0] == ""
+
+    xml = "".join(PyMftParser(str(sample_mft)).entries_xml(path_separator="\\"))
+    assert "<FullPath>WINDOWS\\system32</FullPath>" in xml
+
+    with pytest.raises(ValueError):
+        PyMftParser(str(sample_mft)).entries_csv(path_separator=":")
+
+
+def test_entries_flag_filters(sample_mft):
+    def si_flags(entry):
+        info = next((a.attribute_content for a in entry.attributes() if a.type_code == 0x10), None)
+        return info.file_flags if info else 0
+
+    entries = list(PyMftParser(str(sample_mft)).entries())
+
+    hidden_system = [e.entry_id for e in PyMftParser(str(sample_mft)).entries(flags_any=["HIDDEN", "SYSTEM"])]
+    assert hidden_system
+    assert hidden_system == [e.entry_id for e in entries if si_flags(e) & 0x06]
+
+    files = [e.entry_id for e in PyMftParser(str(sample_mft)).entries(
+        flags_any=["hidden", "FILE_ATTRIBUTE_SYSTEM"], flags_none=["DIRECTORY"],
+    )]
+    assert files and len(files) < len(hidden_system)
+    assert files == [e.entry_id for e in entries if si_flags(e) & 0x06 and not e.is_directory]
+
+    assert [e.entry_id for e in PyMftParser(str(sample_mft)).entries(flags_none=["DIRECTORY"])] == \
+        [e.entry_id for e in entries if not e.is_directory]
+
+    with pytest.raises(ValueError):
+        PyMftParser(str(sample_mft)).entries(flags_any=["INVISIBLE"])
+
+
+def test_directories_and_files(sample_mft):
+    entries = [e for e in PyMftParser(str(sample_mft)).entries() if e.base_entry_id == 0]
+
+    directories = [e.entry_id for e in PyMftParser(str(sample_mft)).directories()]
+    assert 5 in directories
+    assert directories == [e.entry_id for e in entries if e.is_directory]
+
+    files = [e.entry_id for e in PyMftParser(str(sample_mft)).files()]
+    assert 0 in files and 5 not in files
+    assert files == [e.entry_id for e in entries if not e.is_directory]
+
+    assert [e.entry_id for e in PyMftParser(str(sample_mft)).files(start_from=100)] == [i for i in files if i >= 100]
+
+
+def test_batch(sample_mft, tmp_path):
+    copy = tmp_path / "host2.mft"
+    copy.write_bytes(Path(sample_mft).read_bytes())
+    single = b"".join(PyMftParser(str(sample_mft)).entries_csv()).decode().splitlines()
+
+    batch = PyMftBatch([str(sample_mft), copy], sources=["host1", "host2"], threads=2)
+    assert batch.sources == ["host1", "host2"]
+
+    f = io.BytesIO()
+    assert batch.export_csv(f) == 2 * (len(single) - 1)
+    lines = f.getvalue().decode().splitlines()
+    assert lines[0] == "Source," + single[0]
+    assert lines[1:] == ["host1," + line for line in single[1:]] + ["host2," + line for line in single[1:]]
+
+    output = tmp_path / "batch.jsonl.gz"
+    PyMftBatch([str(sample_mft), copy]).export_json(str(output))
+    values = [json.loads(line) for line in gzip.open(output)]
+    assert len(values) == 2 * (len(single) - 1)
+    assert {v["source"] for v in values} == {str(sample_mft), str(copy)}
+    assert values[0]["header"]["record_number"] == 0
+
+    f = io.BytesIO()
+    PyMftBatch([str(sample_mft), copy], sources=["host1", "host2"]).export_arrow_ipc(f, columns=["EntryId"])
+    messages = read_arrow_ipc_messages(f.getvalue())
+    assert b"Source" in messages[0][0] and b"FullPath" not in messages[0][0]
+    assert len(messages) == 1 + 2 * 13
+    assert b"host1" in messages[1][1] and b"host2" in messages[-1][1]
+
+    # MFTs are opened by the workers, their errors are raised as is.
+    with pytest.raises(FileNotFoundError):
+        PyMftBatch([str(sample_mft), tmp_path / "missing.mft"], threads=2).export_csv(io.BytesIO())
+
+    with pytest.raises(ValueError):
+        PyMftBatch([str(sample_mft)], sources=["a", "b"])
+    with pytest.raises(ValueError):
+        PyMftBatch([])
+
+
+def read_thrift_compact(data, position):
+    """Decodes the Thrift compact struct at `position` as a dict of its fields (by id), with the position after it."""
+    def varint():
+        nonlocal position
+        shift = value = 0
+        while True:
+            byte = data[position]
+            position += 1
+            value |= (byte & 0x7F) << shift
+            shift += 7
+            if byte < 0x80:
+                return value
+
+    def zigzag():
+        value = varint()
+        return (value >> 1) ^ -(value & 1)
+
+    def value(kind):
+        nonlocal position
+        if kind in (1, 2):
+            return kind == 1
+        if kind == 3:
+            position += 1
+            return data[position - 1]
+        if kind in (4, 5, 6):
+            return zigzag()
+        if kind == 8:
+            size = varint()
+            position += size
+            return bytes(data[position - size:position])
+        if kind == 9:
+            header = data[position]
+            position += 1
+            size = header >> 4 if header >> 4 != 15 else varint()
+            return [value(header & 0x0F) for _ in range(size)]
+        assert kind == 12
+        fields, field_id = {}, 0
+        while data[position] != 0:
+            header = data[position]
+            position += 1
+            field_id = field_id + (header >> 4) if header >> 4 else zigzag()
+            fields[field_id] = value(header & 0x0F)
+        position += 1
+        return fields
+
+    return value(12), position
+
+
+def test_batch_export_parquet(sample_mft, tmp_path):
+    copy = tmp_path / "host2.mft"
+    copy.write_bytes(Path(sample_mft).read_bytes())
+    ids = [int(line.split(",")[1]) for line in b"".join(PyMftParser(str(sample_mft)).entries_csv()).decode().splitlines()[1:]]
+
+    f = io.BytesIO()
+    batch = PyMftBatch([str(sample_mft), copy], sources=["host1", "host2"], threads=3)
+    rows = batch.export_parquet(f, columns=["EntryId", "FullPath", "StandardInfoCreated"])
+    assert rows == 2 * len(ids)
+
+    data = f.getvalue()
+    assert data[:4] == data[-4:] == b"PAR1"
+    metadata_size = struct.unpack_from("<I", data, len(data) - 8)[0]
+    metadata, end = read_thrift_compact(data, len(data) - 8 - metadata_size)
+    assert end == len(data) - 8
+
+    # `FileMetaData.schema`, `num_rows` and `row_groups`.
+    schema = metadata[2]
+    assert [element[4] for element in schema] == [b"schema", b"Source", b"EntryId", b"FullPath", b"StandardInfoCreated"]
+    # Optional, `TIMESTAMP_MICROS`.
+    assert schema[4][3] == 1 and schema[4][6] == 10
+    assert metadata[3] == rows
+    row_groups = metadata[4]
+    assert len(row_groups) == 2 * 13 and sum(group[3] for group in row_groups) == rows
+
+    def required_values(group, column):
+        """The PLAIN values of a required INT64 or BYTE_ARRAY column chunk."""
+        chunk = group[1][column][3]
+        header, position = read_thrift_compact(data, chunk[9])
+        assert position + header[3] == chunk[9] + chunk[7]
+        values = []
+        for _ in range(header[5][1]):
+            if chunk[1] == 2:
+                values.append(struct.unpack_from("<q", data, position)[0])
+                position += 8
+            else:
+                size = struct.unpack_from("<I", data, position)[0]
+                values.append(data[position + 4:position + 4 + size].decode())
+                position += 4 + size
+        return values
+
+    assert [i for group in row_groups[:13] for i in required_values(group, 1)] == ids
+    assert [i for group in row_groups[13:] for i in required_values(group, 1)] == ids
+    assert set(required_values(row_groups[0], 0)) == {"host1"}
+    assert set(required_values(row_groups[-1], 0)) == {"host2"}
+
+
+def test_batch_export_parquet_reads_with_pyarrow(sample_mft, tmp_path):
+    parquet = pytest.importorskip("pyarrow.parquet")
+
+    output = tmp_path / "batch.parquet"
+    rows = PyMftBatch([str(sample_mft), str(sample_mft)], sources=["host1", "host2"]).export_parquet(str(output))
+
+    table = parquet.read_table(str(output))
+    assert table.num_rows == rows
+    assert table.column("Source")[0].as_py() == "host1" and table.column("Source")[rows - 1].as_py() == "host2"
+    assert table.column("FullPath")[1].as_py() == "$MFTMirr"
+    assert str(table.schema.field("StandardInfoCreated").type) == "timestamp[us, tz=UTC]"
+
+
+def test_command_line(sample_mft, tmp_path):
+    output = tmp_path / "out.csv"
+    assert main(["dump", "--csv", str(output), "--threads", "2", str(sample_mft)]) == 0
+    assert output.read_bytes() == b"".join(PyMftParser(str(sample_mft)).entries_csv())
+
+    output = tmp_path / "large.jsonl.gz"
+    assert main(["dump", "--json", str(output), "--min-size", "1048576", str(sample_mft)]) == 0
+    records = [json.loads(line)["header"]["record_number"] for line in gzip.open(output)]
+    assert records and records == [e.entry_id for e in PyMftParser(str(sample_mft)).entries(min_size=1048576)]
+
+    output = tmp_path / "out.xml"
+    assert main(["dump", "--xml", str(output), "--columns", "EntryId,FullPath", "--max-size", "0", str(sample_mft)]) == 0
+    root = ElementTree.parse(output).getroot()
+    assert [child.tag for child in root[0]] == ["EntryId", "FullPath"]
+
+    assert main(["dump", "--arrow", str(tmp_path / "out.arrow"), "--min-size", "1", str(sample_mft)]) == 1
+    assert main(["dump", "--csv", "a.csv", "--json", "a.jsonl", str(sample_mft)]) == 2
+    assert main(["dump", "--csv", str(output), str(tmp_path / "missing")]) == 1
+
+
+def test_export_stats(sample_mft, tmp_path):
+    rows = PyMftParser(str(sample_mft)).export_csv(str(tmp_path / "plain.csv"))
+    assert isinstance(rows, int)
+
+    stats = PyMftParser(str(sample_mft)).export_csv(str(tmp_path / "mft.csv"), threads=2, stats=True)
+    assert (tmp_path / "mft.csv").read_bytes() == (tmp_path / "plain.csv").read_bytes()
+    assert stats.rows == rows
+    assert stats.records == Path(sample_mft).stat().st_size // 1024
+    assert stats.bytes_read == Path(sample_mft).stat().st_size
+    assert stats.threads == 2
+    assert stats.elapsed > 0 and stats.records_per_second > 0 and stats.bytes_per_second > 0
+    assert stats.path_resolution_seconds > 0 and stats.parsing_seconds > 0 and stats.serialization_seconds > 0
+    assert stats.read_seconds >= 0 and stats.write_seconds >= 0
+
+    stats = PyMftParser(str(sample_mft)).export_json(io.BytesIO(), stats=True)
+    assert stats.rows == rows and stats.path_resolution_seconds == 0
+
+    stats = PyMftParser(str(sample_mft)).export_csv(io.BytesIO(), columns=["EntryId"], stats=True)
+    assert stats.path_resolution_seconds == 0
+
+    assert PyMftParser(str(sample_mft)).export_xml(io.BytesIO(), stats=True).rows == rows
+    assert PyMftParser(str(sample_mft)).export_arrow_ipc(io.BytesIO(), stats=True).rows == rows
+
+
+def test_entry_from_bytes(sample_mft):
+    data = Path(sample_mft).read_bytes()
+    expected = {e.entry_id: e for e in PyMftParser(str(sample_mft)).entries()}
+    windows = next(i for i, e in expected.items() if e.full_path == "WINDOWS/system32")
+
+    entry = PyMftEntry.from_bytes(data[windows * 1024:(windows + 1) * 1024])
+    assert entry.entry_id == windows
+    assert entry.full_path == "system32"
+    assert entry.fixup_ok and entry.is_directory
+    assert entry.sequence == expected[windows].sequence
+    assert [a.type_code for a in entry.attributes()] == [a.type_code for a in expected[windows].attributes()]
+
+    assert PyMftEntry.from_bytes(data[:1024], entry_id=42).entry_id == 42
+
+    # The last two bytes of the first sector no longer match the update sequence number.
+    record = bytearray(data[:1024])
+    record[510:512] = b"\xff\xff"
+    assert PyMftEntry.from_bytes(bytes(record)).fixup_ok is False
+
+    with pytest.raises(ValueError):
+        PyMftEntry.from_bytes(data[:1000])
+    with pytest.raises(RuntimeError):
+        PyMftEntry.from_bytes(b"XXXX" + data[4:1024])
+
+
+
+@pytest.mark.parametrize("offset, value", [
+    (4, 0x3FE), (4, 0x400), (4, 0xFFFF),  # update sequence array offset
+    (6, 0), (6, 0x3FE), (6, 0xFFFF),  # update sequence array size
+    (0x14, 0x10), (0x14, 0x3FF),  # first attribute offset
+    (0x38 + 4, 0),  # length of the first attribute
+])
+def test_entry_from_bytes_malformed(sample_mft, offset, value):
+    data = Path(sample_mft).read_bytes()
+    record = bytearray(data[:1024])
+    record[offset:offset + 2] = struct.pack("<H", value)
+
+    with pytest.raises(RuntimeError, match="malformed record"):
+        PyMftEntry.from_bytes(bytes(record))
+
+    # Through a parser, the record is an error item of the iterator.
+    entries = list(PyMftParser.from_buffer(bytes(record) + data[1024:4 * 1024]).entries())
+    assert isinstance(entries[0], RuntimeError)
+    assert [e.entry_id for e in entries[1:]] == [1, 2, 3]
+
+
+def test_entry_to_bytes(sample_mft):
+    data = Path(sample_mft).read_bytes()
+    entries = [e for e in PyMftParser(str(sample_mft)).entries(skip_empty=False) if e.entry_id < 64]
+
+    assert all(e.fixup_ok is not False for e in entries)
+    assert b"".join(e.to_bytes() for e in entries) == data[:64 * 1024]
+    assert entries[5].to_bytes(apply_fixups=False) == entries[5].raw
+
+    # A subset of the records can be parsed again as an MFT.
+    subset = PyMftParser.from_buffer(b"".join(e.to_bytes() for e in entries[:16]))
+    reparsed = list(subset.entries())
+    assert [(e.entry_id, e.full_path, e.fixup_ok) for e in reparsed] == \
+        [(e.entry_id, e.full_path, e.fixup_ok) for e in entries[:16] if e.signature]
+    assert PyMftEntry.from_bytes(entries[27].to_bytes()).to_bytes() == entries[27].to_bytes()
+
+
+def test_testing_builder(tmp_path):
+    from mft.testing import PyMftBuilder
+
+    created = datetime.datetime(2021, 5, 6, 7, 8, 9, 123456, tzinfo=datetime.timezone.utc)
+    builder = PyMftBuilder(entry_size=4096)
+    users = builder.add_directory("Users")
+    notes = builder.add_file("notes.txt", parent=users, data=b"hello", created=created, file_attributes=0x20)
+    old = builder.add_file("old.bin", size=10 ** 7, deleted=True, modified=132223104000000000 + 10 ** 7, entry_id=40)
+    assert (users, notes, old) == (16, 17, 40)
+
+    with pytest.raises(ValueError):
+        builder.add_file("duplicate", entry_id=notes)
+    with pytest.raises(TypeError):
+        builder.add_file("bad", created="2021-05-06")
+
+    builder.write(str(tmp_path / "MFT"))
+    data = (tmp_path / "MFT").read_bytes()
+    assert data == builder.to_bytes() and len(data) == 41 * 4096
+
+    parser = PyMftParser.from_buffer(data)
+    assert parser.validate().issues == []
+    entries = {e.entry_id: e for e in parser.entries()}
+    assert sorted(entries) == [0, 5, 16, 17, 40]
+    assert all(e.fixup_ok for e in entries.values())
+    assert entries[notes].full_path == "Users/notes.txt"
+    assert entries[users].is_directory and not entries[notes].is_directory
+
+    si = next(a for a in entries[notes].attributes() if a.type_name == "StandardInformation").attribute_content
+    assert si.created == created
+    assert si.modified == datetime.datetime(2020, 1, 1, tzinfo=datetime.timezone.utc)
+    assert next(a for a in entries[notes].attributes() if a.type_name == "DATA").attribute_content.data == b"hello"
+
+    assert "ALLOCATED" not in str(entries[old].flags)
+    assert entries[old].file_size == 10 ** 7
+    assert entries[old].data_runs() == [(0, None, 2442)]
+    si = next(a for a in entries[old].attributes() if a.type_name == "StandardInformation").attribute_content
+    assert si.modified == datetime.datetime(2020, 1, 1, 0, 0, 1, tzinfo=datetime.timezone.utc)
+
+
+@pytest.mark.parametrize("lenient", [False, True])
+def test_attributes_types(sample_mft, lenient):
+    entries = list(itertools.islice(PyMftParser(str(sample_mft), lenient=lenient).entries(), 64))
+
+    for entry in entries:
+        expected = [(a.type_code, a.instance) for a in entry.attributes() if a.type_code in (0x10, 0x30)]
+        assert [(a.type_code, a.instance) for a in entry.attributes(types=[0x10, 0x30])] == expected
+        assert [(a.type_code, a.instance) for a in entry.attributes(types=["StandardInformation", "filename"])] \
+            == expected
+
+    assert all(a.type_name == "DATA" for e in entries for a in e.attributes(types=["DATA"]))
+    assert list(entries[0].attributes(types=[])) == []
+
+    with pytest.raises(ValueError):
+        entries[0].attributes(types=[0x11])
+    with pytest.raises(ValueError):
+        entries[0].attributes(types=["Names"])